@@ -0,0 +1,163 @@
+/*
+    Copyright © 2023, ParallelChain Lab
+    Licensed under the Apache License, Version 2.0: http://www.apache.org/licenses/LICENSE-2.0
+*/
+
+//! Applies a sequence of blocks to a World State, e.g. when a node syncs the blockchain.
+//!
+//! A [BlockApplier] holds the World State between blocks, and applies the transactions of each block with
+//! [transition_v2_batch](Runtime::transition_v2_batch), so the resulting World State is identical to calling it
+//! once per block.
+//!
+//! The Runtime does not define how a block header commits to the receipts of its transactions. To verify blocks,
+//! set a [ReceiptRoot] which computes that commitment, and pass the root in the header of every block to
+//! [apply_block](BlockApplier::apply_block). A block with a mismatching root is not applied.
+
+use std::sync::Arc;
+
+use pchain_types::{
+    blockchain::{ReceiptV2, TransactionV2},
+    cryptography::Sha256Hash,
+};
+use pchain_world_state::{VersionProvider, WorldState, DB};
+
+use crate::{BlockchainParams, Runtime, TransitionError, ValidatorChanges};
+
+/// Computes the root which a block header commits to the receipts of the transactions in the block.
+///
+/// Implementations must be deterministic: the same receipts must always result in the same root.
+pub trait ReceiptRoot: Send + Sync {
+    /// Returns the root of `receipts`, which are in the order of the transactions in the block. A receipt is None if
+    /// the transaction was not executed, e.g. due to failing checks in the pre-charge phase.
+    fn receipt_root(&self, receipts: &[Option<ReceiptV2>]) -> Sha256Hash;
+}
+
+/// Applies blocks in order to a World State. See the [module-level documentation](self).
+pub struct BlockApplier<'a, 'r, S, V>
+where
+    S: DB + Send + Sync + Clone + 'static,
+    V: VersionProvider + Send + Sync + Clone + 'static,
+{
+    runtime: &'r Runtime,
+    /// always Some, except while a block is being applied
+    ws: Option<WorldState<'a, S, V>>,
+    receipt_root: Option<Arc<dyn ReceiptRoot>>,
+}
+
+impl<'a, 'r, S, V> BlockApplier<'a, 'r, S, V>
+where
+    S: DB + Send + Sync + Clone + 'static,
+    V: VersionProvider + Send + Sync + Clone + 'static,
+{
+    /// Creates a BlockApplier which applies blocks to `ws` with `runtime`.
+    pub fn new(runtime: &'r Runtime, ws: WorldState<'a, S, V>) -> Self {
+        Self {
+            runtime,
+            ws: Some(ws),
+            receipt_root: None,
+        }
+    }
+
+    /// Sets the [ReceiptRoot] which verifies the receipts of blocks.
+    pub fn set_receipt_root<R: ReceiptRoot + 'static>(mut self, receipt_root: R) -> Self {
+        self.receipt_root = Some(Arc::new(receipt_root));
+        self
+    }
+
+    /// World State after the blocks applied so far.
+    pub fn world_state(&self) -> &WorldState<'a, S, V> {
+        self.ws.as_ref().unwrap()
+    }
+
+    /// Consumes the BlockApplier, returning the World State after the blocks applied so far.
+    pub fn into_world_state(self) -> WorldState<'a, S, V> {
+        self.ws.unwrap()
+    }
+
+    /// Applies the transactions `txs` of a block with blockchain data `bd`.
+    ///
+    /// If `expected_receipt_root` is Some, the receipts are verified against it with the [ReceiptRoot], and on
+    /// mismatch the World State is left as it was before the block. Verification keeps a copy of the World State
+    /// until the block is applied.
+    ///
+    /// If the Runtime has a [block gas limit](Runtime::set_block_gas_limit) or a
+    /// [compilation time budget](Runtime::set_block_compilation_time_budget), a block whose transactions are not all
+    /// executed is rejected with [TransactionsExcluded](BlockApplierError::TransactionsExcluded), and the World State
+    /// is left as it was before the block. A copy of the World State is then kept until the block is applied.
+    pub fn apply_block(
+        &mut self,
+        txs: Vec<TransactionV2>,
+        bd: BlockchainParams,
+        expected_receipt_root: Option<Sha256Hash>,
+    ) -> Result<AppliedBlock, BlockApplierError> {
+        let ws = self.ws.take().unwrap();
+
+        // the expected root, and the World State to restore on mismatch
+        let verification = match (expected_receipt_root, &self.receipt_root) {
+            (Some(expected), Some(receipt_root)) => {
+                Some((expected, receipt_root.clone(), ws.clone()))
+            }
+            (Some(_), None) => {
+                self.ws = Some(ws);
+                return Err(BlockApplierError::ReceiptRootNotSet);
+            }
+            (None, _) => None,
+        };
+
+        // the World State to restore if transactions are left unexecuted
+        let prev_ws = match (&verification, self.runtime.may_exclude_transactions()) {
+            (None, true) => Some(ws.clone()),
+            _ => None,
+        };
+
+        let result = self.runtime.transition_v2_batch(ws, txs, bd);
+
+        if !result.excluded.is_empty() {
+            self.ws = verification.map(|(_, _, ws)| ws).or(prev_ws);
+            return Err(BlockApplierError::TransactionsExcluded);
+        }
+
+        if let Some((expected, receipt_root, prev_ws)) = verification {
+            let actual = receipt_root.receipt_root(&result.receipts);
+            if actual != expected {
+                self.ws = Some(prev_ws);
+                return Err(BlockApplierError::ReceiptRootMismatch { expected, actual });
+            }
+        }
+
+        self.ws = Some(result.new_state);
+        Ok(AppliedBlock {
+            receipts: result.receipts,
+            errors: result.errors,
+            validator_changes: result.validator_changes,
+        })
+    }
+}
+
+/// Result of applying a block with [apply_block](BlockApplier::apply_block).
+#[derive(Clone, Debug)]
+pub struct AppliedBlock {
+    /// Transaction receipts, in the order of the transactions. None if no commands were executed,
+    /// e.g. due to failing checks in the pre-charge phase
+    pub receipts: Vec<Option<ReceiptV2>>,
+    /// Transition errors, in the order of the transactions. None if no error.
+    pub errors: Vec<Option<TransitionError>>,
+    /// Changes in validator set from the last transaction in the block that executed a
+    /// [Next Epoch](pchain_types::blockchain::Command::NextEpoch) Command. None if there is no such transaction.
+    pub validator_changes: Option<ValidatorChanges>,
+}
+
+/// Reasons that a block is not applied by [apply_block](BlockApplier::apply_block).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BlockApplierError {
+    /// An expected receipt root was given, but no [ReceiptRoot] is set to verify it.
+    ReceiptRootNotSet,
+    /// The root of the receipts of the block differs from the expected one.
+    ReceiptRootMismatch {
+        expected: Sha256Hash,
+        actual: Sha256Hash,
+    },
+    /// Some transactions of the block were not executed, because the block gas limit or the compilation time budget
+    /// of the Runtime ran out.
+    TransactionsExcluded,
+}
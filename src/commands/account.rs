@@ -12,13 +12,13 @@
 //! They can do so directly, or indirectly by triggering the execution of WebAsembly smart contracts,
 //! which in turn hook into the state modification methods of the Wasm host API.
 
-use pchain_types::cryptography::{contract_address_v1, contract_address_v2, PublicAddress};
+use pchain_types::cryptography::PublicAddress;
 use pchain_world_state::{VersionProvider, DB};
 use std::sync::{Arc, Mutex};
 
 use crate::{
     contract::{
-        self, is_cbi_compatible,
+        self, contract_address_v1, contract_address_v2, is_cbi_compatible,
         wasmer::{instance::ContractValidateError, module::ModuleBuildError},
         ContractInstance, ContractModule,
     },
@@ -33,6 +33,10 @@ use crate::execution::state::ExecutionState;
 
 /// Execution of [pchain_types::blockchain::Command::Transfer]
 /// Transfers the specified amount of tokens from the signer's account to the recipient's account.
+///
+/// Transfers do not carry a memo. [TransferInput](pchain_types::runtime::TransferInput) has no field for one, and
+/// [TransferReceipt](pchain_types::blockchain::TransferReceipt) has no field to echo it into, so supporting memos
+/// depends on both types being extended in `pchain-types` first.
 pub(crate) fn transfer<S, E, V>(
     state: &mut ExecutionState<'_, S, E, V>,
     recipient: PublicAddress,
@@ -49,7 +53,9 @@ where
         abort!(state, TransitionError::NotEnoughBalanceForTransfer)
     }
 
-    // Always deduct the amount specified in the transaction
+    // Always deduct the amount specified in the transaction.
+    // The recipient balance is read after the deduction, so that a transfer to
+    // the signer's own address leaves the balance unchanged (except for gas).
     state
         .ctx
         .gas_meter
@@ -107,6 +113,9 @@ where
             .ws_set_balance(target, target_balance.saturating_add(amount));
     }
 
+    // The time budget of the command, if any, includes instantiating the contract
+    state.ctx.sc_context.start_command_timer();
+
     // Instantiation of contract
     let instance =
         match CallInstance::instantiate(state, is_view, target, method, arguments, amount) {
@@ -133,6 +142,9 @@ where
 
     /// the specific Wasm instance
     instance: ContractInstance<'a, S, V>,
+
+    /// address of the called contract
+    target: PublicAddress,
 }
 
 impl<'a, 'b, S, E, V> CallInstance<'a, 'b, S, E, V>
@@ -203,14 +215,26 @@ where
             )
             .map_err(|_| TransitionError::CannotCompile)?;
 
-        Ok(Self { state, instance })
+        Ok(Self {
+            state,
+            instance,
+            target,
+        })
     }
 
     /// Call the Instance and transits the state.
     fn call(self) -> Option<TransitionError> {
         let (ctx, wasm_exec_gas, call_error) = self.instance.call();
         self.state.ctx = ctx;
-        self.state.ctx.gas_meter.manually_charge_gas(wasm_exec_gas);
+        let wasm_exec_gas = self
+            .state
+            .ctx
+            .sc_context
+            .apply_gas_multiplier(&self.target, wasm_exec_gas);
+        self.state
+            .ctx
+            .gas_meter
+            .charge_wasm_execution(wasm_exec_gas);
         if self.state.txn_meta.gas_limit < self.state.ctx.gas_meter.total_gas_used() {
             Some(TransitionError::ExecutionProperGasExhausted)
         } else {
@@ -235,9 +259,9 @@ where
 {
     // compute the deploy destination, which differs between V1 and V2 transactions
     let contract_address = match state.txn_meta.version {
-        TxnVersion::V1 => contract_address_v1(&state.txn_meta.signer, state.txn_meta.nonce),
+        TxnVersion::V1 => contract_address_v1(state.txn_meta.signer, state.txn_meta.nonce),
         TxnVersion::V2 => {
-            contract_address_v2(&state.txn_meta.signer, state.txn_meta.nonce, cmd_index)
+            contract_address_v2(state.txn_meta.signer, state.txn_meta.nonce, cmd_index)
         }
     };
 
@@ -299,21 +323,27 @@ where
             return Err(TransitionError::ContractAlreadyExists);
         }
 
+        // reject oversized bytecode before spending memory and time on compiling it
+        if let Some(max_contract_size) = state.ctx.sc_context.max_contract_size {
+            if bytecode.len() > max_contract_size {
+                return Err(TransitionError::ContractTooLarge);
+            }
+        }
+
         // check if the bytecode can be compiled into a valid Wasm module
-        let module =
-            ContractModule::from_bytecode_checked(&bytecode, state.ctx.sc_context.memory_limit)
-                .map_err(|build_err| match build_err {
-                    ModuleBuildError::DisallowedOpcodePresent => TransitionError::DisallowedOpcode,
-                    ModuleBuildError::Else => TransitionError::CannotCompile,
-                })?;
-
-        // check if the Wasm module is a valid contract according to the ParallelChain Protocol CBI
-        module
-            .validate_proper_contract()
-            .map_err(|validate_err| match validate_err {
+        let module = ContractModule::from_bytecode_checked(&bytecode, &state.ctx.sc_context)
+            .map_err(|build_err| match build_err {
+                ModuleBuildError::DisallowedOpcodePresent => TransitionError::DisallowedOpcode,
+                ModuleBuildError::Else => TransitionError::CannotCompile,
+            })?;
+
+        // check if the Wasm module is a valid contract according to its version of the ParallelChain Protocol CBI
+        module.validate_proper_contract(cbi_version).map_err(
+            |validate_err| match validate_err {
                 ContractValidateError::MethodNotFound => TransitionError::NoExportedContractMethod,
                 ContractValidateError::InstantiateError => TransitionError::CannotCompile,
-            })?;
+            },
+        )?;
 
         Ok(Self {
             state,
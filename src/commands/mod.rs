@@ -15,6 +15,6 @@
 
 pub(crate) mod account;
 
-pub(crate) mod protocol;
+pub mod protocol;
 
 pub(crate) mod staking;
@@ -7,17 +7,24 @@
 //! [Protocol Commands](https://github.com/parallelchain-io/parallelchain-protocol/blob/master/Runtime.md#protocol-commands).
 //!
 //! These commands are not available to users, and can only be triggered by the protocol itself.
+//!
+//! The module also provides read-only functions over the Network Account:
+//! - [validate_world_state], a diagnostic which checks the staking invariants that these commands (and the staking
+//!   commands) maintain.
+//! - [projected_pool_apr], which projects the annualized reward rate of a pool with the reward formulas used here.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use pchain_types::cryptography::PublicAddress;
 use pchain_world_state::{
-    NetworkAccount, NetworkAccountStorage, Pool, StakeValue, VersionProvider, DB, NETWORK_ADDRESS,
+    constants::{MAX_STAKES_PER_POOL, MAX_VALIDATOR_SET_SIZE},
+    NetworkAccount, NetworkAccountStorage, Pool, Stake, StakeValue, VersionProvider, WorldState,
+    DB, NETWORK_ADDRESS,
 };
 
 use crate::{
-    rewards_formulas::{pool_reward, stake_reward},
-    BlockProposalStats, ValidatorChanges,
+    rewards_formulas::{self, pool_reward, EPOCHS_PER_YEAR},
+    BlockProposalStats, BlockchainParams, ValidatorChanges,
 };
 
 use crate::execution::{cache::WorldStateCache, state::ExecutionState};
@@ -27,6 +34,14 @@ use crate::execution::{cache::WorldStateCache, state::ExecutionState};
 /// Execution of [pchain_types::blockchain::Command::NextEpoch]
 /// Execution does not cost gas as this command is triggered by the protocol.
 /// To achieve this, the [NetworkAccountWorldState] is used to perform World State operations.
+///
+/// The next validator set is the whole NVP, which holds the pools with the greatest power. NVP keys are ordered by
+/// power, then by operator address, so among pools with equal power at the boundary of the NVP, the pool with the
+/// greater operator address is selected. The selection never depends on the iteration order of a map.
+///
+/// Rewards are distributed to every stake of every pool in the VP within this single transition, so the work done is
+/// bounded by [MAX_VALIDATOR_SET_SIZE] pools of at most [MAX_STAKES_PER_POOL] delegated stakes each. Rewards are
+/// not split across transitions, as stakes must be rewarded before the VP is replaced by the next validator set.
 pub(crate) fn next_epoch<'a, S, E, V>(
     mut state: ExecutionState<'a, S, E, V>,
 ) -> (ExecutionState<'a, S, E, V>, ValidatorChanges)
@@ -35,6 +50,7 @@ where
     V: VersionProvider + Send + Sync + Clone + 'static,
 {
     let block_performance = state.bd.validator_performance.clone().unwrap();
+    let formula = rewards_formulas::for_version(state.txn_meta.version);
 
     let new_validator_set = {
         let mut state = NetworkAccountWorldState::new(&mut state);
@@ -67,7 +83,7 @@ where
                 .stats
                 .get(&pool_operator)
                 .map_or(BlockProposalStats::new(0), |stat| stat.clone());
-            let pool_reward = pool_reward(
+            let pool_reward = formula.pool_reward(
                 current_epoch,
                 pool_power,
                 stats.num_of_proposed_blocks,
@@ -92,8 +108,12 @@ where
             let mut stakers_to_reward = Vec::new();
             if pool_reward > 0 {
                 for stake in &vp_stakes {
-                    let (stake_reward, commission_fee) =
-                        stake_reward(pool_reward, commission_rate, stake.power, total_stakes);
+                    let (stake_reward, commission_fee) = formula.stake_reward(
+                        pool_reward,
+                        commission_rate,
+                        stake.power,
+                        total_stakes,
+                    );
                     stakers_to_reward.push((stake.owner, stake_reward));
                     total_commission_fee = total_commission_fee.saturating_add(commission_fee);
                 }
@@ -116,7 +136,7 @@ where
             // 1.4 Reward Pool's own stakes
             if pool_reward > 0 {
                 let (pool_operator_stake_reward, _) =
-                    stake_reward(pool_reward, 0, pool_operator_own_stake, total_stakes);
+                    formula.stake_reward(pool_reward, 0, pool_operator_own_stake, total_stakes);
                 let mut operator_deposits =
                     NetworkAccount::deposits(&mut state, pool_operator, pool_operator);
                 let pool_operator_total_reward =
@@ -175,7 +195,8 @@ where
             let _ = NetworkAccount::pvp(&mut state).push(pool.clone(), delegated_stakes);
         }
 
-        // 3. Replace VS with NVS
+        // 3. Replace VS with NVS. Ties in power at the boundary were already broken by operator address when pools
+        // entered the NVP (see increase_stake_power), so every pool in the NVP is selected.
         let mut next_validator_set = Vec::new();
         NetworkAccount::vp(&mut state).clear();
         let pool_length = NetworkAccount::nvp(&mut state).length();
@@ -192,6 +213,8 @@ where
             };
             next_validator_set.push((pool_to_vs.operator, pool_to_vs.power));
 
+            // the order of unordered_values follows the layout of the pool's heap, which depends on the history of
+            // its stakes. It is the same on every node, but not sorted. See ordered_delegated_stakes.
             let delegated_stakes = pool.delegated_stakes().unordered_values();
 
             let _ = NetworkAccount::vp(&mut state).push(pool_to_vs, delegated_stakes);
@@ -231,12 +254,397 @@ where
         ValidatorChanges {
             new_validator_set,
             remove_validator_set,
+            power_updates: next_validator_set,
         }
     };
 
     (state, new_validator_set)
 }
 
+/* ↓↓↓ World State Validation ↓↓↓ */
+
+/// The set of pools in the Network Account in which an [InvariantViolation] was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PoolSet {
+    /// Next Validator Pools, and the pools they refer to
+    Nvp,
+    /// Validator Pools of the current epoch
+    Vp,
+    /// Validator Pools of the previous epoch
+    Pvp,
+}
+
+/// A staking invariant of the Network Account which does not hold in a World State. Returned by [validate_world_state].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvariantViolation {
+    /// A pool appears more than once in the set.
+    DuplicatePool {
+        set: PoolSet,
+        operator: PublicAddress,
+    },
+    /// A pool in the Next Validator Pools does not exist in the pools of the Network Account.
+    PoolNotFound { operator: PublicAddress },
+    /// The power recorded for a pool in the Next Validator Pools differs from the power of the pool itself.
+    NvpPowerMismatch {
+        operator: PublicAddress,
+        nvp_power: u64,
+        pool_power: u64,
+    },
+    /// The power of a pool differs from the sum of its operator stake and delegated stakes.
+    PoolPowerMismatch {
+        set: PoolSet,
+        operator: PublicAddress,
+        power: u64,
+        sum_of_stakes: u64,
+    },
+    /// A stake has more power than the balance of the deposit which backs it. A missing deposit has a balance of zero.
+    StakeExceedsDeposit {
+        operator: PublicAddress,
+        owner: PublicAddress,
+        stake_power: u64,
+        deposit_balance: u64,
+    },
+    /// The pool at an index of the set, or one of its stakes, cannot be read, as in a World State rejected with
+    /// [MalformedWorldState](crate::TransitionError::MalformedWorldState). The rest of the set is not checked.
+    MalformedWorldState { set: PoolSet, index: u32 },
+}
+
+/// Checks the staking invariants of the Network Account in a World State and reports every violation found:
+/// - the Next Validator Pools contain distinct, existing pools with a power that matches the pool's power,
+/// - every pool in the Next Validator Pools, Validator Pools and Previous Validator Pools has a power equal to the sum
+///   of its operator stake and delegated stakes, and appears in its set only once,
+/// - every stake of a pool in the Next Validator Pools is backed by a deposit with at least as much balance.
+///
+/// Pools are found through the pool sets, since the World State does not allow enumerating the storage of the Network
+/// Account. For the same reason, account balances are not checked against a total supply.
+///
+/// The World State is not modified.
+pub fn validate_world_state<S, V>(ws: &WorldState<'_, S, V>) -> Result<(), Vec<InvariantViolation>>
+where
+    S: DB + Send + Sync + Clone + 'static,
+    V: VersionProvider + Send + Sync + Clone + 'static,
+{
+    let mut ws_cache = WorldStateCache::new(ws.clone());
+    let mut state = NetworkAccountWorldState {
+        gas_free_ws_cache: &mut ws_cache,
+    };
+    let mut violations = Vec::new();
+
+    // 1. Next Validator Pools and the pools they refer to
+    let mut operators = HashSet::new();
+    let nvp_length = NetworkAccount::nvp(&mut state).length();
+    for i in 0..nvp_length {
+        let Some(pool_key) = NetworkAccount::nvp(&mut state).get(i) else {
+            violations.push(InvariantViolation::MalformedWorldState {
+                set: PoolSet::Nvp,
+                index: i,
+            });
+            break;
+        };
+        let operator = pool_key.operator;
+        if !operators.insert(operator) {
+            violations.push(InvariantViolation::DuplicatePool {
+                set: PoolSet::Nvp,
+                operator,
+            });
+        }
+
+        if !NetworkAccount::pools(&mut state, operator).exists() {
+            violations.push(InvariantViolation::PoolNotFound { operator });
+            continue;
+        }
+        let pool_power_and_stakes = {
+            let mut pool = NetworkAccount::pools(&mut state, operator);
+            let pool_power = pool.power().unwrap_or(0);
+            let mut stakes: Vec<(PublicAddress, u64)> = Vec::new();
+            if let Some(Some(operator_stake)) = pool.operator_stake() {
+                stakes.push((operator, operator_stake.power));
+            }
+            let mut delegated_stakes = pool.delegated_stakes();
+            (0..delegated_stakes.length())
+                .map(|j| {
+                    delegated_stakes
+                        .get(j)
+                        .map(|stake| (stake.owner, stake.power))
+                })
+                .collect::<Option<Vec<_>>>()
+                .map(|delegated| {
+                    stakes.extend(delegated);
+                    (pool_power, stakes)
+                })
+        };
+        let Some((pool_power, stakes)) = pool_power_and_stakes else {
+            violations.push(InvariantViolation::MalformedWorldState {
+                set: PoolSet::Nvp,
+                index: i,
+            });
+            break;
+        };
+        if pool_key.power != pool_power {
+            violations.push(InvariantViolation::NvpPowerMismatch {
+                operator,
+                nvp_power: pool_key.power,
+                pool_power,
+            });
+        }
+        check_pool_power(PoolSet::Nvp, operator, pool_power, &stakes, &mut violations);
+
+        for (owner, stake_power) in stakes {
+            let deposit_balance = NetworkAccount::deposits(&mut state, operator, owner)
+                .balance()
+                .unwrap_or(0);
+            if stake_power > deposit_balance {
+                violations.push(InvariantViolation::StakeExceedsDeposit {
+                    operator,
+                    owner,
+                    stake_power,
+                    deposit_balance,
+                });
+            }
+        }
+    }
+
+    // 2. Validator Pools and 3. Previous Validator Pools, which are read in the same way through their own accessors
+    macro_rules! check_validator_pools {
+        ($set:expr, $pool_set:path) => {
+            let mut operators = HashSet::new();
+            let length = $pool_set(&mut state).length();
+            for i in 0..length {
+                let pool = (|| {
+                    let (operator, power, operator_stake) = {
+                        let mut pool_set = $pool_set(&mut state);
+                        let mut pool = pool_set.pool_at(i)?;
+                        (
+                            pool.operator()?,
+                            pool.power().unwrap_or(0),
+                            pool.operator_stake().flatten(),
+                        )
+                    };
+                    let mut stakes: Vec<(PublicAddress, u64)> = Vec::new();
+                    if let Some(operator_stake) = operator_stake {
+                        stakes.push((operator, operator_stake.power));
+                    }
+                    if let Some(mut pool) = $pool_set(&mut state).pool(operator) {
+                        let mut delegated_stakes = pool.delegated_stakes();
+                        for j in 0..delegated_stakes.length() {
+                            let stake = delegated_stakes.get(j)?;
+                            stakes.push((stake.owner, stake.power));
+                        }
+                    }
+                    Some((operator, power, stakes))
+                })();
+                let Some((operator, power, stakes)) = pool else {
+                    violations.push(InvariantViolation::MalformedWorldState {
+                        set: $set,
+                        index: i,
+                    });
+                    break;
+                };
+                if !operators.insert(operator) {
+                    violations.push(InvariantViolation::DuplicatePool {
+                        set: $set,
+                        operator,
+                    });
+                }
+                check_pool_power($set, operator, power, &stakes, &mut violations);
+            }
+        };
+    }
+    check_validator_pools!(PoolSet::Vp, NetworkAccount::vp);
+    check_validator_pools!(PoolSet::Pvp, NetworkAccount::pvp);
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(violations)
+    }
+}
+
+/// Records a violation if the power of a pool differs from the sum of its stakes.
+fn check_pool_power(
+    set: PoolSet,
+    operator: PublicAddress,
+    power: u64,
+    stakes: &[(PublicAddress, u64)],
+    violations: &mut Vec<InvariantViolation>,
+) {
+    let sum_of_stakes = stakes.iter().fold(0u64, |sum, (_, stake_power)| {
+        sum.saturating_add(*stake_power)
+    });
+    if power != sum_of_stakes {
+        violations.push(InvariantViolation::PoolPowerMismatch {
+            set,
+            operator,
+            power,
+            sum_of_stakes,
+        });
+    }
+}
+
+/* ↓↓↓ V1 to V2 Migration Bounds ↓↓↓ */
+
+/// Checks that the pool sets of the Network Account have at most [MAX_VALIDATOR_SET_SIZE] pools, that every pool in
+/// them has at most [MAX_STAKES_PER_POOL] delegated stakes, and that every pool within these bounds can be read.
+///
+/// [transition_v1_to_v2](crate::Runtime::transition_v1_to_v2) checks this before it walks the pool sets, so that a
+/// malformed V1 World State is rejected with [MalformedWorldState](crate::TransitionError::MalformedWorldState)
+/// instead of being walked to an unexpected size. The check only reads lengths, and entries within the bounds, so
+/// its own cost is bounded whatever the World State contains.
+pub(crate) fn within_migration_bounds<S, V>(ws: &WorldState<'_, S, V>) -> bool
+where
+    S: DB + Send + Sync + Clone + 'static,
+    V: VersionProvider + Send + Sync + Clone + 'static,
+{
+    let mut ws_cache = WorldStateCache::new(ws.clone());
+    let mut state = NetworkAccountWorldState {
+        gas_free_ws_cache: &mut ws_cache,
+    };
+    let max_pools = MAX_VALIDATOR_SET_SIZE as u32;
+    let max_stakes = MAX_STAKES_PER_POOL as u32;
+
+    let nvp_length = NetworkAccount::nvp(&mut state).length();
+    if nvp_length > max_pools {
+        return false;
+    }
+    for i in 0..nvp_length {
+        let operator = match NetworkAccount::nvp(&mut state).get(i) {
+            Some(pool_key) => pool_key.operator,
+            None => return false,
+        };
+        let stakes_length = NetworkAccount::pools(&mut state, operator)
+            .delegated_stakes()
+            .length();
+        if stakes_length > max_stakes {
+            return false;
+        }
+    }
+
+    macro_rules! check_validator_pools {
+        ($pool_set:path) => {
+            let length = $pool_set(&mut state).length();
+            if length > max_pools {
+                return false;
+            }
+            for i in 0..length {
+                let operator = match $pool_set(&mut state).pool_at(i) {
+                    Some(mut pool) => pool.operator(),
+                    None => None,
+                };
+                let operator = match operator {
+                    Some(operator) => operator,
+                    None => return false,
+                };
+                let stakes_length = match $pool_set(&mut state).pool(operator) {
+                    Some(mut pool) => pool.delegated_stakes().length(),
+                    None => return false,
+                };
+                if stakes_length > max_stakes {
+                    return false;
+                }
+            }
+        };
+    }
+    check_validator_pools!(NetworkAccount::vp);
+    check_validator_pools!(NetworkAccount::pvp);
+
+    true
+}
+
+/* ↓↓↓ Pool Reward Projection ↓↓↓ */
+
+/// Projects the annualized reward rate of the pool of `operator`, i.e. the reward that the pool would receive in a year
+/// relative to its power, if every epoch rewarded it as the next [NextEpoch](pchain_types::blockchain::Command::NextEpoch)
+/// command would.
+///
+/// The per-epoch reward is computed with [pool_reward] from the pool's power in the current Validator Pools, the
+/// current epoch, and the performance of the pool in `bd`. If `bd` has no validator performance, the pool is assumed
+/// to have proposed as many blocks as expected. Pools not in the current Validator Pools earn no reward.
+///
+/// ## Return value
+///
+/// Returns the rate as a tuple of (numerator, denominator), i.e. rate = numerator / denominator, in the same way as
+/// [issuance](crate::rewards_formulas::issuance). The denominator will always be non-zero.
+///
+/// The World State is not modified.
+pub fn projected_pool_apr<S, V>(
+    ws: &WorldState<'_, S, V>,
+    operator: PublicAddress,
+    bd: &BlockchainParams,
+) -> (u128, u128)
+where
+    S: DB + Send + Sync + Clone + 'static,
+    V: VersionProvider + Send + Sync + Clone + 'static,
+{
+    let mut ws_cache = WorldStateCache::new(ws.clone());
+    let mut state = NetworkAccountWorldState {
+        gas_free_ws_cache: &mut ws_cache,
+    };
+
+    let current_epoch = NetworkAccount::new(&mut state).current_epoch();
+    let pool_length = NetworkAccount::vp(&mut state).length();
+    let pool_power = NetworkAccount::vp(&mut state)
+        .pool(operator)
+        .map_or(0, |mut pool| pool.power().unwrap_or(0));
+    if pool_power == 0 {
+        return (0, 1);
+    }
+
+    let (num_of_proposed_blocks, expected_num_of_blocks) = match &bd.validator_performance {
+        Some(block_performance) => (
+            block_performance
+                .stats
+                .get(&operator)
+                .map_or(0, |stats| stats.num_of_proposed_blocks),
+            block_performance.blocks_per_epoch / pool_length,
+        ),
+        None => (1, 1),
+    };
+    let reward = pool_reward(
+        current_epoch,
+        pool_power,
+        num_of_proposed_blocks,
+        expected_num_of_blocks,
+    );
+
+    (reward as u128 * EPOCHS_PER_YEAR as u128, pool_power as u128)
+}
+
+/* ↓↓↓ Ordered Delegated Stakes ↓↓↓ */
+
+/// Returns the delegated stakes of the pool of `operator` in the Network Account, sorted by power and then by owner,
+/// both ascending. Empty if there is no such pool.
+///
+/// The delegated stakes of a pool are kept in a heap, so the order of `delegated_stakes().unordered_values()` depends
+/// on the order in which stakes were inserted, changed and removed. Two pools holding the same stakes may list them
+/// differently. Tools which compare or display stakes should use this function instead.
+///
+/// The World State is not modified.
+pub fn ordered_delegated_stakes<S, V>(
+    ws: &WorldState<'_, S, V>,
+    operator: PublicAddress,
+) -> Vec<Stake>
+where
+    S: DB + Send + Sync + Clone + 'static,
+    V: VersionProvider + Send + Sync + Clone + 'static,
+{
+    let mut ws_cache = WorldStateCache::new(ws.clone());
+    let mut state = NetworkAccountWorldState {
+        gas_free_ws_cache: &mut ws_cache,
+    };
+
+    let mut stakes: Vec<Stake> = NetworkAccount::pools(&mut state, operator)
+        .delegated_stakes()
+        .unordered_values()
+        .iter()
+        .map(|stake| Stake {
+            owner: stake.owner,
+            power: stake.power,
+        })
+        .collect();
+    stakes.sort_by_key(|stake| (stake.power, stake.owner));
+    stakes
+}
+
 /// NetworkAccountWorldState is used only by Protocol Commands for accessing the world state of the Network Account.
 /// It implements NetworkAccountStorage to perform non-chargeable read-write operations to World State.
 pub(crate) struct NetworkAccountWorldState<'a, 'b, S, V>
@@ -257,6 +665,12 @@ where
             gas_free_ws_cache: state.ctx.gas_free_ws_cache_mut(),
         }
     }
+
+    pub(crate) fn from_ws_cache(ws_cache: &'b mut WorldStateCache<'a, S, V>) -> Self {
+        Self {
+            gas_free_ws_cache: ws_cache,
+        }
+    }
 }
 
 impl<'a, 'b, S, V> NetworkAccountStorage for NetworkAccountWorldState<'a, 'b, S, V>
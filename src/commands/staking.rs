@@ -23,8 +23,8 @@ use crate::{
         abort::{abort, abort_if_gas_exhausted},
         state::ExecutionState,
     },
-    gas::{blockchain_storage_cost, CostChange},
-    types::TxnVersion,
+    gas::CostChange,
+    types::{RuntimeEvent, TxnVersion},
     TransitionError,
 };
 
@@ -54,16 +54,28 @@ where
     pool.set_commission_rate(commission_rate);
     pool.set_operator_stake(None);
 
-    // Update NVP
+    // Update NVP. The new pool has no power, so it enters a full NVP only by displacing a pool which also has no
+    // power and a smaller operator address, by the same tie-break as in increase_stake_power.
     let _ = NetworkAccount::nvp(&mut state.ctx.gas_meter)
         .insert_extract(PoolKey { operator, power: 0 });
 
+    state
+        .ctx
+        .gas_meter
+        .command_output_push_event(RuntimeEvent::PoolCreated {
+            operator,
+            commission_rate,
+        });
+
     abort_if_gas_exhausted(state)
 }
 
 /* ↓↓↓ Set Pool Settings Command ↓↓↓ */
 
 /// Execution of [pchain_types::blockchain::Command::SetPoolSettings]
+///
+/// Setting the commission rate to its current value is rejected with [TransitionError::CommissionRateUnchanged],
+/// as is a no-op [SetDepositSettings](pchain_types::blockchain::Command::SetDepositSettings).
 pub(crate) fn set_pool_settings<S, E, V>(
     operator: PublicAddress,
     state: &mut ExecutionState<S, E, V>,
@@ -84,7 +96,7 @@ where
     }
 
     if pool.commission_rate() == Some(new_commission_rate) {
-        abort!(state, TransitionError::InvalidPoolPolicy)
+        abort!(state, TransitionError::CommissionRateUnchanged)
     }
 
     pool.set_commission_rate(new_commission_rate);
@@ -95,6 +107,10 @@ where
 /* ↓↓↓ Delete Pool Command ↓↓↓ */
 
 /// Execution of [pchain_types::blockchain::Command::DeletePool]
+///
+/// Only the pool and its entry in the NVP are deleted. Deposits to the pool are left untouched, including deposits
+/// created earlier in the same transaction, and can still be withdrawn by their owners with
+/// [WithdrawDeposit](pchain_types::blockchain::Command::WithdrawDeposit).
 pub(crate) fn delete_pool<S, E, V>(
     operator: PublicAddress,
     state: &mut ExecutionState<S, E, V>,
@@ -157,6 +173,9 @@ where
 /* ↓↓↓ Set Deposit Settings Command ↓↓↓ */
 
 /// Execution of [pchain_types::blockchain::Command::SetDepositSettings]
+///
+/// Setting auto stake rewards to its current value is rejected with [TransitionError::AutoStakeRewardsUnchanged],
+/// as is a no-op [SetPoolSettings](pchain_types::blockchain::Command::SetPoolSettings).
 pub(crate) fn set_deposit_settings<S, E, V>(
     owner: PublicAddress,
     state: &mut ExecutionState<S, E, V>,
@@ -173,7 +192,7 @@ where
     }
 
     if deposits.auto_stake_rewards() == Some(new_auto_stake_rewards) {
-        abort!(state, TransitionError::InvalidDepositPolicy)
+        abort!(state, TransitionError::AutoStakeRewardsUnchanged)
     }
 
     deposits.set_auto_stake_rewards(new_auto_stake_rewards);
@@ -218,6 +237,16 @@ where
 /* ↓↓↓ Withdraw Deposit Command ↓↓↓ */
 
 /// Execution of [pchain_types::blockchain::Command::WithdrawDeposit]
+///
+/// There is no minimum deposit balance, so a withdrawal may leave any non-zero balance in the deposit, however small.
+/// Such a balance is never stranded: it can be withdrawn by a later WithdrawDeposit once it is not locked by stakes.
+/// A deposit is deleted when its balance reaches zero.
+///
+/// `max_amount` is an upper bound: the command withdraws as much of it as is unlocked, i.e. at most the deposit balance
+/// minus the greater of the owner's stakes in the VP and the PVP, and returns the amount withdrawn. This holds for
+/// operators and delegators alike, and whether or not the pool has stakes in the PVP, e.g. before its first epoch.
+/// The command fails with [TransitionError::InvalidStakeAmount] only if nothing is withdrawn, e.g. the balance equals
+/// the stake in the VP but is below the stake in the PVP.
 pub(crate) fn withdraw_deposit<S, E, V>(
     owner: PublicAddress,
     state: &mut ExecutionState<S, E, V>,
@@ -237,40 +266,14 @@ where
     }
     let deposit_balance = deposits.balance().unwrap();
 
-    // 2. Compute withdrawal amount
-    let prev_epoch_locked_power =
-        NetworkAccount::pvp(gas_meter)
-            .pool(operator)
-            .map_or(0, |mut pool| {
-                if operator == owner {
-                    pool.operator_stake()
-                        .map_or(0, |stake| stake.map_or(0, |s| s.power))
-                } else {
-                    pool.delegated_stakes()
-                        .get_by(&owner)
-                        .map_or(0, |stake| stake.power)
-                }
-            });
-    let cur_epoch_locked_power =
-        NetworkAccount::vp(gas_meter)
-            .pool(operator)
-            .map_or(0, |mut pool| {
-                if operator == owner {
-                    pool.operator_stake()
-                        .map_or(0, |stake| stake.map_or(0, |s| s.power))
-                } else {
-                    pool.delegated_stakes()
-                        .get_by(&owner)
-                        .map_or(0, |stake| stake.power)
-                }
-            });
-    let locked_power = std::cmp::max(prev_epoch_locked_power, cur_epoch_locked_power);
+    // 2. Compute withdrawal amount: up to max_amount, out of the part of the deposit not locked by stakes
+    let locked_power = locked_stake_power(gas_meter, operator, owner);
     let withdrawal_amount = std::cmp::min(max_amount, deposit_balance.saturating_sub(locked_power));
-    let new_deposit_balance = deposit_balance.saturating_sub(withdrawal_amount);
+    let new_deposit_balance = deposit_balance - withdrawal_amount;
 
     // 3. Abort if there is no amount currently available to withdraw.
-    if new_deposit_balance == deposit_balance {
-        // e.g. max_amount = 0  or deposit_balance == locked_power
+    if withdrawal_amount == 0 {
+        // e.g. max_amount = 0  or deposit_balance <= locked_power
         abort!(state, TransitionError::InvalidStakeAmount)
     }
 
@@ -291,7 +294,7 @@ where
     if let Some(stake_power) = stake_of_pool(gas_meter, operator, owner) {
         if new_deposit_balance < stake_power {
             if let Some(prev_pool_power) = NetworkAccount::pools(gas_meter, operator).power() {
-                reduce_stake_power(
+                let amount_unstaked = reduce_stake_power(
                     gas_meter,
                     operator,
                     prev_pool_power,
@@ -299,19 +302,25 @@ where
                     stake_power,
                     stake_power - new_deposit_balance,
                 );
+                gas_meter.command_output_push_event(RuntimeEvent::StakeChanged {
+                    operator,
+                    owner,
+                    power: stake_power - amount_unstaked,
+                });
             }
         }
     }
 
     let ret_val_bytes = withdrawal_amount.to_le_bytes().to_vec();
+    let schedule = state.ctx.gas_meter.schedule;
     let ret_val_cost = match state.txn_meta.version {
         TxnVersion::V1 => {
-            CostChange::deduct(blockchain_storage_cost(ret_val_bytes.len()))
+            CostChange::deduct(schedule.blockchain_storage_cost(ret_val_bytes.len()))
                 .net_cost()
                 .0
         }
         TxnVersion::V2 => {
-            CostChange::deduct(blockchain_storage_cost(std::mem::size_of::<u64>()))
+            CostChange::deduct(schedule.blockchain_storage_cost(std::mem::size_of::<u64>()))
                 .net_cost()
                 .0
         }
@@ -391,16 +400,24 @@ where
         Ok(_) => {}
         Err(_) => abort!(state, TransitionError::InvalidStakeAmount),
     };
+    gas_meter.command_output_push_event(RuntimeEvent::StakeChanged {
+        operator,
+        owner,
+        power: stake_power
+            .unwrap_or(0)
+            .saturating_add(stake_power_to_increase),
+    });
 
     let amt_staked_bytes = stake_power_to_increase.to_le_bytes().to_vec();
+    let schedule = state.ctx.gas_meter.schedule;
     let amt_staked_bytes_cost = match state.txn_meta.version {
         TxnVersion::V1 => {
-            CostChange::deduct(blockchain_storage_cost(amt_staked_bytes.len()))
+            CostChange::deduct(schedule.blockchain_storage_cost(amt_staked_bytes.len()))
                 .net_cost()
                 .0
         }
         TxnVersion::V2 => {
-            CostChange::deduct(blockchain_storage_cost(std::mem::size_of::<u64>()))
+            CostChange::deduct(schedule.blockchain_storage_cost(std::mem::size_of::<u64>()))
                 .net_cost()
                 .0
         }
@@ -472,16 +489,22 @@ where
         stake_power,
         max_amount,
     );
+    gas_meter.command_output_push_event(RuntimeEvent::StakeChanged {
+        operator,
+        owner,
+        power: stake_power - amount_unstaked,
+    });
 
     let amt_unstaked_bytes = amount_unstaked.to_le_bytes().to_vec();
+    let schedule = state.ctx.gas_meter.schedule;
     let amt_unstaked_bytes_cost = match state.txn_meta.version {
         TxnVersion::V1 => {
-            CostChange::deduct(blockchain_storage_cost(amt_unstaked_bytes.len()))
+            CostChange::deduct(schedule.blockchain_storage_cost(amt_unstaked_bytes.len()))
                 .net_cost()
                 .0
         }
         TxnVersion::V2 => {
-            CostChange::deduct(blockchain_storage_cost(std::mem::size_of::<u64>()))
+            CostChange::deduct(schedule.blockchain_storage_cost(std::mem::size_of::<u64>()))
                 .net_cost()
                 .0
         }
@@ -537,6 +560,44 @@ where
     }
 }
 
+/// Power of the owner's stake in the pool of the operator which locks its deposit, i.e. the greater of its stakes in
+/// the previous validator set (PVP) and the current validator set (VP). Stakes absent from a set have no power.
+pub(crate) fn locked_stake_power<T>(
+    state: &mut T,
+    operator: PublicAddress,
+    owner: PublicAddress,
+) -> u64
+where
+    T: NetworkAccountStorage,
+{
+    let prev_epoch_locked_power =
+        NetworkAccount::pvp(state)
+            .pool(operator)
+            .map_or(0, |mut pool| {
+                if operator == owner {
+                    pool.operator_stake()
+                        .map_or(0, |stake| stake.map_or(0, |s| s.power))
+                } else {
+                    pool.delegated_stakes()
+                        .get_by(&owner)
+                        .map_or(0, |stake| stake.power)
+                }
+            });
+    let cur_epoch_locked_power = NetworkAccount::vp(state)
+        .pool(operator)
+        .map_or(0, |mut pool| {
+            if operator == owner {
+                pool.operator_stake()
+                    .map_or(0, |stake| stake.map_or(0, |s| s.power))
+            } else {
+                pool.delegated_stakes()
+                    .get_by(&owner)
+                    .map_or(0, |stake| stake.power)
+            }
+        });
+    std::cmp::max(prev_epoch_locked_power, cur_epoch_locked_power)
+}
+
 /// Reduce stake's power and update Pool position in Next validator set.
 pub(crate) fn reduce_stake_power<T>(
     state: &mut T,
@@ -664,6 +725,9 @@ where
             NetworkAccount::nvp(state).change_key(pool_key);
         }
         None => {
+            // If the NVP is full, the pool displaces the pool with the smallest key only if its own key is greater.
+            // Keys are ordered by power, then by operator address, so on equal power the pool with the greater
+            // operator address is kept, whether it is the incumbent or the new pool.
             let _ = NetworkAccount::nvp(state).insert_extract(PoolKey {
                 operator,
                 power: new_pool_power,
@@ -11,12 +11,16 @@
 //!
 //! When used during [contract](crate::contract) execution, the context also maintains intermediate metadata such as
 //! deferred commands generated during execution and metadata of the contract instance.
+use std::sync::Arc;
+
 use pchain_world_state::{VersionProvider, WorldState, DB};
 
 use crate::{
-    contract::SmartContractContext,
-    execution::cache::WorldStateCache,
+    contract::{FuncError, SmartContractContext},
+    execution::cache::{MaybeUnused, WorldStateCache},
+    fee_distribution::FeeDistributor,
     gas::GasMeter,
+    state_rent::StateRentPolicy,
     types::{CommandOutput, DeferredCommand, TxnVersion},
 };
 
@@ -38,6 +42,25 @@ where
 
     /// GasMeter for the transaction, encapsulates World State access and gas tallying
     pub gas_meter: GasMeter<'a, S, V>,
+
+    /// Policy for charging state rent in the Charge phase. No rent is charged if it is None.
+    pub state_rent_policy: Option<Arc<dyn StateRentPolicy>>,
+
+    /// Distributor of the fees in the Charge phase. The default distribution is used if it is None.
+    pub fee_distributor: Option<Arc<dyn FeeDistributor>>,
+
+    /// Total value transferred out of contracts so far in the transaction, either by the `transfer`
+    /// host function or as the amount of a contract-to-contract call.
+    pub contract_transfer_value: u64,
+
+    /// Skip checking the nonce of the transaction against the nonce of its signer. Only for simulation.
+    pub skip_nonce_check: bool,
+
+    /// Record the balances changed by the transaction in the Charge phase.
+    pub record_balance_changes: bool,
+
+    /// Total number of commands deferred by contracts so far in the transaction.
+    pub spawned_commands: u32,
 }
 
 impl<'a, S, V> TransitionContext<'a, S, V>
@@ -47,18 +70,42 @@ where
 {
     /// initialize a new Transition Context, at the beginning of a new transaction
     pub fn new(version: TxnVersion, ws: WorldState<'a, S, V>, gas_limit: u64) -> Self {
-        let host_gm = GasMeter::new(version, WorldStateCache::new(ws), gas_limit);
+        Self::from_ws_cache(version, WorldStateCache::new(ws), gas_limit)
+    }
+
+    /// initialize a new Transition Context from a World State Cache which may hold writes
+    /// of earlier transactions that are not yet committed to the World State
+    pub fn from_ws_cache(
+        version: TxnVersion,
+        ws_cache: WorldStateCache<'a, S, V>,
+        gas_limit: u64,
+    ) -> Self {
+        let host_gm = GasMeter::new(version, ws_cache, gas_limit);
 
         Self {
             sc_context: Default::default(),
             deferred_commands: Vec::new(),
             gas_meter: host_gm,
+            state_rent_policy: None,
+            fee_distributor: None,
+            contract_transfer_value: 0,
+            skip_nonce_check: false,
+            record_balance_changes: false,
+            spawned_commands: 0,
         }
     }
 
-    /// Add a deferred command to the context.
-    pub fn append_deferred_command(&mut self, cmd: DeferredCommand) {
+    /// Add a deferred command to the context. Fails if contracts have already deferred the
+    /// [maximum number of commands](SmartContractContext::max_spawned_commands) in the transaction.
+    pub fn append_deferred_command(&mut self, cmd: DeferredCommand) -> Result<(), FuncError> {
+        if let Some(max_spawned_commands) = self.sc_context.max_spawned_commands {
+            if self.spawned_commands >= max_spawned_commands {
+                return Err(FuncError::SpawnedCommandLimitExceeded);
+            }
+        }
+        self.spawned_commands += 1;
         self.deferred_commands.push(cmd);
+        Ok(())
     }
 
     /// Clones smart contract context for nested contract calls
@@ -66,6 +113,13 @@ where
         self.sc_context.clone()
     }
 
+    /// Remaining value that contracts may transfer in this transaction, or None if it is unlimited.
+    pub fn contract_transfer_allowance(&self) -> Option<u64> {
+        self.sc_context
+            .max_contract_transfer_value
+            .map(|max| max.saturating_sub(self.contract_transfer_value))
+    }
+
     /// Get the World State Cache which allows read-write without gas metering.
     pub fn gas_free_ws_cache(&self) -> &WorldStateCache<'a, S, V> {
         &self.gas_meter.ws_cache
@@ -87,6 +141,47 @@ where
         self.gas_meter.ws_cache.revert();
     }
 
+    /// Marks the state that [rollback](Self::rollback) returns to, before a contract call whose failure should not
+    /// fail its caller. Only the changes made after the savepoint are recorded, so its cost grows with the changes of
+    /// the call rather than with those of the transaction so far.
+    ///
+    /// A savepoint which is neither rolled back nor [released](Self::release), e.g. because the call failed its
+    /// caller, is closed by the rollback or release of an enclosing savepoint, or by reverting the changes.
+    pub fn savepoint(&mut self) -> Savepoint {
+        let output = &self.gas_meter.output_cache_of_current_command;
+        Savepoint {
+            depth: self.gas_meter.ws_cache.savepoint(),
+            num_logs: output.logs.len(),
+            return_value: output.return_value.clone(),
+            num_events: output.events.len(),
+            num_deferred_commands: self.deferred_commands.len(),
+            contract_transfer_value: self.contract_transfer_value,
+            spawned_commands: self.spawned_commands,
+        }
+    }
+
+    /// Discards the world state changes, logs, return value, events and deferred commands made since the
+    /// `savepoint`. Gas used since then is still charged.
+    pub fn rollback(&mut self, savepoint: Savepoint) {
+        self.gas_meter
+            .ws_cache
+            .rollback_to_savepoint(savepoint.depth);
+        let output = &mut self.gas_meter.output_cache_of_current_command;
+        output.logs.truncate(savepoint.num_logs);
+        output.return_value = savepoint.return_value;
+        output.events.truncate(savepoint.num_events);
+        self.deferred_commands
+            .truncate(savepoint.num_deferred_commands);
+        self.contract_transfer_value = savepoint.contract_transfer_value;
+        self.spawned_commands = savepoint.spawned_commands;
+    }
+
+    /// Keeps the changes made since the `savepoint`, e.g. after the contract call succeeded. They are still discarded
+    /// if an enclosing savepoint is rolled back.
+    pub fn release(&mut self, savepoint: Savepoint) {
+        self.gas_meter.ws_cache.release_savepoint(savepoint.depth);
+    }
+
     /// Outputs the CommandReceipt and clears the intermediate context for next command execution.
     // IMPORTANT: This function must be called after each command execution, whether success or fail
     // as all the tallying and state changes happen here.
@@ -101,3 +196,14 @@ where
         (gas_used, command_output, deferred_commands)
     }
 }
+
+/// State of a [TransitionContext] taken by [savepoint](TransitionContext::savepoint).
+pub(crate) struct Savepoint {
+    depth: usize,
+    num_logs: usize,
+    return_value: MaybeUnused<Vec<u8>>,
+    num_events: usize,
+    num_deferred_commands: usize,
+    contract_transfer_value: u64,
+    spawned_commands: u32,
+}
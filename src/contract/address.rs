@@ -0,0 +1,25 @@
+/*
+    Copyright © 2023, ParallelChain Lab
+    Licensed under the Apache License, Version 2.0: http://www.apache.org/licenses/LICENSE-2.0
+*/
+
+//! Derives the addresses that [Deploy](pchain_types::blockchain::Command::Deploy) commands deploy contracts to.
+//!
+//! The address of a contract is determined by the transaction that deploys it, so it can be computed before the
+//! transaction is executed, e.g. to transfer tokens to the contract earlier in the same transaction. The Deploy
+//! command computes its destination with these functions.
+
+use pchain_types::cryptography::PublicAddress;
+
+/// Address of the contract deployed by a Deploy command in a [TransactionV1](pchain_types::blockchain::TransactionV1)
+/// of `signer` with `nonce`.
+pub fn contract_address_v1(signer: PublicAddress, nonce: u64) -> PublicAddress {
+    pchain_types::cryptography::contract_address_v1(&signer, nonce)
+}
+
+/// Address of the contract deployed by the Deploy command at `command_index` among the commands of a
+/// [TransactionV2](pchain_types::blockchain::TransactionV2) of `signer` with `nonce`. Unlike in V1, a transaction
+/// may deploy several contracts, each to a different address.
+pub fn contract_address_v2(signer: PublicAddress, nonce: u64, command_index: u32) -> PublicAddress {
+    pchain_types::cryptography::contract_address_v2(&signer, nonce, command_index)
+}
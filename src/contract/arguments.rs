@@ -0,0 +1,29 @@
+/*
+    Copyright © 2023, ParallelChain Lab
+    Licensed under the Apache License, Version 2.0: http://www.apache.org/licenses/LICENSE-2.0
+*/
+
+//! Encodes the arguments of contract calls, e.g. to prepare the `arguments` of a
+//! [Call](pchain_types::blockchain::Command::Call) command or of a view call.
+//!
+//! Arguments are passed to a contract as a list of byte strings, one for each parameter of the called method. A
+//! contract built with the ParallelChain SDK decodes each byte string as the Borsh serialization of the value of its
+//! parameter, which [encode_call_arg] produces. The contract reads the whole list with the `arguments` host function,
+//! which writes it into the memory of the contract encoded by [encode_call_args].
+
+use borsh::BorshSerialize;
+
+/// Encodes `arg` as an element of the arguments of a contract call, i.e. its Borsh serialization, e.g.
+/// `vec![encode_call_arg(&"name".to_string()), encode_call_arg(&10u64)]` for a method taking a String and a u64.
+pub fn encode_call_arg<T: BorshSerialize>(arg: &T) -> Vec<u8> {
+    arg.try_to_vec()
+        .expect("Serializing into a Vec should not fail")
+}
+
+/// The bytes of `args` as the `arguments` host function writes them into the memory of a contract: the number of
+/// arguments as a little-endian u32, then each argument prefixed with its length as a little-endian u32. The method
+/// name is not part of the arguments.
+pub fn encode_call_args(args: &[Vec<u8>]) -> Vec<u8> {
+    args.try_to_vec()
+        .expect("Serializing into a Vec should not fail")
+}
@@ -6,7 +6,9 @@
 //! Defines the signature of Wasm host functions that are imported by ParallelChain Smart Contracts
 //!
 //! The definitions follow the CBI Version 0 specification in
-//! [ParallelChain protocol](https://github.com/parallelchain-io/parallelchain-protocol/blob/master/Contracts.md).
+//! [ParallelChain protocol](https://github.com/parallelchain-io/parallelchain-protocol/blob/master/Contracts.md),
+//! and CBI Version 1, which adds `block_proposer`, `get_len`, `current_epoch`, `block_random_bytes`,
+//! `call_with_gas_limit` and `gas_remaining`.
 //!
 //! In the context of Wasm, these host functions provide an API between
 //! the guest smart contract code and the blockchain environment.
@@ -15,7 +17,7 @@
 
 use wasmer::{imports, Function, ImportObject, Store};
 
-use super::wasmer::instance::MethodCallError;
+use super::wasmer::{instance::MethodCallError, memory::MemoryAccessError};
 
 /// CBIHostFunctions defines the interface of host functions used in [wasmer::WasmerEnv].
 /// The Import object that is provided to the Wasm module during instantiation must implement these functions.
@@ -40,8 +42,16 @@ where
 
     /// Gets the value corresponding to a key in the current Contract Account’s Storage.
     /// It returns the length of the value.
+    ///
+    /// A value set earlier in the same transaction, including earlier in the same call, is returned in place of the
+    /// value in the World State.
     fn get(env: &T, key_ptr: u32, key_len: u32, value_ptr_ptr: u32) -> Result<i64, FuncError>;
 
+    /// Gets the length of the value corresponding to a key in the current Contract Account’s Storage,
+    /// without writing the value into linear memory. It returns -1 if the key has no value. Only contracts of CBI
+    /// Version 1 or later may import this function.
+    fn get_len(env: &T, key_ptr: u32, key_len: u32) -> Result<i64, FuncError>;
+
     /// Gets the value corresponding to a key in the Network Account’s Storage.
     /// It returns the length of the value.
     fn get_network_storage(
@@ -64,6 +74,21 @@ where
     /// - `hash_ptr_ptr` references the memory location to store the 32-byte hash.
     fn prev_block_hash(env: &T, hash_ptr_ptr: u32) -> Result<(), FuncError>;
 
+    /// Get the Address of the Proposer of the Block which includes the Transaction containing the current Call. Only
+    /// contracts of CBI Version 1 or later may import this function.
+    /// - `address_ptr_ptr` references the memory location to store the 32-byte address.
+    fn block_proposer(env: &T, address_ptr_ptr: u32) -> Result<(), FuncError>;
+
+    /// Get the Random Bytes of the Block which includes the Transaction containing the current Call, e.g. to seed
+    /// on-chain randomness. Only contracts of CBI Version 1 or later may import this function.
+    /// - `random_bytes_ptr_ptr` references the memory location to store the 32 bytes.
+    fn block_random_bytes(env: &T, random_bytes_ptr_ptr: u32) -> Result<(), FuncError>;
+
+    /// Gets the number of the current Epoch, as recorded in the Network Account's Storage. Epochs are ended by the
+    /// protocol with a NextEpoch Command, so the number of Blocks remaining in the current Epoch is not known. Only
+    /// contracts of CBI Version 1 or later may import this function.
+    fn current_epoch(env: &T) -> Result<u64, FuncError>;
+
     /// Gets the Address of the Account that triggered the current Call. This could either be an External
     /// Account (if the Call is directly triggered by a Call Transaction), or a Contract Account (if the Call is an Internal Call).
     /// - `address_ptr_ptr` references the memory location to store the 32-bytes address.
@@ -105,6 +130,27 @@ where
         rval_ptr_ptr: u32,
     ) -> Result<u32, FuncError>;
 
+    /// call methods of another contract with at most `gas_limit` gas. Only contracts of CBI Version 1 or later may
+    /// import this function.
+    ///
+    /// If the called contract runs out of its `gas_limit` while the calling contract still has gas, the changes made
+    /// by the call are discarded and the calling contract continues, e.g. to fall back on another method. The gas used
+    /// by the call is charged in either case. Other failures of the call fail the calling contract, as in `call`.
+    /// - `call_ptr` references the memory location which stores input args to [pchain_types::blockchain::Command::Call]
+    /// - `return_ptr_ptr` references the memory location to store the return value
+    /// - returns the length of Return Value, or -1 if the call ran out of its `gas_limit`.
+    fn call_with_gas_limit(
+        env: &T,
+        call_input_ptr: u32,
+        call_input_len: u32,
+        gas_limit: u64,
+        rval_ptr_ptr: u32,
+    ) -> Result<i64, FuncError>;
+
+    /// Gets the gas remaining for the current Call, after charging for this function, e.g. to decide whether to attempt
+    /// an expensive operation. Only contracts of CBI Version 1 or later may import this function.
+    fn gas_remaining(env: &T) -> Result<u64, FuncError>;
+
     /// Sets return value of contract execution, which will be stored in the resulting receipt.
     /// - `value_ptr` references the memory location which stores the return value
     fn return_value(env: &T, value_ptr: u32, value_len: u32) -> Result<(), FuncError>;
@@ -170,6 +216,9 @@ where
 
     /// Computes the Keccak256 digest of arbitrary input.
     /// `digest_ptr_ptr` references the memory location to store the 32-byte digest
+    ///
+    /// The hashing and node encoding of the tries which make up the World State root are defined by
+    /// [pchain_world_state], not by the Runtime, so no host function reproduces them.
     fn keccak256(env: &T, msg_ptr: u32, msg_len: u32, digest_ptr_ptr: u32)
         -> Result<(), FuncError>;
 
@@ -199,12 +248,16 @@ where
             "env" => {
                 "set" =>  Function::new_native_with_env(store, env.clone(), K::set),
                 "get" => Function::new_native_with_env(store, env.clone(), K::get),
+                "get_len" => Function::new_native_with_env(store, env.clone(), K::get_len),
                 "get_network_storage" => Function::new_native_with_env(store, env.clone(), K::get_network_storage),
                 "balance" => Function::new_native_with_env(store, env.clone(), K::balance),
 
                 "block_height" => Function::new_native_with_env(store, env.clone(), K::block_height),
                 "block_timestamp" => Function::new_native_with_env(store, env.clone(), K::block_timestamp),
                 "prev_block_hash" => Function::new_native_with_env(store, env.clone(), K::prev_block_hash),
+                "block_proposer" => Function::new_native_with_env(store, env.clone(), K::block_proposer),
+                "block_random_bytes" => Function::new_native_with_env(store, env.clone(), K::block_random_bytes),
+                "current_epoch" => Function::new_native_with_env(store, env.clone(), K::current_epoch),
 
                 "calling_account" => Function::new_native_with_env(store, env.clone(), K::calling_account),
                 "current_account" => Function::new_native_with_env(store, env.clone(), K::current_account),
@@ -215,6 +268,8 @@ where
                 "transaction_hash" => Function::new_native_with_env(store, env.clone(), K::transaction_hash),
 
                 "call" => Function::new_native_with_env(store, env.clone(), K::call),
+                "call_with_gas_limit" => Function::new_native_with_env(store, env.clone(), K::call_with_gas_limit),
+                "gas_remaining" => Function::new_native_with_env(store, env.clone(), K::gas_remaining),
                 "return_value" => Function::new_native_with_env(store, env.clone(), K::return_value),
                 "transfer" => Function::new_native_with_env(store, env.clone(), K::transfer),
                 "defer_create_deposit" => Function::new_native_with_env(store, env.clone(), K::defer_create_deposit),
@@ -247,12 +302,16 @@ where
             "env" => {
                 "set" => Function::new_native(store, not_callable::set),
                 "get" => Function::new_native_with_env(store, env.clone(), K::get),
+                "get_len" => Function::new_native_with_env(store, env.clone(), K::get_len),
                 "get_network_storage" => Function::new_native_with_env(store, env.clone(), K::get_network_storage),
                 "balance" => Function::new_native_with_env(store, env.clone(), K::balance),
 
                 "block_height" => Function::new_native(store, not_callable::block_height),
                 "block_timestamp" => Function::new_native(store, not_callable::block_timestamp),
                 "prev_block_hash" => Function::new_native(store, not_callable::prev_block_hash),
+                "block_proposer" => Function::new_native(store, not_callable::block_proposer),
+                "block_random_bytes" => Function::new_native(store, not_callable::block_random_bytes),
+                "current_epoch" => Function::new_native_with_env(store, env.clone(), K::current_epoch),
 
                 "calling_account" => Function::new_native(store, not_callable::calling_account),
                 "current_account" => Function::new_native_with_env(store, env.clone(), K::current_account),
@@ -263,6 +322,8 @@ where
                 "transaction_hash" => Function::new_native(store, not_callable::transaction_hash),
 
                 "call" => Function::new_native_with_env(store, env.clone(), K::call),
+                "call_with_gas_limit" => Function::new_native_with_env(store, env.clone(), K::call_with_gas_limit),
+                "gas_remaining" => Function::new_native_with_env(store, env.clone(), K::gas_remaining),
                 "return_value" => Function::new_native_with_env(store, env.clone(), K::return_value),
                 "transfer" => Function::new_native(store, not_callable::transfer),
                 "defer_create_deposit" => Function::new_native(store, not_callable::defer_create_deposit),
@@ -285,15 +346,20 @@ where
 }
 
 /// Struct holding components needed to instantiate a contract module
-pub(crate) struct Importable<'a>(pub(crate) ImportObject, &'a Store);
+pub(crate) struct Importable<'a>(pub(crate) ImportObject, pub(crate) &'a Store);
 
 /// A set of host functions with empty implementations, used only during module validation
 /// to instantiate a contract for extracting its exported metadata without executing any of its methods.
+///
+/// Only the host functions of the contract's CBI version are included, so that a contract which imports a host
+/// function introduced in a later CBI version fails to instantiate.
 pub(crate) mod empty {
-    use wasmer::{imports, Function, Store};
+    use wasmer::{imports, Exports, Function, Store};
 
-    pub(crate) fn imports(store: &Store) -> wasmer::ImportObject {
-        imports! {
+    use crate::contract::imports_extended_host_functions;
+
+    pub(crate) fn imports(store: &Store, cbi_version: u32) -> wasmer::ImportObject {
+        let mut import_object = imports! {
             "env" => {
                 "set" => Function::new_native(store, set),
                 "get" => Function::new_native(store, get),
@@ -329,13 +395,40 @@ pub(crate) mod empty {
                 "ripemd" => Function::new_native(store, ripemd),
                 "verify_ed25519_signature" => Function::new_native(store, verify_ed25519_signature),
             }
+        };
+
+        if imports_extended_host_functions(cbi_version) {
+            let mut exports = import_object
+                .get_namespace_exports("env")
+                .unwrap_or_else(Exports::new);
+            exports.insert(
+                "block_proposer",
+                Function::new_native(store, block_proposer),
+            );
+            exports.insert("get_len", Function::new_native(store, get_len));
+            exports.insert("current_epoch", Function::new_native(store, current_epoch));
+            exports.insert(
+                "block_random_bytes",
+                Function::new_native(store, block_random_bytes),
+            );
+            exports.insert(
+                "call_with_gas_limit",
+                Function::new_native(store, call_with_gas_limit),
+            );
+            exports.insert("gas_remaining", Function::new_native(store, gas_remaining));
+            import_object.register("env", exports);
         }
+
+        import_object
     }
 
     pub(crate) fn set(_: u32, _: u32, _: u32, _: u32) {}
     pub(crate) fn get(_: u32, _: u32, _: u32) -> i64 {
         0
     }
+    pub(crate) fn get_len(_: u32, _: u32) -> i64 {
+        0
+    }
     pub(crate) fn get_network_storage(_: u32, _: u32, _: u32) -> i64 {
         0
     }
@@ -350,6 +443,11 @@ pub(crate) mod empty {
         0
     }
     pub(crate) fn prev_block_hash(_: u32) {}
+    pub(crate) fn block_proposer(_: u32) {}
+    pub(crate) fn block_random_bytes(_: u32) {}
+    pub(crate) fn current_epoch() -> u64 {
+        0
+    }
 
     pub(crate) fn calling_account(_: u32) {}
     pub(crate) fn current_account(_: u32) {}
@@ -370,6 +468,12 @@ pub(crate) mod empty {
     pub(crate) fn call(_: u32, _: u32, _: u32) -> u32 {
         0
     }
+    pub(crate) fn call_with_gas_limit(_: u32, _: u32, _: u64, _: u32) -> i64 {
+        0
+    }
+    pub(crate) fn gas_remaining() -> u64 {
+        0
+    }
     pub(crate) fn return_value(_: u32, _: u32) {}
     pub(crate) fn transfer(_: u32) {}
     pub(crate) fn defer_create_deposit(_: u32, _: u32) {}
@@ -406,6 +510,12 @@ mod not_callable {
     pub(crate) fn prev_block_hash(_: u32) -> Result<(), FuncError> {
         Err(FuncError::Internal)
     }
+    pub(crate) fn block_proposer(_: u32) -> Result<(), FuncError> {
+        Err(FuncError::Internal)
+    }
+    pub(crate) fn block_random_bytes(_: u32) -> Result<(), FuncError> {
+        Err(FuncError::Internal)
+    }
 
     pub(crate) fn calling_account(_: u32) -> Result<(), FuncError> {
         Err(FuncError::Internal)
@@ -452,6 +562,10 @@ pub enum FuncError {
     #[error("GasExhaustionError")]
     GasExhaustionError,
 
+    /// Contract passed a pointer or length out of the bounds of its linear memory, or failed to allocate memory
+    #[error("MemoryAccess")]
+    MemoryAccess(MemoryAccessError),
+
     /// MethodCallError inside host function is the error from CtoC call.
     #[error("Runtime")]
     MethodCallError(MethodCallError),
@@ -462,6 +576,22 @@ pub enum FuncError {
 
     #[error("InsufficientBalance")]
     InsufficientBalance,
+
+    /// Storage key passed to a host function exceeds the configured maximum key length
+    #[error("KeyTooLong")]
+    KeyTooLong,
+
+    /// Contract call is nested deeper than the configured maximum call depth
+    #[error("CallDepthExceeded")]
+    CallDepthExceeded,
+
+    /// Contract transfer exceeds the configured maximum value that contracts may transfer in a transaction
+    #[error("TransferLimitExceeded")]
+    TransferLimitExceeded,
+
+    /// Contract defers a command beyond the configured maximum number of deferred commands in a transaction
+    #[error("SpawnedCommandLimitExceeded")]
+    SpawnedCommandLimitExceeded,
 }
 
 impl From<wasmer::RuntimeError> for FuncError {
@@ -470,6 +600,12 @@ impl From<wasmer::RuntimeError> for FuncError {
     }
 }
 
+impl From<MemoryAccessError> for FuncError {
+    fn from(e: MemoryAccessError) -> Self {
+        Self::MemoryAccess(e)
+    }
+}
+
 impl From<anyhow::Error> for FuncError {
     fn from(e: anyhow::Error) -> Self {
         Self::Runtime(e)
@@ -8,17 +8,25 @@
 //! Each version codifies specifications that smart contracts need to follow.
 
 /// current CBI version
-pub const CBI_VERSION: u32 = CBIVER_ADAM;
+pub const CBI_VERSION: u32 = CBIVER_EXTENDED;
 
 /// CBI version defined in protocol v0.4 and v0.5.
 const CBIVER_ADAM: u32 = 0;
 
+/// CBI version which adds host functions to those of [CBIVER_ADAM]. They are listed in the
+/// [CBI host functions](super::cbi_host_functions).
+const CBIVER_EXTENDED: u32 = 1;
+
 /// check if the given CBI version is compatible with the current CBI version
-#[allow(clippy::absurd_extreme_comparisons)]
 pub(crate) const fn is_cbi_compatible(version: u32) -> bool {
     version <= CBI_VERSION
 }
 
+/// check if contracts of the given CBI version may import the host functions added after [CBIVER_ADAM]
+pub(crate) const fn imports_extended_host_functions(version: u32) -> bool {
+    version > CBIVER_ADAM
+}
+
 /// returns present CBI versin
 #[inline]
 pub const fn cbi_version() -> u32 {
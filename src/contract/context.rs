@@ -7,7 +7,23 @@
 //!
 //! The [SmartContractContext] is initialized in the Runtime and passed to [TransitionContext](crate::context::TransitionContext).
 //! It holds settings specific to contract execution and uses a cache to optimize loading times for smart contracts.
-use super::wasmer::cache::Cache;
+use std::{
+    collections::HashMap,
+    sync::{atomic::AtomicBool, Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use pchain_types::cryptography::PublicAddress;
+
+use super::wasmer::{cache::Cache, instance::CallWatcher, store::CompilerBackend};
+
+/// Default upper bound on the length of a storage key passed to the `set` and `get` host functions.
+/// It is set high enough that no existing contract is affected.
+pub const DEFAULT_MAX_STORAGE_KEY_LENGTH: usize = 65_536;
+
+/// Default upper bound on the depth of contract-to-contract calls within a Call command.
+/// It is set high enough that only pathological call chains are affected.
+pub const DEFAULT_MAX_CALL_DEPTH: u32 = 256;
 
 /// Smart Contract Context responsibilities include:
 /// - Holding a cache instance for compiled Wasm modules
@@ -18,4 +34,122 @@ pub(crate) struct SmartContractContext {
     pub cache: Option<Cache>,
     /// smart contract VM memory limit
     pub memory_limit: Option<usize>,
+    /// maximum length of a contract storage key. Defaults to [DEFAULT_MAX_STORAGE_KEY_LENGTH]
+    pub max_storage_key_length: Option<usize>,
+    /// maximum depth of contract-to-contract calls. Defaults to [DEFAULT_MAX_CALL_DEPTH]
+    pub max_call_depth: Option<u32>,
+    /// compiler used to translate contract bytecode into machine code
+    pub compiler_backend: CompilerBackend,
+    /// whether to record the peak number of linear memory pages used by each contract call
+    pub memory_profiling: bool,
+    /// maximum total value that contracts may transfer within a transaction. Unlimited if it is None.
+    pub max_contract_transfer_value: Option<u64>,
+    /// maximum length of contract bytecode accepted by a Deploy command. Unlimited if it is None.
+    pub max_contract_size: Option<usize>,
+    /// maximum number of commands that contracts may defer within a transaction. Unlimited if it is None.
+    pub max_spawned_commands: Option<u32>,
+    /// multipliers, in percent, of the gas used by Wasm execution of specific contracts
+    pub gas_multipliers: Option<Arc<HashMap<PublicAddress, u64>>>,
+    /// cumulative time allowed for compiling contracts with `compiler_backend` within a block. Unlimited if it is None.
+    pub compilation_time_budget: Option<Duration>,
+    /// time spent compiling contracts in the current block. Only set within a block with a compilation time budget.
+    pub compilation_time_used: Option<Arc<Mutex<Duration>>>,
+    /// flag which stops the execution of contracts when it is set
+    pub interrupt: Option<Arc<AtomicBool>>,
+    /// wall-clock time allowed for each Call command. Only set for view calls and estimates. Unlimited if it is None.
+    pub command_time_budget: Option<Duration>,
+    /// time by which the current Call command must complete, set by [start_command_timer](Self::start_command_timer)
+    pub command_deadline: Option<Instant>,
+    /// watcher of the contract calls of the current transaction, started by [call_watcher](Self::call_watcher)
+    pub call_watcher: Option<Arc<CallWatcher>>,
+    /// host functions which replace those imported by contracts, for testing
+    #[cfg(feature = "host-function-overrides")]
+    pub host_function_overrides: Option<super::overrides::HostFunctionOverrides>,
+}
+
+impl SmartContractContext {
+    /// returns the configured maximum storage key length, or the default if it is not set
+    pub fn max_storage_key_length(&self) -> usize {
+        self.max_storage_key_length
+            .unwrap_or(DEFAULT_MAX_STORAGE_KEY_LENGTH)
+    }
+
+    /// returns the configured maximum call depth, or the default if it is not set
+    pub fn max_call_depth(&self) -> u32 {
+        self.max_call_depth.unwrap_or(DEFAULT_MAX_CALL_DEPTH)
+    }
+
+    /// returns `gas` used by Wasm execution of the contract at `address`, scaled by its gas multiplier.
+    /// Multipliers below 100 percent are treated as 100 percent, so execution is never cheaper than the gas schedule.
+    pub fn apply_gas_multiplier(&self, address: &PublicAddress, gas: u64) -> u64 {
+        match self
+            .gas_multipliers
+            .as_ref()
+            .and_then(|multipliers| multipliers.get(address))
+        {
+            Some(&percent) if percent > 100 => {
+                std::cmp::min(gas as u128 * percent as u128 / 100, u64::MAX as u128) as u64
+            }
+            _ => gas,
+        }
+    }
+
+    /// returns the gas that Wasm execution of the contract at `address` may use, such that the gas scaled by its gas
+    /// multiplier is at most `gas_limit`. The inverse of [apply_gas_multiplier](Self::apply_gas_multiplier).
+    pub fn gas_limit_before_multiplier(&self, address: &PublicAddress, gas_limit: u64) -> u64 {
+        match self
+            .gas_multipliers
+            .as_ref()
+            .and_then(|multipliers| multipliers.get(address))
+        {
+            Some(&percent) if percent > 100 => (gas_limit as u128 * 100 / percent as u128) as u64,
+            _ => gas_limit,
+        }
+    }
+
+    /// returns a copy of the context which accounts the compilation time of a new block, if a compilation time
+    /// budget is set
+    pub fn for_block(&self) -> Self {
+        let mut sc_context = self.clone();
+        if sc_context.compilation_time_budget.is_some() {
+            sc_context.compilation_time_used = Some(Arc::new(Mutex::new(Duration::ZERO)));
+        }
+        sc_context
+    }
+
+    /// starts the wall-clock time budget of a Call command, if there is one
+    pub fn start_command_timer(&mut self) {
+        self.command_deadline = self
+            .command_time_budget
+            .map(|budget| Instant::now() + budget);
+    }
+
+    /// returns the watcher which stops the contract calls of the current transaction, starting it on the first call.
+    /// None if contract calls can be stopped neither by an interrupt flag nor by a command time budget.
+    pub fn call_watcher(&mut self) -> Option<Arc<CallWatcher>> {
+        if self.interrupt.is_none() && self.command_time_budget.is_none() {
+            return None;
+        }
+        let interrupt = self.interrupt.clone();
+        Some(
+            self.call_watcher
+                .get_or_insert_with(|| CallWatcher::start(interrupt))
+                .clone(),
+        )
+    }
+
+    /// returns whether the time spent compiling contracts in the current block exceeds its compilation time budget
+    pub fn compilation_time_exceeded(&self) -> bool {
+        match (&self.compilation_time_budget, &self.compilation_time_used) {
+            (Some(budget), Some(used)) => *used.lock().unwrap() > *budget,
+            _ => false,
+        }
+    }
+
+    /// adds `elapsed` to the time spent compiling contracts in the current block
+    pub fn record_compilation_time(&self, elapsed: Duration) {
+        if let Some(used) = &self.compilation_time_used {
+            *used.lock().unwrap() += elapsed;
+        }
+    }
 }
@@ -16,17 +16,17 @@ use pchain_types::{
     blockchain::{Command, Log},
     cryptography::PublicAddress,
     runtime::CallInput,
-    serialization::{Deserializable, Serializable},
+    serialization::Deserializable,
 };
-use pchain_world_state::{VersionProvider, DB, NETWORK_ADDRESS};
+use pchain_world_state::{NetworkAccount, VersionProvider, DB, NETWORK_ADDRESS};
 
 use crate::{
-    contract::{CBIHostFunctions, FuncError},
-    gas::{blockchain_log_cost, blockchain_storage_cost, CostChange, HostFuncGasMeter},
+    contract::{encode_call_args, CBIHostFunctions, FuncError},
+    gas::{CostChange, HostFuncGasMeter},
     types::{CallTx, DeferredCommand, TxnMetadata},
 };
 
-use super::wasmer::env::Env;
+use super::wasmer::{env::Env, instance::MethodCallError};
 
 /// Within every host function defined on the HostFunction struct,
 /// the Env struct is available by reference to retrieve the current execution context.
@@ -47,6 +47,9 @@ where
         val_len: u32,
     ) -> Result<(), FuncError> {
         let mut ctx = env.context.lock().unwrap();
+        if key_len as usize > ctx.sc_context.max_storage_key_length() {
+            return Err(FuncError::KeyTooLong);
+        }
         let mut wasmer_gas_global = env.wasmer_gas_global.lock().unwrap();
         let mut fn_gas_meter =
             HostFuncGasMeter::new(&mut ctx.gas_meter, &mut wasmer_gas_global, env);
@@ -66,12 +69,24 @@ where
         val_ptr_ptr: u32,
     ) -> Result<i64, FuncError> {
         let mut ctx = env.context.lock().unwrap();
+        if key_len as usize > ctx.sc_context.max_storage_key_length() {
+            return Err(FuncError::KeyTooLong);
+        }
         let mut wasmer_gas_global = env.wasmer_gas_global.lock().unwrap();
         let mut fn_gas_meter =
             HostFuncGasMeter::new(&mut ctx.gas_meter, &mut wasmer_gas_global, env);
 
         let key = fn_gas_meter.read_bytes(key_ptr, key_len)?;
-        let value = fn_gas_meter.ws_get_storage_data(env.call_tx.target, &key);
+        let value = if env.is_view {
+            let mut storage_reads = env.storage_reads.lock().unwrap();
+            fn_gas_meter.ws_get_storage_data_read_only(
+                &mut storage_reads,
+                env.call_tx.target,
+                &key,
+            )
+        } else {
+            fn_gas_meter.ws_get_storage_data(env.call_tx.target, &key)
+        };
 
         let ret_val = match value {
             Some(value) => fn_gas_meter.write_bytes(value, val_ptr_ptr)? as i64,
@@ -81,6 +96,21 @@ where
         Ok(ret_val)
     }
 
+    fn get_len(env: &Env<'a, S, V>, key_ptr: u32, key_len: u32) -> Result<i64, FuncError> {
+        let mut ctx = env.context.lock().unwrap();
+        if key_len as usize > ctx.sc_context.max_storage_key_length() {
+            return Err(FuncError::KeyTooLong);
+        }
+        let mut wasmer_gas_global = env.wasmer_gas_global.lock().unwrap();
+        let mut fn_gas_meter =
+            HostFuncGasMeter::new(&mut ctx.gas_meter, &mut wasmer_gas_global, env);
+
+        let key = fn_gas_meter.read_bytes(key_ptr, key_len)?;
+        let value_len = fn_gas_meter.ws_get_storage_data_len(env.call_tx.target, &key);
+
+        Ok(value_len.map_or(-1, |len| len as i64))
+    }
+
     fn get_network_storage(
         env: &Env<'a, S, V>,
         key_ptr: u32,
@@ -127,7 +157,50 @@ where
                 hash_ptr_ptr,
             )
             .map(|_| ())
-            .map_err(FuncError::Runtime)
+            .map_err(FuncError::MemoryAccess)
+    }
+
+    fn block_proposer(env: &Env<'a, S, V>, address_ptr_ptr: u32) -> Result<(), FuncError> {
+        let mut ctx = env.context.lock().unwrap();
+        let mut wasmer_gas_global = env.wasmer_gas_global.lock().unwrap();
+        let mut fn_gas_meter =
+            HostFuncGasMeter::new(&mut ctx.gas_meter, &mut wasmer_gas_global, env);
+
+        let read_cost = fn_gas_meter.schedule().block_proposer_read_cost;
+        fn_gas_meter.deduct_gas(read_cost);
+        fn_gas_meter
+            .write_bytes(
+                env.params_from_blockchain.proposer_address.to_vec(),
+                address_ptr_ptr,
+            )
+            .map(|_| ())
+            .map_err(FuncError::MemoryAccess)
+    }
+
+    fn block_random_bytes(env: &Env<'a, S, V>, random_bytes_ptr_ptr: u32) -> Result<(), FuncError> {
+        let mut ctx = env.context.lock().unwrap();
+        let mut wasmer_gas_global = env.wasmer_gas_global.lock().unwrap();
+        let mut fn_gas_meter =
+            HostFuncGasMeter::new(&mut ctx.gas_meter, &mut wasmer_gas_global, env);
+
+        let read_cost = fn_gas_meter.schedule().block_random_bytes_read_cost;
+        fn_gas_meter.deduct_gas(read_cost);
+        fn_gas_meter
+            .write_bytes(
+                env.params_from_blockchain.random_bytes.to_vec(),
+                random_bytes_ptr_ptr,
+            )
+            .map(|_| ())
+            .map_err(FuncError::MemoryAccess)
+    }
+
+    fn current_epoch(env: &Env<'a, S, V>) -> Result<u64, FuncError> {
+        let mut ctx = env.context.lock().unwrap();
+        let mut wasmer_gas_global = env.wasmer_gas_global.lock().unwrap();
+        let mut fn_gas_meter =
+            HostFuncGasMeter::new(&mut ctx.gas_meter, &mut wasmer_gas_global, env);
+
+        Ok(NetworkAccount::new(&mut fn_gas_meter).current_epoch())
     }
 
     fn calling_account(env: &Env<'a, S, V>, address_ptr_ptr: u32) -> Result<(), FuncError> {
@@ -138,7 +211,7 @@ where
         fn_gas_meter
             .write_bytes(env.call_tx.signer.to_vec(), address_ptr_ptr)
             .map(|_| ())
-            .map_err(FuncError::Runtime)
+            .map_err(FuncError::MemoryAccess)
     }
 
     fn current_account(env: &Env<'a, S, V>, address_ptr_ptr: u32) -> Result<(), FuncError> {
@@ -149,7 +222,7 @@ where
         fn_gas_meter
             .write_bytes(env.call_tx.target.to_vec(), address_ptr_ptr)
             .map(|_| ())
-            .map_err(FuncError::Runtime)
+            .map_err(FuncError::MemoryAccess)
     }
 
     fn method(env: &Env<'a, S, V>, method_ptr_ptr: u32) -> Result<u32, FuncError> {
@@ -159,13 +232,13 @@ where
 
         fn_gas_meter
             .write_bytes(env.call_tx.method.as_bytes().to_vec(), method_ptr_ptr)
-            .map_err(FuncError::Runtime)
+            .map_err(FuncError::MemoryAccess)
     }
 
     fn arguments(env: &Env<'a, S, V>, arguments_ptr_ptr: u32) -> Result<u32, FuncError> {
         match &env.call_tx.arguments {
             Some(args) => {
-                let arguments = <Vec<Vec<u8>> as Serializable>::serialize(args);
+                let arguments = encode_call_args(args);
                 let mut ctx = env.context.lock().unwrap();
                 let mut wasmer_gas_global = env.wasmer_gas_global.lock().unwrap();
                 let fn_gas_meter =
@@ -173,7 +246,7 @@ where
 
                 fn_gas_meter
                     .write_bytes(arguments, arguments_ptr_ptr)
-                    .map_err(FuncError::Runtime)
+                    .map_err(FuncError::MemoryAccess)
             }
             None => Ok(0),
         }
@@ -195,7 +268,7 @@ where
         fn_gas_meter
             .write_bytes(env.call_tx.hash.to_vec(), hash_ptr_ptr)
             .map(|_| ())
-            .map_err(FuncError::Runtime)
+            .map_err(FuncError::MemoryAccess)
     }
 
     fn log(env: &Env<'a, S, V>, log_ptr: u32, log_len: u32) -> Result<(), FuncError> {
@@ -209,9 +282,13 @@ where
 
         // check gas before appending log, to preserve behaviour of v0.4
         // in future versions, to refactor it such that the gas meter operation itself checks for gas exhaustion and aborts
-        let log_cost = CostChange::deduct(blockchain_log_cost(log.topic.len(), log.value.len()))
-            .net_cost()
-            .0;
+        let log_cost = CostChange::deduct(
+            fn_gas_meter
+                .schedule()
+                .blockchain_log_cost(log.topic.len(), log.value.len()),
+        )
+        .net_cost()
+        .0;
         if log_cost > fn_gas_meter.remaining_gas() {
             // manually deduct to full exhuastion
             fn_gas_meter.deduct_gas(log_cost);
@@ -232,9 +309,10 @@ where
 
         // charge gas before appending return value, to preserve behaviour of v0.4
         // in future versions, to refactor it such that the gas meter operation itself checks for gas exhaustion and aborts
-        let ret_val_cost = CostChange::deduct(blockchain_storage_cost(value.len()))
-            .net_cost()
-            .0;
+        let ret_val_cost =
+            CostChange::deduct(fn_gas_meter.schedule().blockchain_storage_cost(value.len()))
+                .net_cost()
+                .0;
         if ret_val_cost > fn_gas_meter.remaining_gas() {
             // manually deduct to full exhuastion
             fn_gas_meter.deduct_gas(ret_val_cost);
@@ -251,112 +329,49 @@ where
         call_input_len: u32,
         return_ptr_ptr: u32,
     ) -> Result<u32, FuncError> {
-        let mut ctx = env.context.lock().unwrap();
-        let sc_context = ctx.clone_smart_contract_context();
-        let mut wasmer_gas_global = env.wasmer_gas_global.lock().unwrap();
-        let mut fn_gas_meter =
-            HostFuncGasMeter::new(&mut ctx.gas_meter, &mut wasmer_gas_global, env);
-
-        // Parse the call command arguments
-        let (target, method, arguments, amount) = {
-            let call_command_bytes = fn_gas_meter
-                .read_bytes(call_input_ptr, call_input_len)
-                .map_err(FuncError::Runtime)?;
-            let call_command = Command::deserialize(&call_command_bytes)
-                .map_err(|e| FuncError::Runtime(e.into()))?;
-
-            match call_command {
-                Command::Call(CallInput {
-                    target,
-                    method,
-                    arguments,
-                    amount,
-                }) => (target, method, arguments, amount),
-                _ => return Err(FuncError::Internal),
-            }
-        };
-
-        // error if transfer amount is specified in view call.
-        if env.is_view && amount.is_some() {
-            return Err(FuncError::Internal);
-        }
-
-        // transfer from calling contract address (call_tx.target) to the target address first.
-        if let Some(amount) = amount {
-            transfer_from_contract(env.call_tx.target, amount, target, &mut fn_gas_meter)?;
-        }
-
-        // Get the Contract Code and create the contract module
-        let contract_module = fn_gas_meter
-            .ws_cached_contract(target, &sc_context)
-            .ok_or(FuncError::ContractNotFound)?;
-
-        // by default, fields would be inherited from parent transaction
-        let call_tx = CallTx {
-            base_tx: TxnMetadata {
-                command_kinds: env.call_tx.command_kinds.clone(),
-                signer: env.call_tx.target,
-                gas_limit: fn_gas_meter.remaining_gas(),
-                ..env.call_tx.base_tx
-            },
-            amount,
-            arguments,
-            method,
-            target,
-        };
+        // without a gas limit, running out of gas fails the calling contract, so the call never returns None
+        call_contract(env, call_input_ptr, call_input_len, None, return_ptr_ptr)
+            .map(Option::unwrap_or_default)
+    }
 
-        // release mutexes for child contract to acquire and instantiate
-        drop(wasmer_gas_global);
-        drop(ctx);
-
-        // Instantiate and call the child contract
-        let (_, child_call_gas_consumed, child_call_error) = contract_module
-            .instantiate(
-                env.context.clone(), // here we only clone the existing Arc from the parent
-                env.call_counter.saturating_add(1),
-                env.is_view,
-                call_tx,
-                env.params_from_blockchain.clone(),
-            )
-            .map_err(|_| FuncError::ContractNotFound)?
-            .call();
+    fn call_with_gas_limit(
+        env: &Env<'a, S, V>,
+        call_input_ptr: u32,
+        call_input_len: u32,
+        gas_limit: u64,
+        return_ptr_ptr: u32,
+    ) -> Result<i64, FuncError> {
+        call_contract(
+            env,
+            call_input_ptr,
+            call_input_len,
+            Some(gas_limit),
+            return_ptr_ptr,
+        )
+        .map(|return_value_len| return_value_len.map_or(-1, i64::from))
+    }
 
-        // reacquire the TransitionContext in the parent function
+    fn gas_remaining(env: &Env<'a, S, V>) -> Result<u64, FuncError> {
         let mut ctx = env.context.lock().unwrap();
         let mut wasmer_gas_global = env.wasmer_gas_global.lock().unwrap();
         let mut fn_gas_meter =
             HostFuncGasMeter::new(&mut ctx.gas_meter, &mut wasmer_gas_global, env);
 
-        fn_gas_meter.deduct_gas(child_call_gas_consumed);
-
-        match child_call_error {
-            None => {
-                // Take the child result in parent's execution context.
-                if let Some(res) = fn_gas_meter.command_output_cache().take_return_value() {
-                    return fn_gas_meter
-                        .write_bytes(res, return_ptr_ptr)
-                        .map_err(FuncError::Runtime);
-                }
-            }
-            Some(e) => {
-                if fn_gas_meter.remaining_gas() == 0 {
-                    return Err(FuncError::GasExhaustionError);
-                }
-                return Err(FuncError::MethodCallError(e));
-            }
-        }
-        Ok(0)
+        let read_cost = fn_gas_meter.schedule().gas_remaining_read_cost;
+        fn_gas_meter.deduct_gas(read_cost);
+        Ok(fn_gas_meter.remaining_gas())
     }
 
     fn transfer(env: &Env<'a, S, V>, transfer_input_ptr: u32) -> Result<(), FuncError> {
         let mut ctx = env.context.lock().unwrap();
+        let transfer_allowance = ctx.contract_transfer_allowance();
         let mut wasmer_gas_global = env.wasmer_gas_global.lock().unwrap();
         let mut fn_gas_meter =
             HostFuncGasMeter::new(&mut ctx.gas_meter, &mut wasmer_gas_global, env);
 
         let transfer_bytes = fn_gas_meter
             .read_bytes(transfer_input_ptr, std::mem::size_of::<[u8; 40]>() as u32)
-            .map_err(FuncError::Runtime)?;
+            .map_err(FuncError::MemoryAccess)?;
 
         // first 32-bytes are the recipient address, last 8 is the amount
         let (recipient, amount_bytes) = transfer_bytes.split_at(32);
@@ -367,8 +382,12 @@ where
             env.call_tx.target, // calling contract's address from transaction execution context
             amount,
             recipient,
+            transfer_allowance,
             &mut fn_gas_meter,
-        )
+        )?;
+
+        ctx.contract_transfer_value = ctx.contract_transfer_value.saturating_add(amount);
+        Ok(())
     }
 
     fn defer_create_deposit(
@@ -382,7 +401,7 @@ where
 
         let serialized_command = fn_gas_meter
             .read_bytes(create_deposit_input_ptr, create_deposit_input_len)
-            .map_err(FuncError::Runtime)?;
+            .map_err(FuncError::MemoryAccess)?;
         let command =
             Command::deserialize(&serialized_command).map_err(|e| FuncError::Runtime(e.into()))?;
 
@@ -393,9 +412,7 @@ where
         ctx.append_deferred_command(DeferredCommand {
             command,
             contract_address: env.call_tx.target,
-        });
-
-        Ok(())
+        })
     }
 
     fn defer_set_deposit_settings(
@@ -412,7 +429,7 @@ where
                 set_deposit_settings_input_ptr,
                 set_deposit_settings_input_len,
             )
-            .map_err(FuncError::Runtime)?;
+            .map_err(FuncError::MemoryAccess)?;
         let command =
             Command::deserialize(&serialized_command).map_err(|e| FuncError::Runtime(e.into()))?;
 
@@ -423,9 +440,7 @@ where
         ctx.append_deferred_command(DeferredCommand {
             command,
             contract_address: env.call_tx.target,
-        });
-
-        Ok(())
+        })
     }
 
     fn defer_topup_deposit(
@@ -439,7 +454,7 @@ where
 
         let serialized_command = fn_gas_meter
             .read_bytes(top_up_deposit_input_ptr, top_up_deposit_input_len)
-            .map_err(FuncError::Runtime)?;
+            .map_err(FuncError::MemoryAccess)?;
         let command =
             Command::deserialize(&serialized_command).map_err(|e| FuncError::Runtime(e.into()))?;
 
@@ -450,9 +465,7 @@ where
         ctx.append_deferred_command(DeferredCommand {
             command,
             contract_address: env.call_tx.target,
-        });
-
-        Ok(())
+        })
     }
 
     fn defer_withdraw_deposit(
@@ -466,7 +479,7 @@ where
 
         let serialized_command = fn_gas_meter
             .read_bytes(withdraw_deposit_input_ptr, withdraw_deposit_input_len)
-            .map_err(FuncError::Runtime)?;
+            .map_err(FuncError::MemoryAccess)?;
         let command =
             Command::deserialize(&serialized_command).map_err(|e| FuncError::Runtime(e.into()))?;
 
@@ -477,9 +490,7 @@ where
         ctx.append_deferred_command(DeferredCommand {
             command,
             contract_address: env.call_tx.target,
-        });
-
-        Ok(())
+        })
     }
 
     fn defer_stake_deposit(
@@ -493,7 +504,7 @@ where
 
         let serialized_command = fn_gas_meter
             .read_bytes(stake_deposit_input_ptr, stake_deposit_input_len)
-            .map_err(FuncError::Runtime)?;
+            .map_err(FuncError::MemoryAccess)?;
         let command =
             Command::deserialize(&serialized_command).map_err(|e| FuncError::Runtime(e.into()))?;
 
@@ -504,9 +515,7 @@ where
         ctx.append_deferred_command(DeferredCommand {
             command,
             contract_address: env.call_tx.target,
-        });
-
-        Ok(())
+        })
     }
 
     fn defer_unstake_deposit(
@@ -520,7 +529,7 @@ where
 
         let serialized_command = fn_gas_meter
             .read_bytes(unstake_deposit_input_ptr, unstake_deposit_input_len)
-            .map_err(FuncError::Runtime)?;
+            .map_err(FuncError::MemoryAccess)?;
         let command =
             Command::deserialize(&serialized_command).map_err(|e| FuncError::Runtime(e.into()))?;
 
@@ -531,9 +540,7 @@ where
         ctx.append_deferred_command(DeferredCommand {
             command,
             contract_address: env.call_tx.target,
-        });
-
-        Ok(())
+        })
     }
 
     fn sha256(
@@ -612,17 +619,199 @@ where
     }
 }
 
+/// Execution logic for calling a contract from a contract. Returns the length of the return value written to the
+/// memory of the calling contract.
+///
+/// If `gas_limit` is given, the called contract runs with at most `gas_limit` gas. If it runs out of that gas while the
+/// calling contract still has gas, the changes made since the start of the call are rolled back and None is returned.
+fn call_contract<S, V>(
+    env: &Env<'_, S, V>,
+    call_input_ptr: u32,
+    call_input_len: u32,
+    gas_limit: Option<u64>,
+    return_ptr_ptr: u32,
+) -> Result<Option<u32>, FuncError>
+where
+    S: DB + Sync + Send + Clone + 'static,
+    V: VersionProvider + Send + Sync + Clone + 'static,
+{
+    let mut ctx = env.context.lock().unwrap();
+    let sc_context = ctx.clone_smart_contract_context();
+    // the callee is one call deeper than the caller
+    if env.call_counter >= sc_context.max_call_depth() {
+        return Err(FuncError::CallDepthExceeded);
+    }
+    let transfer_allowance = ctx.contract_transfer_allowance();
+    let mut wasmer_gas_global = env.wasmer_gas_global.lock().unwrap();
+
+    // Parse the call command arguments
+    let (target, method, arguments, amount) = {
+        let fn_gas_meter = HostFuncGasMeter::new(&mut ctx.gas_meter, &mut wasmer_gas_global, env);
+        let call_command_bytes = fn_gas_meter
+            .read_bytes(call_input_ptr, call_input_len)
+            .map_err(FuncError::MemoryAccess)?;
+        let call_command =
+            Command::deserialize(&call_command_bytes).map_err(|e| FuncError::Runtime(e.into()))?;
+
+        match call_command {
+            Command::Call(CallInput {
+                target,
+                method,
+                arguments,
+                amount,
+            }) => (target, method, arguments, amount),
+            _ => return Err(FuncError::Internal),
+        }
+    };
+
+    // error if transfer amount is specified in view call.
+    if env.is_view && amount.is_some() {
+        return Err(FuncError::Internal);
+    }
+
+    // the effects of the call, including the transfer of its amount, are rolled back if it fails. The savepoint is
+    // closed on every path below
+    let savepoint = gas_limit.map(|_| ctx.savepoint());
+    let mut fn_gas_meter = HostFuncGasMeter::new(&mut ctx.gas_meter, &mut wasmer_gas_global, env);
+
+    // transfer from calling contract address (call_tx.target) to the target address first, then get the Contract Code
+    // and create the contract module
+    let contract_module = amount
+        .map_or(Ok(()), |amount| {
+            transfer_from_contract(
+                env.call_tx.target,
+                amount,
+                target,
+                transfer_allowance,
+                &mut fn_gas_meter,
+            )
+        })
+        .and_then(|_| {
+            fn_gas_meter
+                .ws_cached_contract(target, &sc_context)
+                .ok_or(FuncError::ContractNotFound)
+        });
+    let contract_module = match contract_module {
+        Ok(contract_module) => contract_module,
+        Err(e) => {
+            drop(fn_gas_meter);
+            if let Some(savepoint) = savepoint {
+                ctx.rollback(savepoint);
+            }
+            return Err(e);
+        }
+    };
+
+    // by default, fields would be inherited from parent transaction. The gas used by the child is scaled by its gas
+    // multiplier afterwards, so the scaled gas must fit in the gas available to it
+    let remaining_gas = fn_gas_meter.remaining_gas();
+    let child_gas_limit = sc_context.gas_limit_before_multiplier(
+        &target,
+        gas_limit.map_or(remaining_gas, |gas_limit| gas_limit.min(remaining_gas)),
+    );
+    let call_tx = CallTx {
+        base_tx: TxnMetadata {
+            command_kinds: env.call_tx.command_kinds.clone(),
+            signer: env.call_tx.target,
+            gas_limit: child_gas_limit,
+            ..env.call_tx.base_tx
+        },
+        amount,
+        arguments,
+        method,
+        target,
+    };
+
+    // count the transferred amount towards the transaction's limit before the child contract runs
+    ctx.contract_transfer_value = ctx
+        .contract_transfer_value
+        .saturating_add(amount.unwrap_or(0));
+
+    // release mutexes for child contract to acquire and instantiate
+    drop(wasmer_gas_global);
+    drop(ctx);
+
+    // Instantiate and call the child contract
+    let instance = match contract_module.instantiate(
+        env.context.clone(), // here we only clone the existing Arc from the parent
+        env.call_counter.saturating_add(1),
+        env.is_view,
+        call_tx,
+        env.params_from_blockchain.clone(),
+    ) {
+        Ok(instance) => instance,
+        Err(_) => {
+            if let Some(savepoint) = savepoint {
+                env.context.lock().unwrap().rollback(savepoint);
+            }
+            return Err(FuncError::ContractNotFound);
+        }
+    };
+    let (_, child_call_gas_consumed, child_call_error) = instance.call();
+
+    // reacquire the TransitionContext in the parent function
+    let mut ctx = env.context.lock().unwrap();
+    // the effects of a successful call are kept, and those of a failed call are discarded, whether or not its failure
+    // also fails the caller
+    if let Some(savepoint) = savepoint {
+        match child_call_error {
+            None => ctx.release(savepoint),
+            Some(_) => ctx.rollback(savepoint),
+        }
+    }
+    let mut wasmer_gas_global = env.wasmer_gas_global.lock().unwrap();
+    let mut fn_gas_meter = HostFuncGasMeter::new(&mut ctx.gas_meter, &mut wasmer_gas_global, env);
+
+    fn_gas_meter.deduct_gas(sc_context.apply_gas_multiplier(&target, child_call_gas_consumed));
+
+    match child_call_error {
+        None => {
+            // Take the child result in parent's execution context.
+            if let Some(res) = fn_gas_meter.command_output_cache().take_return_value() {
+                return fn_gas_meter
+                    .write_bytes(res, return_ptr_ptr)
+                    .map(Some)
+                    .map_err(FuncError::MemoryAccess);
+            }
+        }
+        Some(_) if fn_gas_meter.remaining_gas() == 0 => {
+            return Err(FuncError::GasExhaustionError);
+        }
+        Some(MethodCallError::GasExhaustion) if gas_limit.is_none() => {
+            // the child ran out of the gas of the parent, which may be left with less than the rounding of the
+            // child's gas multiplier
+            let remaining_gas = fn_gas_meter.remaining_gas();
+            fn_gas_meter.deduct_gas(remaining_gas);
+            return Err(FuncError::GasExhaustionError);
+        }
+        Some(MethodCallError::GasExhaustion) => {
+            // the child ran out of its gas limit, but the parent can continue
+            return Ok(None);
+        }
+        Some(e) => {
+            return Err(FuncError::MethodCallError(e));
+        }
+    }
+    Ok(Some(0))
+}
+
 /// Execution logic for transferring tokens from a contract
 fn transfer_from_contract<S, V>(
     signer: PublicAddress,
     amount: u64,
     recipient: PublicAddress,
+    transfer_allowance: Option<u64>,
     gas_meter: &mut HostFuncGasMeter<'_, '_, S, Env<'_, S, V>, V>,
 ) -> Result<(), FuncError>
 where
     S: DB + Send + Sync + Clone,
     V: VersionProvider + Send + Sync + Clone,
 {
+    // 0. Verify that the amount is within the remaining allowance of the transaction, if there is one
+    if matches!(transfer_allowance, Some(allowance) if amount > allowance) {
+        return Err(FuncError::TransferLimitExceeded);
+    }
+
     // 1. Verify that the caller's balance is >= amount
     let from_balance = gas_meter.ws_get_balance(signer);
     let from_address_new_balance = from_balance
@@ -40,8 +40,25 @@ where
         self.environment
             .init_wasmer_gas_global(self.instance.remaining_points());
 
-        // Invoke Wasm Execution
-        let call_result = unsafe { self.instance.call_method() };
+        // Invoke Wasm Execution, which can be stopped by the interrupt handle of the Runtime if there is one, or by the
+        // deadline of the current command
+        let (watcher, deadline) = {
+            let mut ctx = self.environment.context.lock().unwrap();
+            (
+                ctx.sc_context.call_watcher(),
+                ctx.sc_context.command_deadline,
+            )
+        };
+        let call_result = match watcher {
+            Some(watcher) => unsafe {
+                self.instance.call_method_interruptible(
+                    &watcher,
+                    &self.environment.wasmer_gas_global,
+                    deadline,
+                )
+            },
+            None => unsafe { self.instance.call_method() },
+        };
 
         // drop the variable of wasmer remaining gas
         self.environment.drop_wasmer_gas_global();
@@ -64,7 +81,32 @@ where
         // Also, the Wasmer importable also holds refs to the Mutex.
         // This might be refactored in future with a change to Wasmer's API
 
-        let ctx = self.environment.context.lock().unwrap().clone();
+        let mut ctx = self.environment.context.lock().unwrap();
+
+        // Wasm linear memory never shrinks, so its final size is the high-water mark of this call
+        if ctx.sc_context.memory_profiling {
+            if let Some(memory) = self.environment.memory_ref() {
+                let pages = memory.size().0;
+                let memory_pages = ctx
+                    .gas_meter
+                    .output_cache_of_current_command
+                    .memory_pages
+                    .as_mut();
+                *memory_pages = std::cmp::max(*memory_pages, pages);
+            }
+        }
+
+        // contract-to-contract calls made by this call have added their own gas to the same profile
+        #[cfg(feature = "profiling")]
+        ctx.gas_meter
+            .output_cache_of_current_command
+            .opcode_gas_profile
+            .as_mut()
+            .add(&crate::contract::wasmer::gas_profiler::opcode_gas_profile(
+                &self.instance.0,
+            ));
+
+        let ctx = ctx.clone();
         (ctx, total_gas, call_error)
     }
 }
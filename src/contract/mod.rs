@@ -11,6 +11,12 @@
 //! and can use exported functionality provided by the [host](host_functions).
 //! The state transition function prepares the execution [context] and builds an [instance] of the contract.
 
+pub mod address;
+pub use address::{contract_address_v1, contract_address_v2};
+
+pub mod arguments;
+pub use arguments::{encode_call_arg, encode_call_args};
+
 pub mod cbi_host_functions;
 pub(crate) use cbi_host_functions::*;
 
@@ -30,3 +36,6 @@ pub(crate) use instance::*;
 
 pub mod module;
 pub(crate) use module::*;
+
+#[cfg(feature = "host-function-overrides")]
+pub mod overrides;
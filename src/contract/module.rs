@@ -10,6 +10,7 @@
 use std::{
     mem::transmute,
     sync::{Arc, Mutex},
+    time::Instant,
 };
 
 use pchain_types::cryptography::PublicAddress;
@@ -40,11 +41,17 @@ pub(crate) struct ContractModule {
 impl ContractModule {
     /// called during contract invocation for faster loading of the Wasm module
     pub fn from_cache(address: PublicAddress, sc_context: &SmartContractContext) -> Option<Self> {
-        let store = store::instantiate_store(u64::MAX, sc_context.memory_limit);
+        let store = store::instantiate_store(
+            u64::MAX,
+            sc_context.memory_limit,
+            sc_context.compiler_backend,
+        );
         sc_context
             .cache
             .as_ref()
-            .and_then(|cache| Module::from_cache(address, cache, &store))
+            .and_then(|cache| {
+                Module::from_cache(address, cache, &store, sc_context.compiler_backend)
+            })
             .map(|module| Self { store, module })
     }
 
@@ -52,12 +59,25 @@ impl ContractModule {
     /// compiles bytecode for the very first time with validation
     pub(crate) fn from_bytecode_checked(
         contract_code: &Vec<u8>,
-        memory_limit: Option<usize>,
+        sc_context: &SmartContractContext,
     ) -> Result<Self, ModuleBuildError> {
-        let store = store::instantiate_store(u64::MAX, memory_limit);
-        let module =
-            Module::from_wasm_bytecode_checked(contract::CBI_VERSION, contract_code, &store)?;
-        Ok(Self { store, module })
+        let store = store::instantiate_store(
+            u64::MAX,
+            sc_context.memory_limit,
+            sc_context.compiler_backend,
+        );
+        let start = Instant::now();
+        let module = Module::from_wasm_bytecode_checked(
+            contract::CBI_VERSION,
+            contract_code,
+            &store,
+            sc_context.compiler_backend,
+        );
+        sc_context.record_compilation_time(start.elapsed());
+        Ok(Self {
+            store,
+            module: module?,
+        })
     }
 
     /// called during subsequent contract invocation
@@ -67,21 +87,37 @@ impl ContractModule {
         contract_code: &Vec<u8>,
         sc_context: &SmartContractContext,
     ) -> Option<Self> {
-        let store = store::instantiate_store(u64::MAX, sc_context.memory_limit);
-        let module =
-            Module::from_wasm_bytecode_unchecked(contract::CBI_VERSION, contract_code, &store)
-                .ok()?;
+        let store = store::instantiate_store(
+            u64::MAX,
+            sc_context.memory_limit,
+            sc_context.compiler_backend,
+        );
+        let start = Instant::now();
+        let module = Module::from_wasm_bytecode_unchecked(
+            contract::CBI_VERSION,
+            contract_code,
+            &store,
+            sc_context.compiler_backend,
+        );
+        sc_context.record_compilation_time(start.elapsed());
+        let module = Self {
+            store,
+            module: module.ok()?,
+        };
 
         if let Some(sc_cache) = &sc_context.cache {
-            module.cache_to(address, sc_cache);
+            module.cache(address, sc_cache);
         }
 
-        Some(Self { store, module })
+        Some(module)
     }
 
-    /// check if the Wasm module is a proper contract according to the Parallelchain CBI
-    pub(crate) fn validate_proper_contract(&self) -> Result<(), ContractValidateError> {
-        self.module.validate_entry_point(&self.store)
+    /// check if the Wasm module is a proper contract according to the given version of the Parallelchain CBI
+    pub(crate) fn validate_proper_contract(
+        &self,
+        cbi_version: u32,
+    ) -> Result<(), ContractValidateError> {
+        self.module.validate_entry_point(&self.store, cbi_version)
     }
 
     pub(crate) fn cache(&self, contract_address: PublicAddress, cache: &Cache) {
@@ -105,6 +141,13 @@ impl ContractModule {
         V: VersionProvider + Send + Sync + Clone + 'static,
     {
         let gas_limit = tx.gas_limit;
+        #[cfg(feature = "host-function-overrides")]
+        let host_function_overrides = ctx
+            .lock()
+            .unwrap()
+            .sc_context
+            .host_function_overrides
+            .clone();
         let environment = env::Env::new(ctx, call_counter, is_view, tx, bd);
 
         // SAFETY: The following unsafe block assumes that the Env AWLAYS outlives the Wasm instance.
@@ -129,6 +172,12 @@ impl ContractModule {
             )
         };
 
+        #[cfg(feature = "host-function-overrides")]
+        let importable = match &host_function_overrides {
+            Some(overrides) => overrides.apply(importable),
+            None => importable,
+        };
+
         // cast Env back to the original lifetime after use
         let environment: env::Env<'a, S, V> = unsafe { transmute(environment) };
 
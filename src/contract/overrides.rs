@@ -0,0 +1,107 @@
+/*
+    Copyright © 2023, ParallelChain Lab
+    Licensed under the Apache License, Version 2.0: http://www.apache.org/licenses/LICENSE-2.0
+*/
+
+//! Replaces host functions imported by contracts with test doubles.
+//!
+//! [HostFunctionOverrides] are set on the [Runtime](crate::Runtime) with
+//! [set_host_function_overrides](crate::Runtime::set_host_function_overrides). Every contract instantiated by that
+//! Runtime imports the overriding functions in place of the [host functions](crate::contract::host_functions) of the
+//! same name, e.g. to return a fixed value from `block_height`, or to capture the calls to `_log`.
+//!
+//! Overrides change the results of a transition, so this module is only compiled with the `host-function-overrides`
+//! feature, which must never be enabled in a build that takes part in consensus.
+
+use std::{collections::HashMap, sync::Arc};
+
+use wasmer::{
+    Exports, Function, FunctionType, LazyInit, Memory, NativeFunc, RuntimeError, Store, Val,
+};
+
+use super::{wasmer::memory::MemoryContext, Importable};
+
+/// Signature of a function which overrides a host function. It is called with the arguments passed by the contract,
+/// and returns the values to return to the contract, or an error which traps the contract.
+pub type HostFunctionOverride =
+    dyn Fn(&HostFunctionOverrideEnv, &[Val]) -> Result<Vec<Val>, RuntimeError> + Send + Sync;
+
+/// A set of host functions which override those of the same name in the `env` namespace imported by contracts.
+#[derive(Clone, Default)]
+pub struct HostFunctionOverrides {
+    functions: HashMap<String, (FunctionType, Arc<HostFunctionOverride>)>,
+}
+
+impl HostFunctionOverrides {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Overrides the host function `name` with `function`. `ty` must be the signature of the host function as imported
+    /// by contracts (see [CBIHostFunctions](crate::contract::cbi_host_functions::CBIHostFunctions)), otherwise
+    /// contracts which import it fail to instantiate.
+    pub fn set<F>(mut self, name: &str, ty: FunctionType, function: F) -> Self
+    where
+        F: Fn(&HostFunctionOverrideEnv, &[Val]) -> Result<Vec<Val>, RuntimeError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.functions
+            .insert(name.to_string(), (ty, Arc::new(function)));
+        self
+    }
+
+    /// Replaces the overridden functions in the `env` namespace of `importable`.
+    pub(crate) fn apply<'a>(&self, importable: Importable<'a>) -> Importable<'a> {
+        let Importable(mut import_object, store) = importable;
+        let mut exports = import_object
+            .get_namespace_exports("env")
+            .unwrap_or_else(Exports::new);
+        for (name, (ty, function)) in &self.functions {
+            exports.insert(name, override_function(store, ty, function.clone()));
+        }
+        import_object.register("env", exports);
+        Importable(import_object, store)
+    }
+}
+
+fn override_function(
+    store: &Store,
+    ty: &FunctionType,
+    function: Arc<HostFunctionOverride>,
+) -> Function {
+    let env = HostFunctionOverrideEnv {
+        function,
+        memory: LazyInit::default(),
+        alloc: LazyInit::default(),
+    };
+    Function::new_with_env(store, ty, env, |env, args| (env.function)(env, args))
+}
+
+/// Environment of an overriding host function, which gives access to the linear memory of the calling contract
+/// through [MemoryContext].
+#[derive(wasmer::WasmerEnv, Clone)]
+pub struct HostFunctionOverrideEnv {
+    function: Arc<HostFunctionOverride>,
+
+    #[wasmer(export)]
+    memory: LazyInit<Memory>,
+
+    #[wasmer(export(name = "alloc"))]
+    alloc: LazyInit<NativeFunc<u32, wasmer::WasmPtr<u8, wasmer::Array>>>,
+}
+
+impl MemoryContext for HostFunctionOverrideEnv {
+    /// # Panics
+    /// Will panic if the Wasm instance fails to initialize linear memory correctly.
+    fn memory(&self) -> &Memory {
+        self.memory_ref().unwrap()
+    }
+
+    /// # Panics
+    /// Will panic if the native function to allocate linear memory is not found.
+    fn alloc(&self) -> &NativeFunc<u32, wasmer::WasmPtr<u8, wasmer::Array>> {
+        self.alloc_ref().unwrap()
+    }
+}
@@ -7,8 +7,9 @@
 //!
 //! The `Cache` is a key component used by [SmartContractContext](crate::contract::SmartContractContext),
 //! facilitating efficient retrieval of modules compiled from smart contract bytecode.
-//! In addition to caching, it maintains critical metadata, including the CBI version and
-//! the size of the Wasm bytecode pre-compilation.
+//! In addition to caching, it maintains critical metadata, including the CBI version,
+//! the size of the Wasm bytecode pre-compilation, and the [CompilerBackend] which compiled the module.
+//! A module compiled with another backend than the one requested is never loaded.
 
 use anyhow::Result;
 use pchain_types::cryptography::PublicAddress;
@@ -20,7 +21,7 @@ use std::{
 use wasmer::{DeserializeError, Module, SerializeError};
 use wasmer_cache::{Cache as WasmerCache, FileSystemCache};
 
-use crate::contract;
+use crate::contract::{self, wasmer::store::CompilerBackend};
 
 /// Represents the backing storage for Wasm module cache.
 /// The `Cache` struct encapsulates a [FileSystemCache] from Wasmer,
@@ -49,11 +50,12 @@ impl Cache {
         }
     }
 
-    /// load the cached Module with Metadata from file storage
+    /// load the cached Module with Metadata from file storage, if it was compiled with `compiler_backend`
     pub(crate) fn load(
         &self,
         address: PublicAddress,
         store: &wasmer::Store,
+        compiler_backend: CompilerBackend,
     ) -> Result<(Module, ModuleMetadata), DeserializeError> {
         let key = wasmer_cache::Hash::new(address);
         let file_storage = self
@@ -61,10 +63,16 @@ impl Cache {
             .try_read()
             .map_err(|_| DeserializeError::Io(Error::from(ErrorKind::Interrupted)))?;
 
-        let module = unsafe { file_storage.load(store, key)? };
         let metadata = file_storage
             .metadata(key)
             .map_err(|_| DeserializeError::Io(Error::from(ErrorKind::NotFound)))?;
+        if metadata.compiler_backend != compiler_backend {
+            return Err(DeserializeError::Incompatible(format!(
+                "module was compiled with {:?}",
+                metadata.compiler_backend
+            )));
+        }
+        let module = unsafe { file_storage.load(store, key)? };
 
         Ok((module, metadata))
     }
@@ -75,6 +83,7 @@ impl Cache {
         address: PublicAddress,
         module: &wasmer::Module,
         bytes_length: usize,
+        compiler_backend: CompilerBackend,
     ) -> Result<(), SerializeError> {
         let key = wasmer_cache::Hash::new(address);
         let mut file_storage = self
@@ -89,6 +98,7 @@ impl Cache {
                 ModuleMetadata {
                     cbi_version: contract::CBI_VERSION,
                     bytecode_length: bytes_length,
+                    compiler_backend,
                 },
             )
             .map_err(|_| SerializeError::Io(Error::from(ErrorKind::NotFound)))?;
@@ -111,7 +121,7 @@ impl FileStorage {
         let mut file = std::fs::File::open(path).map_err(|_| ())?;
         let mut buf = Vec::new();
         file.read_to_end(&mut buf).map_err(|_| ())?;
-        Ok(ModuleMetadata::from(buf))
+        ModuleMetadata::try_from(buf)
     }
 
     fn set_metadata(
@@ -123,6 +133,7 @@ impl FileStorage {
         let mut file = std::fs::OpenOptions::new()
             .write(true)
             .create(true)
+            .truncate(true)
             .open(path)
             .map_err(|e| {
                 println!("{:?}", e);
@@ -158,6 +169,7 @@ impl WasmerCache for FileStorage {
 pub struct ModuleMetadata {
     pub cbi_version: u32,
     pub bytecode_length: usize,
+    pub compiler_backend: CompilerBackend,
 }
 
 impl From<ModuleMetadata> for Vec<u8> {
@@ -165,19 +177,30 @@ impl From<ModuleMetadata> for Vec<u8> {
         [
             value.cbi_version.to_le_bytes().to_vec(),
             value.bytecode_length.to_le_bytes().to_vec(),
+            vec![value.compiler_backend.id()],
         ]
         .concat()
     }
 }
 
-impl From<Vec<u8>> for ModuleMetadata {
-    fn from(bytes: Vec<u8>) -> Self {
-        let (cbi_bytes, wasm_bytes) = bytes.split_at(std::mem::size_of::<u32>());
+/// Fails on metadata which does not record a known compiler backend, e.g. metadata written before the backend was
+/// recorded, so that the module is compiled again.
+impl TryFrom<Vec<u8>> for ModuleMetadata {
+    type Error = ();
+
+    fn try_from(bytes: Vec<u8>) -> Result<Self, Self::Error> {
+        if bytes.len() != std::mem::size_of::<u32>() + std::mem::size_of::<usize>() + 1 {
+            return Err(());
+        }
+        let (cbi_bytes, rest) = bytes.split_at(std::mem::size_of::<u32>());
+        let (wasm_bytes, backend_bytes) = rest.split_at(std::mem::size_of::<usize>());
         let cbi_version: u32 = u32::from_le_bytes(cbi_bytes.try_into().unwrap());
         let bytecode_length: usize = usize::from_le_bytes(wasm_bytes.try_into().unwrap());
-        Self {
+        let compiler_backend = CompilerBackend::from_id(backend_bytes[0]).ok_or(())?;
+        Ok(Self {
             cbi_version,
             bytecode_length,
-        }
+            compiler_backend,
+        })
     }
 }
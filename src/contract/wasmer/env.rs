@@ -17,7 +17,10 @@ use std::sync::{Arc, Mutex};
 use wasmer::{Global, LazyInit, Memory, NativeFunc};
 
 use super::memory::MemoryContext;
-use crate::{context::TransitionContext, gas::WasmerGasGlobal, types::CallTx, BlockchainParams};
+use crate::{
+    context::TransitionContext, execution::cache::StorageReads, gas::WasmerGasGlobal,
+    types::CallTx, BlockchainParams,
+};
 
 /// The Environment is implemented as an Env struct tracking relevant state variables.
 /// From wasmer, we derive the necessary WasmerEnv trait for the Env struct to be used to
@@ -47,6 +50,9 @@ where
     /// Indicator of whether this environment is created for a view call.
     pub is_view: bool,
 
+    /// Values read from the storage of the contract by a view call, kept for the whole call. Empty in other calls.
+    pub storage_reads: Arc<Mutex<StorageReads>>,
+
     /// Link to the linear memory instance boostrapped by the relevant Wasmer instance
     #[wasmer(export)]
     pub memory: LazyInit<Memory>,
@@ -80,6 +86,7 @@ where
             call_tx,
             params_from_blockchain,
             is_view,
+            storage_reads: Arc::new(Mutex::new(StorageReads::default())),
         }
     }
 
@@ -0,0 +1,251 @@
+/*
+    Copyright © 2023, ParallelChain Lab
+    Licensed under the Apache License, Version 2.0: http://www.apache.org/licenses/LICENSE-2.0
+*/
+
+//! Defines a middleware which profiles the gas used by Wasm opcodes by category of opcode.
+//!
+//! The [GasProfiler] wraps the metering middleware. Every opcode is fed to the metering middleware as usual, and the
+//! profiler adds the cost of the opcode to the category it belongs to. At the end of each block of opcodes, where the
+//! metering middleware charges the cost of the block, the profiler adds the costs of the block to one exported global
+//! per category. Its instructions are emitted after metering, so they are not metered and do not change gas usage.
+//!
+//! The globals are read into an [OpcodeGasProfile] after each contract call. Only compiled with the `profiling`
+//! feature. Machine code compiled with the profiler differs from machine code compiled without it, so a smart
+//! contract cache should not be shared between builds with and without the feature.
+
+use std::sync::Mutex;
+
+use loupe::{MemoryUsage, MemoryUsageTracker};
+use wasmer::{
+    wasmparser::Operator, ExportIndex, FunctionMiddleware, GlobalInit, GlobalType,
+    LocalFunctionIndex, MiddlewareError, MiddlewareReaderState, ModuleMiddleware, Mutability, Type,
+};
+use wasmer_types::ModuleInfo;
+
+use crate::gas::{wasm_opcode_gas_schedule, OpcodeGasProfile};
+
+/// Names of the exported globals which hold the gas of each category, in the order of [OpcodeCategory].
+const PROFILE_GLOBAL_NAMES: [&str; 4] = [
+    "pchain_gas_profile_control",
+    "pchain_gas_profile_memory",
+    "pchain_gas_profile_arithmetic",
+    "pchain_gas_profile_call",
+];
+
+/// Category of a Wasm opcode in an [OpcodeGasProfile].
+#[derive(Clone, Copy)]
+enum OpcodeCategory {
+    Control = 0,
+    Memory = 1,
+    Arithmetic = 2,
+    Call = 3,
+}
+
+impl OpcodeCategory {
+    fn of(operator: &Operator) -> Self {
+        match operator {
+            Operator::Call { .. }
+            | Operator::CallIndirect { .. }
+            | Operator::ReturnCall { .. }
+            | Operator::ReturnCallIndirect { .. } => OpcodeCategory::Call,
+
+            Operator::Nop
+            | Operator::Unreachable
+            | Operator::Block { .. }
+            | Operator::Loop { .. }
+            | Operator::If { .. }
+            | Operator::Else
+            | Operator::End
+            | Operator::Br { .. }
+            | Operator::BrIf { .. }
+            | Operator::BrTable { .. }
+            | Operator::Return
+            | Operator::Drop
+            | Operator::Select
+            | Operator::TypedSelect { .. }
+            | Operator::Try { .. }
+            | Operator::Catch { .. }
+            | Operator::CatchAll
+            | Operator::Throw { .. }
+            | Operator::Rethrow { .. }
+            | Operator::Delegate { .. } => OpcodeCategory::Control,
+
+            Operator::LocalGet { .. }
+            | Operator::LocalSet { .. }
+            | Operator::LocalTee { .. }
+            | Operator::GlobalGet { .. }
+            | Operator::GlobalSet { .. }
+            | Operator::I32Load { .. }
+            | Operator::I64Load { .. }
+            | Operator::I32Load8S { .. }
+            | Operator::I32Load8U { .. }
+            | Operator::I32Load16S { .. }
+            | Operator::I32Load16U { .. }
+            | Operator::I64Load8S { .. }
+            | Operator::I64Load8U { .. }
+            | Operator::I64Load16S { .. }
+            | Operator::I64Load16U { .. }
+            | Operator::I64Load32S { .. }
+            | Operator::I64Load32U { .. }
+            | Operator::I32Store { .. }
+            | Operator::I64Store { .. }
+            | Operator::I32Store8 { .. }
+            | Operator::I32Store16 { .. }
+            | Operator::I64Store8 { .. }
+            | Operator::I64Store16 { .. }
+            | Operator::I64Store32 { .. }
+            | Operator::MemorySize { .. }
+            | Operator::MemoryGrow { .. }
+            | Operator::MemoryInit { .. }
+            | Operator::MemoryCopy { .. }
+            | Operator::MemoryFill { .. }
+            | Operator::DataDrop { .. }
+            | Operator::TableInit { .. }
+            | Operator::TableCopy { .. }
+            | Operator::TableFill { .. }
+            | Operator::TableGet { .. }
+            | Operator::TableSet { .. }
+            | Operator::TableGrow { .. }
+            | Operator::TableSize { .. }
+            | Operator::ElemDrop { .. }
+            | Operator::RefNull { .. }
+            | Operator::RefIsNull
+            | Operator::RefFunc { .. } => OpcodeCategory::Memory,
+
+            _ => OpcodeCategory::Arithmetic,
+        }
+    }
+}
+
+/// GasProfiler is the middleware which wraps the metering middleware `M` to profile the gas used by Wasm opcodes.
+/// See the [module-level documentation](self).
+#[derive(Debug)]
+pub struct GasProfiler<M: ModuleMiddleware> {
+    metering: M,
+    /// indexes of the exported globals of the module being compiled, set in `transform_module_info`.
+    global_indexes: Mutex<Option<[u32; 4]>>,
+}
+
+impl<M: ModuleMiddleware> GasProfiler<M> {
+    pub fn new(metering: M) -> Self {
+        Self {
+            metering,
+            global_indexes: Mutex::new(None),
+        }
+    }
+}
+
+impl<M: ModuleMiddleware> MemoryUsage for GasProfiler<M> {
+    fn size_of_val(&self, tracker: &mut dyn MemoryUsageTracker) -> usize {
+        std::mem::size_of_val(self) + self.metering.size_of_val(tracker)
+    }
+}
+
+impl<M: ModuleMiddleware> ModuleMiddleware for GasProfiler<M> {
+    fn generate_function_middleware(
+        &self,
+        local_function_index: LocalFunctionIndex,
+    ) -> Box<dyn FunctionMiddleware> {
+        Box::new(FunctionGasProfiler {
+            metering: self
+                .metering
+                .generate_function_middleware(local_function_index),
+            global_indexes: self
+                .global_indexes
+                .lock()
+                .unwrap()
+                .expect("GasProfiler should transform the module info before generating function middlewares"),
+            accumulated_gas: [0; 4],
+        })
+    }
+
+    fn transform_module_info(&self, module_info: &mut ModuleInfo) {
+        self.metering.transform_module_info(module_info);
+
+        let global_indexes = PROFILE_GLOBAL_NAMES.map(|name| {
+            let global_index = module_info
+                .globals
+                .push(GlobalType::new(Type::I64, Mutability::Var));
+            module_info
+                .global_initializers
+                .push(GlobalInit::I64Const(0));
+            module_info
+                .exports
+                .insert(name.to_string(), ExportIndex::Global(global_index));
+            global_index.as_u32()
+        });
+        *self.global_indexes.lock().unwrap() = Some(global_indexes);
+    }
+}
+
+#[derive(Debug)]
+struct FunctionGasProfiler {
+    metering: Box<dyn FunctionMiddleware>,
+    global_indexes: [u32; 4],
+    /// gas of the current block of opcodes by category.
+    accumulated_gas: [u64; 4],
+}
+
+impl FunctionMiddleware for FunctionGasProfiler {
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        let category = OpcodeCategory::of(&operator) as usize;
+        self.accumulated_gas[category] += wasm_opcode_gas_schedule(&operator);
+
+        // the opcodes which end a block in the metering middleware
+        if matches!(
+            operator,
+            Operator::Loop { .. }
+                | Operator::End
+                | Operator::Else
+                | Operator::Br { .. }
+                | Operator::BrTable { .. }
+                | Operator::BrIf { .. }
+                | Operator::Call { .. }
+                | Operator::CallIndirect { .. }
+                | Operator::Return
+        ) {
+            for (global_index, gas) in self.global_indexes.iter().zip(self.accumulated_gas) {
+                if gas > 0 {
+                    state.extend(&[
+                        Operator::GlobalGet {
+                            global_index: *global_index,
+                        },
+                        Operator::I64Const { value: gas as i64 },
+                        Operator::I64Add,
+                        Operator::GlobalSet {
+                            global_index: *global_index,
+                        },
+                    ]);
+                }
+            }
+            self.accumulated_gas = [0; 4];
+        }
+
+        self.metering.feed(operator, state)
+    }
+}
+
+/// reads the profile of the gas used by Wasm opcodes from the exported globals of `instance`. Instances of modules
+/// compiled without the profiler, e.g. loaded from a cache populated by a build without the `profiling` feature, have
+/// an empty profile.
+pub(crate) fn opcode_gas_profile(instance: &wasmer::Instance) -> OpcodeGasProfile {
+    let [control_gas, memory_gas, arithmetic_gas, call_gas] = PROFILE_GLOBAL_NAMES.map(|name| {
+        instance
+            .exports
+            .get_global(name)
+            .ok()
+            .and_then(|global| global.get().i64())
+            .map_or(0, |gas| gas as u64)
+    });
+    OpcodeGasProfile {
+        control_gas,
+        memory_gas,
+        arithmetic_gas,
+        call_gas,
+    }
+}
@@ -10,7 +10,21 @@
 //! This design allows users of `ContractInstance`
 //! to call a contract method without caring about the implementation.
 
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, Weak,
+    },
+    time::{Duration, Instant},
+};
+
 use anyhow::Result;
+
+use crate::gas::WasmerGasGlobal;
+
+/// Interval at which the [CallWatcher] checks the interrupt flag and the deadlines of the contract calls it watches.
+const INTERRUPT_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
 /// The struct contains a [wasmer::Instance] which be be invoked through its callable function.
 pub(in crate::contract) struct Instance(pub(crate) wasmer::Instance);
 
@@ -51,10 +65,38 @@ impl Instance {
         match execution_result{
             Ok(_) => Ok(remaining_gas),
             Err(_) if remaining_gas == 0 => Err((remaining_gas, MethodCallError::GasExhaustion)),
+            Err(e) if is_stack_overflow(&e) => Err((remaining_gas, MethodCallError::CallStackExhausted)),
             Err(e) /* remaining_gas > 0 */ => Err((remaining_gas, MethodCallError::Runtime(e)))
         }
     }
 
+    /// call_method_interruptible executes the named method of the Instance as [call_method](Self::call_method), but
+    /// lets `watcher` stop the execution promptly once its interrupt flag is set, or once `deadline` has passed.
+    ///
+    /// `gas_global` is the metering global of this Instance, as shared with its host functions. The watcher exhausts
+    /// it once the call is stopped, which traps the execution at the start of the next block of opcodes. If the call
+    /// then fails by running out of gas, the cause is [MethodCallError::Interrupted] or
+    /// [MethodCallError::CommandTimeout], and no gas remains. Other failures are returned as they are.
+    pub(crate) unsafe fn call_method_interruptible(
+        &self,
+        watcher: &CallWatcher,
+        gas_global: &Arc<Mutex<WasmerGasGlobal>>,
+        deadline: Option<Instant>,
+    ) -> Result<u64, (u64, MethodCallError)> {
+        if let Some(cause) = watcher.stop_cause(deadline) {
+            return Err((0, cause.into()));
+        }
+
+        watcher.watch(gas_global.clone(), deadline);
+        let result = self.call_method();
+        let stopped_by = watcher.unwatch();
+
+        match (result, stopped_by) {
+            (Err((_, MethodCallError::GasExhaustion)), Some(cause)) => Err((0, cause.into())),
+            (result, _) => result,
+        }
+    }
+
     /// return a global variable which can read and modify the metering remaining points of wasm execution of this Instance
     pub(crate) fn remaining_points(&self) -> wasmer::Global {
         self.0
@@ -65,13 +107,117 @@ impl Instance {
     }
 }
 
+/// Stops the contract calls of a transaction once the interrupt flag of the Runtime is set, or once the deadline of
+/// their command has passed. A single thread watches all the calls of the transaction, including contract-to-contract
+/// calls, and exits once the watcher is dropped.
+///
+/// A call is stopped by exhausting its metering global. The global is written under the same lock as the host
+/// functions of the call use, and only once, as the gas of a call is never increased after it starts.
+pub(crate) struct CallWatcher {
+    interrupt: Option<Arc<AtomicBool>>,
+    /// the contract calls which are executing, the innermost last
+    calls: Mutex<Vec<WatchedCall>>,
+}
+
+struct WatchedCall {
+    gas_global: Arc<Mutex<WasmerGasGlobal>>,
+    deadline: Option<Instant>,
+    stopped_by: Option<StopCause>,
+}
+
+/// reasons for which the [CallWatcher] stops a contract call
+#[derive(Clone, Copy)]
+enum StopCause {
+    Interrupted,
+    CommandTimeout,
+}
+
+impl From<StopCause> for MethodCallError {
+    fn from(cause: StopCause) -> Self {
+        match cause {
+            StopCause::Interrupted => MethodCallError::Interrupted,
+            StopCause::CommandTimeout => MethodCallError::CommandTimeout,
+        }
+    }
+}
+
+impl CallWatcher {
+    /// starts the watcher thread of a transaction whose contract calls can be stopped by `interrupt`
+    pub fn start(interrupt: Option<Arc<AtomicBool>>) -> Arc<Self> {
+        let watcher = Arc::new(Self {
+            interrupt,
+            calls: Mutex::new(Vec::new()),
+        });
+        let weak: Weak<Self> = Arc::downgrade(&watcher);
+        std::thread::spawn(move || {
+            while let Some(watcher) = weak.upgrade() {
+                watcher.poll();
+                drop(watcher);
+                std::thread::sleep(INTERRUPT_POLL_INTERVAL);
+            }
+        });
+        watcher
+    }
+
+    /// returns why a call with `deadline` should be stopped now, if it should
+    fn stop_cause(&self, deadline: Option<Instant>) -> Option<StopCause> {
+        match (&self.interrupt, deadline) {
+            (Some(interrupt), _) if interrupt.load(Ordering::Relaxed) => {
+                Some(StopCause::Interrupted)
+            }
+            (_, Some(deadline)) if Instant::now() >= deadline => Some(StopCause::CommandTimeout),
+            _ => None,
+        }
+    }
+
+    /// exhausts the metering globals of the calls which should be stopped, and are not stopped yet
+    fn poll(&self) {
+        let mut calls = self.calls.lock().unwrap();
+        for call in calls.iter_mut().filter(|call| call.stopped_by.is_none()) {
+            if let Some(cause) = self.stop_cause(call.deadline) {
+                call.gas_global.lock().unwrap().exhaust();
+                call.stopped_by = Some(cause);
+            }
+        }
+    }
+
+    /// watches a call which is about to execute, nested in the calls which are already watched
+    fn watch(&self, gas_global: Arc<Mutex<WasmerGasGlobal>>, deadline: Option<Instant>) {
+        self.calls.lock().unwrap().push(WatchedCall {
+            gas_global,
+            deadline,
+            stopped_by: None,
+        });
+    }
+
+    /// stops watching the innermost call, and returns why it was stopped, if it was
+    fn unwatch(&self) -> Option<StopCause> {
+        self.calls
+            .lock()
+            .unwrap()
+            .pop()
+            .and_then(|call| call.stopped_by)
+    }
+}
+
+/// returns true if the runtime error is a trap raised by exhausting the call stack, e.g. by deep recursion
+fn is_stack_overflow(error: &wasmer::RuntimeError) -> bool {
+    matches!(
+        error.clone().to_trap(),
+        Some(wasmer_vm::TrapCode::StackOverflow)
+    )
+}
+
 /// MethodCallError enumerates through the possible reasons why a call into a contract Instance's exported methods might
 /// terminate early.
 #[derive(Debug)]
 pub enum MethodCallError {
     Runtime(wasmer::RuntimeError),
     GasExhaustion,
+    CallStackExhausted,
     NoExportedMethod(wasmer::ExportError),
+    Interrupted,
+    CommandTimeout,
 }
 
 /// ContractValidateError enumerates through the possible reasons why the contract is not runnable
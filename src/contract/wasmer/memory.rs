@@ -5,9 +5,19 @@
 
 //! Defines a struct that implements operations of reading and writing byte arrays into the linear memory of the Wasm instance.
 
-use anyhow::{anyhow, Result};
 use wasmer::{Array, Memory, NativeFunc, WasmPtr};
 
+/// Error in accessing Wasm linear memory. Caused by a contract passing a pointer or length which does not describe
+/// a valid segment of its linear memory, or by its `alloc` function failing.
+#[derive(Debug, thiserror::Error)]
+pub enum MemoryAccessError {
+    #[error("fail to allocate linear memory: {0}")]
+    Alloc(String),
+
+    #[error("segment of {len} bytes at offset {offset} is out of the bounds of linear memory")]
+    OutOfBounds { offset: u32, len: u32 },
+}
+
 /// Provides read-write access to Wasm linear memory through the [Wasmer Environment](crate::contract::wasmer::env).
 /// This Memory context interfaces with Wasmer's exports to facilitate memory operations.
 pub trait MemoryContext {
@@ -15,19 +25,26 @@ pub trait MemoryContext {
     fn alloc(&self) -> &NativeFunc<u32, WasmPtr<u8, Array>>;
 
     /// set the return values to memory and return the length
-    fn write_bytes_to_memory(&self, value: Vec<u8>, val_ptr_ptr: u32) -> Result<u32> {
+    fn write_bytes_to_memory(
+        &self,
+        value: Vec<u8>,
+        val_ptr_ptr: u32,
+    ) -> Result<u32, MemoryAccessError> {
         let memory = self.memory();
         let alloc = self.alloc();
 
         // Allocate segment.
         let segment_ptr = alloc
             .call(value.len() as u32)
-            .map_err(|err| anyhow!("MODERATE: fail to allocate linear memory: {}", err))?;
+            .map_err(|err| MemoryAccessError::Alloc(err.to_string()))?;
 
         // Write bytes.
-        let segment = segment_ptr
-            .deref(memory, 0, value.len() as u32)
-            .ok_or(anyhow!("MODERATE: fail to dereference linear memory"))?;
+        let segment = segment_ptr.deref(memory, 0, value.len() as u32).ok_or(
+            MemoryAccessError::OutOfBounds {
+                offset: segment_ptr.offset(),
+                len: value.len() as u32,
+            },
+        )?;
 
         for i in 0..value.len() {
             segment[i].set(value[i]);
@@ -38,20 +55,26 @@ pub trait MemoryContext {
 
         // Write linear memory offset (val_offset) to the memory segment pointed to by `val_ptr_ptr`
         let val_ptr_ptr: WasmPtr<u32, wasmer::Array> = WasmPtr::new(val_ptr_ptr);
-        let val_ptr_segment = val_ptr_ptr.deref(memory, 0, 1).unwrap();
+        let val_ptr_segment =
+            val_ptr_ptr
+                .deref(memory, 0, 1)
+                .ok_or(MemoryAccessError::OutOfBounds {
+                    offset: val_ptr_ptr.offset(),
+                    len: std::mem::size_of::<u32>() as u32,
+                })?;
         val_ptr_segment[0].set(val_offset);
 
         Ok(value_len as u32)
     }
 
     /// read bytes from memory given the offset and len of the memory location
-    fn read_bytes_from_memory(&self, offset: u32, len: u32) -> Result<Vec<u8>> {
+    fn read_bytes_from_memory(&self, offset: u32, len: u32) -> Result<Vec<u8>, MemoryAccessError> {
         let memory = self.memory();
         let bytes_ptr: WasmPtr<u8, Array> = WasmPtr::new(offset);
 
         let bytes = bytes_ptr
             .deref(memory, 0, len)
-            .ok_or(anyhow!("MODERATE: fail to read bytes from linear memory"))?;
+            .ok_or(MemoryAccessError::OutOfBounds { offset, len })?;
 
         let mut bytes_copy = Vec::new();
         for byte in bytes {
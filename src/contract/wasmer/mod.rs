@@ -12,6 +12,9 @@
 //! and the store, equipped with a [Wasmer OpCode Filter](non_determinism_filter),
 //! maintains the runtime state and ensures that only valid contracts are executed.
 //!
+//! With the `profiling` feature, the store also includes a [gas profiler](gas_profiler) which attributes the gas used
+//! by Wasm opcodes to categories of opcodes.
+//!
 //! To reduce compilation times, a [cache] is also available for storing compiled smart contracts.
 
 pub mod env;
@@ -24,6 +27,9 @@ pub mod store;
 
 pub mod non_determinism_filter;
 
+#[cfg(feature = "profiling")]
+pub mod gas_profiler;
+
 pub mod cache;
 
 pub mod custom_tunables;
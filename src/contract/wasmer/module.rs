@@ -8,6 +8,7 @@
 use pchain_types::cryptography::PublicAddress;
 
 use crate::contract::wasmer::cache::{Cache as SmartContractCache, ModuleMetadata};
+use crate::contract::wasmer::store::CompilerBackend;
 use crate::contract::{empty, Importable};
 
 use super::instance::{ContractValidateError, Instance, CONTRACT_METHOD};
@@ -17,21 +18,27 @@ use super::instance::{ContractValidateError, Instance, CONTRACT_METHOD};
 pub(in crate::contract) struct Module(pub wasmer::Module, pub ModuleMetadata);
 
 impl Module {
-    /// returns the contract module cached in smart contract cache
+    /// returns the contract module cached in smart contract cache, if it was compiled with `compiler_backend`
     pub fn from_cache(
         address: PublicAddress,
         cache: &SmartContractCache,
         wasmer_store: &wasmer::Store,
+        compiler_backend: CompilerBackend,
     ) -> Option<Module> {
         cache
-            .load(address, wasmer_store)
+            .load(address, wasmer_store, compiler_backend)
             .ok()
             .map(|(m, d)| Module(m, d))
     }
 
     /// caches the contract module
     pub fn cache_to(&self, address: PublicAddress, cache: &SmartContractCache) {
-        let _ = cache.store(address, &self.0, self.1.bytecode_length);
+        let _ = cache.store(
+            address,
+            &self.0,
+            self.1.bytecode_length,
+            self.1.compiler_backend,
+        );
     }
 
     /// compiles bytecode with validation, potentially slow
@@ -39,20 +46,17 @@ impl Module {
         cbi_version: u32,
         bytecode: &Vec<u8>,
         wasmer_store: &wasmer::Store,
+        compiler_backend: CompilerBackend,
     ) -> Result<Module, ModuleBuildError> {
-        let wasmer_module = wasmer::Module::from_binary(wasmer_store, bytecode).map_err(|e| {
-            if e.to_string().contains("OpcodeError") {
-                ModuleBuildError::DisallowedOpcodePresent
-            } else {
-                ModuleBuildError::Else
-            }
-        })?;
+        let wasmer_module = wasmer::Module::from_binary(wasmer_store, bytecode)
+            .map_err(|e| ModuleBuildError::from_compile_error(&e.to_string()))?;
 
         Ok(Module(
             wasmer_module,
             ModuleMetadata {
                 cbi_version,
                 bytecode_length: bytecode.len(),
+                compiler_backend,
             },
         ))
     }
@@ -63,23 +67,18 @@ impl Module {
         cbi_version: u32,
         bytecode: &Vec<u8>,
         wasmer_store: &wasmer::Store,
+        compiler_backend: CompilerBackend,
     ) -> Result<Module, ModuleBuildError> {
         let wasmer_module =
-            unsafe { wasmer::Module::from_binary_unchecked(wasmer_store, bytecode) }.map_err(
-                |e| {
-                    if e.to_string().contains("OpcodeError") {
-                        ModuleBuildError::DisallowedOpcodePresent
-                    } else {
-                        ModuleBuildError::Else
-                    }
-                },
-            )?;
+            unsafe { wasmer::Module::from_binary_unchecked(wasmer_store, bytecode) }
+                .map_err(|e| ModuleBuildError::from_compile_error(&e.to_string()))?;
 
         Ok(Module(
             wasmer_module,
             ModuleMetadata {
                 cbi_version,
                 bytecode_length: bytecode.len(),
+                compiler_backend,
             },
         ))
     }
@@ -104,10 +103,12 @@ impl Module {
     }
 
     /// returns whether this contract Module
-    /// exports a correctly named entry point method which can be invoked by the call() function.
+    /// exports a correctly named entry point method which can be invoked by the call() function,
+    /// and imports only host functions of the given CBI version.
     pub fn validate_entry_point(
         &self,
         wasmer_store: &wasmer::Store,
+        cbi_version: u32,
     ) -> Result<(), ContractValidateError> {
         if !self
             .0
@@ -117,7 +118,7 @@ impl Module {
         {
             return Err(ContractValidateError::MethodNotFound);
         }
-        let imports_object = empty::imports(wasmer_store);
+        let imports_object = empty::imports(wasmer_store, cbi_version);
         if let Ok(instance) = wasmer::Instance::new(&self.0, &imports_object) {
             if instance
                 .exports
@@ -140,3 +141,16 @@ pub(crate) enum ModuleBuildError {
     /// Errors other than `DisallowedOpcodePresent`
     Else,
 }
+
+impl ModuleBuildError {
+    /// classifies the compile error with the message `error`. The opcode rejected by the
+    /// [NonDeterminismFilter](super::non_determinism_filter::NonDeterminismFilter) is not kept, as it depends on which
+    /// function is compiled first. See [disallowed_opcode](super::non_determinism_filter::disallowed_opcode).
+    fn from_compile_error(error: &str) -> Self {
+        if error.contains("OpcodeError") {
+            ModuleBuildError::DisallowedOpcodePresent
+        } else {
+            ModuleBuildError::Else
+        }
+    }
+}
@@ -7,11 +7,16 @@
 //!
 //! Non-deterministic operations refer to some specific Opcodes in Wasm execution,
 //! for example, floating point operations.
+//!
+//! The filter rejects a function at its first disallowed operator. Since functions may be compiled in parallel, the
+//! compile error of a contract may name any of its disallowed operators. [disallowed_opcode] finds the first one of
+//! the contract deterministically.
 
 use loupe::MemoryUsage;
 use wasmer::{
-    wasmparser::Operator, FunctionMiddleware, LocalFunctionIndex, MiddlewareError,
-    MiddlewareReaderState, ModuleMiddleware,
+    wasmparser::{Operator, Parser, Payload},
+    FunctionMiddleware, LocalFunctionIndex, MiddlewareError, MiddlewareReaderState,
+    ModuleMiddleware,
 };
 
 /// NonDeterminismFilterConfig defines boolean flags specific to each opcode family.
@@ -42,70 +47,9 @@ struct NonDeterminismFilterConfig {
     allow_exception_handling: bool,
 }
 
-/// NonDeterminismFilter is the middleware that disallows use of features from Wasm which may induce non-determinism.
-#[derive(Debug, MemoryUsage)]
-#[non_exhaustive]
-pub struct NonDeterminismFilter {
-    config: NonDeterminismFilterConfig,
-}
-
-impl NonDeterminismFilter {
-    // spins up a new instance for NonDeterminismFilter middleware with custom config setting.
-    // Currently set to private.The access is given through a default implementation with a preset
-    // config setting.
-    fn create(config: NonDeterminismFilterConfig) -> Self {
-        Self { config }
-    }
-}
-
-impl Default for NonDeterminismFilter {
-    // default is an implementation for NonDeterminismFilter that loads
-    // a set of boolean flags on NonDeterminismFilterConfig when the method "default" is called.
-    fn default() -> Self {
-        Self::create(NonDeterminismFilterConfig {
-            // floating point operations are set to false to enforce determinism inside the ParallelChain Mainnet ecosystem.
-            allow_floating_point_ops: false,
-            // simd ops are set to false to promote enforce inside the ParallelChain Mainnet ecosystem.
-            allow_simd_ops: false,
-            // atomic operations are set to false as they need Wasm threads to execute.
-            allow_atomic_ops: false,
-            // bulk memory operations are set to true.
-            allow_bulk_memory_operations: true,
-            // reference types are set to true.
-            allow_reference_types: true,
-            // exception handling has been set to true.
-            allow_exception_handling: true,
-        })
-    }
-}
-
-impl ModuleMiddleware for NonDeterminismFilter {
-    fn generate_function_middleware(&self, _: LocalFunctionIndex) -> Box<dyn FunctionMiddleware> {
-        Box::new(FunctionNonDeterminismFilter::new(self.config))
-    }
-}
-
-#[derive(Debug)]
-#[non_exhaustive]
-struct FunctionNonDeterminismFilter {
-    config: NonDeterminismFilterConfig,
-}
-
-impl FunctionNonDeterminismFilter {
-    fn new(config: NonDeterminismFilterConfig) -> Self {
-        Self { config }
-    }
-}
-
-/// FunctionMiddleware enables checks for each Wasm opcode family
-/// Raises MiddlewareError if the corresponding flag in NonDeterminismFilterConfig is false
-impl FunctionMiddleware for FunctionNonDeterminismFilter {
-    // Process the given operator.
-    fn feed<'a>(
-        &mut self,
-        operator: Operator<'a>,
-        state: &mut MiddlewareReaderState<'a>,
-    ) -> Result<(), MiddlewareError> {
+impl NonDeterminismFilterConfig {
+    /// returns the opcode family of `operator` if it is disallowed by this config, e.g. "Floating Point Operations"
+    fn rejected_family(&self, operator: &Operator) -> Option<&'static str> {
         match operator {
             // Opcode family of Reference types
             Operator::RefNull { .. }
@@ -118,13 +62,7 @@ impl FunctionMiddleware for FunctionNonDeterminismFilter {
             | Operator::TableSet { .. }
             | Operator::TableGrow { .. }
             | Operator::TableSize { .. } => {
-                if self.config.allow_reference_types {
-                    state.push_operator(operator);
-                    Ok(())
-                } else {
-                    let msg = "OpcodeError: Reference Types";
-                    Err(MiddlewareError::new(" ", msg))
-                }
+                (!self.allow_reference_types).then_some("Reference Types")
             }
             // Opcode family of Atomic operations using Wasm threads
             Operator::MemoryAtomicNotify { .. }
@@ -194,13 +132,7 @@ impl FunctionMiddleware for FunctionNonDeterminismFilter {
             | Operator::I64AtomicRmw8CmpxchgU { .. }
             | Operator::I64AtomicRmw16CmpxchgU { .. }
             | Operator::I64AtomicRmw32CmpxchgU { .. } => {
-                if self.config.allow_atomic_ops {
-                    state.push_operator(operator);
-                    Ok(())
-                } else {
-                    let msg = "OpcodeError: Atomic Operations";
-                    Err(MiddlewareError::new(" ", msg))
-                }
+                (!self.allow_atomic_ops).then_some("Atomic Operations")
             }
             // Opcode family of logical operations, memory and integer type mathematical operators
             Operator::Unreachable
@@ -311,10 +243,7 @@ impl FunctionMiddleware for FunctionNonDeterminismFilter {
             | Operator::I64Extend16S
             | Operator::I64ExtendI32S
             | Operator::I64Extend32S
-            | Operator::I64ExtendI32U => {
-                state.push_operator(operator);
-                Ok(())
-            }
+            | Operator::I64ExtendI32U => None,
             // Opcode family of fixed width SIMD operations
             Operator::V128Load { .. }
             | Operator::V128Store { .. }
@@ -506,15 +435,7 @@ impl FunctionMiddleware for FunctionNonDeterminismFilter {
             | Operator::F64x2ConvertLowI32x4S
             | Operator::F64x2ConvertLowI32x4U
             | Operator::F32x4DemoteF64x2Zero
-            | Operator::F64x2PromoteLowF32x4 => {
-                if self.config.allow_simd_ops {
-                    state.push_operator(operator);
-                    Ok(())
-                } else {
-                    let msg = "OpcodeError: SIMD Operations";
-                    Err(MiddlewareError::new(" ", msg))
-                }
-            }
+            | Operator::F64x2PromoteLowF32x4 => (!self.allow_simd_ops).then_some("SIMD Operations"),
             // Opcode family of floating point operations
             Operator::F32Load { .. }
             | Operator::F64Load { .. }
@@ -654,13 +575,7 @@ impl FunctionMiddleware for FunctionNonDeterminismFilter {
             | Operator::I32x4RelaxedTruncSatF32x4U
             | Operator::F32x4ConvertI32x4S
             | Operator::F32x4ConvertI32x4U => {
-                if self.config.allow_floating_point_ops {
-                    state.push_operator(operator);
-                    Ok(())
-                } else {
-                    let msg = "OpcodeError: Floating Point Operations";
-                    Err(MiddlewareError::new(" ", msg))
-                }
+                (!self.allow_floating_point_ops).then_some("Floating Point Operations")
             }
             // Opcode family of bulk memory operations
             Operator::MemoryInit { .. }
@@ -671,13 +586,7 @@ impl FunctionMiddleware for FunctionNonDeterminismFilter {
             | Operator::TableInit { .. }
             | Operator::ElemDrop { .. }
             | Operator::TableFill { .. } => {
-                if self.config.allow_bulk_memory_operations {
-                    state.push_operator(operator);
-                    Ok(())
-                } else {
-                    let msg = "OpcodeError: Bulk Memory Operations";
-                    Err(MiddlewareError::new(" ", msg))
-                }
+                (!self.allow_bulk_memory_operations).then_some("Bulk Memory Operations")
             }
             // Opcode family for exception handling
             Operator::Try { .. }
@@ -686,13 +595,134 @@ impl FunctionMiddleware for FunctionNonDeterminismFilter {
             | Operator::Throw { .. }
             | Operator::Rethrow { .. }
             | Operator::CatchAll => {
-                if self.config.allow_exception_handling {
-                    state.push_operator(operator);
-                    Ok(())
-                } else {
-                    let msg = "OpcodeError: Exception Handling";
-                    Err(MiddlewareError::new(" ", msg))
+                (!self.allow_exception_handling).then_some("Exception Handling")
+            }
+        }
+    }
+}
+
+/// NonDeterminismFilter is the middleware that disallows use of features from Wasm which may induce non-determinism.
+#[derive(Debug, MemoryUsage)]
+#[non_exhaustive]
+pub struct NonDeterminismFilter {
+    config: NonDeterminismFilterConfig,
+}
+
+impl NonDeterminismFilter {
+    // spins up a new instance for NonDeterminismFilter middleware with custom config setting.
+    // Currently set to private.The access is given through a default implementation with a preset
+    // config setting.
+    fn create(config: NonDeterminismFilterConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl Default for NonDeterminismFilter {
+    // default is an implementation for NonDeterminismFilter that loads
+    // a set of boolean flags on NonDeterminismFilterConfig when the method "default" is called.
+    fn default() -> Self {
+        Self::create(NonDeterminismFilterConfig {
+            // floating point operations are set to false to enforce determinism inside the ParallelChain Mainnet ecosystem.
+            allow_floating_point_ops: false,
+            // simd ops are set to false to promote enforce inside the ParallelChain Mainnet ecosystem.
+            allow_simd_ops: false,
+            // atomic operations are set to false as they need Wasm threads to execute.
+            allow_atomic_ops: false,
+            // bulk memory operations are set to true.
+            allow_bulk_memory_operations: true,
+            // reference types are set to true.
+            allow_reference_types: true,
+            // exception handling has been set to true.
+            allow_exception_handling: true,
+        })
+    }
+}
+
+impl ModuleMiddleware for NonDeterminismFilter {
+    fn generate_function_middleware(&self, _: LocalFunctionIndex) -> Box<dyn FunctionMiddleware> {
+        Box::new(FunctionNonDeterminismFilter::new(self.config))
+    }
+}
+
+#[derive(Debug)]
+#[non_exhaustive]
+struct FunctionNonDeterminismFilter {
+    config: NonDeterminismFilterConfig,
+}
+
+impl FunctionNonDeterminismFilter {
+    fn new(config: NonDeterminismFilterConfig) -> Self {
+        Self { config }
+    }
+}
+
+/// A disallowed opcode in a contract, as found by [disallowed_opcode].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisallowedOpcode {
+    /// Name of the opcode as its wasmparser Operator variant, e.g. "F64Add".
+    pub opcode: String,
+    /// Index of the function among the functions defined in the contract, not counting imported functions.
+    pub function_index: u32,
+    /// Index of the operator among the operators of the function body.
+    pub offset: usize,
+}
+
+/// Returns the first disallowed opcode of the contract `bytecode`, in the function with the lowest index, or None if
+/// the filter allows every opcode of the contract, or the bytecode cannot be parsed.
+///
+/// Deployment of a contract with a disallowed opcode fails with
+/// [DisallowedOpcode](crate::TransitionError::DisallowedOpcode), which does not carry the opcode. This function
+/// finds it for the developer of the contract, and always finds the same one.
+pub fn disallowed_opcode(bytecode: &[u8]) -> Option<DisallowedOpcode> {
+    let config = NonDeterminismFilter::default().config;
+    let mut function_index = 0;
+    for payload in Parser::new(0).parse_all(bytecode) {
+        if let Payload::CodeSectionEntry(body) = payload.ok()? {
+            let mut operators = body.get_operators_reader().ok()?;
+            let mut offset = 0;
+            while !operators.eof() {
+                let operator = operators.read().ok()?;
+                if config.rejected_family(&operator).is_some() {
+                    return Some(DisallowedOpcode {
+                        opcode: opcode_name(&operator),
+                        function_index,
+                        offset,
+                    });
                 }
+                offset += 1;
+            }
+            function_index += 1;
+        }
+    }
+    None
+}
+
+/// name of the opcode of `operator` as its Operator variant, e.g. "F64Add"
+fn opcode_name(operator: &Operator) -> String {
+    format!("{:?}", operator)
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// FunctionMiddleware enables checks for each Wasm opcode family
+/// Raises MiddlewareError if the corresponding flag in NonDeterminismFilterConfig is false
+impl FunctionMiddleware for FunctionNonDeterminismFilter {
+    // Process the given operator.
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        match self.config.rejected_family(&operator) {
+            Some(family) => {
+                let msg = format!("OpcodeError: {family}: {}", opcode_name(&operator));
+                Err(MiddlewareError::new(" ", msg))
+            }
+            None => {
+                state.push_operator(operator);
+                Ok(())
             }
         }
     }
@@ -748,6 +778,14 @@ mod tests {
         let store = Store::new(&Universal::new(compiler_config).engine());
         let result = Module::new(&store, &wasm);
         assert!(result.unwrap_err().to_string().contains("OpcodeError"));
+        assert_eq!(
+            disallowed_opcode(&wasm),
+            Some(DisallowedOpcode {
+                opcode: "F64ConvertI64U".to_string(),
+                function_index: 0,
+                offset: 1,
+            })
+        );
 
         // Allow Floating Point
         let mut fitler = NonDeterminismFilter::default();
@@ -760,6 +798,53 @@ mod tests {
         assert!(result.is_ok())
     }
 
+    #[test]
+    fn check_disallowed_opcode_in_first_function() {
+        let wasm = wat::parse_str(
+            r#"
+            (module
+                (func $sum (param i64 i64) (result i64)
+                    local.get 0
+                    local.get 1
+                    i64.add
+                )
+                (func $add (param f64 f64) (result f64)
+                    local.get 0
+                    local.get 1
+                    f64.add
+                )
+                (func $to_float (param i64) (result f64)
+                    local.get 0
+                    f64.convert_i64_u
+                ))
+            "#,
+        )
+        .unwrap();
+
+        // the lowest function index, whichever function the compiler rejected
+        assert_eq!(
+            disallowed_opcode(&wasm),
+            Some(DisallowedOpcode {
+                opcode: "F64Add".to_string(),
+                function_index: 1,
+                offset: 2,
+            })
+        );
+
+        let wasm = wat::parse_str(
+            r#"
+            (module
+                (func (export "sum") (param i64 i64) (result i64)
+                    (local.get 0)
+                    (local.get 1)
+                    (i64.add)
+                ))
+            "#,
+        )
+        .unwrap();
+        assert_eq!(disallowed_opcode(&wasm), None);
+    }
+
     #[test]
     fn check_simd() {
         let wasm = wat::parse_str(
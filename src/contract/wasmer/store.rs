@@ -10,6 +10,7 @@
 //!
 //! Utilized by [ContractModule](crate::contract::wasmer::module), this module includes functions for:
 //! - Instantiating the store with custom middleware, such as [NonDeterminismFilter](super::non_determinism_filter::NonDeterminismFilter), and configuring gas and memory limits.
+//! - Selecting the [CompilerBackend] which translates Wasm into machine code.
 //! - Setting a cap on the Wasm linear memory.
 //!
 //! These functions play a pivotal role in tailoring the Wasmer-provided store to the exact needs of the contract execution environment.
@@ -26,19 +27,95 @@ use crate::gas::wasm_opcode_gas_schedule;
 use super::custom_tunables::CustomTunables;
 use super::non_determinism_filter::NonDeterminismFilter;
 
+/// Compiler used to translate contract Wasm bytecode into machine code.
+///
+/// The choice of compiler mostly trades off compilation speed against execution speed. The
+/// [filter](super::non_determinism_filter::NonDeterminismFilter) and gas metering middlewares operate on Wasm opcodes
+/// before compilation, so every backend produces identical gas usage. However, the size of the native stack frames
+/// differs between backends, so the call depth at which a contract exhausts the call stack, and fails with
+/// [CallStackExhausted](crate::TransitionError::CallStackExhausted), depends on the backend. All nodes which execute
+/// the same blocks must therefore use the same backend.
+///
+/// Backends other than Singlepass are enabled by the crate features `cranelift` and `llvm`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CompilerBackend {
+    /// Optimised for fast compilation. This is the default.
+    #[default]
+    Singlepass,
+    /// Balances compilation speed and speed of the generated code.
+    #[cfg(feature = "cranelift")]
+    Cranelift,
+    /// Optimised for speed of the generated code, at the cost of slow compilation.
+    #[cfg(feature = "llvm")]
+    LLVM,
+}
+
+impl CompilerBackend {
+    /// identifies the backend in the metadata of the smart contract cache
+    pub(crate) fn id(&self) -> u8 {
+        match self {
+            CompilerBackend::Singlepass => 0,
+            #[cfg(feature = "cranelift")]
+            CompilerBackend::Cranelift => 1,
+            #[cfg(feature = "llvm")]
+            CompilerBackend::LLVM => 2,
+        }
+    }
+
+    /// the backend identified by `id`, or None if it is unknown or not enabled in this build
+    pub(crate) fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(CompilerBackend::Singlepass),
+            #[cfg(feature = "cranelift")]
+            1 => Some(CompilerBackend::Cranelift),
+            #[cfg(feature = "llvm")]
+            2 => Some(CompilerBackend::LLVM),
+            _ => None,
+        }
+    }
+}
+
 /// Instantiate a Store which includes customised middleware e.g. [filter](super::non_determinism_filter::NonDeterminismFilter).
-pub fn instantiate_store(gas_limit: u64, memory_limit: Option<usize>) -> Store {
+pub fn instantiate_store(
+    gas_limit: u64,
+    memory_limit: Option<usize>,
+    compiler_backend: CompilerBackend,
+) -> Store {
     // call non_determinism_filter.rs to disallow non-deterministic types
     let nd_filter = Arc::new(NonDeterminismFilter::default());
 
     // define the metering middleware
+    #[cfg(not(feature = "profiling"))]
     let metering = Arc::new(Metering::new(gas_limit, wasm_opcode_gas_schedule));
+    // wrap the metering middleware to profile the gas of opcodes, without metering the profiling instructions
+    #[cfg(feature = "profiling")]
+    let metering = Arc::new(super::gas_profiler::GasProfiler::new(Metering::new(
+        gas_limit,
+        wasm_opcode_gas_schedule,
+    )));
 
-    // use the Singlepass compiler which is optimised for fast compilation
-    let mut compiler_config = Singlepass::new();
-    compiler_config.push_middleware(nd_filter);
-    compiler_config.push_middleware(metering);
-    let engine = Universal::new(compiler_config).engine();
+    let engine = match compiler_backend {
+        CompilerBackend::Singlepass => {
+            let mut compiler_config = Singlepass::new();
+            compiler_config.push_middleware(nd_filter);
+            compiler_config.push_middleware(metering);
+            Universal::new(compiler_config).engine()
+        }
+        #[cfg(feature = "cranelift")]
+        CompilerBackend::Cranelift => {
+            let mut compiler_config = wasmer_compiler_cranelift::Cranelift::new();
+            compiler_config.push_middleware(nd_filter);
+            compiler_config.push_middleware(metering);
+            Universal::new(compiler_config).engine()
+        }
+        #[cfg(feature = "llvm")]
+        CompilerBackend::LLVM => {
+            let mut compiler_config = wasmer_compiler_llvm::LLVM::new();
+            compiler_config.push_middleware(nd_filter);
+            compiler_config.push_middleware(metering);
+            Universal::new(compiler_config).engine()
+        }
+    };
 
     // creates a Wasmer store with an optional guest memory limit
     // If no memory limit is set, the method falls back to creating the store without custom memory adjustment
@@ -12,14 +12,21 @@
 
 use pchain_types::blockchain::{ExitCodeV1, ExitCodeV2};
 
-use crate::contract::{wasmer::instance::MethodCallError, FuncError};
+use crate::contract::{
+    wasmer::{instance::MethodCallError, memory::MemoryAccessError},
+    FuncError,
+};
 
-/// Descriptive error definitions of a Transition
+/// Descriptive error definitions of a Transition. Each error has a stable numeric [code](TransitionError::code).
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum TransitionError {
     /// Failed to upgrade World State
     FailedWorldStateUpgrade,
 
+    /// World State to be upgraded has a pool set, or delegated stakes of a pool, which exceed their maximum size or
+    /// cannot be read.
+    MalformedWorldState,
+
     /// Nonce is not current nonce.
     WrongNonce,
 
@@ -29,10 +36,20 @@ pub enum TransitionError {
     /// Not enough balance to pay for transfer.
     NotEnoughBalanceForTransfer,
 
+    /// Gas limit is lower than the transaction inclusion cost, so the transaction cannot be included in a block.
+    /// Returned in the Pre-Charge phase before any state is changed, so the transaction may be resubmitted with a
+    /// higher gas limit.
+    GasLimitTooLow,
+
     /// Gas limit was insufficient to cover pre-execution costs.
+    ///
+    /// Unused: no longer returned, as the only pre-execution cost is the inclusion cost, for which
+    /// [GasLimitTooLow](TransitionError::GasLimitTooLow) is returned instead. Kept because TransitionError is a public
+    /// enum, so removing a variant would break code which names it.
     PreExecutionGasExhausted,
 
-    /// The contract bytecode contains disallowed opcodes.
+    /// The contract bytecode contains disallowed opcodes, e.g. a floating point operation. The first of them is found
+    /// with [disallowed_opcode](crate::contract::wasmer::non_determinism_filter::disallowed_opcode).
     DisallowedOpcode,
 
     /// Contract cannot be compiled into machine code (it is probably invalid Wasm).
@@ -59,6 +76,10 @@ pub enum TransitionError {
     /// Runtime error during execution proper of the entree smart contract.
     RuntimeError,
 
+    /// Execution proper of the entree smart contract trapped because it exhausted the call stack,
+    /// e.g. by recursing too deeply. Distinct from running out of gas.
+    CallStackExhausted,
+
     /// Gas limit was insufficient to cover execution proper costs of an internal transaction.
     InternalExecutionProperGasExhaustion,
 
@@ -74,21 +95,32 @@ pub enum TransitionError {
     /// Staking Command - Unstake Deposit fails because the Pool has no stakes.
     PoolHasNoStakes,
 
-    /// Staking Command fails because pool policy is invalid.
-    /// Scenarios such as
-    /// 1. commission fee is greater than 100
-    /// 2. commission fee is as same as the origin onw
+    /// Staking Command fails because pool policy is invalid, i.e. the commission fee is greater than 100.
+    /// Setting the commission rate of a pool to its current value fails with
+    /// [CommissionRateUnchanged](TransitionError::CommissionRateUnchanged) instead.
     InvalidPoolPolicy,
 
+    /// Staking Command - Set Pool Settings fails because the commission rate is the same as the current one.
+    /// An update which changes nothing is rejected rather than treated as a no-op.
+    CommissionRateUnchanged,
+
     /// Staking Command - Create Deposits fails because the deposits already exists
     DepositsAlreadyExists,
 
     /// Staking Command fails because the deposits does not exist.
     DepositsNotExists,
 
-    /// Staking Command - Set Deposit Settings fails because the deposit amount
+    /// Staking Command - Set Deposit Settings fails because the new deposit settings are invalid.
+    ///
+    /// Unused: no longer returned, as a Set Deposit Settings Command which changes nothing fails with
+    /// [AutoStakeRewardsUnchanged](TransitionError::AutoStakeRewardsUnchanged) instead. Kept because TransitionError
+    /// is a public enum, so removing a variant would break code which names it.
     InvalidDepositPolicy,
 
+    /// Staking Command - Set Deposit Settings fails because the auto stake rewards setting is the same as the current
+    /// one. An update which changes nothing is rejected rather than treated as a no-op.
+    AutoStakeRewardsUnchanged,
+
     /// Staking Command fails because the specified amount does not match with the requirement of the operation.
     /// Scenarios such as
     /// 1. Stake power has already reached upper limit (deposit amount) for Command - Stake Deposit
@@ -101,12 +133,133 @@ pub enum TransitionError {
 
     /// There is more than 1 NextEpoch Command in a transaction.
     InvalidNextEpochCommand,
+
+    /// Contract passed a storage key longer than the configured maximum key length.
+    KeyTooLong,
+
+    /// Contracts attempted to transfer more than the configured maximum value in a transaction.
+    TransferLimitExceeded,
+
+    /// A contract-to-contract call was nested deeper than the configured maximum call depth.
+    CallDepthExceeded,
+
+    /// Execution of a contract was stopped by the [interrupt handle](crate::Runtime::set_interrupt) of the Runtime.
+    Interrupted,
+
+    /// A Call command took longer than the [command time budget](crate::Runtime::set_command_time_budget) of the
+    /// Runtime.
+    CommandTimeout,
+
+    /// A Deploy command's contract bytecode is longer than the
+    /// [maximum contract size](crate::Runtime::set_max_contract_size) of the Runtime.
+    ContractTooLarge,
+
+    /// Contracts attempted to defer more commands in a transaction than the configured
+    /// [maximum number of spawned commands](crate::Runtime::set_max_spawned_commands).
+    SpawnedCommandLimitExceeded,
+}
+
+impl TransitionError {
+    /// Stable numeric code of the error, e.g. to pass it across an FFI boundary. Codes are assigned explicitly and
+    /// never change or get reused: a new variant gets the next unused code, wherever it is declared. Codes start
+    /// from 1, so 0 is free to mean "no error".
+    pub fn code(&self) -> u32 {
+        match self {
+            TransitionError::FailedWorldStateUpgrade => 1,
+            TransitionError::MalformedWorldState => 2,
+            TransitionError::WrongNonce => 3,
+            TransitionError::NotEnoughBalanceForGasLimit => 4,
+            TransitionError::NotEnoughBalanceForTransfer => 5,
+            TransitionError::GasLimitTooLow => 6,
+            TransitionError::PreExecutionGasExhausted => 7,
+            TransitionError::DisallowedOpcode => 8,
+            TransitionError::CannotCompile => 9,
+            TransitionError::NoExportedContractMethod => 10,
+            TransitionError::OtherDeployError => 11,
+            TransitionError::ContractAlreadyExists => 12,
+            TransitionError::NoContractcode => 13,
+            TransitionError::InvalidCBI => 14,
+            TransitionError::ExecutionProperGasExhausted => 15,
+            TransitionError::RuntimeError => 16,
+            TransitionError::CallStackExhausted => 17,
+            TransitionError::InternalExecutionProperGasExhaustion => 18,
+            TransitionError::InternalRuntimeError => 19,
+            TransitionError::PoolAlreadyExists => 20,
+            TransitionError::PoolNotExists => 21,
+            TransitionError::PoolHasNoStakes => 22,
+            TransitionError::InvalidPoolPolicy => 23,
+            TransitionError::CommissionRateUnchanged => 24,
+            TransitionError::DepositsAlreadyExists => 25,
+            TransitionError::DepositsNotExists => 26,
+            TransitionError::InvalidDepositPolicy => 27,
+            TransitionError::AutoStakeRewardsUnchanged => 28,
+            TransitionError::InvalidStakeAmount => 29,
+            TransitionError::InvalidCommands => 30,
+            TransitionError::InvalidNextEpochCommand => 31,
+            TransitionError::KeyTooLong => 32,
+            TransitionError::TransferLimitExceeded => 33,
+            TransitionError::CallDepthExceeded => 34,
+            TransitionError::Interrupted => 35,
+            TransitionError::CommandTimeout => 36,
+            TransitionError::ContractTooLarge => 37,
+            TransitionError::SpawnedCommandLimitExceeded => 38,
+        }
+    }
+
+    /// The error with the given [code](Self::code), or None if no error has this code.
+    pub fn from_code(code: u32) -> Option<Self> {
+        let error = match code {
+            1 => TransitionError::FailedWorldStateUpgrade,
+            2 => TransitionError::MalformedWorldState,
+            3 => TransitionError::WrongNonce,
+            4 => TransitionError::NotEnoughBalanceForGasLimit,
+            5 => TransitionError::NotEnoughBalanceForTransfer,
+            6 => TransitionError::GasLimitTooLow,
+            7 => TransitionError::PreExecutionGasExhausted,
+            8 => TransitionError::DisallowedOpcode,
+            9 => TransitionError::CannotCompile,
+            10 => TransitionError::NoExportedContractMethod,
+            11 => TransitionError::OtherDeployError,
+            12 => TransitionError::ContractAlreadyExists,
+            13 => TransitionError::NoContractcode,
+            14 => TransitionError::InvalidCBI,
+            15 => TransitionError::ExecutionProperGasExhausted,
+            16 => TransitionError::RuntimeError,
+            17 => TransitionError::CallStackExhausted,
+            18 => TransitionError::InternalExecutionProperGasExhaustion,
+            19 => TransitionError::InternalRuntimeError,
+            20 => TransitionError::PoolAlreadyExists,
+            21 => TransitionError::PoolNotExists,
+            22 => TransitionError::PoolHasNoStakes,
+            23 => TransitionError::InvalidPoolPolicy,
+            24 => TransitionError::CommissionRateUnchanged,
+            25 => TransitionError::DepositsAlreadyExists,
+            26 => TransitionError::DepositsNotExists,
+            27 => TransitionError::InvalidDepositPolicy,
+            28 => TransitionError::AutoStakeRewardsUnchanged,
+            29 => TransitionError::InvalidStakeAmount,
+            30 => TransitionError::InvalidCommands,
+            31 => TransitionError::InvalidNextEpochCommand,
+            32 => TransitionError::KeyTooLong,
+            33 => TransitionError::TransferLimitExceeded,
+            34 => TransitionError::CallDepthExceeded,
+            35 => TransitionError::Interrupted,
+            36 => TransitionError::CommandTimeout,
+            37 => TransitionError::ContractTooLarge,
+            38 => TransitionError::SpawnedCommandLimitExceeded,
+            _ => return None,
+        };
+        Some(error)
+    }
 }
 
 impl From<MethodCallError> for TransitionError {
     fn from(call_error: MethodCallError) -> Self {
         match call_error {
             MethodCallError::GasExhaustion => TransitionError::ExecutionProperGasExhausted,
+            MethodCallError::CallStackExhausted => TransitionError::CallStackExhausted,
+            MethodCallError::Interrupted => TransitionError::Interrupted,
+            MethodCallError::CommandTimeout => TransitionError::CommandTimeout,
             MethodCallError::NoExportedMethod(_) => TransitionError::RuntimeError,
             MethodCallError::Runtime(e) => {
                 // check for internal errors
@@ -115,6 +268,16 @@ impl From<MethodCallError> for TransitionError {
                     Ok(FuncError::GasExhaustionError) => {
                         TransitionError::ExecutionProperGasExhausted
                     }
+                    Ok(FuncError::KeyTooLong) => TransitionError::KeyTooLong,
+                    Ok(FuncError::TransferLimitExceeded) => TransitionError::TransferLimitExceeded,
+                    Ok(FuncError::CallDepthExceeded) => TransitionError::CallDepthExceeded,
+                    Ok(FuncError::SpawnedCommandLimitExceeded) => {
+                        TransitionError::SpawnedCommandLimitExceeded
+                    }
+                    // a bad pointer or length is a fault of the contract, like a Wasm trap
+                    Ok(FuncError::MemoryAccess(MemoryAccessError::OutOfBounds { .. })) => {
+                        TransitionError::RuntimeError
+                    }
                     Ok(_) => TransitionError::InternalRuntimeError,
                 }
             }
@@ -137,6 +300,7 @@ impl<'a> From<&'a TransitionError> for ExitCodeV2 {
         match value {
             TransitionError::ExecutionProperGasExhausted
             | TransitionError::InternalExecutionProperGasExhaustion => ExitCodeV2::GasExhausted,
+            // including CallStackExhausted, which is told apart from gas exhaustion by the TransitionError
             _ => ExitCodeV2::Error,
         }
     }
@@ -6,7 +6,10 @@
 //! Temporary store for outputs from processing a single command.
 //!
 //! Used in the [GasMeter](crate::gas::GasMeter) and [HostFuncGasMeter](crate::gas::HostFuncGasMeter).
-use crate::types::CommandOutput;
+use crate::{
+    gas::OpcodeGasProfile,
+    types::{CommandOutput, RuntimeEvent},
+};
 use pchain_types::blockchain::Log;
 
 /// CommandOutputCache is compatible with the return fields of both CommandReceiptV1 and CommandReceiptV2.
@@ -27,6 +30,24 @@ pub(crate) struct CommandOutputCache {
 
     /// value returned from result of UnstakeDeposit command.
     pub amount_unstaked: MaybeUnused<u64>,
+
+    /// peak number of Wasm linear memory pages used by contract calls in the command.
+    /// Only recorded if memory profiling is enabled in the SmartContractContext.
+    pub memory_pages: MaybeUnused<u32>,
+
+    /// gas used by the Wasm opcodes of contract calls in the command, by category of opcode.
+    /// Only recorded if the crate is built with the `profiling` feature.
+    pub opcode_gas_profile: MaybeUnused<OpcodeGasProfile>,
+
+    /// runtime events emitted while executing the command, ordered by the sequence of emission.
+    /// Only recorded if `record_events` is set.
+    pub events: MaybeUnused<Vec<RuntimeEvent>>,
+
+    /// whether runtime events are recorded. It is kept across commands.
+    pub record_events: bool,
+
+    /// whether the [GasBreakdown](crate::gas::GasBreakdown) of commands is recorded. It is kept across commands.
+    pub record_gas_breakdown: bool,
 }
 
 impl CommandOutputCache {
@@ -38,6 +59,17 @@ impl CommandOutputCache {
             amount_withdrawn: self.amount_withdrawn.take_or_default(),
             amount_staked: self.amount_staked.take_or_default(),
             amount_unstaked: self.amount_unstaked.take_or_default(),
+            memory_pages: self.memory_pages.take_or_default(),
+            opcode_gas_profile: self.opcode_gas_profile.take_or_default(),
+            events: self.events.take_or_default(),
+            gas_breakdown: Default::default(),
+        }
+    }
+
+    /// appends a runtime event if recording of events is enabled
+    pub fn push_event(&mut self, event: RuntimeEvent) {
+        if self.record_events {
+            self.events.as_mut().push(event);
         }
     }
 
@@ -72,3 +104,15 @@ where
             .map_or_else(T::default, std::convert::identity)
     }
 }
+
+impl<T> MaybeUnused<Vec<T>> {
+    pub fn len(&self) -> usize {
+        self.0.as_ref().map_or(0, Vec::len)
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        if let Some(items) = self.0.as_mut() {
+            items.truncate(len);
+        }
+    }
+}
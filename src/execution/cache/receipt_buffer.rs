@@ -7,6 +7,21 @@
 //!
 //! This storage is used to accumulate command receipts
 //! to form a single receipt when the transaction is fully processed.
+//!
+//! Commands deferred by a contract call do not get receipts of their own. Their receipts are merged, in the order in
+//! which the deferred commands executed, into the receipt of the Call command which deferred them. Merging a deferred
+//! command receipt into the Call receipt:
+//! - adds its gas used to the gas used of the Call receipt,
+//! - replaces the exit code of the Call receipt with its exit code,
+//! - replaces the return value of the Call receipt with its output (the return value of a Call, or the amount of a
+//!   WithdrawDeposit, StakeDeposit or UnstakeDeposit as little-endian bytes). Other commands leave it unchanged.
+//!
+//! The logs of the Call receipt are kept. The merged receipt therefore has the total gas used by the Call and all of
+//! its deferred commands, and the exit code of the last command executed. As receipts are committed in the block,
+//! this rule is part of consensus.
+//!
+//! The buffer's storage becomes the command receipts of the transaction receipt, so it is sized for the commands of
+//! the transaction upfront, and cannot be reused for another transaction.
 use pchain_types::blockchain::{
     CommandReceiptV1, CommandReceiptV2, ExitCodeV2, ReceiptV1, ReceiptV2,
 };
@@ -20,11 +35,18 @@ pub(crate) struct CommandReceiptBuffer<E> {
 }
 
 impl<E> CommandReceiptBuffer<E> {
-    pub fn new() -> Self {
+    /// creates a buffer with room for the receipts of `num_commands` commands, which is the number of command
+    /// receipts in a ReceiptV2, including those of commands which were not executed.
+    pub fn with_capacity(num_commands: usize) -> Self {
         Self {
-            cmd_rcps: Vec::new(),
+            cmd_rcps: Vec::with_capacity(num_commands),
         }
     }
+
+    /// number of command receipts pushed so far, excluding those of deferred commands
+    pub fn num_command_receipts(&self) -> usize {
+        self.cmd_rcps.len()
+    }
 }
 
 pub(crate) trait ProcessReceipts<E, R> {
@@ -62,7 +84,7 @@ impl ProcessReceipts<CommandReceiptV2, ReceiptV2> for CommandReceiptBuffer<Comma
         self.cmd_rcps.push(command_receipt);
     }
 
-    /// Combine the information from next Command Receipt.
+    /// Combine the information from next Command Receipt, following the rule in the [module documentation](self).
     /// Assumption: execution of a deferred command will not spawn non-deferred command.
     fn push_deferred_command_receipt(&mut self, command_receipt: CommandReceiptV2) {
         if let Some(last_command_receipt) = self.cmd_rcps.last_mut() {
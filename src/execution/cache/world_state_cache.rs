@@ -12,11 +12,17 @@
 //!
 //! It also leverages caching, and batching of updates, to improve read and write peformance.
 
-use std::{cell::RefCell, collections::HashMap};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 use pchain_types::cryptography::PublicAddress;
 use pchain_world_state::{VersionProvider, WorldState, DB};
 
+use crate::{observer::WorldStateObserver, types::WorldStateKey};
+
 /// Unified container for different caches representing the various types of data
 ///
 /// Each data type for an Account is held in its own sets of cache, excluding nonces
@@ -46,6 +52,14 @@ where
     pub cbi_versions: CacheCBIVersion,
     pub contract_codes: CacheContractCode,
     pub storage_data: CacheStorageData,
+    /// accounts accessed through this cache. Only recorded if enabled by
+    /// [record_touched_accounts](Self::record_touched_accounts).
+    touched_accounts: RefCell<Option<HashSet<PublicAddress>>>,
+    /// data accessed through this cache, in the order of first access. Only recorded if enabled by
+    /// [record_touched_keys](Self::record_touched_keys).
+    touched_keys: RefCell<Option<Vec<WorldStateKey>>>,
+    /// observer of the reads and writes made through this cache, set by [set_observer](Self::set_observer).
+    observer: Option<Arc<dyn WorldStateObserver>>,
 }
 
 impl<'a, S, V> WorldStateCache<'a, S, V>
@@ -60,22 +74,166 @@ where
             balances: Default::default(),
             cbi_versions: Default::default(),
             contract_codes: Default::default(),
-            storage_data: CacheStorageData {
-                reads: RefCell::new(HashMap::new()),
-                writes: HashMap::new(),
-            },
+            storage_data: Default::default(),
+            touched_accounts: Default::default(),
+            touched_keys: Default::default(),
+            observer: None,
+        }
+    }
+
+    /// sets the observer of the reads and writes made through this cache. Removes the observer if None.
+    pub fn set_observer(&mut self, observer: Option<Arc<dyn WorldStateObserver>>) {
+        self.observer = observer;
+    }
+
+    /// passes a read to the observer, if any. The key and value are only built if there is an observer.
+    pub fn observe_read(
+        &self,
+        key: impl FnOnce() -> WorldStateKey,
+        value: impl FnOnce() -> Option<Vec<u8>>,
+    ) {
+        if let Some(observer) = &self.observer {
+            observer.on_read(&key(), &value());
         }
     }
 
-    /// remove cached writes and return the value,
+    /// passes a write to the observer, if any. The key and value are only built if there is an observer.
+    pub fn observe_write(
+        &self,
+        key: impl FnOnce() -> WorldStateKey,
+        value: impl FnOnce() -> Option<Vec<u8>>,
+    ) {
+        if let Some(observer) = &self.observer {
+            observer.on_write(&key(), &value());
+        }
+    }
+
+    /// starts recording the accounts whose data is read or written through this cache, including reads served from
+    /// the caches and from the [smart contract cache](crate::Cache).
+    pub fn record_touched_accounts(&mut self) {
+        self.touched_accounts.replace(Some(HashSet::new()));
+    }
+
+    /// accounts recorded since [record_touched_accounts](Self::record_touched_accounts). Empty if not recording.
+    pub fn touched_accounts(&self) -> HashSet<PublicAddress> {
+        self.touched_accounts.borrow().clone().unwrap_or_default()
+    }
+
+    /// starts recording the data read or written through this cache, in the same way as
+    /// [record_touched_accounts](Self::record_touched_accounts).
+    pub fn record_touched_keys(&mut self) {
+        self.touched_keys.replace(Some(Vec::new()));
+    }
+
+    /// data recorded since [record_touched_keys](Self::record_touched_keys), in the order in which it was first
+    /// accessed. Empty if not recording.
+    pub fn touched_keys(&self) -> Vec<WorldStateKey> {
+        self.touched_keys.borrow().clone().unwrap_or_default()
+    }
+
+    /// records an access to the data of `address`, if recording
+    pub fn touch(&self, address: &PublicAddress) {
+        if let Some(touched_accounts) = self.touched_accounts.borrow_mut().as_mut() {
+            touched_accounts.insert(*address);
+        }
+    }
+
+    /// records an access to the data identified by `key`, and to the data of its account, if recording. The key is
+    /// only built if data is recorded.
+    pub fn touch_key(&self, key: impl FnOnce() -> WorldStateKey) {
+        let mut touched_keys = self.touched_keys.borrow_mut();
+        match touched_keys.as_mut() {
+            Some(touched_keys) => {
+                let key = key();
+                self.touch(&key.address());
+                if !touched_keys.contains(&key) {
+                    touched_keys.push(key);
+                }
+            }
+            None => {
+                if self.touched_accounts.borrow().is_some() {
+                    self.touch(&key().address());
+                }
+            }
+        }
+    }
+
+    /// remove cached writes and return the value. The cached read is dropped too, as the caller writes the balance
+    /// directly to World State.
     /// gas free operation, only used for accounting during charge phase
     pub fn purge_balance(&mut self, address: PublicAddress) -> u64 {
         let balance = self.balance(&address);
-        self.balances.writes.remove(&address);
+        self.balances.remove(&address);
         balance
     }
 
-    /// reverts changes to all read-write caches
+    /// marks the pending writes as the state that [revert](Self::revert) returns to. Only the writes made after the
+    /// checkpoint are recorded for revert, so a checkpoint takes constant time, and the read caches are kept.
+    /// Used when the cache is carried across transactions without committing to World State in between,
+    /// and after the Pre-Charge phase writes directly to World State.
+    pub fn checkpoint(&mut self) {
+        self.balances.checkpoint();
+        self.cbi_versions.checkpoint();
+        self.contract_codes.checkpoint();
+        self.storage_data.checkpoint();
+    }
+
+    /// net change in the size of contract code and contract storage (keys and values) caused by the writes made
+    /// since the last [checkpoint](Self::checkpoint). Gas free operation, only used for accounting during charge phase.
+    /// ### Panics
+    /// panics on unexpected errors with the account trie or storage trie, which might reflect an invalid World State
+    pub fn write_set_size_delta(&self) -> i64 {
+        let mut size_delta: i64 = 0;
+
+        // only the data written since the checkpoint can have changed size
+        for (address, prev_code) in self.contract_codes.checkpoint.iter() {
+            let Some(code) = self.contract_codes.writes.get(address) else {
+                continue;
+            };
+            let prev_code_len = match prev_code {
+                Some(prev_code) => prev_code.len(),
+                None => self
+                    .ws
+                    .account_trie()
+                    .code(address)
+                    .expect(&format!(
+                        "Account trie should get contract code for {:?}",
+                        address
+                    ))
+                    .map_or(0, |code| code.len()),
+            };
+            size_delta += code.len() as i64 - prev_code_len as i64;
+        }
+
+        // an empty value deletes the key, so the entry no longer takes up space
+        let entry_size = |key: &[u8], value: &[u8]| -> i64 {
+            if value.is_empty() {
+                0
+            } else {
+                (key.len() + value.len()) as i64
+            }
+        };
+        for ((address, key), prev_value) in self.storage_data.checkpoint.iter() {
+            let Some(value) = self.storage_data.writes.get(&(*address, key.clone())) else {
+                continue;
+            };
+            let prev_size = match prev_value {
+                Some(prev_value) => entry_size(key, prev_value),
+                None => self
+                    .ws
+                    .storage_trie(address)
+                    .expect(&format!("Storage trie should exist for {:?}", address))
+                    .get(key)
+                    .expect(&format!("Storage trie should get data for {:?}", address))
+                    .map_or(0, |prev_value| entry_size(key, &prev_value)),
+            };
+            size_delta += entry_size(key, value) - prev_size;
+        }
+
+        size_delta
+    }
+
+    /// reverts the writes made since the last [checkpoint](Self::checkpoint)
     pub fn revert(&mut self) {
         self.balances.revert();
         self.cbi_versions.revert();
@@ -83,20 +241,63 @@ where
         self.storage_data.revert();
     }
 
+    /// opens a savepoint, so that the writes made after this point can be discarded by
+    /// [rollback_to_savepoint](Self::rollback_to_savepoint), e.g. those of a contract call which fails without failing
+    /// its caller. Savepoints nest, and are identified by their depth, which this returns.
+    ///
+    /// Like a [checkpoint](Self::checkpoint), a savepoint records only the writes made after it, so it takes constant
+    /// time, and rolling back or releasing it takes time proportional to those writes.
+    pub fn savepoint(&mut self) -> usize {
+        self.balances.savepoint();
+        self.cbi_versions.savepoint();
+        self.contract_codes.savepoint();
+        self.storage_data.savepoint()
+    }
+
+    /// discards the writes made since the savepoint at `depth` was opened, and closes it together with the savepoints
+    /// nested in it. The read caches are kept, as they only hold data read from World State.
+    pub fn rollback_to_savepoint(&mut self, depth: usize) {
+        self.balances.rollback_to_savepoint(depth);
+        self.cbi_versions.rollback_to_savepoint(depth);
+        self.contract_codes.rollback_to_savepoint(depth);
+        self.storage_data.rollback_to_savepoint(depth);
+    }
+
+    /// keeps the writes made since the savepoint at `depth` was opened, and closes it together with the savepoints
+    /// nested in it, so that they are discarded if an enclosing savepoint is rolled back.
+    pub fn release_savepoint(&mut self, depth: usize) {
+        self.balances.release_savepoint(depth);
+        self.cbi_versions.release_savepoint(depth);
+        self.contract_codes.release_savepoint(depth);
+        self.storage_data.release_savepoint(depth);
+    }
+
     /// retrieve the balance of native tokens for a particular account
     /// ### panics
     /// panics on unexpected errors with the account trie, which might reflect an invalid World State
     pub fn balance(&self, address: &PublicAddress) -> u64 {
-        self.balances
+        self.touch_key(|| WorldStateKey::Balance(*address));
+        let balance = self
+            .balances
             .get(address, |key| self.ws.account_trie().balance(key).ok())
             .expect(&format!(
                 "Account trie should get balance for {:?}",
                 address
-            ))
+            ));
+        self.observe_read(
+            || WorldStateKey::Balance(*address),
+            || Some(balance.to_le_bytes().to_vec()),
+        );
+        balance
     }
 
     /// sets account balance to the balance cache, needs to be committed separately
     pub fn set_balance(&mut self, address: PublicAddress, balance: u64) {
+        self.touch_key(|| WorldStateKey::Balance(address));
+        self.observe_write(
+            || WorldStateKey::Balance(address),
+            || Some(balance.to_le_bytes().to_vec()),
+        );
         self.balances.set(address, balance);
     }
 
@@ -104,15 +305,26 @@ where
     /// ### panics
     /// panics on unexpected errors with the account trie, which might reflect an invalid World State
     pub fn cbi_version(&self, address: &PublicAddress) -> Option<u32> {
-        self.cbi_versions.get(address, |key| {
+        self.touch_key(|| WorldStateKey::CbiVersion(*address));
+        let cbi_version = self.cbi_versions.get(address, |key| {
             self.ws.account_trie().cbi_version(key).expect(&format!(
                 "Account trie should get CBI version for {:?}",
                 address
             ))
-        })
+        });
+        self.observe_read(
+            || WorldStateKey::CbiVersion(*address),
+            || cbi_version.map(|version| version.to_le_bytes().to_vec()),
+        );
+        cbi_version
     }
 
     pub fn set_cbi_version(&mut self, address: PublicAddress, cbi_version: u32) {
+        self.touch_key(|| WorldStateKey::CbiVersion(address));
+        self.observe_write(
+            || WorldStateKey::CbiVersion(address),
+            || Some(cbi_version.to_le_bytes().to_vec()),
+        );
         self.cbi_versions.set(address, cbi_version);
     }
 
@@ -120,16 +332,24 @@ where
     /// ### panics
     /// panics on unexpected errors with the account trie, which might reflect an invalid World State
     pub fn contract_code(&self, address: &PublicAddress) -> Option<Vec<u8>> {
-        self.contract_codes.get(address, |key| {
+        self.touch_key(|| WorldStateKey::ContractCode(*address));
+        let code = self.contract_codes.get(address, |key| {
             self.ws.account_trie().code(key).expect(&format!(
                 "Account trie should get contract code for {:?}",
                 address
             ))
-        })
+        });
+        self.observe_read(|| WorldStateKey::ContractCode(*address), || code.clone());
+        code
     }
 
     /// stores contract code to the contract cache, needs to be committed separately
     pub fn set_contract_code(&mut self, address: PublicAddress, code: Vec<u8>) {
+        self.touch_key(|| WorldStateKey::ContractCode(address));
+        self.observe_write(
+            || WorldStateKey::ContractCode(address),
+            || Some(code.clone()),
+        );
         self.contract_codes.set(address, code);
     }
 
@@ -137,6 +357,7 @@ where
     /// # Panics
     ///  Will panic on unexpected errors with the storage trie, which reflects an invalid World State
     pub fn contains_storage_data(&mut self, address: PublicAddress, key: &[u8]) -> bool {
+        self.touch_key(|| WorldStateKey::StorageData(address, key.to_vec()));
         self.storage_data
             .contains(&(address, key.to_vec()), |(addr, key)| -> bool {
                 self.ws
@@ -150,32 +371,71 @@ where
             })
     }
 
-    /// retrieves data from account storage
+    /// retrieves data from account storage. Data set in this cache is returned in place of the data in the World State,
+    /// so that a contract reads its own writes before they are committed.
     /// # Panics
     /// Will panic on unexpected errors with the storage trie, which reflects an invalid World State
     pub fn storage_data(&mut self, address: PublicAddress, key: &[u8]) -> Option<Vec<u8>> {
-        self.storage_data
+        self.touch_key(|| WorldStateKey::StorageData(address, key.to_vec()));
+        let value = self
+            .storage_data
             .get(&(address, key.to_vec()), |(addr, k)| {
                 self.ws
                     .storage_trie(addr)
                     .expect(&format!("Storage trie should exist for {:?}", address))
                     .get(k)
                     .expect(&format!("Storage trie should get data for {:?}", address))
-            })
+            });
+        self.observe_read(
+            || WorldStateKey::StorageData(address, key.to_vec()),
+            || value.clone(),
+        );
+        value
     }
 
-    /// sets key-value to account storage cache, needs to be committed separately
+    /// sets key-value to account storage cache, needs to be committed separately. An empty value deletes the key.
     pub fn set_storage_data(&mut self, address: PublicAddress, key: &[u8], value: Vec<u8>) {
+        self.touch_key(|| WorldStateKey::StorageData(address, key.to_vec()));
+        self.observe_write(
+            || WorldStateKey::StorageData(address, key.to_vec()),
+            || (!value.is_empty()).then(|| value.clone()),
+        );
         self.storage_data.set((address, key.to_vec()), value);
     }
 
+    /// returns a read-only view of the account storage of `address`, for contracts which read a lot of storage data
+    /// without writing it. Values read from the Storage Trie are cached in `reads`, which should only be used for
+    /// this address, so that a reader created for every read still reads each value once. See [StorageReader].
+    pub fn storage_reader<'r>(
+        &self,
+        address: PublicAddress,
+        reads: &'r mut StorageReads,
+    ) -> StorageReader<'_, 'r, 'a, S, V> {
+        self.touch(&address);
+        StorageReader {
+            ws_cache: self,
+            address,
+            reads,
+        }
+    }
+
     /// writes the actual values to the relevant data structures in the World State.
     /// this method is typically invoked at the end of every commmand's execution to persist the changes.
     /// ### Panics
     /// panics if any of the writes fail.
     pub fn commit_to_world_state(self) -> WorldState<'a, S, V> {
+        self.commit_to_world_state_with_stats().0
+    }
+
+    /// writes the actual values to the World State as [commit_to_world_state](Self::commit_to_world_state), and also
+    /// returns [CommitStats] of the writes, e.g. for storage-growth metrics.
+    /// ### Panics
+    /// panics if any of the writes fail.
+    pub fn commit_to_world_state_with_stats(self) -> (WorldState<'a, S, V>, CommitStats) {
         let mut ws = self.ws;
+        let mut stats = CommitStats::default();
         for (address, balance) in self.balances.writes.into_iter() {
+            stats.add_write(std::mem::size_of::<u64>());
             ws.account_trie_mut()
                 .set_balance(&address, balance)
                 .expect(&format!(
@@ -185,6 +445,7 @@ where
         }
 
         for (address, version) in self.cbi_versions.writes.into_iter() {
+            stats.add_write(std::mem::size_of::<u32>());
             ws.account_trie_mut()
                 .set_cbi_version(&address, version)
                 .expect(&format!(
@@ -194,6 +455,7 @@ where
         }
 
         for (address, code) in self.contract_codes.writes.into_iter() {
+            stats.add_write(code.len());
             ws.account_trie_mut()
                 .set_code(&address, code)
                 .expect(&format!(
@@ -206,6 +468,12 @@ where
         // as calling .set() individually will be slower
         let mut aggregated_storage_writes = HashMap::with_capacity(self.storage_data.writes.len());
         for ((address, key), value) in self.storage_data.writes.into_iter() {
+            // an empty value deletes the key from the Storage Trie
+            if value.is_empty() {
+                stats.keys_deleted += 1;
+            } else {
+                stats.add_write(value.len());
+            }
             aggregated_storage_writes
                 .entry(address)
                 .or_insert_with(HashMap::new)
@@ -219,7 +487,110 @@ where
                 .expect(&format!("Storage trie should set data for {:?}", address));
         }
 
-        ws
+        (ws, stats)
+    }
+}
+
+/// Statistics of the writes persisted by [WorldStateCache::commit_to_world_state_with_stats].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CommitStats {
+    /// Number of balances, CBI versions, contract codes and storage values written, excluding deletions.
+    pub keys_written: usize,
+    /// Total size in bytes of the values written: 8 per balance, 4 per CBI version, and the length of each contract
+    /// code and storage value.
+    pub bytes_written: usize,
+    /// Number of storage values deleted.
+    pub keys_deleted: usize,
+}
+
+impl CommitStats {
+    fn add_write(&mut self, bytes: usize) {
+        self.keys_written += 1;
+        self.bytes_written += bytes;
+    }
+}
+
+/// Maximum number of values cached by a [StorageReader].
+const STORAGE_READER_CACHE_CAPACITY: usize = 1024;
+
+/// Values read from the account storage of one address by [StorageReader]s, up to [STORAGE_READER_CACHE_CAPACITY]
+/// values. It outlives the readers, e.g. to be kept for the duration of a view call.
+#[derive(Default)]
+pub(crate) struct StorageReads(HashMap<Vec<u8>, Option<Vec<u8>>>);
+
+/// Read-only view of the account storage of one address, obtained by [WorldStateCache::storage_reader].
+///
+/// Procedure of a Read operation: First, the `writes` of the storage data cache are checked, so that pending writes
+/// are read as in [WorldStateCache::storage_data]. If data is not found, search in the [StorageReads] given to the
+/// reader. If data is still not found, access the Storage Trie. The result is cached to the [StorageReads] up to
+/// [STORAGE_READER_CACHE_CAPACITY] values.
+///
+/// The reader does not populate the read cache of the [WorldStateCache] and cannot write, so the write set is never
+/// changed by reading through it. Reads are not charged by the reader: view calls read through it, and are charged,
+/// by [HostFuncGasMeter::ws_get_storage_data_read_only](crate::gas::HostFuncGasMeter::ws_get_storage_data_read_only).
+pub(crate) struct StorageReader<'c, 'r, 'a, S, V>
+where
+    S: DB + Send + Sync + Clone + 'static,
+    V: VersionProvider + Send + Sync + Clone,
+{
+    ws_cache: &'c WorldStateCache<'a, S, V>,
+    address: PublicAddress,
+    reads: &'r mut StorageReads,
+}
+
+impl<'c, 'r, 'a, S, V> StorageReader<'c, 'r, 'a, S, V>
+where
+    S: DB + Send + Sync + Clone + 'static,
+    V: VersionProvider + Send + Sync + Clone,
+{
+    /// address of the account storage being read
+    pub fn address(&self) -> PublicAddress {
+        self.address
+    }
+
+    /// retrieves data from the account storage, in the same way as [WorldStateCache::storage_data].
+    /// # Panics
+    /// Will panic on unexpected errors with the storage trie, which reflects an invalid World State
+    pub fn get(&mut self, key: &[u8]) -> Option<Vec<u8>> {
+        self.ws_cache
+            .touch_key(|| WorldStateKey::StorageData(self.address, key.to_vec()));
+        let value = self.read(key);
+        self.ws_cache.observe_read(
+            || WorldStateKey::StorageData(self.address, key.to_vec()),
+            || value.clone(),
+        );
+        value
+    }
+
+    fn read(&mut self, key: &[u8]) -> Option<Vec<u8>> {
+        if let Some(value) = self
+            .ws_cache
+            .storage_data
+            .writes
+            .get(&(self.address, key.to_vec()))
+        {
+            return Some(value.clone());
+        }
+
+        if let Some(value) = self.reads.0.get(key) {
+            return value.clone();
+        }
+
+        let value = self
+            .ws_cache
+            .ws
+            .storage_trie(&self.address)
+            .expect(&format!("Storage trie should exist for {:?}", self.address))
+            .get(key)
+            .expect(&format!(
+                "Storage trie should get data for {:?}",
+                self.address
+            ));
+
+        if self.reads.0.len() < STORAGE_READER_CACHE_CAPACITY {
+            self.reads.0.insert(key.to_vec(), value.clone());
+        }
+        value
     }
 }
 
@@ -257,6 +628,11 @@ pub(crate) struct CacheData<K, V> {
     pub writes: HashMap<K, V>,
     /// reads caches key-value pairs from Read operations.
     pub reads: RefCell<HashMap<K, Option<V>>>,
+    /// undo log of the last checkpoint: for each key written since, its value in `writes` at the checkpoint, or None
+    /// if it had no pending write. Restored on revert.
+    pub checkpoint: HashMap<K, Option<V>>,
+    /// undo logs of the open savepoints, innermost last, recorded in the same way as `checkpoint`.
+    savepoints: Vec<HashMap<K, Option<V>>>,
 }
 
 impl<K, V> CacheData<K, V>
@@ -286,7 +662,40 @@ where
 
     /// Insert to write set.
     pub fn set(&mut self, key: K, value: V) {
-        self.writes.insert(key, value);
+        if self.is_recorded(&key) {
+            self.writes.insert(key, value);
+        } else {
+            let prev = self.writes.insert(key.clone(), value);
+            self.record_undo(key, prev);
+        }
+    }
+
+    /// Remove from both read and write sets.
+    pub fn remove(&mut self, key: &K) {
+        self.reads.borrow_mut().remove(key);
+        if let Some(prev) = self.writes.remove(key) {
+            self.record_undo(key.clone(), Some(prev));
+        }
+    }
+
+    /// whether the undo logs already have the value of `key` before it was first changed
+    fn is_recorded(&self, key: &K) -> bool {
+        self.checkpoint.contains_key(key)
+            && self
+                .savepoints
+                .last()
+                .is_none_or(|undo_log| undo_log.contains_key(key))
+    }
+
+    /// records `prev`, the value of `key` in the write set before it was changed, in the undo logs which do not
+    /// have it yet
+    fn record_undo(&mut self, key: K, prev: Option<V>) {
+        if let Some(undo_log) = self.savepoints.last_mut() {
+            if !undo_log.contains_key(&key) {
+                undo_log.insert(key.clone(), prev.clone());
+            }
+        }
+        self.checkpoint.entry(key).or_insert(prev);
     }
 
     /// Check if this key is set before.
@@ -303,9 +712,53 @@ where
         || ws_contains(key)
     }
 
-    /// reverts changes to both read and write caches
+    /// marks the current writes as the checkpoint for revert, by starting a new undo log. Open savepoints are closed.
+    pub fn checkpoint(&mut self) {
+        self.checkpoint.clear();
+        self.savepoints.clear();
+    }
+
+    /// opens a savepoint and returns its depth
+    pub fn savepoint(&mut self) -> usize {
+        self.savepoints.push(HashMap::new());
+        self.savepoints.len() - 1
+    }
+
+    /// reverts the write cache back to the savepoint at `depth`, and closes it and the savepoints nested in it
+    pub fn rollback_to_savepoint(&mut self, depth: usize) {
+        while self.savepoints.len() > depth {
+            let undo_log = self.savepoints.pop().unwrap();
+            for (key, prev) in undo_log {
+                match prev {
+                    Some(value) => self.writes.insert(key, value),
+                    None => self.writes.remove(&key),
+                };
+            }
+        }
+    }
+
+    /// closes the savepoint at `depth` and the savepoints nested in it, keeping their writes. The enclosing
+    /// savepoint, if any, takes over their undo logs.
+    pub fn release_savepoint(&mut self, depth: usize) {
+        while self.savepoints.len() > depth {
+            let undo_log = self.savepoints.pop().unwrap();
+            if let Some(enclosing_undo_log) = self.savepoints.last_mut() {
+                for (key, prev) in undo_log {
+                    enclosing_undo_log.entry(key).or_insert(prev);
+                }
+            }
+        }
+    }
+
+    /// reverts the write cache back to the last checkpoint, and closes the open savepoints. The read cache only holds
+    /// data read from World State, so it is kept.
     pub fn revert(&mut self) {
-        self.reads.borrow_mut().clear();
-        self.writes.clear();
+        self.savepoints.clear();
+        for (key, prev) in self.checkpoint.drain() {
+            match prev {
+                Some(value) => self.writes.insert(key, value),
+                None => self.writes.remove(&key),
+            };
+        }
     }
 }
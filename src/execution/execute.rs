@@ -15,6 +15,11 @@
 //!
 //! While user-sent Commands are handled here, specialized Commands like NextEpoch are managed separately
 //! in [execute_next_epoch_command](crate::execution::execute_next_epoch).
+//!
+//! The set of Commands is closed: [Command] is defined in `pchain-types`, and a transaction containing a Command
+//! variant which that crate does not know fails to deserialize before it reaches the Runtime. Hence there is no
+//! "unsupported command" path in the Runtime, and dispatch matches every variant explicitly, so that a new variant
+//! is a compile error here rather than being silently skipped or failing at execution time.
 
 use pchain_types::{
     blockchain::Command,
@@ -140,6 +145,7 @@ where
             operator,
             max_amount,
         }) => staking::unstake_deposit(actor, state, operator, max_amount),
-        _ => unreachable!(), // Next Epoch Command
+        // transactions containing a Next Epoch Command are executed by execute_next_epoch_command
+        Command::NextEpoch => unreachable!(),
     }
 }
@@ -21,7 +21,9 @@
 //! a stack. Execution order starts from the top item. When a [Call](pchain_types::blockchain::Command::Call)
 //! Command is executed successfully and outputs `Deferred Commands`, these Deferred Commands will be
 //! encapsulated into Command Tasks and pushed onto stack. This stack model allows the Deferred Command
-//! to be executed sequentially after its parent Call Command.
+//! to be executed sequentially after its parent Call Command. The number of Deferred Commands in a transaction is
+//! limited by the [maximum number of spawned commands](crate::Runtime::set_max_spawned_commands), beyond which the
+//! Call Command fails with [SpawnedCommandLimitExceeded](TransitionError::SpawnedCommandLimitExceeded).
 //!
 //! Each Command Task completes with a Command Receipt. If execution fails,
 //! the process aborts and then proceeds immediately to the Charge Phase.
@@ -30,15 +32,38 @@
 //!
 //! Finally in the Charge Phase, the Signer's balance will be refunded according to the actual gas used.
 //! Some fees are also transferred to Proposer and Treasury.
+//!
+//! ### Contract-to-contract calls
+//!
+//! A contract calling another contract with the `call` host function does not create a Command Task. The callee is
+//! executed within the Call Command of its caller, and shares its Transition Context, so the callee reads all writes
+//! made earlier in the transaction, including those of its callers, and the caller reads the writes of the callee
+//! once the call returns. This holds if the callee re-enters a contract that is already executing: the re-entered
+//! contract is executed as any other callee, without a reentrancy guard. The gas used by nested calls is charged to
+//! the Call Command. Call depth is limited by the [maximum call depth](crate::Runtime::set_max_call_depth), beyond
+//! which a call fails with [CallDepthExceeded](TransitionError::CallDepthExceeded), as well as by gas and by the stack
+//! of the host, which aborts the transaction with [CallStackExhausted](TransitionError::CallStackExhausted). If a
+//! callee fails, the Call Command fails, and the changes of the whole Call Command are reverted.
+//!
+//! ### Tracing
+//!
+//! With the `tracing` feature, which is enabled by default, the Pre-Charge and Charge phases are wrapped in the
+//! `pre_charge` and `charge` spans, and each Command Task in a `work` span with the fields `command_kind`,
+//! `command_index`, `deferred` and `gas_used`, which is recorded when the command completes.
 
-use pchain_types::blockchain::{Command, CommandReceiptV1, CommandReceiptV2, ReceiptV1, ReceiptV2};
+use pchain_types::{
+    blockchain::{Command, CommandReceiptV1, CommandReceiptV2, ReceiptV1, ReceiptV2},
+    cryptography::PublicAddress,
+};
 use pchain_world_state::{VersionProvider, DB};
 
 use crate::{
     execution::{
+        cache::WorldStateCache,
         execute::Execute,
         state::{ExecutionState, FinalizeState},
     },
+    gas::GasBreakdown,
     transition::TransitionV2Result,
     types::{CommandKind, DeferredCommand},
     TransitionError, TransitionV1Result,
@@ -72,6 +97,18 @@ where
 
         // Execute command
         let cmd_kind = executable_cmd.command_kind();
+        #[cfg(feature = "tracing")]
+        let (work_span, gas_used_before) = (
+            tracing::info_span!(
+                "work",
+                command_kind = ?cmd_kind,
+                command_index,
+                deferred = !is_txn_sent_cmd,
+                gas_used = tracing::field::Empty
+            )
+            .entered(),
+            state.ctx.gas_meter.total_gas_used_for_executed_commands(),
+        );
         let execution_result = executable_cmd.consume_and_execute(&mut state, command_index);
 
         let deferred_cmds_from_execution = P::handle_command_execution_result(
@@ -80,6 +117,13 @@ where
             &execution_result,
             is_txn_sent_cmd,
         );
+        #[cfg(feature = "tracing")]
+        {
+            let gas_used = state.ctx.gas_meter.total_gas_used_for_executed_commands();
+            work_span.record("gas_used", gas_used.saturating_sub(gas_used_before));
+            // close the span before a possible abort, so that the Charge phase is not traced as part of the command
+            drop(work_span);
+        }
 
         // Handle potential execution errors
         match execution_result {
@@ -195,15 +239,25 @@ where
     V: VersionProvider + Send + Sync + Clone,
 {
     fn handle_precharge_error(
-        state: ExecutionState<'a, S, CommandReceiptV2, V>,
+        mut state: ExecutionState<'a, S, CommandReceiptV2, V>,
         error: TransitionError,
     ) -> TransitionV2Result<'a, S, V> {
+        let events = state.take_events();
         let (new_state, _): (_, ReceiptV2) = state.finalize_receipt();
         TransitionV2Result {
             new_state,
             receipt: None,
             error: Some(error),
             validator_changes: None,
+            write_set_commitment: None,
+            memory_profile: None,
+            opcode_gas_profile: None,
+            gas_breakdown: None,
+            events,
+            dry_run: false,
+            base_fee_paid: 0,
+            priority_fee_paid: 0,
+            balance_changes: Vec::new(),
         }
     }
 
@@ -221,30 +275,156 @@ where
     }
 
     fn handle_abort(
-        state: ExecutionState<'a, S, CommandReceiptV2, V>,
+        mut state: ExecutionState<'a, S, CommandReceiptV2, V>,
         error: TransitionError,
     ) -> TransitionV2Result<'a, S, V> {
-        let (new_state, receipt) = phases::charge(state).finalize_receipt();
+        let memory_profile = state.take_memory_profile();
+        let opcode_gas_profile = state.take_opcode_gas_profile();
+        let gas_breakdown = state.take_gas_breakdown();
+        let events = state.take_events();
+        let mut state = phases::charge(state);
+        let (base_fee_paid, priority_fee_paid) = state.fees_paid;
+        let balance_changes = std::mem::take(&mut state.balance_changes);
+        let (new_state, receipt) = state.finalize_receipt();
         TransitionV2Result {
             new_state,
             error: Some(error),
             receipt: Some(receipt),
             validator_changes: None,
+            write_set_commitment: None,
+            memory_profile,
+            opcode_gas_profile,
+            gas_breakdown,
+            events,
+            dry_run: false,
+            base_fee_paid,
+            priority_fee_paid,
+            balance_changes,
         }
     }
 
     fn handle_charge(
-        state: ExecutionState<'a, S, CommandReceiptV2, V>,
+        mut state: ExecutionState<'a, S, CommandReceiptV2, V>,
     ) -> TransitionV2Result<'a, S, V> {
-        let (new_state, receipt) = phases::charge(state).finalize_receipt();
+        let memory_profile = state.take_memory_profile();
+        let opcode_gas_profile = state.take_opcode_gas_profile();
+        let gas_breakdown = state.take_gas_breakdown();
+        let events = state.take_events();
+        let mut state = phases::charge(state);
+        let (base_fee_paid, priority_fee_paid) = state.fees_paid;
+        let balance_changes = std::mem::take(&mut state.balance_changes);
+        let (new_state, receipt) = state.finalize_receipt();
         TransitionV2Result {
             new_state,
             error: None,
             receipt: Some(receipt),
             validator_changes: None,
+            write_set_commitment: None,
+            memory_profile,
+            opcode_gas_profile,
+            gas_breakdown,
+            events,
+            dry_run: false,
+            base_fee_paid,
+            priority_fee_paid,
+            balance_changes,
         }
     }
 }
+/// Result of executing the commands of a TransactionV2 without committing the
+/// World State Cache, so that it can be carried over to the next transaction.
+pub(crate) struct UncommittedTransitionV2Result<'a, S, V>
+where
+    S: DB + Send + Sync + Clone + 'static,
+    V: VersionProvider + Send + Sync + Clone,
+{
+    pub ws_cache: WorldStateCache<'a, S, V>,
+    pub receipt: Option<ReceiptV2>,
+    pub error: Option<TransitionError>,
+    /// Gas used by each executed command by kind of operation, as in [TransitionV2Result::gas_breakdown].
+    pub gas_breakdown: Option<Vec<GasBreakdown>>,
+    /// Base fee and priority fee paid by the signer, as in [TransitionV2Result::base_fee_paid] and
+    /// [TransitionV2Result::priority_fee_paid].
+    pub fees_paid: (u64, u64),
+    /// Final balances of the accounts whose balances were changed, as in [TransitionV2Result::balance_changes].
+    pub balance_changes: Vec<(PublicAddress, u64)>,
+}
+
+/// Strategy struct for V2 specific execution output, without committing to World State
+struct ExecuteCommandsV2Uncommitted;
+
+impl<'a, S, V> CommandStrategy<'a, S, CommandReceiptV2, UncommittedTransitionV2Result<'a, S, V>, V>
+    for ExecuteCommandsV2Uncommitted
+where
+    S: DB + Send + Sync + Clone,
+    V: VersionProvider + Send + Sync + Clone,
+{
+    fn handle_precharge_error(
+        state: ExecutionState<'a, S, CommandReceiptV2, V>,
+        error: TransitionError,
+    ) -> UncommittedTransitionV2Result<'a, S, V> {
+        let (ws_cache, _) = state.finalize_receipt_uncommitted();
+        UncommittedTransitionV2Result {
+            ws_cache,
+            receipt: None,
+            error: Some(error),
+            gas_breakdown: None,
+            fees_paid: (0, 0),
+            balance_changes: Vec::new(),
+        }
+    }
+
+    fn handle_command_execution_result(
+        state: &mut ExecutionState<S, CommandReceiptV2, V>,
+        command_kind: CommandKind,
+        execution_result: &Result<(), TransitionError>,
+        is_user_sent: bool,
+    ) -> Option<Vec<DeferredCommand>> {
+        if is_user_sent {
+            return state.finalize_cmd_receipt_collect_deferred(command_kind, execution_result);
+        }
+        state.finalize_deferred_cmd_receipt(command_kind, execution_result);
+        None
+    }
+
+    fn handle_abort(
+        mut state: ExecutionState<'a, S, CommandReceiptV2, V>,
+        error: TransitionError,
+    ) -> UncommittedTransitionV2Result<'a, S, V> {
+        let gas_breakdown = state.take_gas_breakdown();
+        let mut state = phases::charge(state);
+        let fees_paid = state.fees_paid;
+        let balance_changes = std::mem::take(&mut state.balance_changes);
+        let (ws_cache, receipt) = state.finalize_receipt_uncommitted();
+        UncommittedTransitionV2Result {
+            ws_cache,
+            receipt: Some(receipt),
+            error: Some(error),
+            gas_breakdown,
+            fees_paid,
+            balance_changes,
+        }
+    }
+
+    fn handle_charge(
+        mut state: ExecutionState<'a, S, CommandReceiptV2, V>,
+    ) -> UncommittedTransitionV2Result<'a, S, V> {
+        let gas_breakdown = state.take_gas_breakdown();
+        let mut state = phases::charge(state);
+        let fees_paid = state.fees_paid;
+        let balance_changes = std::mem::take(&mut state.balance_changes);
+        let (ws_cache, receipt) = state.finalize_receipt_uncommitted();
+        UncommittedTransitionV2Result {
+            ws_cache,
+            receipt: Some(receipt),
+            error: None,
+            gas_breakdown,
+            fees_paid,
+            balance_changes,
+        }
+    }
+}
+
 /// Stack to sequence command execution
 #[derive(Debug)]
 pub(crate) struct ExecutableCommands(Vec<ExecutableCommand>);
@@ -346,3 +526,15 @@ where
 {
     execute_commands::<_, _, _, _, ExecuteCommandsV2>(state, commands)
 }
+
+/// Execution entry point for commands in TransactionV2, leaving the World State Cache uncommitted
+pub(crate) fn execute_commands_v2_uncommitted<'a, S, V>(
+    state: ExecutionState<'a, S, CommandReceiptV2, V>,
+    commands: Vec<Command>,
+) -> UncommittedTransitionV2Result<'a, S, V>
+where
+    S: DB + Send + Sync + Clone,
+    V: VersionProvider + Send + Sync + Clone + 'static,
+{
+    execute_commands::<_, _, _, _, ExecuteCommandsV2Uncommitted>(state, commands)
+}
@@ -14,16 +14,38 @@
 //! and charges the maximum-allowable gas fee from the transaction's signer, before actual execution.
 //!
 //! - [Charge](https://github.com/parallelchain-io/parallelchain-protocol/blob/master/Runtime.md#charge)
-//! refunds any amount of remaining gas to the signer, and transfers the gas fee to the proposer and the treasury.
+//! refunds any amount of remaining gas to the signer, and distributes the gas fee with a
+//! [FeeDistributor](crate::FeeDistributor), by default to the proposer and the treasury.
 
+use std::collections::BTreeMap;
+
+use pchain_types::{blockchain::TransactionV2, cryptography::PublicAddress};
 use pchain_world_state::{VersionProvider, DB};
 
 use crate::{
-    execution::state::ExecutionState,
-    rewards_formulas::{TREASURY_CUT_OF_BASE_FEE_DENOM, TREASURY_CUT_OF_BASE_FEE_NUM},
-    TransitionError,
+    execution::{cache::WorldStateCache, state::ExecutionState},
+    fee_distribution::{DefaultFeeDistributor, FeeDistributor},
+    rewards_formulas::priority_fee_reward,
+    types::{RuntimeEvent, WorldStateKey},
+    BlockchainParams, TransitionError,
 };
 
+/// Minimum balance that the signer of `tx` must hold for it to pass the pre-Charge phase in a block with `bp`, i.e.
+/// `gas_limit * (this_base_fee + priority_fee_per_gas)`. Values transferred by the commands of `tx` are not charged
+/// upfront, so they are not included.
+///
+/// Returns None if the amount overflows a u64, in which case no balance is enough.
+pub fn min_signer_balance_v2(tx: &TransactionV2, bp: &BlockchainParams) -> Option<u64> {
+    pre_charge_amount(tx.gas_limit, bp.this_base_fee, tx.priority_fee_per_gas)
+}
+
+/// pre_charge = gas_limit * (base_fee + priority_fee)
+fn pre_charge_amount(gas_limit: u64, base_fee: u64, priority_fee: u64) -> Option<u64> {
+    base_fee
+        .checked_add(priority_fee)
+        .and_then(|fee| gas_limit.checked_mul(fee))
+}
+
 /// Execute the pre-Charge phase and aborts on error.
 pub(crate) fn pre_charge<S, E, V>(
     state: &mut ExecutionState<S, E, V>,
@@ -32,6 +54,9 @@ where
     S: DB + Send + Sync + Clone + 'static,
     V: VersionProvider + Send + Sync + Clone,
 {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::info_span!("pre_charge").entered();
+
     state.ctx.gas_meter.charge_txn_pre_exec_inclusion(
         state.txn_meta.version,
         state.txn_meta.size,
@@ -43,42 +68,47 @@ where
     // because they are internal housekeeping and not part of the txn execution
 
     let signer = state.txn_meta.signer;
+    let skip_nonce_check = state.ctx.skip_nonce_check;
     let ws_cache = state.ctx.gas_free_ws_cache_mut();
 
     let origin_nonce = ws_cache.ws.account_trie().nonce(&signer).expect(&format!(
         "Account trie should get CBI version for {:?}",
         signer
     ));
-    if state.txn_meta.nonce != origin_nonce {
+    if !skip_nonce_check && state.txn_meta.nonce != origin_nonce {
         return Err(TransitionError::WrongNonce);
     }
 
-    let origin_balance = ws_cache
-        .ws
-        .account_trie()
-        .balance(&signer)
-        .expect(&format!("Account trie should get balance for {:?}", signer));
+    // read through the cache, which may hold a pending balance if it is carried across transactions
+    let origin_balance = ws_cache.balance(&signer);
 
-    let gas_limit = state.txn_meta.gas_limit;
-    let base_fee = state.bd.this_base_fee;
-    let priority_fee = state.txn_meta.priority_fee_per_gas;
-
-    // pre_charge = gas_limit * (base_fee + priority_fee)
-    let pre_charge = base_fee
-        .checked_add(priority_fee)
-        .and_then(|fee| gas_limit.checked_mul(fee))
-        .ok_or(TransitionError::NotEnoughBalanceForGasLimit)?; // Overflow check
+    let pre_charge = pre_charge_amount(
+        state.txn_meta.gas_limit,
+        state.bd.this_base_fee,
+        state.txn_meta.priority_fee_per_gas,
+    )
+    .ok_or(TransitionError::NotEnoughBalanceForGasLimit)?; // Overflow check
 
     // pre_charged_balance = origin_balance - pre_charge
     let pre_charged_balance = origin_balance
         .checked_sub(pre_charge)
         .ok_or(TransitionError::NotEnoughBalanceForGasLimit)?; // pre_charge > origin_balance
 
+    ws_cache.purge_balance(signer);
     ws_cache
         .ws
         .account_trie_mut()
         .set_balance(&signer, pre_charged_balance)
         .expect(&format!("Account trie should set balance for {:?}", signer));
+    ws_cache.observe_write(
+        || WorldStateKey::Balance(signer),
+        || Some(pre_charged_balance.to_le_bytes().to_vec()),
+    );
+
+    // changes from here onwards are reverted if the commands are aborted
+    ws_cache.checkpoint();
+
+    state.origin_signer_balance = origin_balance;
 
     Ok(())
 }
@@ -92,6 +122,9 @@ where
     S: DB + Send + Sync + Clone + 'static,
     V: VersionProvider + Send + Sync + Clone,
 {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::info_span!("charge").entered();
+
     let signer = state.txn_meta.signer;
     let base_fee = state.bd.this_base_fee;
     let priority_fee = state.txn_meta.priority_fee_per_gas;
@@ -102,55 +135,150 @@ where
     );
     let gas_unused = state.txn_meta.gas_limit.saturating_sub(gas_used); // Safety for avoiding underflow
 
+    let state_rent_policy = state.ctx.state_rent_policy.clone();
+    let fee_distributor = state.ctx.fee_distributor.clone();
+    let record_balance_changes = state.ctx.record_balance_changes;
     let ws_cache = state.ctx.gas_free_ws_cache_mut();
 
+    let origin_signer_balance = state.origin_signer_balance;
+    let pre_charged_signer_balance = balance_at_checkpoint(ws_cache, &signer);
+
+    // Balances before the transaction of the accounts whose balances were written by the commands, and of the signer,
+    // whose balance was pre-charged for the gas limit. Only read if balance changes are recorded.
+    let mut balances_before: BTreeMap<PublicAddress, u64> = BTreeMap::new();
+    if record_balance_changes {
+        balances_before.extend(
+            ws_cache
+                .balances
+                .writes
+                .keys()
+                .map(|address| (*address, balance_at_checkpoint(ws_cache, address))),
+        );
+        balances_before.insert(signer, origin_signer_balance);
+    }
+
     // Finalize signer's balance
     let signer_balance = ws_cache.purge_balance(signer);
     let new_signer_balance = signer_balance + gas_unused * (base_fee + priority_fee);
 
-    // Transfer priority fee to Proposer
-    let proposer_address = state.bd.proposer_address;
-    let mut proposer_balance = ws_cache.purge_balance(proposer_address);
-    if signer == proposer_address {
-        proposer_balance = new_signer_balance;
-    }
-    let new_proposer_balance = proposer_balance.saturating_add(gas_used * priority_fee);
+    // Charge state rent for the change in state size, capped at the signer's balance. It goes to the Treasury
+    let state_rent = state_rent_policy.map_or(0, |policy| {
+        std::cmp::min(
+            policy.rent(ws_cache.write_set_size_delta()),
+            new_signer_balance,
+        )
+    });
+    let new_signer_balance = new_signer_balance - state_rent;
 
-    // Burn the gas to Treasury account
+    // Distribute the fees, by default the priority fee to Proposer and a cut of the base fee to Treasury
+    let proposer_address = state.bd.proposer_address;
     let treasury_address = state.bd.treasury_address;
-    let mut treasury_balance = ws_cache.purge_balance(treasury_address);
-    if signer == treasury_address {
-        treasury_balance = new_signer_balance;
+    let base_fee_amount = gas_used * base_fee;
+    let priority_fee_amount = priority_fee_reward(gas_used, priority_fee);
+    state.fees_paid = (base_fee_amount, priority_fee_amount);
+
+    let credits = match &fee_distributor {
+        Some(fee_distributor) => fee_distributor.distribute(
+            base_fee_amount,
+            priority_fee_amount,
+            proposer_address,
+            treasury_address,
+        ),
+        None => DefaultFeeDistributor.distribute(
+            base_fee_amount,
+            priority_fee_amount,
+            proposer_address,
+            treasury_address,
+        ),
+    };
+
+    // Cap the credits at the fees of the transaction, in the order returned by the distributor, so that a distributor
+    // which credits too much cannot create balance. The credit which exceeds the fees is reduced to the remainder, and
+    // the credits after it to zero.
+    let mut uncredited_fees = base_fee_amount + priority_fee_amount;
+    let mut credits: Vec<(PublicAddress, u64)> = credits
+        .into_iter()
+        .map(|(address, amount)| {
+            let amount = std::cmp::min(amount, uncredited_fees);
+            uncredited_fees -= amount;
+            (address, amount)
+        })
+        .collect();
+    debug_assert!(
+        credits
+            .iter()
+            .fold(0u128, |sum, (_, amount)| sum + *amount as u128)
+            <= base_fee_amount as u128 + priority_fee_amount as u128,
+        "Credits should not exceed the fees of the transaction"
+    );
+    credits.push((treasury_address, state_rent));
+    let credits_to_signer: u64 = credits
+        .iter()
+        .filter(|(address, _)| *address == signer)
+        .map(|(_, amount)| amount)
+        .sum();
+
+    // Apply the credits on top of the finalized signer's balance
+    let mut new_balances: Vec<(PublicAddress, u64)> = vec![(signer, new_signer_balance)];
+    for (address, amount) in credits {
+        match new_balances.iter_mut().find(|(a, _)| *a == address) {
+            Some((_, balance)) => *balance = balance.saturating_add(amount),
+            None => {
+                let balance = ws_cache.purge_balance(address);
+                if record_balance_changes {
+                    balances_before.entry(address).or_insert(balance);
+                }
+                new_balances.push((address, balance.saturating_add(amount)));
+            }
+        }
     }
-    if proposer_address == treasury_address {
-        treasury_balance = new_proposer_balance;
+
+    // Commit updated balances
+    for (address, balance) in &new_balances {
+        ws_cache
+            .ws
+            .account_trie_mut()
+            .set_balance(address, *balance)
+            .expect(&format!(
+                "Account trie should set balance for {:?}",
+                address
+            ));
+        ws_cache.observe_write(
+            || WorldStateKey::Balance(*address),
+            || Some(balance.to_le_bytes().to_vec()),
+        );
     }
-    let new_treasury_balance = treasury_balance.saturating_add(
-        (gas_used * base_fee * TREASURY_CUT_OF_BASE_FEE_NUM) / TREASURY_CUT_OF_BASE_FEE_DENOM,
+
+    // Guard against fee-accounting drift: the signer's balance before the pre-Charge phase exceeds its committed
+    // balance by exactly the gas used at the fees per gas, the net amount moved out by the commands and the state
+    // rent, less the credits back to the signer.
+    debug_assert_eq!(
+        origin_signer_balance as i128
+            - ws_cache.ws.account_trie().balance(&signer).unwrap() as i128,
+        gas_used as i128 * (base_fee as i128 + priority_fee as i128)
+            + (pre_charged_signer_balance as i128 - signer_balance as i128)
+            + state_rent as i128
+            - credits_to_signer as i128,
+        "Signer should pay exactly the gas used, the amount moved by the commands and the state rent"
     );
 
-    // Commit updated balances
-    ws_cache
-        .ws
-        .account_trie_mut()
-        .set_balance(&signer, new_signer_balance)
-        .expect(&format!("Account trie should set balance for {:?}", signer));
-    ws_cache
-        .ws
-        .account_trie_mut()
-        .set_balance(&proposer_address, new_proposer_balance)
-        .expect(&format!(
-            "Account trie should set balance for {:?}",
-            proposer_address
-        ));
-    ws_cache
-        .ws
-        .account_trie_mut()
-        .set_balance(&treasury_address, new_treasury_balance)
-        .expect(&format!(
-            "Account trie should set balance for {:?}",
-            treasury_address
-        ));
+    // Balances changed by the transaction, i.e. the balances committed above, and the balances written by the commands
+    // which are committed with the World State Cache
+    let balance_changes = if record_balance_changes {
+        let mut balances_after: BTreeMap<PublicAddress, u64> = ws_cache
+            .balances
+            .writes
+            .iter()
+            .map(|(address, balance)| (*address, *balance))
+            .collect();
+        balances_after.extend(new_balances.iter().copied());
+        balances_after
+            .into_iter()
+            .filter(|(address, balance)| balances_before.get(address) != Some(balance))
+            .collect()
+    } else {
+        Vec::new()
+    };
 
     // Commit Signer's Nonce
     let nonce = ws_cache
@@ -166,5 +294,36 @@ where
         .set_nonce(&signer, nonce)
         .expect(&format!("Account trie should set nonce for {:?}", signer));
 
+    state.balance_changes = balance_changes;
+
+    if state.records_events() {
+        for (address, balance) in new_balances {
+            state
+                .events
+                .push(RuntimeEvent::BalanceChanged { address, balance });
+        }
+    }
+
     state
 }
+
+/// balance of `address` at the last checkpoint of `ws_cache`, i.e. before the commands of the transaction, without
+/// going through the read cache
+fn balance_at_checkpoint<S, V>(ws_cache: &WorldStateCache<S, V>, address: &PublicAddress) -> u64
+where
+    S: DB + Send + Sync + Clone + 'static,
+    V: VersionProvider + Send + Sync + Clone,
+{
+    // a balance not written since the checkpoint is as it was at the checkpoint
+    let pending_balance = match ws_cache.balances.checkpoint.get(address) {
+        Some(balance) => balance.as_ref(),
+        None => ws_cache.balances.writes.get(address),
+    };
+    match pending_balance {
+        Some(balance) => *balance,
+        None => ws_cache.ws.account_trie().balance(address).expect(&format!(
+            "Account trie should get balance for {:?}",
+            address
+        )),
+    }
+}
@@ -51,7 +51,7 @@ where
     // Validate the input transaction:
     // - There can only be one NextEpoch Command in a transaction.
     // - Block performance is required for execution of next epoch transaction.
-    // - Transaction nonce matches with the nonce in state
+    // - Transaction nonce matches with the nonce in state, unless the check is skipped for simulation
 
     let ws_cache = state.ctx.gas_free_ws_cache();
     let nonce = ws_cache
@@ -63,7 +63,7 @@ where
     if commands.len() != 1
         || commands.first() != Some(&Command::NextEpoch)
         || state.bd.validator_performance.is_none()
-        || state.txn_meta.nonce != nonce
+        || (!state.ctx.skip_nonce_check && state.txn_meta.nonce != nonce)
     {
         return P::handle_invalid_next_epoch_command(state);
     }
@@ -137,6 +137,15 @@ where
             receipt: None,
             error: Some(TransitionError::InvalidNextEpochCommand),
             validator_changes: None,
+            write_set_commitment: None,
+            memory_profile: None,
+            opcode_gas_profile: None,
+            gas_breakdown: None,
+            events: None,
+            dry_run: false,
+            base_fee_paid: 0,
+            priority_fee_paid: 0,
+            balance_changes: Vec::new(),
         }
     }
 
@@ -154,6 +163,15 @@ where
             error: None,
             validator_changes: Some(validator_changes),
             receipt: Some(receipt),
+            write_set_commitment: None,
+            memory_profile: None,
+            opcode_gas_profile: None,
+            gas_breakdown: None,
+            events: None,
+            dry_run: false,
+            base_fee_paid: 0,
+            priority_fee_paid: 0,
+            balance_changes: Vec::new(),
         }
     }
 }
@@ -10,6 +10,12 @@
 //!
 //! They result in a [CommandReceiptV1] or [CommandReceiptV2], similar to regular command executions.
 //! However, the `gas_used` in View Calls serves only as a reference, given the absence of actual gas consumption.
+//!
+//! A View Call is executed against a dummy transaction and default [BlockchainParams](crate::BlockchainParams), and host
+//! functions which read the block context (e.g. block height or timestamp) are not callable from it. Hence the
+//! result depends only on the given World State, gas limit, target, method and arguments, and not on any global
+//! state kept by the Runtime between calls. Executing the same View Call against a historical World State snapshot
+//! always gives the same result as executing it when that snapshot was the latest state.
 
 use pchain_types::{
     blockchain::{CallReceipt, CommandReceiptV1, CommandReceiptV2, ExitCodeV1, ExitCodeV2},
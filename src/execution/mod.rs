@@ -24,6 +24,9 @@ pub mod execute_view;
 
 pub mod execute_next_epoch;
 
+#[cfg(feature = "test-helpers")]
+pub mod test_helpers;
+
 #[cfg(test)]
 mod tests {
     mod basic;
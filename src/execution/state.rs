@@ -15,19 +15,23 @@
 //! they are effectively 'taken', or consumed, in the process.
 //! This ensures that each Command is executed only once and prevents accidental reuse.
 
-use pchain_types::blockchain::{
-    CommandReceiptV1, CommandReceiptV2, ExitCodeV1, ExitCodeV2, ReceiptV1, ReceiptV2,
+use pchain_types::{
+    blockchain::{
+        CommandReceiptV1, CommandReceiptV2, ExitCodeV1, ExitCodeV2, ReceiptV1, ReceiptV2,
+    },
+    cryptography::PublicAddress,
 };
 use pchain_world_state::{VersionProvider, WorldState, DB};
 use receipt_buffer::ProcessReceipts;
 
 use crate::{
     context::TransitionContext,
-    types::{self, CommandKind, DeferredCommand, TxnMetadata},
+    gas::{GasBreakdown, OpcodeGasProfile},
+    types::{self, CommandKind, DeferredCommand, RuntimeEvent, TxnMetadata},
     BlockchainParams, TransitionError,
 };
 
-use super::cache::{receipt_buffer, CommandReceiptBuffer};
+use super::cache::{receipt_buffer, CommandReceiptBuffer, WorldStateCache};
 
 /// A unified repository of the transaction's current state.
 ///
@@ -51,6 +55,28 @@ where
 
     /// Output cache for Command Receipts, which store the results and metadata of executed commands.
     pub receipt: CommandReceiptBuffer<E>,
+
+    /// Peak Wasm linear memory pages used by each executed command, for profiling.
+    pub memory_profile: Vec<u32>,
+
+    /// Gas used by Wasm opcodes in each executed command by category of opcode, for profiling.
+    pub opcode_gas_profile: Vec<OpcodeGasProfile>,
+
+    /// Runtime events of the transaction, in the order in which they took place.
+    pub events: Vec<RuntimeEvent>,
+
+    /// Gas used by each executed command by kind of operation, for gas accounting.
+    pub gas_breakdown: Vec<GasBreakdown>,
+
+    /// Balance of the signer before the pre-Charge phase. Set in the pre-Charge phase.
+    pub origin_signer_balance: u64,
+
+    /// Base fee and priority fee paid by the signer for the gas used. Set in the Charge phase.
+    pub fees_paid: (u64, u64),
+
+    /// Final balances of the accounts whose balances were changed by the transaction, in the order of their addresses.
+    /// Set in the Charge phase.
+    pub balance_changes: Vec<(PublicAddress, u64)>,
 }
 
 impl<'a, S, E, V> ExecutionState<'a, S, E, V>
@@ -63,13 +89,68 @@ where
         bd: BlockchainParams,
         ctx: TransitionContext<'a, S, V>,
     ) -> Self {
+        let num_commands = txn_meta.command_kinds.len();
         Self {
             txn_meta,
             bd,
             ctx,
-            receipt: CommandReceiptBuffer::<E>::new(),
+            receipt: CommandReceiptBuffer::<E>::with_capacity(num_commands),
+            memory_profile: Vec::new(),
+            opcode_gas_profile: Vec::new(),
+            events: Vec::new(),
+            gas_breakdown: Vec::new(),
+            origin_signer_balance: 0,
+            fees_paid: (0, 0),
+            balance_changes: Vec::new(),
+        }
+    }
+
+    /// Whether runtime events are recorded for this transaction.
+    pub fn records_events(&self) -> bool {
+        self.ctx
+            .gas_meter
+            .output_cache_of_current_command
+            .record_events
+    }
+
+    /// Takes the runtime events of the transaction. None if recording of events is not enabled.
+    pub fn take_events(&mut self) -> Option<Vec<RuntimeEvent>> {
+        self.records_events()
+            .then(|| std::mem::take(&mut self.events))
+    }
+
+    /// Appends the events emitted by a command. If the command failed, all changes of the transaction
+    /// were reverted, so only the events which do not describe a state change are kept.
+    fn append_command_events(&mut self, events: Vec<RuntimeEvent>, is_success: bool) {
+        if is_success {
+            self.events.extend(events);
+        } else {
+            self.events
+                .retain(|event| matches!(event, RuntimeEvent::CommandCompleted { .. }));
         }
     }
+
+    /// Takes the memory profile of the executed commands. None if memory profiling is not enabled.
+    pub fn take_memory_profile(&mut self) -> Option<Vec<u32>> {
+        self.ctx
+            .sc_context
+            .memory_profiling
+            .then(|| std::mem::take(&mut self.memory_profile))
+    }
+
+    /// Takes the opcode gas profile of the executed commands. None if the crate is built without the `profiling` feature.
+    pub fn take_opcode_gas_profile(&mut self) -> Option<Vec<OpcodeGasProfile>> {
+        cfg!(feature = "profiling").then(|| std::mem::take(&mut self.opcode_gas_profile))
+    }
+
+    /// Takes the gas breakdown of the executed commands. None if recording of gas breakdowns is not enabled.
+    pub fn take_gas_breakdown(&mut self) -> Option<Vec<GasBreakdown>> {
+        self.ctx
+            .gas_meter
+            .output_cache_of_current_command
+            .record_gas_breakdown
+            .then(|| std::mem::take(&mut self.gas_breakdown))
+    }
 }
 
 impl<'a, S, V> FinalizeState<'a, S, ReceiptV1, V> for ExecutionState<'a, S, CommandReceiptV1, V>
@@ -128,19 +209,76 @@ where
             });
     }
 }
-impl<'a, S, V> FinalizeState<'a, S, ReceiptV2, V> for ExecutionState<'a, S, CommandReceiptV2, V>
+#[cfg(any(test, feature = "test-helpers"))]
+impl<'a, S, V> ExecutionState<'a, S, CommandReceiptV2, V>
 where
     S: DB + Send + Sync + Clone + 'static,
     V: VersionProvider + Send + Sync + Clone,
 {
-    fn finalize_receipt(self) -> (WorldState<'a, S, V>, ReceiptV2) {
+    /// Assembles a ready-to-execute state of a [TransactionV2](pchain_types::blockchain::TransactionV2) signed by
+    /// `signer`, which has a zero priority fee and a maximum base fee of `bd.this_base_fee`. The `commands` are not
+    /// taken into the state: they only set the command kinds and the size of the transaction, and are passed to the
+    /// execution functions separately.
+    ///
+    /// For property tests and fuzzing of Commands. Only available in tests or with the `test-helpers` feature, which
+    /// exposes it to other crates through
+    /// [execute_commands_v2_from_parts](crate::execution::test_helpers::execute_commands_v2_from_parts).
+    ///
+    /// ```ignore
+    /// let commands = vec![Command::Transfer(TransferInput { recipient, amount: 100 })];
+    /// let state = ExecutionState::from_parts(ws, signer, 0, 1_000_000, &commands, bd);
+    /// let result = execute_commands_v2(state, commands);
+    /// assert_eq!(result.receipt.unwrap().exit_code, ExitCodeV2::Ok);
+    /// ```
+    pub fn from_parts(
+        ws: WorldState<'a, S, V>,
+        signer: PublicAddress,
+        nonce: u64,
+        gas_limit: u64,
+        commands: &[pchain_types::blockchain::Command],
+        bd: BlockchainParams,
+    ) -> Self {
+        let tx = pchain_types::blockchain::TransactionV2 {
+            signer,
+            nonce,
+            commands: commands.to_vec(),
+            gas_limit,
+            max_base_fee_per_gas: bd.this_base_fee,
+            priority_fee_per_gas: 0,
+            signature: [0u8; 64],
+            hash: [0u8; 32],
+        };
+        let ctx = TransitionContext::new(types::TxnVersion::V2, ws, gas_limit);
+        Self::new(TxnMetadata::from(&tx), bd, ctx)
+    }
+}
+
+impl<'a, S, V> ExecutionState<'a, S, CommandReceiptV2, V>
+where
+    S: DB + Send + Sync + Clone + 'static,
+    V: VersionProvider + Send + Sync + Clone,
+{
+    /// Same as [finalize_receipt](FinalizeState::finalize_receipt), but outputs the World State Cache
+    /// without committing its writes to the World State.
+    pub fn finalize_receipt_uncommitted(self) -> (WorldStateCache<'a, S, V>, ReceiptV2) {
         let gas_used = self.ctx.gas_meter.total_gas_used_for_executed_commands();
         (
-            self.ctx.into_ws_cache().commit_to_world_state(),
+            self.ctx.into_ws_cache(),
             self.receipt
                 .into_receipt(gas_used, &self.txn_meta.command_kinds),
         )
     }
+}
+
+impl<'a, S, V> FinalizeState<'a, S, ReceiptV2, V> for ExecutionState<'a, S, CommandReceiptV2, V>
+where
+    S: DB + Send + Sync + Clone + 'static,
+    V: VersionProvider + Send + Sync + Clone,
+{
+    fn finalize_receipt(self) -> (WorldState<'a, S, V>, ReceiptV2) {
+        let (ws_cache, receipt) = self.finalize_receipt_uncommitted();
+        (ws_cache.commit_to_world_state(), receipt)
+    }
     fn finalize_cmd_receipt_collect_deferred<Q>(
         &mut self,
         command_kind: CommandKind,
@@ -152,8 +290,22 @@ where
         };
 
         // extract receipt from current execution result
-        let (gas_used, command_output, deferred_commands_from_call) =
+        let (gas_used, mut command_output, deferred_commands_from_call) =
             self.ctx.complete_cmd_execution();
+        self.memory_profile.push(command_output.memory_pages);
+        self.opcode_gas_profile
+            .push(command_output.opcode_gas_profile);
+        self.gas_breakdown.push(command_output.gas_breakdown);
+        if self.records_events() {
+            let events = std::mem::take(&mut command_output.events);
+            self.append_command_events(events, execution_result.is_ok());
+            self.events.push(RuntimeEvent::CommandCompleted {
+                command_index: self.receipt.num_command_receipts(),
+                command_kind,
+                exit_code: exit_code.clone(),
+                gas_used,
+            });
+        }
         self.receipt
             .push_command_receipt(types::create_executed_cmd_rcp_v2(
                 &command_kind,
@@ -175,7 +327,21 @@ where
         };
 
         // extract receipt from current execution result
-        let (gas_used, command_output, _) = self.ctx.complete_cmd_execution();
+        let (gas_used, mut command_output, _) = self.ctx.complete_cmd_execution();
+        // deferred commands are folded into the receipt of their parent command
+        if let Some(memory_pages) = self.memory_profile.last_mut() {
+            *memory_pages = std::cmp::max(*memory_pages, command_output.memory_pages);
+        }
+        if let Some(opcode_gas_profile) = self.opcode_gas_profile.last_mut() {
+            opcode_gas_profile.add(&command_output.opcode_gas_profile);
+        }
+        if let Some(gas_breakdown) = self.gas_breakdown.last_mut() {
+            gas_breakdown.add(&command_output.gas_breakdown);
+        }
+        if self.records_events() {
+            let events = std::mem::take(&mut command_output.events);
+            self.append_command_events(events, execution_result.is_ok());
+        }
         self.receipt
             .push_deferred_command_receipt(types::create_executed_cmd_rcp_v2(
                 &command_kind,
@@ -0,0 +1,40 @@
+/*
+    Copyright © 2023, ParallelChain Lab
+    Licensed under the Apache License, Version 2.0: http://www.apache.org/licenses/LICENSE-2.0
+*/
+
+//! Helpers for property tests and fuzzing of Commands, which execute Commands without preparing a signed
+//! transaction. Only available with the `test-helpers` feature, which is not for production builds.
+
+use pchain_types::{blockchain::Command, cryptography::PublicAddress};
+use pchain_world_state::{VersionProvider, WorldState, DB};
+
+use crate::{transition::TransitionV2Result, BlockchainParams};
+
+use super::{execute_commands::execute_commands_v2, state::ExecutionState};
+
+/// Executes `commands` as a [TransactionV2](pchain_types::blockchain::TransactionV2) signed by `signer`, which has a
+/// zero priority fee and a maximum base fee of `bd.this_base_fee`, on `ws`. All phases are executed as in
+/// [transition_v2](crate::Runtime::transition_v2), with the default settings of the Runtime, except for the checks
+/// on the signature and hash of the transaction, which are left empty.
+///
+/// ```ignore
+/// let commands = vec![Command::Transfer(TransferInput { recipient, amount: 100 })];
+/// let result = execute_commands_v2_from_parts(ws, signer, 0, 1_000_000, commands, bd);
+/// assert_eq!(result.receipt.unwrap().exit_code, ExitCodeV2::Ok);
+/// ```
+pub fn execute_commands_v2_from_parts<'a, S, V>(
+    ws: WorldState<'a, S, V>,
+    signer: PublicAddress,
+    nonce: u64,
+    gas_limit: u64,
+    commands: Vec<Command>,
+    bd: BlockchainParams,
+) -> TransitionV2Result<'a, S, V>
+where
+    S: DB + Send + Sync + Clone + 'static,
+    V: VersionProvider + Send + Sync + Clone + 'static,
+{
+    let state = ExecutionState::from_parts(ws, signer, nonce, gas_limit, &commands, bd);
+    execute_commands_v2(state, commands)
+}
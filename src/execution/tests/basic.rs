@@ -2,17 +2,34 @@
     Copyright © 2023, ParallelChain Lab
     Licensed under the Apache License, Version 2.0: http://www.apache.org/licenses/LICENSE-2.0
 */
+use std::collections::HashMap;
+
 use pchain_types::{
-    blockchain::{Command, ExitCodeV1, ExitCodeV2},
+    blockchain::{
+        CallReceipt, Command, CommandReceiptV2, ExitCodeV1, ExitCodeV2, Log, StakeDepositReceipt,
+        UnstakeDepositReceipt,
+    },
     runtime::TransferInput,
 };
+use pchain_world_state::V2;
 
 use crate::{
-    execution::execute_commands::{execute_commands_v1, execute_commands_v2},
+    execution::{
+        cache::{
+            CommandReceiptBuffer, CommitStats, ProcessReceipts, StorageReads, WorldStateCache,
+        },
+        execute_commands::{execute_commands_v1, execute_commands_v2},
+        state::ExecutionState,
+    },
     gas::{
-        get_cost_read, get_cost_traverse, set_cost_delete_old_value, set_cost_rehash,
-        set_cost_write_new_value, ACCOUNT_TRIE_KEY_LENGTH,
+        get_cost_read, get_cost_traverse,
+        operations::{ws_read_only_storage_data, ws_storage_data},
+        set_cost_delete_old_value, set_cost_rehash, set_cost_write_new_value, GasSchedule,
+        ACCOUNT_TRIE_KEY_LENGTH,
     },
+    rewards_formulas::{priority_fee_reward, proposer_reward_v2},
+    types::{CommandKind, TxnVersion},
+    TransitionError,
 };
 
 use super::test_utils::*;
@@ -90,6 +107,122 @@ fn test_transfer() {
     assert_eq!(owner_balance_after, DEFAULT_AMOUNT + amount);
 }
 
+#[test]
+// Commands Transfer charged by the default gas schedule, which is pinned to the gas used before the schedule existed
+fn test_transfer_default_gas_schedule() {
+    let fixture = TestFixture::new();
+    let mut state = create_state_v1(Some(fixture.ws()));
+    assert_eq!(state.ctx.gas_meter.schedule, GasSchedule::default());
+
+    let commands = vec![Command::Transfer(TransferInput {
+        recipient: ACCOUNT_B,
+        amount: 999_999,
+    })];
+    set_tx_v1(&mut state, ACCOUNT_A, 0, &commands);
+
+    let ret = execute_commands_v1(state, commands);
+    assert_eq!(ret.error, None);
+    assert_eq!(extract_gas_used(&ret), 32820);
+}
+
+#[test]
+// Commands Transfer charged by a tuned gas schedule
+fn test_transfer_gas_schedule() {
+    let schedule = GasSchedule {
+        mpt_write_per_byte_cost: 2 * GasSchedule::MAINNET.mpt_write_per_byte_cost,
+        mpt_rehash_per_byte_cost: 2 * GasSchedule::MAINNET.mpt_rehash_per_byte_cost,
+        ..GasSchedule::MAINNET
+    };
+
+    let fixture = TestFixture::new();
+    let mut state = create_state_v1(Some(fixture.ws()));
+    state.ctx.gas_meter.schedule = schedule;
+
+    let amount = 999_999;
+    let commands = vec![Command::Transfer(TransferInput {
+        recipient: ACCOUNT_B,
+        amount,
+    })];
+
+    set_tx_v1(&mut state, ACCOUNT_A, 0, &commands);
+    let tx_base_cost_v1 =
+        schedule.tx_inclusion_cost_v1(state.txn_meta.size, &state.txn_meta.command_kinds);
+    assert!(tx_base_cost_v1 > 133530);
+
+    let ret = execute_commands_v1(state, commands);
+    assert_eq!(
+        (
+            &ret.error,
+            &ret.receipt.as_ref().unwrap().last().unwrap().exit_code
+        ),
+        (&None, &ExitCodeV1::Success)
+    );
+
+    assert_eq!(
+        extract_gas_used(&ret),
+        xfer_cmd_cost_with_schedule(&schedule)
+    );
+    assert!(extract_gas_used(&ret) > xfer_cmd_cost());
+    let sender_balance_after = ret.new_state.account_trie().balance(&ACCOUNT_A).unwrap();
+    assert_eq!(
+        sender_balance_after,
+        DEFAULT_AMOUNT - amount - tx_base_cost_v1 - extract_gas_used(&ret)
+    );
+}
+
+#[test]
+// Commands Transfer to the signer's own address
+fn test_transfer_to_self() {
+    let fixture = TestFixture::new();
+    let mut state = create_state_v1(Some(fixture.ws()));
+
+    let commands = vec![Command::Transfer(TransferInput {
+        recipient: ACCOUNT_A,
+        amount: 999_999,
+    })];
+
+    let tx_base_cost_v1 = set_tx_v1(&mut state, ACCOUNT_A, 0, &commands);
+    let ret = execute_commands_v1(state, commands);
+    assert_eq!(
+        (
+            &ret.error,
+            &ret.receipt.as_ref().unwrap().last().unwrap().exit_code
+        ),
+        (&None, &ExitCodeV1::Success)
+    );
+
+    // balance is unchanged except for gas
+    let signer_balance_after = ret.new_state.account_trie().balance(&ACCOUNT_A).unwrap();
+    assert_eq!(
+        signer_balance_after,
+        DEFAULT_AMOUNT - tx_base_cost_v1 - extract_gas_used(&ret)
+    );
+}
+
+#[test]
+// Gas limit below the inclusion cost fails in the Pre-Charge phase without changing any state
+fn test_gas_limit_too_low() {
+    let fixture = TestFixture::new();
+    let mut state = create_state_v1(Some(fixture.ws()));
+
+    let commands = vec![Command::Transfer(TransferInput {
+        recipient: ACCOUNT_B,
+        amount: 999_999,
+    })];
+    let tx_base_cost_v1 = set_tx_v1(&mut state, ACCOUNT_A, 0, &commands);
+    state.txn_meta.gas_limit = tx_base_cost_v1 - 1;
+    state.ctx.gas_meter.gas_limit = tx_base_cost_v1 - 1;
+
+    let ret = execute_commands_v1(state, commands);
+    assert_eq!(ret.error, Some(TransitionError::GasLimitTooLow));
+    assert!(ret.receipt.is_none());
+
+    let account_trie = ret.new_state.account_trie();
+    assert_eq!(account_trie.nonce(&ACCOUNT_A).unwrap(), 0);
+    assert_eq!(account_trie.balance(&ACCOUNT_A).unwrap(), DEFAULT_AMOUNT);
+    assert_eq!(account_trie.balance(&ACCOUNT_B).unwrap(), DEFAULT_AMOUNT);
+}
+
 //
 //
 //
@@ -169,6 +302,233 @@ fn test_transfer_v2() {
     assert_eq!(owner_balance_after, 500_000_000 + amount);
 }
 
+#[cfg(feature = "tracing")]
+#[test]
+// Execution emits a span for each phase, and a work span for each command
+fn test_execution_spans_v2() {
+    let fixture = TestFixture::new();
+    let mut state = create_state_v2(Some(fixture.ws()));
+
+    let commands = vec![
+        Command::Transfer(TransferInput {
+            recipient: ACCOUNT_B,
+            amount: 1,
+        }),
+        Command::Transfer(TransferInput {
+            recipient: ACCOUNT_C,
+            amount: 1,
+        }),
+    ];
+    set_tx_v2(&mut state, ACCOUNT_A, 0, &commands);
+
+    let recorder = SpanRecorder::default();
+    let ret = tracing::subscriber::with_default(recorder.clone(), || {
+        execute_commands_v2(state, commands)
+    });
+    assert!(ret.error.is_none());
+
+    let spans = recorder.spans.lock().unwrap();
+    let names: Vec<&str> = spans.iter().map(|span| span.name).collect();
+    assert_eq!(names, vec!["pre_charge", "work", "work", "charge"]);
+
+    let command_receipts = &ret.receipt.as_ref().unwrap().command_receipts;
+    let work_spans = spans.iter().filter(|span| span.name == "work");
+    for (i, (span, receipt)) in work_spans.zip(command_receipts).enumerate() {
+        assert_eq!(span.fields["command_kind"], "Transfer");
+        assert_eq!(span.fields["command_index"], i.to_string());
+        assert_eq!(span.fields["deferred"], "false");
+        assert_eq!(
+            span.fields["gas_used"],
+            crate::types::gas_used_and_exit_code_v2(receipt)
+                .0
+                .to_string()
+        );
+    }
+}
+
+#[test]
+// Commands Transfer to the signer's own address
+fn test_transfer_to_self_v2() {
+    let fixture = TestFixture::new();
+    let mut state = create_state_v2(Some(fixture.ws()));
+
+    let commands = vec![Command::Transfer(TransferInput {
+        recipient: ACCOUNT_A,
+        amount: 999_999,
+    })];
+
+    set_tx_v2(&mut state, ACCOUNT_A, 0, &commands);
+    let ret = execute_commands_v2(state, commands);
+    assert!(ret.error.is_none());
+    let receipt = ret.receipt.as_ref().expect("Receipt expected");
+    assert_eq!(receipt.exit_code, ExitCodeV2::Ok);
+
+    // balance is unchanged except for gas
+    let signer_balance_after = ret.new_state.account_trie().balance(&ACCOUNT_A).unwrap();
+    assert_eq!(signer_balance_after, DEFAULT_AMOUNT - receipt.gas_used);
+}
+
+#[test]
+// Commands Transfer executed on a state assembled by ExecutionState::from_parts
+fn test_transfer_from_parts_v2() {
+    let fixture = TestFixture::new();
+    let amount = 999_999;
+    let commands = vec![Command::Transfer(TransferInput {
+        recipient: ACCOUNT_B,
+        amount,
+    })];
+
+    let state = ExecutionState::from_parts(
+        fixture.ws(),
+        ACCOUNT_A,
+        0,
+        1_000_000,
+        &commands,
+        create_bd(),
+    );
+    let ret = execute_commands_v2(state, commands);
+    assert!(ret.error.is_none());
+    let receipt = ret.receipt.as_ref().expect("Receipt expected");
+    assert_eq!(receipt.exit_code, ExitCodeV2::Ok);
+
+    let account_trie = ret.new_state.account_trie();
+    assert_eq!(account_trie.nonce(&ACCOUNT_A).unwrap(), 1);
+    assert_eq!(
+        account_trie.balance(&ACCOUNT_A).unwrap(),
+        DEFAULT_AMOUNT - amount - receipt.gas_used
+    );
+    assert_eq!(
+        account_trie.balance(&ACCOUNT_B).unwrap(),
+        DEFAULT_AMOUNT + amount
+    );
+}
+
+#[test]
+// Gas limit below the inclusion cost fails in the Pre-Charge phase without changing any state
+fn test_gas_limit_too_low_v2() {
+    let fixture = TestFixture::new();
+    let mut state = create_state_v2(Some(fixture.ws()));
+
+    let commands = vec![Command::Transfer(TransferInput {
+        recipient: ACCOUNT_B,
+        amount: 999_999,
+    })];
+    let tx_base_cost_v2 = set_tx_v2(&mut state, ACCOUNT_A, 0, &commands);
+    state.txn_meta.gas_limit = tx_base_cost_v2 - 1;
+    state.ctx.gas_meter.gas_limit = tx_base_cost_v2 - 1;
+
+    let ret = execute_commands_v2(state, commands);
+    assert_eq!(ret.error, Some(TransitionError::GasLimitTooLow));
+    assert!(ret.receipt.is_none());
+
+    let account_trie = ret.new_state.account_trie();
+    assert_eq!(account_trie.nonce(&ACCOUNT_A).unwrap(), 0);
+    assert_eq!(account_trie.balance(&ACCOUNT_A).unwrap(), DEFAULT_AMOUNT);
+    assert_eq!(account_trie.balance(&ACCOUNT_B).unwrap(), DEFAULT_AMOUNT);
+}
+
+#[test]
+// Reads through a read-only storage reader cost the same as normal storage reads, but leave the caches untouched
+fn test_storage_reader() {
+    let fixture = TestFixture::new();
+    let mut ws = fixture.ws::<V2>();
+    let data: HashMap<Vec<u8>, Vec<u8>> = (0u8..10).map(|i| (vec![i], vec![i; 32])).collect();
+    ws.storage_trie_mut(&ACCOUNT_B)
+        .unwrap()
+        .batch_set(&data)
+        .unwrap();
+    let mut ws_cache = WorldStateCache::new(ws);
+
+    let schedule = GasSchedule::default();
+    let mut gas = 0;
+    let mut expected_gas = 0;
+    let mut reads = StorageReads::default();
+    for i in 0..1000 {
+        // half of the keys do not exist. A reader is created for every read, as in view calls.
+        let key = [(i % 20) as u8];
+        let mut reader = ws_cache.storage_reader(ACCOUNT_B, &mut reads);
+        let (value, cost) = ws_read_only_storage_data(&schedule, TxnVersion::V2, &mut reader, &key);
+        assert_eq!(value, data.get(&key[..]).cloned());
+        gas += cost.net_cost().0;
+    }
+    assert!(ws_cache.storage_data.writes.is_empty());
+    assert!(ws_cache.storage_data.reads.borrow().is_empty());
+
+    for i in 0..1000 {
+        let key = [(i % 20) as u8];
+        let (_, cost) = ws_storage_data(&schedule, TxnVersion::V2, &mut ws_cache, ACCOUNT_B, &key);
+        expected_gas += cost.net_cost().0;
+    }
+    assert!(gas > 0);
+    assert_eq!(gas, expected_gas);
+}
+
+#[test]
+// Committing reports the number of keys written and deleted, and the bytes of the values written
+fn test_commit_stats() {
+    let fixture = TestFixture::new();
+    let mut ws = fixture.ws::<V2>();
+    ws.storage_trie_mut(&ACCOUNT_B)
+        .unwrap()
+        .set(&[1].to_vec(), vec![1; 32])
+        .unwrap();
+    let mut ws_cache = WorldStateCache::new(ws);
+
+    ws_cache.set_balance(ACCOUNT_A, 100);
+    ws_cache.set_balance(ACCOUNT_C, 200);
+    ws_cache.set_storage_data(ACCOUNT_B, &[1], Vec::new());
+
+    let (ws, stats) = ws_cache.commit_to_world_state_with_stats();
+    assert_eq!(
+        stats,
+        CommitStats {
+            keys_written: 2,
+            bytes_written: 16,
+            keys_deleted: 1,
+        }
+    );
+    assert_eq!(ws.account_trie().balance(&ACCOUNT_C).unwrap(), 200);
+    assert!(!ws
+        .storage_trie(&ACCOUNT_B)
+        .unwrap()
+        .contains(&[1].to_vec())
+        .unwrap());
+}
+
+#[test]
+// Priority fee credited to the proposer in the Charge phase
+fn test_proposer_reward_v2() {
+    let fixture = TestFixture::new();
+    let mut state = create_state_v2(Some(fixture.ws()));
+
+    let commands = vec![Command::Transfer(TransferInput {
+        recipient: ACCOUNT_B,
+        amount: 999_999,
+    })];
+    set_tx_v2(&mut state, ACCOUNT_A, 0, &commands);
+    let priority_fee_per_gas = 2;
+    state.txn_meta.priority_fee_per_gas = priority_fee_per_gas;
+
+    let mut tx = create_tx_v2(ACCOUNT_A);
+    tx.priority_fee_per_gas = priority_fee_per_gas;
+    let bd = create_bd();
+    assert_eq!(proposer_reward_v2(&tx), tx.gas_limit * priority_fee_per_gas);
+
+    let ret = execute_commands_v2(state, commands);
+    assert!(ret.error.is_none());
+    let gas_used = ret.receipt.as_ref().unwrap().gas_used;
+    let proposer_balance = ret
+        .new_state
+        .account_trie()
+        .balance(&bd.proposer_address)
+        .unwrap();
+    assert_eq!(
+        proposer_balance,
+        priority_fee_reward(gas_used, priority_fee_per_gas)
+    );
+    assert!(proposer_balance <= proposer_reward_v2(&tx));
+}
+
 //
 //
 //
@@ -208,3 +568,72 @@ fn xfer_cmd_cost() -> u64 {
     )
     .expect("Expect gas cost to be a non-negative int")
 }
+
+fn xfer_cmd_cost_with_schedule(schedule: &GasSchedule) -> u64 {
+    const U64_LEN: usize = std::mem::size_of::<u64>();
+
+    let get_sender_init_bal_cost = try_i64!(schedule.get_cost_traverse(ACCOUNT_TRIE_KEY_LENGTH))
+        + try_i64!(schedule.get_cost_read(U64_LEN));
+
+    // note: the set will additionally perform another get
+    let set_sender_new_bal_cost = get_sender_init_bal_cost
+        + try_i64!(schedule.set_cost_write_new_value(U64_LEN))
+        + try_i64!(schedule.set_cost_rehash(ACCOUNT_TRIE_KEY_LENGTH))
+        - try_i64!(schedule.set_cost_delete_old_value(ACCOUNT_TRIE_KEY_LENGTH, U64_LEN, U64_LEN));
+
+    let get_recipient_init_bal_cost = get_sender_init_bal_cost; // similar operation
+
+    let set_recipient_new_bal_cost = set_sender_new_bal_cost; // similar operation
+
+    u64::try_from(
+        get_sender_init_bal_cost
+            + set_sender_new_bal_cost
+            + get_recipient_init_bal_cost
+            + set_recipient_new_bal_cost,
+    )
+    .expect("Expect gas cost to be a non-negative int")
+}
+
+/// Receipts of commands deferred by a Call are merged into the Call receipt: gas used is summed, and the exit code
+/// and return value are those of the last deferred command.
+#[test]
+fn test_deferred_command_receipts_merge_v2() {
+    let log = Log {
+        topic: vec![1],
+        value: vec![2],
+    };
+    let mut receipt_buffer = CommandReceiptBuffer::<CommandReceiptV2>::new();
+    receipt_buffer.push_command_receipt(CommandReceiptV2::Call(CallReceipt {
+        exit_code: ExitCodeV2::Ok,
+        gas_used: 100,
+        logs: vec![log.clone()],
+        return_value: vec![9],
+    }));
+    receipt_buffer.push_deferred_command_receipt(CommandReceiptV2::StakeDeposit(
+        StakeDepositReceipt {
+            exit_code: ExitCodeV2::Ok,
+            gas_used: 20,
+            amount_staked: 5,
+        },
+    ));
+    receipt_buffer.push_deferred_command_receipt(CommandReceiptV2::UnstakeDeposit(
+        UnstakeDepositReceipt {
+            exit_code: ExitCodeV2::Error,
+            gas_used: 30,
+            amount_unstaked: 7,
+        },
+    ));
+
+    let receipt = receipt_buffer.into_receipt(150, &[CommandKind::Call]);
+    assert_eq!(receipt.exit_code, ExitCodeV2::Error);
+    assert_eq!(receipt.command_receipts.len(), 1);
+    match &receipt.command_receipts[0] {
+        CommandReceiptV2::Call(cr) => {
+            assert_eq!(cr.gas_used, 100 + 20 + 30);
+            assert_eq!(cr.exit_code, ExitCodeV2::Error);
+            assert_eq!(cr.return_value, 7u64.to_le_bytes().to_vec());
+            assert_eq!(cr.logs, vec![log]);
+        }
+        _ => panic!("Call command receipt expected"),
+    }
+}
@@ -4,15 +4,35 @@
 */
 use std::collections::HashMap;
 
-use pchain_world_state::{NetworkAccount, Pool, Stake};
+use pchain_types::{blockchain::Command, runtime::StakeDepositInput};
+use pchain_world_state::{NetworkAccount, NetworkAccountSized, Pool, PoolKey, Stake, StakeValue};
 
-use crate::commands::protocol;
+use crate::{
+    commands::protocol,
+    execution::{execute_commands::execute_commands_v2, execute_next_epoch::execute_next_epoch_v2},
+    Runtime, TransitionError, ValidatorPerformance,
+};
 
 use super::test_utils::*;
 
 // Note: The next epoch functions tested here are non-chargeable
 // ctx.gas_meter is used only to prepare testing state
 
+// Validator performance built from the proposers of the blocks in an epoch
+#[test]
+fn test_validator_performance_from_proposers() {
+    let vp = ValidatorPerformance::from_proposers(&[ACCOUNT_A, ACCOUNT_B, ACCOUNT_A]);
+    assert_eq!(vp.blocks_per_epoch, 3);
+    assert_eq!(vp.stats.len(), 2);
+    assert_eq!(vp.stats.get(&ACCOUNT_A).unwrap().num_of_proposed_blocks, 2);
+    assert_eq!(vp.stats.get(&ACCOUNT_B).unwrap().num_of_proposed_blocks, 1);
+    assert!(vp.validate());
+
+    let mut vp = vp;
+    vp.blocks_per_epoch = 2;
+    assert!(!vp.validate());
+}
+
 // Prepare: no pool in world state
 // Prepare: empty pvp and vp.
 // Commands (account a): Next Epoch
@@ -228,7 +248,7 @@ fn test_next_epoch_single_pool_with_vp() {
     // pool rewards = (100_000 * 8.346 / 100) / 365 = 22
     // reward for b = 22 * 9 / 10 = 19
     // reward for a = 22 * 1 / 10 = 2
-    // commission fee from b = 19 * 1% = 0
+    // commission fee from b = 19 * 1% = 0, rounded down (see rewards_formulas::stake_reward)
     // reward for b after commission fee = 19 - 0 = 19
     // reward for a after commission fee = 2 + 0 = 2
 
@@ -348,7 +368,7 @@ fn test_next_epoch_single_pool_auto_stake() {
     // pool rewards = (100_000 * 8.346 / 100) / 365 = 22
     // reward for b = 22 * 9 / 10 = 19
     // reward for a = 22 * 1 / 10 = 2
-    // commission fee from b = 19 * 1% = 0
+    // commission fee from b = 19 * 1% = 0, rounded down (see rewards_formulas::stake_reward)
     // reward for b after commission fee = 19 - 0 = 19
     // reward for a after commission fee = 2 + 0 = 2
 
@@ -975,7 +995,7 @@ fn test_next_epoch_single_pool_with_vp_v2() {
     // pool rewards = (100_000 * 8.346 / 100) / 365 = 22
     // reward for b = 22 * 9 / 10 = 19
     // reward for a = 22 * 1 / 10 = 2
-    // commission fee from b = 19 * 1% = 0
+    // commission fee from b = 19 * 1% = 0, rounded down (see rewards_formulas::stake_reward)
     // reward for b after commission fee = 19 - 0 = 19
     // reward for a after commission fee = 2 + 0 = 2
 
@@ -1095,7 +1115,7 @@ fn test_next_epoch_single_pool_auto_stake_v2() {
     // pool rewards = (100_000 * 8.346 / 100) / 365 = 22
     // reward for b = 22 * 9 / 10 = 19
     // reward for a = 22 * 1 / 10 = 2
-    // commission fee from b = 19 * 1% = 0
+    // commission fee from b = 19 * 1% = 0, rounded down (see rewards_formulas::stake_reward)
     // reward for b after commission fee = 19 - 0 = 19
     // reward for a after commission fee = 2 + 0 = 2
 
@@ -1495,3 +1515,281 @@ fn test_next_epoch_multiple_pools_and_stakes_auto_stake_v2() {
         }
     }
 }
+
+// Prepare: pool (account a) in world state, included in nvp.
+//              with delegated stakes of account b, auto_stake_reward = false
+//              with non-zero value of Operator Stake, auto_stake_reward = false
+// Commands (account a): Next Epoch
+// Exception:
+// - Pool power changed without updating the nvp and stakes
+// - Deposit of account b reduced below its stake
+#[test]
+fn test_validate_world_state() {
+    let fixture = TestFixture::new();
+    let ws = {
+        let mut state = create_state_v1(Some(fixture.ws()));
+        setup_pool(
+            &mut state, ACCOUNT_A, 10_000, ACCOUNT_B, 90_000, false, false,
+        );
+        state.ctx.into_ws_cache().commit_to_world_state()
+    };
+    assert_eq!(protocol::validate_world_state(&ws), Ok(()));
+
+    // invariants still hold after the pool is copied into vp
+    let state = execute_next_epoch_test_v1(create_state_v1(Some(ws)));
+    let ws = state.ctx.into_ws_cache().commit_to_world_state();
+    assert_eq!(protocol::validate_world_state(&ws), Ok(()));
+
+    // corrupt the pool and the deposit of account b
+    let ws = {
+        let mut state = create_state_v1(Some(ws));
+        NetworkAccount::pools(&mut state.ctx.gas_meter, ACCOUNT_A).set_power(50_000);
+        NetworkAccount::deposits(&mut state.ctx.gas_meter, ACCOUNT_A, ACCOUNT_B).set_balance(1_000);
+        state.ctx.into_ws_cache().commit_to_world_state()
+    };
+    let violations = protocol::validate_world_state(&ws).unwrap_err();
+    assert_eq!(
+        violations,
+        vec![
+            protocol::InvariantViolation::NvpPowerMismatch {
+                operator: ACCOUNT_A,
+                nvp_power: 100_000,
+                pool_power: 50_000,
+            },
+            protocol::InvariantViolation::PoolPowerMismatch {
+                set: protocol::PoolSet::Nvp,
+                operator: ACCOUNT_A,
+                power: 50_000,
+                sum_of_stakes: 100_000,
+            },
+            protocol::InvariantViolation::StakeExceedsDeposit {
+                operator: ACCOUNT_A,
+                owner: ACCOUNT_B,
+                stake_power: 90_000,
+                deposit_balance: 1_000,
+            },
+        ]
+    );
+}
+
+// Prepare: pool (account a) in world state, included in nvp.
+//              with delegated stakes of account b, auto_stake_reward = false
+//              with non-zero value of Operator Stake, auto_stake_reward = false
+// Commands (account a): Next Epoch
+// Projected reward rate of the pool in vp, and of a pool not in vp
+#[test]
+fn test_projected_pool_apr() {
+    let fixture = TestFixture::new();
+    let ws = {
+        let mut state = create_state_v1(Some(fixture.ws()));
+        setup_pool(
+            &mut state, ACCOUNT_A, 10_000, ACCOUNT_B, 90_000, false, false,
+        );
+        state.ctx.into_ws_cache().commit_to_world_state()
+    };
+
+    // no pool in vp before the first epoch
+    let mut bd = create_bd();
+    bd.validator_performance = Some(single_node_performance(ACCOUNT_A, 1));
+    assert_eq!(protocol::projected_pool_apr(&ws, ACCOUNT_A, &bd), (0, 1));
+
+    let mut state = create_state_v1(Some(ws));
+    state.bd.validator_performance = Some(single_node_performance(ACCOUNT_A, 1));
+    let state = execute_next_epoch_test_v1(state);
+    let ws = state.ctx.into_ws_cache().commit_to_world_state();
+
+    // pool rewards = (100_000 * 8.346 / 100) / 365 = 22 per epoch
+    assert_eq!(
+        protocol::projected_pool_apr(&ws, ACCOUNT_A, &bd),
+        (22 * 365, 100_000)
+    );
+    // the projection assumes expected performance if there is no validator performance
+    bd.validator_performance = None;
+    assert_eq!(
+        protocol::projected_pool_apr(&ws, ACCOUNT_A, &bd),
+        (22 * 365, 100_000)
+    );
+    // no reward without proposing blocks
+    bd.validator_performance = Some(single_node_performance(ACCOUNT_B, 1));
+    assert_eq!(
+        protocol::projected_pool_apr(&ws, ACCOUNT_A, &bd),
+        (0, 100_000)
+    );
+    assert_eq!(protocol::projected_pool_apr(&ws, ACCOUNT_B, &bd), (0, 1));
+}
+
+// Prepare: pool (account a) in world state, with the same delegated stakes inserted in different orders
+// Ordered delegated stakes are sorted by power and then by owner, whatever the order of insertion
+#[test]
+fn test_ordered_delegated_stakes() {
+    let stakes = [
+        (ACCOUNT_B, 300),
+        (ACCOUNT_C, 100),
+        (ACCOUNT_D, 300),
+        ([5u8; 32], 200),
+    ];
+    for order in [[0, 1, 2, 3], [3, 2, 1, 0], [2, 0, 3, 1], [1, 3, 0, 2]] {
+        let fixture = TestFixture::new();
+        let mut state = create_state_v2(Some(fixture.ws()));
+        let mut pool = NetworkAccount::pools(&mut state.ctx.gas_meter, ACCOUNT_A);
+        pool.set_operator(ACCOUNT_A);
+        pool.set_power(900);
+        pool.set_commission_rate(1);
+        for i in order {
+            let (owner, power) = stakes[i];
+            NetworkAccount::pools(&mut state.ctx.gas_meter, ACCOUNT_A)
+                .delegated_stakes()
+                .insert(StakeValue::new(Stake { owner, power }))
+                .unwrap();
+        }
+        let ws = state.ctx.into_ws_cache().commit_to_world_state();
+
+        let ordered: Vec<([u8; 32], u64)> = protocol::ordered_delegated_stakes(&ws, ACCOUNT_A)
+            .iter()
+            .map(|stake| (stake.owner, stake.power))
+            .collect();
+        assert_eq!(
+            ordered,
+            vec![
+                (ACCOUNT_C, 100),
+                ([5u8; 32], 200),
+                (ACCOUNT_B, 300),
+                (ACCOUNT_D, 300)
+            ]
+        );
+        assert!(protocol::ordered_delegated_stakes(&ws, ACCOUNT_B).is_empty());
+    }
+}
+
+// Prepare: full nvp, where the pool (account a) with the smallest power is at the boundary
+// Prepare: pool with equal power to pool (account a), entering the nvp by Stake Deposit
+// Command: Next Epoch
+// - pool (account b) has a greater address than account a, and becomes a validator instead of it
+// - pool [1, 0, 0, ...] has a smaller address than account a, and does not become a validator
+#[test]
+fn test_next_epoch_equal_power_at_boundary_v2() {
+    let smaller_operator = {
+        let mut address = [0u8; 32];
+        address[0] = 1;
+        address
+    };
+    for (operator, selected) in [(ACCOUNT_B, true), (smaller_operator, false)] {
+        let fixture = TestFixture::new();
+        let mut state = create_state_v2(Some(fixture.ws()));
+        create_full_pools_in_nvp(&mut state, false, false);
+        let mut pool = NetworkAccount::pools(&mut state.ctx.gas_meter, operator);
+        pool.set_operator(operator);
+        pool.set_commission_rate(1);
+        pool.set_power(0);
+        pool.set_operator_stake(None);
+        let mut deposit = NetworkAccount::deposits(&mut state.ctx.gas_meter, operator, ACCOUNT_C);
+        deposit.set_balance(100_000);
+        deposit.set_auto_stake_rewards(false);
+        let ws = state.ctx.into_ws_cache().commit_to_world_state();
+
+        let mut state = create_state_v2(Some(ws));
+        let commands = vec![Command::StakeDeposit(StakeDepositInput {
+            operator,
+            max_amount: 100_000,
+        })];
+        set_tx_v2(&mut state, ACCOUNT_C, 0, &commands);
+        let ret = execute_commands_v2(state, commands);
+        assert_eq!(ret.error, None);
+
+        let mut state = create_state_v2(Some(ret.new_state));
+        state.bd.validator_performance = Some(all_nodes_performance());
+        let ret = execute_next_epoch_v2(state, vec![Command::NextEpoch]);
+        assert_eq!(ret.error, None);
+        let new_validator_set = ret.validator_changes.unwrap().new_validator_set;
+        assert_eq!(
+            new_validator_set.len(),
+            TEST_MAX_VALIDATOR_SET_SIZE as usize
+        );
+        assert_eq!(new_validator_set.contains(&(operator, 100_000)), selected);
+        assert_eq!(new_validator_set.contains(&(ACCOUNT_A, 100_000)), !selected);
+
+        let mut state = create_state_v2(Some(ret.new_state));
+        assert_eq!(
+            NetworkAccount::vp(&mut state.ctx.gas_meter)
+                .pool(operator)
+                .is_some(),
+            selected
+        );
+        assert_eq!(
+            NetworkAccount::vp(&mut state.ctx.gas_meter)
+                .pool(ACCOUNT_A)
+                .is_some(),
+            !selected
+        );
+    }
+}
+
+// Prepare: full nvp with pools of different power
+// Command: Next Epoch, twice
+// - power updates list every validator in vp with its power after each epoch, in the order of vp
+// - in the second epoch the validator set does not change, so there are no new validators, but the power updates are
+//   still listed
+#[test]
+fn test_next_epoch_power_updates_v2() {
+    let fixture = TestFixture::new();
+    let mut state = create_state_v2(Some(fixture.ws()));
+    create_full_pools_in_nvp(&mut state, false, false);
+    let ws = state.ctx.into_ws_cache().commit_to_world_state();
+
+    let mut ws = Some(ws);
+    for epoch in 0..2 {
+        let mut state = create_state_v2(ws.take());
+        state.bd.validator_performance = Some(all_nodes_performance());
+        let ret = execute_next_epoch_v2(state, vec![Command::NextEpoch]);
+        assert_eq!(ret.error, None);
+        let validator_changes = ret.validator_changes.unwrap();
+
+        let mut state = create_state_v2(Some(ret.new_state));
+        let mut vp = NetworkAccount::vp(&mut state.ctx.gas_meter);
+        let vp_powers: Vec<_> = (0..vp.length())
+            .map(|i| {
+                let pool: Pool = vp.pool_at(i).unwrap().try_into().unwrap();
+                (pool.operator, pool.power)
+            })
+            .collect();
+        assert_eq!(vp_powers.len(), TEST_MAX_VALIDATOR_SET_SIZE as usize);
+        assert_eq!(validator_changes.power_updates, vp_powers);
+        if epoch == 0 {
+            assert_eq!(validator_changes.new_validator_set, vp_powers);
+        } else {
+            assert!(validator_changes.new_validator_set.is_empty());
+        }
+        assert!(validator_changes.remove_validator_set.is_empty());
+
+        ws = Some(state.ctx.into_ws_cache().commit_to_world_state());
+    }
+}
+
+// Prepare: nvp with one more pool than the maximum validator set size
+// Transition V1 to V2 is rejected, as the World State is malformed
+#[test]
+fn test_transition_v1_to_v2_malformed_world_state() {
+    let fixture = TestFixture::new();
+    let mut state = create_state_v1(Some(fixture.ws()));
+    create_full_pools_in_nvp(&mut state, false, false);
+    let ws = state.ctx.into_ws_cache().commit_to_world_state();
+    assert!(protocol::within_migration_bounds(&ws));
+
+    let mut state = create_state_v1(Some(ws));
+    NetworkAccountSized::<_, { TEST_MAX_VALIDATOR_SET_SIZE + 1 }, { TEST_MAX_STAKES_PER_POOL }>::nvp(
+        &mut state.ctx.gas_meter,
+    )
+    .insert(PoolKey {
+        operator: [200u8; 32],
+        power: 1,
+    })
+    .unwrap();
+    let ws = state.ctx.into_ws_cache().commit_to_world_state();
+    assert!(!protocol::within_migration_bounds(&ws));
+
+    let mut tx = create_tx_v1(ACCOUNT_A);
+    tx.commands = vec![Command::NextEpoch];
+    let result = Runtime::new().transition_v1_to_v2(ws, tx, create_bd());
+    assert_eq!(result.error, Some(TransitionError::MalformedWorldState));
+    assert!(result.new_state.is_none());
+}
@@ -145,7 +145,7 @@ fn test_create_pool_set_policy() {
             commission_rate: 2,
         })],
     );
-    assert_eq!(ret.error, Some(TransitionError::InvalidPoolPolicy));
+    assert_eq!(ret.error, Some(TransitionError::CommissionRateUnchanged));
 
     assert_eq!(extract_gas_used(&ret), 4010);
 }
@@ -369,7 +369,7 @@ fn test_create_deposit_set_policy() {
     ];
     set_tx_v1(&mut state, ACCOUNT_B, 1, &commands);
     let ret = execute_commands_v1(state, commands);
-    assert_eq!(ret.error, Some(TransitionError::InvalidDepositPolicy));
+    assert_eq!(ret.error, Some(TransitionError::AutoStakeRewardsUnchanged));
     assert_eq!(extract_gas_used(&ret), 5290);
 }
 
@@ -662,7 +662,7 @@ fn test_create_pool_set_policy_v2() {
             commission_rate: 2,
         })],
     );
-    assert_eq!(ret.error, Some(TransitionError::InvalidPoolPolicy));
+    assert_eq!(ret.error, Some(TransitionError::CommissionRateUnchanged));
     let rcp = ret.receipt.as_ref().expect("Receipt expected");
     assert!(verify_receipt_content_v2(
         rcp,
@@ -935,7 +935,7 @@ fn test_create_deposit_set_policy_v2() {
         ExitCodeV2::Error,
         0
     ));
-    assert_eq!(ret.error, Some(TransitionError::InvalidDepositPolicy));
+    assert_eq!(ret.error, Some(TransitionError::AutoStakeRewardsUnchanged));
 }
 
 // Prepare: pool (account a) in world state
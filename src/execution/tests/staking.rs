@@ -10,12 +10,12 @@ use pchain_types::{
         WithdrawDepositInput,
     },
 };
-use pchain_world_state::{NetworkAccount, Pool, Stake, StakeValue};
+use pchain_world_state::{NetworkAccount, Pool, PoolKey, Stake, StakeValue, V2};
 
 use crate::{
     execution::execute_commands::{execute_commands_v1, execute_commands_v2},
     execution::execute_next_epoch::{execute_next_epoch_v1, execute_next_epoch_v2},
-    TransitionError,
+    Runtime, TransitionError,
 };
 
 use super::test_utils::*;
@@ -2372,6 +2372,132 @@ fn test_stake_deposit_delegated_stakes_nvp_insert_v2() {
     );
 }
 
+// Prepare: set maximum number of pools in world state, the minimum pool (account a) has power 100_000.
+// Prepare: pools not inside nvp, with deposits (account c) of 100_000
+// Commands (account c): Stake Deposit (to increase the power of the pool to exactly the minimum power in nvp)
+// On equal power, the pool with the greater operator address is kept in nvp:
+// - pool (account b) has a greater address than account a, and displaces it
+// - pool [1, 0, 0, ...] has a smaller address than account a, and is not inserted
+#[test]
+fn test_stake_deposit_delegated_stakes_nvp_insert_equal_power_v2() {
+    let smaller_operator = {
+        let mut address = [0u8; 32];
+        address[0] = 1;
+        address
+    };
+    for (operator, displaces_incumbent) in [(ACCOUNT_B, true), (smaller_operator, false)] {
+        let fixture = TestFixture::new();
+        let mut state = create_state_v2(Some(fixture.ws()));
+        create_full_pools_in_nvp(&mut state, false, false);
+        let mut pool = NetworkAccount::pools(&mut state.ctx.gas_meter, operator);
+        pool.set_operator(operator);
+        pool.set_commission_rate(1);
+        pool.set_power(0);
+        pool.set_operator_stake(None);
+        let mut deposit = NetworkAccount::deposits(&mut state.ctx.gas_meter, operator, ACCOUNT_C);
+        deposit.set_balance(100_000);
+        deposit.set_auto_stake_rewards(false);
+
+        let ws = state.ctx.into_ws_cache().commit_to_world_state();
+        let mut state = create_state_v2(Some(ws));
+        let min_pool_in_nvp = NetworkAccount::nvp(&mut state.ctx.gas_meter)
+            .get(0)
+            .unwrap();
+        assert_eq!(
+            (min_pool_in_nvp.operator, min_pool_in_nvp.power),
+            (ACCOUNT_A, 100_000)
+        );
+
+        let commands = vec![Command::StakeDeposit(StakeDepositInput {
+            operator,
+            max_amount: 100_000,
+        })];
+        set_tx_v2(&mut state, ACCOUNT_C, 0, &commands);
+        let ret = execute_commands_v2(state, commands);
+        assert_eq!(ret.error, None);
+
+        let mut state = create_state_v2(Some(ret.new_state));
+        assert_eq!(
+            NetworkAccount::nvp(&mut state.ctx.gas_meter).length(),
+            TEST_MAX_VALIDATOR_SET_SIZE as u32
+        );
+        assert_eq!(
+            NetworkAccount::nvp(&mut state.ctx.gas_meter)
+                .get_by(&operator)
+                .is_some(),
+            displaces_incumbent
+        );
+        assert_eq!(
+            NetworkAccount::nvp(&mut state.ctx.gas_meter)
+                .get_by(&ACCOUNT_A)
+                .is_some(),
+            !displaces_incumbent
+        );
+        // the pool's power is updated regardless of its membership in nvp
+        assert_eq!(
+            NetworkAccount::pools(&mut state.ctx.gas_meter, operator).power(),
+            Some(100_000)
+        );
+    }
+}
+
+// Prepare: set maximum number of pools in world state, where the power of one pool (the incumbent) is reduced to zero
+// Commands (account b): Create Pool (which has zero power, equal to the minimum power in nvp)
+// On equal power, the pool with the greater operator address is kept in nvp:
+// - pool (account b) has a greater address than the incumbent [1, 1, 1, ...], and displaces it
+// - pool (account b) has a smaller address than the incumbent [5, 1, 1, ...], and is not inserted
+#[test]
+fn test_create_pool_nvp_insert_equal_power_v2() {
+    for (incumbent_index, displaces_incumbent) in [(1, true), (5, false)] {
+        let fixture = TestFixture::new();
+        let mut state = create_state_v2(Some(fixture.ws()));
+        create_full_pools_in_nvp(&mut state, false, false);
+        let (incumbent, _, _) = init_setup_pool_power(incumbent_index);
+        let mut pool = NetworkAccount::pools(&mut state.ctx.gas_meter, incumbent);
+        pool.set_power(0);
+        pool.set_operator_stake(None);
+        NetworkAccount::nvp(&mut state.ctx.gas_meter).change_key(PoolKey {
+            operator: incumbent,
+            power: 0,
+        });
+
+        let ws = state.ctx.into_ws_cache().commit_to_world_state();
+        let mut state = create_state_v2(Some(ws));
+        let min_pool_in_nvp = NetworkAccount::nvp(&mut state.ctx.gas_meter)
+            .get(0)
+            .unwrap();
+        assert_eq!(
+            (min_pool_in_nvp.operator, min_pool_in_nvp.power),
+            (incumbent, 0)
+        );
+
+        let commands = vec![Command::CreatePool(CreatePoolInput { commission_rate: 1 })];
+        set_tx_v2(&mut state, ACCOUNT_B, 0, &commands);
+        let ret = execute_commands_v2(state, commands);
+        assert_eq!(ret.error, None);
+
+        let mut state = create_state_v2(Some(ret.new_state));
+        assert_eq!(
+            NetworkAccount::nvp(&mut state.ctx.gas_meter).length(),
+            TEST_MAX_VALIDATOR_SET_SIZE as u32
+        );
+        assert_eq!(
+            NetworkAccount::nvp(&mut state.ctx.gas_meter)
+                .get_by(&ACCOUNT_B)
+                .is_some(),
+            displaces_incumbent
+        );
+        assert_eq!(
+            NetworkAccount::nvp(&mut state.ctx.gas_meter)
+                .get_by(&incumbent)
+                .is_some(),
+            !displaces_incumbent
+        );
+        // the pool is created regardless of its membership in nvp
+        assert!(NetworkAccount::pools(&mut state.ctx.gas_meter, ACCOUNT_B).exists());
+    }
+}
+
 // // Prepare: pool (account a), with maximum number of stakes in world state
 // // Prepare: deposits (account c) to pool (account a)
 // // Commands (account c): Stake Deposit (to be included in delegated stakes)
@@ -2816,6 +2942,57 @@ fn test_stake_deposit_same_owner_existing_v2() {
     assert_eq!(delegated_stake.length(), 0);
 }
 
+// Prepare: pool (account a) in world state, with non-zero value of Operator Stake
+// Prepare: deposits (account a) to pool (account a)
+// Commands (account a): 10 x Stake Deposit, all reading the pool from the same transaction's cache
+#[test]
+fn test_stake_deposit_repeated_in_one_transaction_v2() {
+    let fixture = TestFixture::new();
+    let mut state = create_state_v2(Some(fixture.ws()));
+    let mut pool = NetworkAccount::pools(&mut state.ctx.gas_meter, ACCOUNT_A);
+    pool.set_operator(ACCOUNT_A);
+    pool.set_power(100_000);
+    pool.set_commission_rate(1);
+    pool.set_operator_stake(Some(Stake {
+        owner: ACCOUNT_A,
+        power: 80_000,
+    }));
+    let mut deposit = NetworkAccount::deposits(&mut state.ctx.gas_meter, ACCOUNT_A, ACCOUNT_A);
+    deposit.set_balance(100_000);
+    deposit.set_auto_stake_rewards(false);
+
+    let ws = state.ctx.into_ws_cache().commit_to_world_state();
+    let state = create_state_v2(Some(ws));
+    let ret = execute_commands_v2(
+        state,
+        vec![
+            Command::StakeDeposit(StakeDepositInput {
+                operator: ACCOUNT_A,
+                max_amount: 1_000,
+            });
+            10
+        ],
+    );
+    assert!(ret.error.is_none());
+    let receipt = ret.receipt.as_ref().expect("Receipt expected");
+    assert_eq!(receipt.command_receipts.len(), 10);
+    for command_receipt in &receipt.command_receipts {
+        if let CommandReceiptV2::StakeDeposit(cr) = command_receipt {
+            assert_eq!(cr.exit_code, ExitCodeV2::Ok);
+            assert_eq!(cr.amount_staked, 1_000);
+        } else {
+            panic!("Stake deposit command receipt expected");
+        }
+    }
+
+    let mut state = create_state_v2(Some(ret.new_state));
+
+    let mut pool = NetworkAccount::pools(&mut state.ctx.gas_meter, ACCOUNT_A);
+    let operator_state = pool.operator_stake().unwrap().unwrap();
+    assert_eq!(operator_state.power, 90_000);
+    assert_eq!(pool.power().unwrap(), 110_000);
+}
+
 // Prepare: pool (account a) in world state, with delegated stakes of account b
 // Prepare: deposits (account b) to pool (account a)
 // Commands (account b): Unstake Deposit
@@ -4368,3 +4545,290 @@ fn test_withdrawal_deposit_bounded_by_pvp_v2() {
 
     assert_eq!(owner_balance_before, owner_balance_after + 471_180 - 10_000);
 }
+
+// Prepare: pool (account a) in world state, with delegated stakes of account b
+// Prepare: deposits (account b) to pool (account a), with the given balance
+// Prepare: stakes of account b in vp and pvp with the given powers, where the pool is absent from a set if the power is 0
+// Commands (account b): Withdraw Deposit (of as much as possible)
+fn withdraw_deposit_locked_by_vp_and_pvp(
+    deposit_balance: u64,
+    vp_stake_power: u64,
+    pvp_stake_power: u64,
+) -> (Option<TransitionError>, u64, u64) {
+    let fixture = TestFixture::new();
+    let mut state = create_state_v2(Some(fixture.ws()));
+    let mut pool = NetworkAccount::pools(&mut state.ctx.gas_meter, ACCOUNT_A);
+    pool.set_operator(ACCOUNT_A);
+    pool.set_power(deposit_balance);
+    pool.set_commission_rate(1);
+    pool.set_operator_stake(None);
+    let mut deposit = NetworkAccount::deposits(&mut state.ctx.gas_meter, ACCOUNT_A, ACCOUNT_B);
+    deposit.set_balance(deposit_balance);
+    deposit.set_auto_stake_rewards(false);
+    NetworkAccount::pools(&mut state.ctx.gas_meter, ACCOUNT_A)
+        .delegated_stakes()
+        .insert(StakeValue::new(Stake {
+            owner: ACCOUNT_B,
+            power: deposit_balance,
+        }))
+        .unwrap();
+    for (power, is_vp) in [(pvp_stake_power, false), (vp_stake_power, true)] {
+        if power == 0 {
+            continue;
+        }
+        let pool = Pool {
+            operator: ACCOUNT_A,
+            commission_rate: 1,
+            power,
+            operator_stake: None,
+        };
+        let stakes = vec![StakeValue::new(Stake {
+            owner: ACCOUNT_B,
+            power,
+        })];
+        if is_vp {
+            NetworkAccount::vp(&mut state.ctx.gas_meter)
+                .push(pool, stakes)
+                .unwrap();
+        } else {
+            NetworkAccount::pvp(&mut state.ctx.gas_meter)
+                .push(pool, stakes)
+                .unwrap();
+        }
+    }
+
+    let ws = state.ctx.into_ws_cache().commit_to_world_state();
+    let mut state = create_state_v2(Some(ws));
+    let commands = vec![Command::WithdrawDeposit(WithdrawDepositInput {
+        operator: ACCOUNT_A,
+        max_amount: u64::MAX,
+    })];
+    set_tx_v2(&mut state, ACCOUNT_B, 0, &commands);
+    let ret = execute_commands_v2(state, commands);
+
+    let amount_withdrawn = match ret.receipt.as_ref().unwrap().command_receipts.last() {
+        Some(CommandReceiptV2::WithdrawDeposit(cr)) => cr.amount_withdrawn,
+        _ => panic!("Withdraw deposit command receipt expected"),
+    };
+    let mut state = create_state_v2(Some(ret.new_state));
+    let new_deposit_balance =
+        NetworkAccount::deposits(&mut state.ctx.gas_meter, ACCOUNT_A, ACCOUNT_B)
+            .balance()
+            .unwrap();
+    (ret.error, amount_withdrawn, new_deposit_balance)
+}
+
+// Deposit equals the stake in vp, but is below the stake in pvp: nothing can be withdrawn
+#[test]
+fn test_withdrawal_deposit_equals_vp_below_pvp_v2() {
+    assert_eq!(
+        withdraw_deposit_locked_by_vp_and_pvp(80_000, 80_000, 90_000),
+        (Some(TransitionError::InvalidStakeAmount), 0, 80_000)
+    );
+}
+
+// Deposit equals the stake in pvp, but is below the stake in vp: nothing can be withdrawn
+#[test]
+fn test_withdrawal_deposit_equals_pvp_below_vp_v2() {
+    assert_eq!(
+        withdraw_deposit_locked_by_vp_and_pvp(80_000, 90_000, 80_000),
+        (Some(TransitionError::InvalidStakeAmount), 0, 80_000)
+    );
+}
+
+// Deposit exceeds the greater of the stakes in vp and pvp by one: only that one can be withdrawn
+#[test]
+fn test_withdrawal_deposit_above_vp_and_pvp_v2() {
+    assert_eq!(
+        withdraw_deposit_locked_by_vp_and_pvp(90_001, 80_000, 90_000),
+        (None, 1, 90_000)
+    );
+    assert_eq!(
+        withdraw_deposit_locked_by_vp_and_pvp(90_001, 90_000, 80_000),
+        (None, 1, 90_000)
+    );
+}
+
+// Pool has no stakes in pvp, e.g. before its first epoch, and the stake in vp locks the whole deposit: nothing can be
+// withdrawn
+#[test]
+fn test_withdrawal_deposit_no_pvp_locked_equals_deposit_v2() {
+    assert_eq!(
+        withdraw_deposit_locked_by_vp_and_pvp(80_000, 80_000, 0),
+        (Some(TransitionError::InvalidStakeAmount), 0, 80_000)
+    );
+}
+
+// Pool has no stakes in pvp, e.g. before its first epoch, and the stake in vp locks part of the deposit: the withdrawal
+// is clamped to the unlocked part
+#[test]
+fn test_withdrawal_deposit_no_pvp_locked_below_deposit_v2() {
+    assert_eq!(
+        withdraw_deposit_locked_by_vp_and_pvp(80_000, 50_000, 0),
+        (None, 30_000, 50_000)
+    );
+}
+
+// Prepare: deposits (account b) to pool (account a), without stakes
+// Commands (account b): Withdraw Deposit (leaving a balance of 1), Withdraw Deposit (of the remaining balance)
+#[test]
+fn test_withdrawal_deposit_leaves_dust_v2() {
+    let fixture = TestFixture::new();
+    let mut state = create_state_v2(Some(fixture.ws()));
+    let mut deposit = NetworkAccount::deposits(&mut state.ctx.gas_meter, ACCOUNT_A, ACCOUNT_B);
+    deposit.set_balance(100_000);
+    deposit.set_auto_stake_rewards(false);
+    let ws = state.ctx.into_ws_cache().commit_to_world_state();
+
+    // there is no minimum deposit balance, so a withdrawal may leave a tiny balance behind
+    let mut state = create_state_v2(Some(ws));
+    let commands = vec![Command::WithdrawDeposit(WithdrawDepositInput {
+        operator: ACCOUNT_A,
+        max_amount: 99_999,
+    })];
+    set_tx_v2(&mut state, ACCOUNT_B, 0, &commands);
+    let ret = execute_commands_v2(state, commands);
+    assert!(ret.error.is_none());
+
+    let mut state = create_state_v2(Some(ret.new_state));
+    assert_eq!(
+        NetworkAccount::deposits(&mut state.ctx.gas_meter, ACCOUNT_A, ACCOUNT_B)
+            .balance()
+            .unwrap(),
+        1
+    );
+
+    // the remaining balance is not stranded, and withdrawing it deletes the deposit
+    let commands = vec![Command::WithdrawDeposit(WithdrawDepositInput {
+        operator: ACCOUNT_A,
+        max_amount: u64::MAX,
+    })];
+    set_tx_v2(&mut state, ACCOUNT_B, 1, &commands);
+    let ret = execute_commands_v2(state, commands);
+    assert!(ret.error.is_none());
+    if let Some(CommandReceiptV2::WithdrawDeposit(cr)) =
+        ret.receipt.as_ref().unwrap().command_receipts.last()
+    {
+        assert_eq!(cr.amount_withdrawn, 1);
+    } else {
+        panic!("Withdraw deposit command receipt expected");
+    }
+
+    let mut state = create_state_v2(Some(ret.new_state));
+    assert!(!NetworkAccount::deposits(&mut state.ctx.gas_meter, ACCOUNT_A, ACCOUNT_B).exists());
+}
+
+// Prepare: pool (account a), in nvp
+// Commands (account a): Create Deposit (to pool a), Delete Pool
+// Commands (account a): Withdraw Deposit
+// The pool is deleted, while the deposit created earlier in the transaction stays and can be withdrawn.
+#[test]
+fn test_create_deposit_then_delete_pool_v2() {
+    let fixture = TestFixture::new();
+    let mut state = create_state_v2(Some(fixture.ws()));
+    let mut pool = NetworkAccount::pools(&mut state.ctx.gas_meter, ACCOUNT_A);
+    pool.set_operator(ACCOUNT_A);
+    pool.set_power(0);
+    pool.set_commission_rate(1);
+    pool.set_operator_stake(None);
+    NetworkAccount::nvp(&mut state.ctx.gas_meter)
+        .insert(PoolKey {
+            operator: ACCOUNT_A,
+            power: 0,
+        })
+        .unwrap();
+    let ws = state.ctx.into_ws_cache().commit_to_world_state();
+
+    let mut state = create_state_v2(Some(ws));
+    let commands = vec![
+        Command::CreateDeposit(CreateDepositInput {
+            operator: ACCOUNT_A,
+            balance: 50_000,
+            auto_stake_rewards: false,
+        }),
+        Command::DeletePool,
+    ];
+    set_tx_v2(&mut state, ACCOUNT_A, 0, &commands);
+    let ret = execute_commands_v2(state, commands);
+    assert_eq!(ret.error, None);
+    assert_eq!(ret.receipt.as_ref().unwrap().exit_code, ExitCodeV2::Ok);
+
+    let mut state = create_state_v2(Some(ret.new_state));
+    assert!(!NetworkAccount::pools(&mut state.ctx.gas_meter, ACCOUNT_A).exists());
+    assert!(NetworkAccount::nvp(&mut state.ctx.gas_meter)
+        .get_by(&ACCOUNT_A)
+        .is_none());
+    assert_eq!(
+        NetworkAccount::deposits(&mut state.ctx.gas_meter, ACCOUNT_A, ACCOUNT_A).balance(),
+        Some(50_000)
+    );
+
+    let commands = vec![Command::WithdrawDeposit(WithdrawDepositInput {
+        operator: ACCOUNT_A,
+        max_amount: 50_000,
+    })];
+    set_tx_v2(&mut state, ACCOUNT_A, 1, &commands);
+    let ret = execute_commands_v2(state, commands);
+    assert_eq!(ret.error, None);
+    if let Some(CommandReceiptV2::WithdrawDeposit(cr)) =
+        ret.receipt.as_ref().unwrap().command_receipts.last()
+    {
+        assert_eq!(cr.amount_withdrawn, 50_000);
+    } else {
+        panic!("Withdraw deposit command receipt expected");
+    }
+
+    let mut state = create_state_v2(Some(ret.new_state));
+    assert!(!NetworkAccount::deposits(&mut state.ctx.gas_meter, ACCOUNT_A, ACCOUNT_A).exists());
+}
+
+// Commands (account a): Create Pool, Create Deposit
+// Commands (account b): Create Deposit, Stake Deposit
+// A scripted staking scenario with interleaved signers, each transaction carrying the next nonce of its signer
+#[test]
+fn test_staking_sequence_v2() {
+    let txs = TxSequenceBuilder::new()
+        .add(
+            ACCOUNT_A,
+            vec![Command::CreatePool(CreatePoolInput { commission_rate: 1 })],
+        )
+        .add(
+            ACCOUNT_B,
+            vec![Command::CreateDeposit(CreateDepositInput {
+                operator: ACCOUNT_A,
+                balance: 100_000,
+                auto_stake_rewards: false,
+            })],
+        )
+        .add(
+            ACCOUNT_A,
+            vec![Command::CreateDeposit(CreateDepositInput {
+                operator: ACCOUNT_A,
+                balance: 50_000,
+                auto_stake_rewards: false,
+            })],
+        )
+        .add(
+            ACCOUNT_B,
+            vec![Command::StakeDeposit(StakeDepositInput {
+                operator: ACCOUNT_A,
+                max_amount: 100_000,
+            })],
+        )
+        .build_v2();
+    assert_eq!(
+        txs.iter().map(|tx| tx.nonce).collect::<Vec<_>>(),
+        vec![0, 0, 1, 1]
+    );
+
+    let fixture = TestFixture::new();
+    let mut ws = fixture.ws::<V2>();
+    for tx in txs {
+        let result = Runtime::new().transition_v2(ws, tx, create_bd());
+        assert_eq!(result.error, None);
+        assert_eq!(result.receipt.unwrap().exit_code, ExitCodeV2::Ok);
+        ws = result.new_state;
+    }
+    assert_eq!(ws.account_trie().nonce(&ACCOUNT_A).unwrap(), 2);
+    assert_eq!(ws.account_trie().nonce(&ACCOUNT_B).unwrap(), 2);
+}
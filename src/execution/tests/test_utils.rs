@@ -147,6 +147,46 @@ pub(crate) fn create_tx_v2(signer: PublicAddress) -> TransactionV2 {
     }
 }
 
+/// Builds a scripted sequence of transactions, assigning each signer's nonces in order.
+///
+/// ```ignore
+/// let txs = TxSequenceBuilder::new()
+///     .add(ACCOUNT_A, vec![create_pool])
+///     .add(ACCOUNT_B, vec![create_deposit])
+///     .add(ACCOUNT_B, vec![stake_deposit]) // nonce 1
+///     .build_v2();
+/// ```
+#[derive(Default)]
+pub(crate) struct TxSequenceBuilder {
+    next_nonces: HashMap<PublicAddress, u64>,
+    txs: Vec<(PublicAddress, u64, Vec<Command>)>,
+}
+
+impl TxSequenceBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a transaction by `signer` with the next nonce of the signer.
+    pub fn add(mut self, signer: PublicAddress, commands: Vec<Command>) -> Self {
+        let next_nonce = self.next_nonces.entry(signer).or_insert(0);
+        self.txs.push((signer, *next_nonce, commands));
+        *next_nonce += 1;
+        self
+    }
+
+    pub fn build_v2(self) -> Vec<TransactionV2> {
+        self.txs
+            .into_iter()
+            .map(|(signer, nonce, commands)| TransactionV2 {
+                nonce,
+                commands,
+                ..create_tx_v2(signer)
+            })
+            .collect()
+    }
+}
+
 pub(crate) fn create_bd() -> BlockchainParams {
     let mut validator_performance = ValidatorPerformance::default();
     validator_performance.blocks_per_epoch = TEST_MAX_VALIDATOR_SET_SIZE as u32;
@@ -513,3 +553,56 @@ macro_rules! try_i64 {
 }
 
 pub(crate) use try_i64;
+
+/// Subscriber which records the name and fields of every span, to assert the spans emitted during execution.
+#[cfg(feature = "tracing")]
+#[derive(Clone, Default)]
+pub(crate) struct SpanRecorder {
+    pub spans: std::sync::Arc<std::sync::Mutex<Vec<RecordedSpan>>>,
+}
+
+#[cfg(feature = "tracing")]
+#[derive(Debug, Default)]
+pub(crate) struct RecordedSpan {
+    pub name: &'static str,
+    pub fields: HashMap<&'static str, String>,
+}
+
+#[cfg(feature = "tracing")]
+impl tracing::field::Visit for RecordedSpan {
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        self.fields.insert(field.name(), format!("{:?}", value));
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl tracing::Subscriber for SpanRecorder {
+    fn enabled(&self, _metadata: &tracing::Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+        let mut spans = self.spans.lock().unwrap();
+        let mut recorded = RecordedSpan {
+            name: span.metadata().name(),
+            ..Default::default()
+        };
+        span.record(&mut recorded);
+        spans.push(recorded);
+        // span ids must be non-zero
+        tracing::span::Id::from_u64(spans.len() as u64)
+    }
+
+    fn record(&self, span: &tracing::span::Id, values: &tracing::span::Record<'_>) {
+        let mut spans = self.spans.lock().unwrap();
+        values.record(&mut spans[span.into_u64() as usize - 1]);
+    }
+
+    fn record_follows_from(&self, _span: &tracing::span::Id, _follows: &tracing::span::Id) {}
+
+    fn event(&self, _event: &tracing::Event<'_>) {}
+
+    fn enter(&self, _span: &tracing::span::Id) {}
+
+    fn exit(&self, _span: &tracing::span::Id) {}
+}
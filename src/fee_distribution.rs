@@ -0,0 +1,60 @@
+/*
+    Copyright © 2023, ParallelChain Lab
+    Licensed under the Apache License, Version 2.0: http://www.apache.org/licenses/LICENSE-2.0
+*/
+
+//! Pluggable policy for distributing the fees of a transaction in the Charge phase.
+//!
+//! A [FeeDistributor] is set on the [Runtime](crate::Runtime) with
+//! [set_fee_distributor](crate::Runtime::set_fee_distributor). In the Charge phase of every transaction, it is given
+//! the base fee and priority fee paid by the signer for the gas used, and returns the credits to apply to accounts.
+//! Any part of the fees which is not credited is burned.
+//!
+//! If no distributor is set, the [DefaultFeeDistributor] is used.
+
+use pchain_types::cryptography::PublicAddress;
+
+use crate::rewards_formulas::{TREASURY_CUT_OF_BASE_FEE_DENOM, TREASURY_CUT_OF_BASE_FEE_NUM};
+
+/// Computes how the fees of a transaction are credited to accounts.
+///
+/// Implementations must be deterministic: the same arguments must always result in the same credits.
+pub trait FeeDistributor: Send + Sync {
+    /// Returns the credits, as pairs of account address and amount in grays, for a transaction which paid
+    /// `base_fee_amount` in base fee and `priority_fee_amount` in priority fee for the gas it used. Credits are applied
+    /// in the order returned, and an address may appear more than once.
+    ///
+    /// The credits should not sum to more than `base_fee_amount + priority_fee_amount`, and the remainder is burned.
+    /// Credits beyond the fees are not applied: the credit which exceeds them is reduced to what is left of the fees,
+    /// and the credits after it to zero.
+    fn distribute(
+        &self,
+        base_fee_amount: u64,
+        priority_fee_amount: u64,
+        proposer: PublicAddress,
+        treasury: PublicAddress,
+    ) -> Vec<(PublicAddress, u64)>;
+}
+
+/// Credits the priority fee to the proposer, and the Treasury's cut of the base fee
+/// ([TREASURY_CUT_OF_BASE_FEE_NUM] / [TREASURY_CUT_OF_BASE_FEE_DENOM]) to the Treasury. The rest of the base fee is
+/// burned.
+pub struct DefaultFeeDistributor;
+
+impl FeeDistributor for DefaultFeeDistributor {
+    fn distribute(
+        &self,
+        base_fee_amount: u64,
+        priority_fee_amount: u64,
+        proposer: PublicAddress,
+        treasury: PublicAddress,
+    ) -> Vec<(PublicAddress, u64)> {
+        vec![
+            (proposer, priority_fee_amount),
+            (
+                treasury,
+                (base_fee_amount * TREASURY_CUT_OF_BASE_FEE_NUM) / TREASURY_CUT_OF_BASE_FEE_DENOM,
+            ),
+        ]
+    }
+}
@@ -0,0 +1,93 @@
+/*
+    Copyright © 2023, ParallelChain Lab
+    Licensed under the Apache License, Version 2.0: http://www.apache.org/licenses/LICENSE-2.0
+*/
+
+//! Attribution of the gas used by a command to the kinds of operations it performed.
+//!
+//! The [GasMeter](crate::gas::GasMeter) and the [HostFuncGasMeter](crate::gas::HostFuncGasMeter) tally the gas of
+//! every chargeable operation by kind while a command executes. When the command completes, the tallies are clamped
+//! to the gas used in the command receipt, and the gas which is not attributed to any operation, i.e. the gas of Wasm
+//! opcodes, is the remainder. A [GasBreakdown] therefore always adds up to the `gas_used` of its command receipt.
+//!
+//! The transaction inclusion cost is not part of any command, so it is not part of any breakdown.
+
+/// Gas used by a command, by the kind of operation that used it. See the [module-level documentation](self).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GasBreakdown {
+    /// Gas for reading the World State, e.g. balances, storage data and contract code, including reads by host
+    /// functions.
+    pub read_gas: u64,
+    /// Gas for writing the World State and the command receipt (logs and return values), net of refunds for removed
+    /// data, including writes by host functions.
+    pub write_gas: u64,
+    /// Gas for host functions which operate within the Wasm environment: reading and writing Wasm linear memory, and
+    /// cryptographic functions.
+    pub wasm_gas: u64,
+    /// Gas for executing Wasm opcodes, and any other gas of contract calls, e.g. the adjustment by a
+    /// [contract gas multiplier](crate::Runtime::set_contract_gas_multipliers).
+    pub opcode_gas: u64,
+}
+
+impl GasBreakdown {
+    /// Sum of the gas of all kinds of operations.
+    pub fn total(&self) -> u64 {
+        self.read_gas
+            .saturating_add(self.write_gas)
+            .saturating_add(self.wasm_gas)
+            .saturating_add(self.opcode_gas)
+    }
+
+    /// Adds the gas of `other` to this breakdown, e.g. to fold a deferred command into its parent command.
+    pub(crate) fn add(&mut self, other: &GasBreakdown) {
+        self.read_gas = self.read_gas.saturating_add(other.read_gas);
+        self.write_gas = self.write_gas.saturating_add(other.write_gas);
+        self.wasm_gas = self.wasm_gas.saturating_add(other.wasm_gas);
+        self.opcode_gas = self.opcode_gas.saturating_add(other.opcode_gas);
+    }
+
+    /// Clamps the tallied gas of operations to `gas_used`, which is less than the tally if the command exhausted its gas
+    /// limit, and attributes the remainder to opcodes.
+    pub(crate) fn clamp_to(tally: &GasBreakdown, gas_used: u64) -> GasBreakdown {
+        let mut remaining = gas_used;
+        let mut take = |gas: u64| {
+            let gas = std::cmp::min(gas, remaining);
+            remaining -= gas;
+            gas
+        };
+        let read_gas = take(tally.read_gas);
+        let wasm_gas = take(tally.wasm_gas);
+        let write_gas = take(tally.write_gas);
+        GasBreakdown {
+            read_gas,
+            write_gas,
+            wasm_gas,
+            opcode_gas: remaining,
+        }
+    }
+}
+
+#[test]
+fn test_clamp_to() {
+    let tally = GasBreakdown {
+        read_gas: 10,
+        write_gas: 20,
+        wasm_gas: 5,
+        opcode_gas: 0,
+    };
+    let breakdown = GasBreakdown::clamp_to(&tally, 50);
+    assert_eq!(breakdown.opcode_gas, 15);
+    assert_eq!(breakdown.total(), 50);
+
+    // the gas limit makes gas used less than the tally
+    let breakdown = GasBreakdown::clamp_to(&tally, 25);
+    assert_eq!(
+        breakdown,
+        GasBreakdown {
+            read_gas: 10,
+            write_gas: 10,
+            wasm_gas: 5,
+            opcode_gas: 0,
+        }
+    );
+}
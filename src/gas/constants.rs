@@ -9,6 +9,9 @@
 //! The constants in this module are based on the specification described in the gas section of
 //! [Parallelchain Mainnet Protocol](https://github.com/parallelchain-io/parallelchain-protocol).
 //!
+//! The costs defined here make up [GasSchedule::MAINNET], the default [GasSchedule] through which operations are
+//! charged. The functions here compute costs with the Mainnet schedule.
+//!
 //! The table below lists the protocol-defined equivalents of the constants and formulas defined here, where applicable.
 //! Do note that higher-level operation-specific formulas are defined directly
 //! within the [operations](crate::gas::operations) module.
@@ -36,6 +39,15 @@
 //! |G_txincl               | [tx_inclusion_cost_v1] |
 //! |G_txinclv2             | [tx_inclusion_cost_v2] |
 //!
+//! ## Transaction signatures
+//!
+//! The Runtime does not verify the signature of a transaction. Transactions are expected to be verified before they
+//! are passed to the transition function, e.g. when they are received into a mempool or a block, and a transaction
+//! with an invalid signature must never be included in a block. Hence there is no gas cost for signature verification:
+//! the signature is charged only as 64 bytes of transaction storage in the [inclusion cost](tx_inclusion_cost_v2).
+//! [CRYPTO_ED25519_PER_BYTE] applies only to signatures verified by contracts through the
+//! `verify_ed25519_signature` host function.
+//!
 
 /* ↓↓↓ Gas Costs for Wasm opcode execution ↓↓↓ */
 
@@ -43,6 +55,8 @@ use wasmer::wasmparser::Operator;
 
 use crate::types::CommandKind;
 
+use super::GasSchedule;
+
 /// wasm_opcode_gas_schedule maps between a Wasm Operator to the cost of executing it.
 /// It specifies the gas cost of executing every legal opcode for the smart contract method calls.
 pub fn wasm_opcode_gas_schedule(operator: &Operator) -> u64 {
@@ -195,20 +209,12 @@ pub const WASM_BYTE_CODE_PER_BYTE_COST: u64 = 3;
 
 /// Cost of reading `len` bytes from Wasm linear memory.
 pub const fn wasm_memory_read_cost(len: usize) -> u64 {
-    let cost = ceil_div_8(len as u64).saturating_mul(WASM_MEMORY_READ_PER64_BITS_COST);
-    if cost == 0 {
-        return 1;
-    } // = max(cost, 1) to make sure charging for a non-zero cost
-    cost
+    GasSchedule::MAINNET.wasm_memory_read_cost(len)
 }
 
 /// Cost of writing `len` bytes into Wasm linear memory.
 pub const fn wasm_memory_write_cost(len: usize) -> u64 {
-    let cost = ceil_div_8(len as u64).saturating_mul(WASM_MEMORY_WRITE_PER64_BITS_COST);
-    if cost == 0 {
-        return 1;
-    } // = max(cost, 1) to make sure charging for a non-zero cost
-    cost
+    GasSchedule::MAINNET.wasm_memory_write_cost(len)
 }
 
 /// Ceiling of the value after dividing by 8.
@@ -242,26 +248,7 @@ pub const MIN_CMDRECP_SIZE_V2_EXTENDED: u64 = 17;
 ///     - proposer's balance
 ///     - treasury's balance
 pub fn tx_inclusion_cost_v1(tx_size: usize, commands: &Vec<CommandKind>) -> u64 {
-    // (1) Transaction storage size
-    let tx_size = tx_size as u64;
-    // (2) Minimum size of receipt
-    let min_receipt_size = minimum_receipt_size_v1(commands);
-    // (3) Cost for 5 read-write operations
-    let rw_key_cost = (
-        // Read cost
-        get_cost_traverse(ACCOUNT_TRIE_KEY_LENGTH)
-            .saturating_add(get_cost_read(8))
-            // Write cost
-            .saturating_add(set_cost_write_new_value(8))
-            .saturating_add(set_cost_rehash(ACCOUNT_TRIE_KEY_LENGTH))
-    )
-    .saturating_mul(5);
-
-    // Multiply by blockchain storage write cost and add the cost of 5 read-write operations.
-    tx_size
-        .saturating_add(min_receipt_size)
-        .saturating_mul(BLOCKCHAIN_WRITE_PER_BYTE_COST)
-        .saturating_add(rw_key_cost)
+    GasSchedule::MAINNET.tx_inclusion_cost_v1(tx_size, commands)
 }
 
 /// minimum cost for a V2 transaction to be included in the blockchain.
@@ -275,27 +262,12 @@ pub fn tx_inclusion_cost_v1(tx_size: usize, commands: &Vec<CommandKind>) -> u64
 ///     - proposer's balance
 ///     - treasury's balance
 ///
+/// It does not include a cost for verifying the signature of the transaction, which is not done by the Runtime (see
+/// [Transaction signatures](self#transaction-signatures)).
+///
 /// supersedes [V1](tx_inclusion_cost_v1)
 pub fn tx_inclusion_cost_v2(tx_size: usize, commands: &Vec<CommandKind>) -> u64 {
-    // (1) Transaction storage size
-    let tx_size = tx_size as u64;
-    // (2) Minimum size of receipt
-    let min_receipt_size = minimum_receipt_size_v2(commands);
-    // (3) Cost for 5 read-write operations
-    let rw_key_cost = (
-        // Read cost
-        get_cost_traverse(ACCOUNT_TRIE_KEY_LENGTH)
-            .saturating_add(get_cost_read(8))
-            // Write cost
-            .saturating_add(set_cost_write_new_value(8))
-            .saturating_add(set_cost_rehash(ACCOUNT_TRIE_KEY_LENGTH))
-    )
-    .saturating_mul(5);
-    // Multiply by blockchain storage write cost and add the cost of 5 read-write operations.
-    tx_size
-        .saturating_add(min_receipt_size)
-        .saturating_mul(BLOCKCHAIN_WRITE_PER_BYTE_COST)
-        .saturating_add(rw_key_cost)
+    GasSchedule::MAINNET.tx_inclusion_cost_v2(tx_size, commands)
 }
 
 /// Serialized size of a ReceiptV1 for `Vec<CommandKind>` containing minimum-sized command receipts.
@@ -314,24 +286,24 @@ pub fn minimum_receipt_size_v2(commands: &Vec<CommandKind>) -> u64 {
 
 /// blockchain_return_values_cost calculates the cost of writing byte data into the receipt.
 pub const fn blockchain_storage_cost(data_len: usize) -> u64 {
-    // data_len * C_txdata
-    (data_len as u64).saturating_mul(BLOCKCHAIN_WRITE_PER_BYTE_COST)
+    GasSchedule::MAINNET.blockchain_storage_cost(data_len)
 }
 
 /// blockchain_log_cost calculates the cost of writing a log into the receipt.
+///
+/// Topic bytes cost [LOG_TOPIC_PER_BYTE_COST] and value bytes cost [LOG_DATA_PER_BYTE_COST], in addition to the cost
+/// of reading the whole log from Wasm linear memory.
 pub const fn blockchain_log_cost(topic_len: usize, val_len: usize) -> u64 {
-    let topic_len = topic_len as u64;
-    let val_len = val_len as u64;
-    let log_len = topic_len.saturating_add(val_len);
-
-    // Ceil(l/8) * C_wasmread
-    (ceil_div_8(log_len).saturating_mul(WASM_MEMORY_READ_PER64_BITS_COST))
-        // t * C_sha256
-        .saturating_add(topic_len.saturating_mul(CRYPTO_SHA256_PER_BYTE))
-        // l X Z
-        .saturating_add(log_len.saturating_mul(BLOCKCHAIN_WRITE_PER_BYTE_COST))
+    GasSchedule::MAINNET.blockchain_log_cost(topic_len, val_len)
 }
 
+/// Cost of including 1 byte of a log's topic in a receipt. Topics are hashed so that logs can be indexed and
+/// queried by topic, so each byte costs [CRYPTO_SHA256_PER_BYTE] on top of [LOG_DATA_PER_BYTE_COST].
+pub const LOG_TOPIC_PER_BYTE_COST: u64 = CRYPTO_SHA256_PER_BYTE + LOG_DATA_PER_BYTE_COST;
+
+/// Cost of including 1 byte of a log's value in a receipt.
+pub const LOG_DATA_PER_BYTE_COST: u64 = BLOCKCHAIN_WRITE_PER_BYTE_COST;
+
 /* ↓↓↓ World state storage and access ↓↓↓ */
 
 /// The length of keys in the root world state MPT.
@@ -349,65 +321,66 @@ pub const MPT_REHASH_PER_BYTE_COST: u64 = 130;
 pub const MPT_WRITE_REFUND_PROPORTION: u64 = 50;
 /// Proportion of get cost which is discounted if the tuple contains a contract.
 pub const MPT_GET_CODE_DISCOUNT_PROPORTION: u64 = 50;
+/// Surcharge for setting a value on a storage key which holds no value, on top of the cost of writing the value, to
+/// price the growth of the World State. The Mainnet Protocol does not define one, so it is zero.
+pub const STORAGE_NEW_KEY_SURCHARGE: u64 = 0;
 /// Length of a Keccak256 hash.
 pub const KECCAK256_LENGTH: u64 = 32;
 
 /// calculates the cost of traversing between nodes in the MPT data structure,
 /// based on the length of the key. The cost is proportional to the number of nodes traversed.
 pub const fn get_cost_traverse(key_len: usize) -> u64 {
-    (key_len as u64).saturating_mul(MPT_TRAVERSE_PER_BYTE_COST)
+    GasSchedule::MAINNET.get_cost_traverse(key_len)
 }
 
 /// calculates the cost of reading the value stored at a particular MPT node
 pub const fn get_cost_read(value_len: usize) -> u64 {
-    (value_len as u64).saturating_mul(MPT_READ_PER_BYTE_COST)
+    GasSchedule::MAINNET.get_cost_read(value_len)
 }
 
 /// discount_code_read applies a discount to the read cost if the value read is contract code
 pub fn discount_code_read(code_read_cost: u64) -> u64 {
-    code_read_cost
-        .saturating_mul(MPT_GET_CODE_DISCOUNT_PROPORTION)
-        .saturating_div(100)
+    GasSchedule::MAINNET.discount_code_read(code_read_cost)
 }
 
 /// Set Cost (2): Cost for deleting the old value for a refund
 /// Note, Set Cost (1) is calculated under Get costs
-#[allow(clippy::double_comparisons)]
 pub const fn set_cost_delete_old_value(
     key_len: usize,
     old_val_len: usize,
     new_val_len: usize,
 ) -> u64 {
-    let old_val_len = old_val_len as u64; // (a)
-    let new_val_len = new_val_len as u64; // (b)
-    if (old_val_len > 0 || old_val_len == 0) && new_val_len > 0 {
-        // a * C_write * C_refund
-        old_val_len
-            .saturating_mul(MPT_WRITE_PER_BYTE_COST * MPT_WRITE_REFUND_PROPORTION)
-            .saturating_div(100)
-    } else if old_val_len > 0 && new_val_len == 0 {
-        // (k + a) * C_write * C_refund
-        ((key_len as u64).saturating_add(old_val_len))
-            .saturating_mul(MPT_WRITE_PER_BYTE_COST * MPT_WRITE_REFUND_PROPORTION)
-            .saturating_div(100)
-    } else {
-        // old_val_len == 0 && new_val_len == 0
-        0
-    }
+    GasSchedule::MAINNET.set_cost_delete_old_value(key_len, old_val_len, new_val_len)
 }
 
 /// Set Cost (3): Cost for writing a new value
 pub const fn set_cost_write_new_value(new_val_len: usize) -> u64 {
-    // b * C_write
-    (new_val_len as u64).saturating_mul(MPT_WRITE_PER_BYTE_COST)
+    GasSchedule::MAINNET.set_cost_write_new_value(new_val_len)
 }
 
 /// Set Cost (4): Cost for recomputing node hashes until the root
 pub const fn set_cost_rehash(key_len: usize) -> u64 {
-    // k * C_rehash
-    (key_len as u64).saturating_mul(MPT_REHASH_PER_BYTE_COST)
+    GasSchedule::MAINNET.set_cost_rehash(key_len)
 }
 
+/// Set Cost (5): Surcharge for setting a value on a storage key which holds no value
+pub const fn set_cost_new_key(old_val_len: usize, new_val_len: usize) -> u64 {
+    GasSchedule::MAINNET.set_cost_new_key(old_val_len, new_val_len)
+}
+
+/* ↓↓↓ Gas Costs for block context host functions ↓↓↓ */
+
+/// Fixed cost of reading the block proposer's address, charged on top of the cost of writing it to Wasm memory.
+pub const BLOCK_PROPOSER_READ_COST: u64 = 100;
+
+/// Fixed cost of reading the block's random bytes, charged on top of the cost of writing them to Wasm memory.
+pub const BLOCK_RANDOM_BYTES_READ_COST: u64 = 100;
+
+/* ↓↓↓ Gas Costs for execution context host functions ↓↓↓ */
+
+/// Fixed cost of reading the gas remaining for a contract call.
+pub const GAS_REMAINING_READ_COST: u64 = 100;
+
 /* ↓↓↓ Gas Costs for crypto functions ↓↓↓ */
 
 /// Multiplier of computing the SHA256 hash over the length of a message.
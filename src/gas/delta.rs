@@ -0,0 +1,122 @@
+/*
+    Copyright © 2023, ParallelChain Lab
+    Licensed under the Apache License, Version 2.0: http://www.apache.org/licenses/LICENSE-2.0
+*/
+
+//! Diagnostics which explain why the gas used by a transaction differs from an estimate of it.
+//!
+//! An estimate is the [ReceiptV2] from executing a transaction against some World State (e.g., the latest state known
+//! to a wallet), and the actual receipt is from executing the same transaction in a block. [explain_gas_delta]
+//! attributes the difference in gas used to the commands of the transaction.
+//!
+//! The Runtime does not record the storage accesses of a command, so a command which read or wrote state that
+//! changed between the estimate and the execution is reported as using different gas with the same exit code.
+
+use pchain_types::blockchain::{ExitCodeV2, ReceiptV2};
+
+use crate::types::gas_used_and_exit_code_v2;
+
+/// Explains the difference between the gas used by a transaction in `estimate` and in `actual`.
+pub fn explain_gas_delta(estimate: &ReceiptV2, actual: &ReceiptV2) -> GasDeltaReport {
+    let num_commands = std::cmp::max(
+        estimate.command_receipts.len(),
+        actual.command_receipts.len(),
+    );
+    let gas_used_and_exit_code = |receipt: &ReceiptV2, index: usize| {
+        receipt
+            .command_receipts
+            .get(index)
+            .map(gas_used_and_exit_code_v2)
+            .unwrap_or((0, ExitCodeV2::NotExecuted))
+    };
+
+    let command_deltas = (0..num_commands)
+        .filter_map(|index| {
+            let (estimated_gas_used, estimated_exit_code) = gas_used_and_exit_code(estimate, index);
+            let (actual_gas_used, actual_exit_code) = gas_used_and_exit_code(actual, index);
+            if estimated_gas_used == actual_gas_used && estimated_exit_code == actual_exit_code {
+                return None;
+            }
+            let cause = if estimated_exit_code == actual_exit_code {
+                GasDeltaCause::StateChanged
+            } else {
+                GasDeltaCause::ExitCodeChanged {
+                    estimated: estimated_exit_code,
+                    actual: actual_exit_code,
+                }
+            };
+            Some(CommandGasDelta {
+                index,
+                estimated_gas_used,
+                actual_gas_used,
+                cause,
+            })
+        })
+        .collect();
+
+    GasDeltaReport {
+        estimated_gas_used: estimate.gas_used,
+        actual_gas_used: actual.gas_used,
+        command_deltas,
+    }
+}
+
+/// Result of [explain_gas_delta].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GasDeltaReport {
+    /// Total gas used by the transaction in the estimate.
+    pub estimated_gas_used: u64,
+    /// Total gas used by the transaction in the actual execution.
+    pub actual_gas_used: u64,
+    /// Commands which used different gas or exited differently, in the order of the commands in the transaction.
+    pub command_deltas: Vec<CommandGasDelta>,
+}
+
+impl GasDeltaReport {
+    /// Difference between the actual and the estimated total gas used. Positive if the estimate was too low.
+    pub fn gas_delta(&self) -> i128 {
+        self.actual_gas_used as i128 - self.estimated_gas_used as i128
+    }
+
+    /// Part of [gas_delta](Self::gas_delta) which is not attributed to any command, e.g. a difference in the
+    /// inclusion cost of the transaction.
+    pub fn unattributed_gas_delta(&self) -> i128 {
+        self.gas_delta()
+            - self
+                .command_deltas
+                .iter()
+                .map(CommandGasDelta::gas_delta)
+                .sum::<i128>()
+    }
+}
+
+/// Difference in the gas used by a single command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandGasDelta {
+    /// Index of the command in the transaction.
+    pub index: usize,
+    pub estimated_gas_used: u64,
+    pub actual_gas_used: u64,
+    pub cause: GasDeltaCause,
+}
+
+impl CommandGasDelta {
+    /// Difference between the actual and the estimated gas used by the command.
+    pub fn gas_delta(&self) -> i128 {
+        self.actual_gas_used as i128 - self.estimated_gas_used as i128
+    }
+}
+
+/// Reason identified by the Runtime for a command using different gas.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GasDeltaCause {
+    /// The command exited with a different exit code, i.e. it took a different branch of execution. For example, it
+    /// failed, ran out of gas, or was not executed because an earlier command failed.
+    ExitCodeChanged {
+        estimated: ExitCodeV2,
+        actual: ExitCodeV2,
+    },
+    /// The command exited with the same exit code but used different gas, because state which it read or wrote
+    /// changed between the estimate and the execution (e.g., a storage value of a different length).
+    StateChanged,
+}
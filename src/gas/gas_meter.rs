@@ -17,21 +17,20 @@
 //! Designed as a singleton, the GasMeter can be cloned for operational convenience,
 //! yet there is always a single, authoritative instance in operation at any given time.
 
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 
 use crate::execution::cache::{CommandOutputCache, WorldStateCache};
 use crate::{
     contract::{ContractModule, SmartContractContext},
-    types::{CommandKind, CommandOutput, TxnVersion},
+    types::{CommandKind, CommandOutput, RuntimeEvent, TxnVersion},
     TransitionError,
 };
 use pchain_types::cryptography::PublicAddress;
 use pchain_world_state::{NetworkAccountStorage, VersionProvider, DB, NETWORK_ADDRESS};
 
 use super::{
-    constants::{tx_inclusion_cost_v1, tx_inclusion_cost_v2},
     operations::{self, OperationReceipt},
-    CostChange,
+    CostChange, GasBreakdown, GasSchedule,
 };
 
 /// GasMeter contains both gas-accounting variables and data structures which involve chargeable operations.
@@ -47,6 +46,9 @@ where
     /// gas limit of the entire txn
     pub gas_limit: u64,
 
+    /// costs of the chargeable operations
+    pub schedule: GasSchedule,
+
     /// stores txn inclusion gas separately because it is not considered to belong to a single command
     gas_used_for_txn_inclusion: u64,
 
@@ -55,7 +57,7 @@ where
 
     /// stores the gas used by current command,
     /// finalized and reset at the end of each command
-    gas_used_for_current_command: GasUsed,
+    pub gas_used_for_current_command: GasUsed,
 
     /* ↓↓↓ Operations involving the following data structures are chargeable ↓↓↓ */
     /// stores all resulting outputs from executing the current command
@@ -75,6 +77,7 @@ where
             version,
             ws_cache,
             gas_limit,
+            schedule: GasSchedule::default(),
             total_gas_used_for_executed_commands: 0,
             gas_used_for_txn_inclusion: 0,
             gas_used_for_current_command: GasUsed::default(),
@@ -86,7 +89,7 @@ where
     /// data for generating the command receipt, and updates the gas counter which is used
     /// at the end of transaction execution.
     pub fn take_current_command_result(&mut self) -> (u64, CommandOutput) {
-        let command_output = self.output_cache_of_current_command.take();
+        let mut command_output = self.output_cache_of_current_command.take();
 
         // check if the gas used for current command exceeds gas limit, and use the clamped value
         // as the field 'gas_used' in the command receipt.
@@ -101,6 +104,11 @@ where
             )
         };
 
+        if self.output_cache_of_current_command.record_gas_breakdown {
+            command_output.gas_breakdown =
+                GasBreakdown::clamp_to(&self.gas_used_for_current_command.tally(), gas_used);
+        }
+
         // update the total gas used
         self.total_gas_used_for_executed_commands = self
             .total_gas_used_for_executed_commands
@@ -117,17 +125,33 @@ where
     /// Use with caution!! Method adds on gas consumed outside of calling GasMeter methods,
     /// which should be exceptional cases, not the norm, for accurate gas charging.
     ///
-    /// Presently used only in the following scenarios:
-    /// 1) Gas consumed in Wasm environment as they are not metered by GasMeter
-    /// 2) Gas consumed for exhausting gas fully in WithdrawDeposit, StakeDeposit and UnstakeDeposit
-    /// when the commands are executed with insufficient gas to write the return value
+    /// Presently used only for exhausting gas fully in WithdrawDeposit, StakeDeposit and UnstakeDeposit
+    /// when the commands are executed with insufficient gas to write the return value.
+    /// Gas consumed in Wasm environment is charged by [charge_wasm_execution](GasMeter::charge_wasm_execution).
     pub fn manually_charge_gas(&mut self, gas: u64) {
+        let cost_change = CostChange::deduct(gas);
+        self.gas_used_for_current_command.charge(cost_change);
+        self.gas_used_for_current_command.tally_write(cost_change);
+    }
+
+    /// Adds on gas consumed in Wasm environment as they are not metered by GasMeter. The host functions
+    /// called in the Wasm environment tally their own gas, so the gas is not tallied here.
+    pub fn charge_wasm_execution(&mut self, gas: u64) {
         self.gas_used_for_current_command
             .charge(CostChange::deduct(gas));
     }
 
+    /// charges a write to the World State or the command output
     fn charge<T>(&self, op_receipt: OperationReceipt<T>) -> T {
         self.gas_used_for_current_command.charge(op_receipt.1);
+        self.gas_used_for_current_command.tally_write(op_receipt.1);
+        op_receipt.0
+    }
+
+    /// charges a read from the World State
+    fn charge_read<T>(&self, op_receipt: OperationReceipt<T>) -> T {
+        self.gas_used_for_current_command.charge(op_receipt.1);
+        self.gas_used_for_current_command.tally_read(op_receipt.1);
         op_receipt.0
     }
 
@@ -155,12 +179,16 @@ where
     ) -> Result<(), TransitionError> {
         // stored separately because it is not considered to belong to a single command
         let required_cost = match version {
-            TxnVersion::V1 => tx_inclusion_cost_v1(tx_size, tx_command_kinds),
-            TxnVersion::V2 => tx_inclusion_cost_v2(tx_size, tx_command_kinds),
+            TxnVersion::V1 => self
+                .schedule
+                .tx_inclusion_cost_v1(tx_size, tx_command_kinds),
+            TxnVersion::V2 => self
+                .schedule
+                .tx_inclusion_cost_v2(tx_size, tx_command_kinds),
         };
 
         if required_cost > self.gas_limit {
-            return Err(TransitionError::PreExecutionGasExhausted);
+            return Err(TransitionError::GasLimitTooLow);
         } else {
             self.gas_used_for_txn_inclusion = required_cost;
         }
@@ -169,6 +197,7 @@ where
 
     pub fn command_output_set_return_value(&mut self, return_value: Vec<u8>) {
         let result = operations::command_output_set_return_value(
+            &self.schedule,
             self.output_cache_of_current_command.return_value.as_mut(),
             return_value,
         );
@@ -177,6 +206,7 @@ where
 
     pub fn command_output_set_amount_withdrawn(&mut self, amount_withdrawn: u64) {
         let result = operations::command_output_set_amount_withdrawn(
+            &self.schedule,
             self.output_cache_of_current_command
                 .amount_withdrawn
                 .as_mut(),
@@ -187,6 +217,7 @@ where
 
     pub fn command_output_set_amount_staked(&mut self, amount_staked: u64) {
         let result = operations::command_output_set_amount_staked(
+            &self.schedule,
             self.output_cache_of_current_command.amount_staked.as_mut(),
             amount_staked,
         );
@@ -195,6 +226,7 @@ where
 
     pub fn command_output_set_amount_unstaked(&mut self, amount_unstaked: u64) {
         let result = operations::command_output_set_amount_unstaked(
+            &self.schedule,
             self.output_cache_of_current_command
                 .amount_unstaked
                 .as_mut(),
@@ -203,34 +235,50 @@ where
         self.charge(result)
     }
 
+    /// Records a runtime event, if enabled. Not chargeable because events are not part of the receipt.
+    pub fn command_output_push_event(&mut self, event: RuntimeEvent) {
+        self.output_cache_of_current_command.push_event(event);
+    }
+
     /* ↓↓↓ Facade methods for World State operations ↓↓↓ */
 
     //
     // CONTAINS methods
     //
     pub fn ws_contains_storage_data(&mut self, address: PublicAddress, key: &[u8]) -> bool {
-        let result =
-            operations::ws_contains_storage_data(self.version, &mut self.ws_cache, address, key);
-        self.charge(result)
+        let result = operations::ws_contains_storage_data(
+            &self.schedule,
+            self.version,
+            &mut self.ws_cache,
+            address,
+            key,
+        );
+        self.charge_read(result)
     }
 
     //
     // GET methods
     //
     pub fn ws_storage_data(&mut self, address: PublicAddress, key: &[u8]) -> Option<Vec<u8>> {
-        let result = operations::ws_storage_data(self.version, &mut self.ws_cache, address, key);
-        let value = self.charge(result)?;
+        let result = operations::ws_storage_data(
+            &self.schedule,
+            self.version,
+            &mut self.ws_cache,
+            address,
+            key,
+        );
+        let value = self.charge_read(result)?;
         (!value.is_empty()).then_some(value)
     }
 
     pub fn ws_balance(&self, address: PublicAddress) -> u64 {
-        let result = operations::ws_balance(&self.ws_cache, &address);
-        self.charge(result)
+        let result = operations::ws_balance(&self.schedule, &self.ws_cache, &address);
+        self.charge_read(result)
     }
 
     pub fn ws_cbi_version(&self, address: PublicAddress) -> Option<u32> {
-        let result = operations::ws_cbi_version(&self.ws_cache, &address);
-        self.charge(result)
+        let result = operations::ws_cbi_version(&self.schedule, &self.ws_cache, &address);
+        self.charge_read(result)
     }
 
     pub fn ws_cached_contract(
@@ -238,7 +286,8 @@ where
         address: PublicAddress,
         sc_context: &SmartContractContext,
     ) -> Option<ContractModule> {
-        self.charge(operations::ws_cached_contract(
+        self.charge_read(operations::ws_cached_contract(
+            &self.schedule,
             &self.ws_cache,
             sc_context,
             address,
@@ -249,32 +298,53 @@ where
     // SET methods
     //
     pub fn ws_set_storage_data(&mut self, address: PublicAddress, key: &[u8], value: Vec<u8>) {
-        let result =
-            operations::ws_set_storage_data(self.version, &mut self.ws_cache, address, key, value);
+        let result = operations::ws_set_storage_data(
+            &self.schedule,
+            self.version,
+            &mut self.ws_cache,
+            address,
+            key,
+            value,
+        );
         self.charge(result)
     }
 
     /// Sets balance in the write set, note it does not write to WS immediately.
     pub fn ws_set_balance(&mut self, address: PublicAddress, value: u64) {
-        let result = operations::ws_set_balance(&mut self.ws_cache, address, value);
-        self.charge(result)
+        let result = operations::ws_set_balance(&self.schedule, &mut self.ws_cache, address, value);
+        self.charge(result);
+        self.command_output_push_event(RuntimeEvent::BalanceChanged {
+            address,
+            balance: value,
+        });
     }
 
     /// Sets CBI version in the write set, note it does not write to WS immediately.
     pub fn ws_set_cbi_version(&mut self, address: PublicAddress, cbi_version: u32) {
-        let result = operations::ws_set_cbi_version(&mut self.ws_cache, address, cbi_version);
+        let result = operations::ws_set_cbi_version(
+            &self.schedule,
+            &mut self.ws_cache,
+            address,
+            cbi_version,
+        );
         self.charge(result)
     }
 
     /// Sets contract bytecode in the write set, note it does not write to WS immediately.
     pub fn ws_set_code(&mut self, address: PublicAddress, code: Vec<u8>) {
-        let result = operations::ws_set_contract_code(&mut self.ws_cache, address, code);
+        let result =
+            operations::ws_set_contract_code(&self.schedule, &mut self.ws_cache, address, code);
         self.charge(result)
     }
 }
 
 /// GasMeter implements NetworkAccountStorage to expose *chargeable* read-write operations to the
 /// network account's storage, such as when contracts interact with it.
+///
+/// Handles such as `NetworkAccount::pools(&mut gas_meter, operator)` only borrow the GasMeter, so creating one is
+/// free. Their reads go through the [WorldStateCache](crate::execution::cache::WorldStateCache), whose read cache
+/// lives for the whole transaction, so a value read by one command is not read from the Storage Trie again by the
+/// commands after it.
 impl<'a, S, V> NetworkAccountStorage for GasMeter<'a, S, V>
 where
     S: DB + Send + Sync + Clone,
@@ -301,9 +371,14 @@ where
 /// A `RefCell` is used here to enable interior mutability.
 /// This design choice allows the struct to modify its `total` field
 /// (the gas used) even when methods are called with only a read-only reference.
+///
+/// It also tallies the gas of chargeable operations by kind, including those charged by the
+/// [HostFuncGasMeter](crate::gas::HostFuncGasMeter), for the [GasBreakdown] of the command.
 #[derive(Clone, Default)]
 pub(crate) struct GasUsed {
     total: RefCell<CostChange>,
+    tally: Cell<GasBreakdown>,
+    refunds: Cell<u64>,
 }
 
 impl GasUsed {
@@ -317,5 +392,34 @@ impl GasUsed {
 
     pub fn reset(&mut self) {
         *self.total.borrow_mut() = CostChange::default();
+        self.tally.take();
+        self.refunds.take();
+    }
+
+    /// the gas of chargeable operations by kind, with writes net of refunds. Gas of Wasm opcodes is not tallied.
+    pub fn tally(&self) -> GasBreakdown {
+        let mut tally = self.tally.get();
+        tally.write_gas = tally.write_gas.saturating_sub(self.refunds.get());
+        tally
+    }
+
+    pub fn tally_read(&self, cost_change: CostChange) {
+        let mut tally = self.tally.get();
+        tally.read_gas = tally.read_gas.saturating_add(cost_change.net_cost().0);
+        self.tally.set(tally);
+    }
+
+    pub fn tally_write(&self, cost_change: CostChange) {
+        let mut tally = self.tally.get();
+        let (cost, refund) = cost_change.net_cost();
+        tally.write_gas = tally.write_gas.saturating_add(cost);
+        self.tally.set(tally);
+        self.refunds.set(self.refunds.get().saturating_add(refund));
+    }
+
+    pub fn tally_wasm(&self, cost_change: CostChange) {
+        let mut tally = self.tally.get();
+        tally.wasm_gas = tally.wasm_gas.saturating_add(cost_change.net_cost().0);
+        self.tally.set(tally);
     }
 }
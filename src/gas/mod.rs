@@ -10,9 +10,21 @@
 pub(crate) mod cost_change;
 pub(crate) use cost_change::*;
 
+pub mod breakdown;
+pub use breakdown::GasBreakdown;
+
 pub mod constants;
 pub use constants::*;
 
+pub mod delta;
+pub use delta::{explain_gas_delta, CommandGasDelta, GasDeltaCause, GasDeltaReport};
+
+pub mod opcode_profile;
+pub use opcode_profile::OpcodeGasProfile;
+
+pub mod schedule;
+pub use schedule::GasSchedule;
+
 pub(crate) mod operations;
 
 pub(crate) mod wasmer_gas;
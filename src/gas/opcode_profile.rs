@@ -0,0 +1,45 @@
+/*
+    Copyright © 2023, ParallelChain Lab
+    Licensed under the Apache License, Version 2.0: http://www.apache.org/licenses/LICENSE-2.0
+*/
+
+//! Attribution of the gas used by Wasm opcodes to categories of opcodes, for optimizing contracts.
+//!
+//! Only recorded if the crate is built with the `profiling` feature, which adds the
+//! [GasProfiler](crate::contract::wasmer::gas_profiler::GasProfiler) to the middlewares that contracts are compiled
+//! with. Profiling is diagnostic only, and does not change the gas used by any transaction.
+
+/// Gas used by the Wasm opcodes of contract calls in a command, by category of opcode, as charged by
+/// [wasm_opcode_gas_schedule](crate::gas::wasm_opcode_gas_schedule).
+///
+/// The profile counts the gas of every block of opcodes that a contract started executing, so it may exceed the gas
+/// charged for opcodes in a command which exhausted its gas limit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OpcodeGasProfile {
+    /// Gas for control flow, e.g. blocks, branches and returns, and for dropping and selecting values.
+    pub control_gas: u64,
+    /// Gas for accessing Wasm linear memory, tables, locals and globals.
+    pub memory_gas: u64,
+    /// Gas for numeric operations, e.g. constants, arithmetic, comparisons and conversions.
+    pub arithmetic_gas: u64,
+    /// Gas for calling functions.
+    pub call_gas: u64,
+}
+
+impl OpcodeGasProfile {
+    /// Sum of the gas of all categories of opcodes.
+    pub fn total(&self) -> u64 {
+        self.control_gas
+            .saturating_add(self.memory_gas)
+            .saturating_add(self.arithmetic_gas)
+            .saturating_add(self.call_gas)
+    }
+
+    /// Adds the gas of `other` to this profile, e.g. to fold a contract-to-contract call into its caller.
+    pub(crate) fn add(&mut self, other: &OpcodeGasProfile) {
+        self.control_gas = self.control_gas.saturating_add(other.control_gas);
+        self.memory_gas = self.memory_gas.saturating_add(other.memory_gas);
+        self.arithmetic_gas = self.arithmetic_gas.saturating_add(other.arithmetic_gas);
+        self.call_gas = self.call_gas.saturating_add(other.call_gas);
+    }
+}
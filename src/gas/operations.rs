@@ -29,13 +29,16 @@ use sha2::{Digest as sha256_digest, Sha256};
 use tiny_keccak::{Hasher as _, Keccak};
 
 use crate::{
-    contract::{wasmer::memory::MemoryContext, ContractModule, SmartContractContext},
-    execution::cache::{CacheValue, WorldStateCache},
-    types::TxnVersion,
+    contract::{
+        wasmer::memory::{MemoryAccessError, MemoryContext},
+        ContractModule, SmartContractContext,
+    },
+    execution::cache::{CacheValue, StorageReader, WorldStateCache},
+    types::{TxnVersion, WorldStateKey},
 };
 
 use super::constants::*;
-use super::CostChange;
+use super::{CostChange, GasSchedule};
 
 pub(crate) type OperationReceipt<T> = (T, CostChange);
 
@@ -43,8 +46,11 @@ pub(crate) type OperationReceipt<T> = (T, CostChange);
 /* ↓↓↓ Functions for World State Access ↓↓↓ */
 
 /// Implements the `G_st_set` and `G_st_set_v2` gas cost formulas in the Mainnet Protocol,
-/// and sets storage data on the Storage Trie for a particular account address
+/// and sets storage data on the Storage Trie for a particular account address.
+/// Setting a value on a key which holds no value also costs the
+/// [new key surcharge](GasSchedule::new_key_surcharge) of the schedule, which is zero in the Mainnet Protocol.
 pub(crate) fn ws_set_storage_data<S, V>(
+    schedule: &GasSchedule,
     txn_version: TxnVersion,
     ws_cache: &mut WorldStateCache<S, V>,
     address: PublicAddress,
@@ -56,7 +62,7 @@ where
     V: VersionProvider + Send + Sync + Clone,
 {
     let new_val_len = CacheValue::len(&value);
-    let (old_val_len, get_cost) = ws_storage_data(txn_version, ws_cache, address, key);
+    let (old_val_len, get_cost) = ws_storage_data(schedule, txn_version, ws_cache, address, key);
     let old_val_len = old_val_len.as_ref().map_or(0, CacheValue::len);
     
     ws_cache.set_storage_data(address, key, value);
@@ -66,14 +72,16 @@ where
         // step 1
         get_cost
         // step 2
-        + CostChange::reward(set_cost_delete_old_value(
+        + CostChange::reward(schedule.set_cost_delete_old_value(
             traversed_key_len,
             old_val_len,
             new_val_len))
         // step 3 
-        + CostChange::deduct(set_cost_write_new_value(new_val_len))
+        + CostChange::deduct(schedule.set_cost_write_new_value(new_val_len))
         // step 4    
-        + CostChange::deduct(set_cost_rehash(traversed_key_len));
+        + CostChange::deduct(schedule.set_cost_rehash(traversed_key_len))
+        // step 5
+        + CostChange::deduct(schedule.set_cost_new_key(old_val_len, new_val_len));
 
     ((), cost)
 }
@@ -81,6 +89,7 @@ where
 /// Implements the `G_at_set` gas cost formula in the Mainnet Protocol,
 /// and sets an account's balance in the Account Trie
 pub(crate) fn ws_set_balance<S, V>(
+    schedule: &GasSchedule,
     ws_cache: &mut WorldStateCache<S, V>,
     address: PublicAddress,
     balance: u64,
@@ -91,16 +100,16 @@ where
 {
     let key_len = ACCOUNT_TRIE_KEY_LENGTH;
     let new_val_len = balance.len();
-    let (old_val_len, get_cost) = ws_balance(ws_cache, &address);
+    let (old_val_len, get_cost) = ws_balance(schedule, ws_cache, &address);
     let old_val_len = old_val_len.len();
 
     // old_val_len is obtained from Get so the cost of reading the key is already charged
-    let set_cost = CostChange::reward(set_cost_delete_old_value(
+    let set_cost = CostChange::reward(schedule.set_cost_delete_old_value(
         key_len,
         old_val_len,
         new_val_len,
-    )) + CostChange::deduct(set_cost_write_new_value(new_val_len))
-        + CostChange::deduct(set_cost_rehash(key_len));
+    )) + CostChange::deduct(schedule.set_cost_write_new_value(new_val_len))
+        + CostChange::deduct(schedule.set_cost_rehash(key_len));
 
     ws_cache.set_balance(address, balance);
     ((), get_cost + set_cost)
@@ -109,6 +118,7 @@ where
 /// Implements the `G_at_set` gas cost formula in the Mainnet Protocol,
 /// and sets a contract account's CBI version in the Account Trie
 pub(crate) fn ws_set_cbi_version<S, V>(
+    schedule: &GasSchedule,
     ws_cache: &mut WorldStateCache<S, V>,
     address: PublicAddress,
     version: u32,
@@ -119,16 +129,16 @@ where
 {
     let key_len = ACCOUNT_TRIE_KEY_LENGTH;
     let new_val_len = version.len();
-    let (old_val_len, get_cost) = ws_cbi_version(ws_cache, &address);
+    let (old_val_len, get_cost) = ws_cbi_version(schedule, ws_cache, &address);
     let old_val_len = old_val_len.as_ref().map_or(0, CacheValue::len);
 
     // old_val_len is obtained from Get so the cost of reading the key is already charged
-    let set_cost = CostChange::reward(set_cost_delete_old_value(
+    let set_cost = CostChange::reward(schedule.set_cost_delete_old_value(
         key_len,
         old_val_len,
         new_val_len,
-    )) + CostChange::deduct(set_cost_write_new_value(new_val_len))
-        + CostChange::deduct(set_cost_rehash(key_len));
+    )) + CostChange::deduct(schedule.set_cost_write_new_value(new_val_len))
+        + CostChange::deduct(schedule.set_cost_rehash(key_len));
 
     ws_cache.set_cbi_version(address, version);
 
@@ -138,6 +148,7 @@ where
 /// Implements the `G_at_set` gas cost formula in the Mainnet Protocol,
 /// and stores a contract account's CBI balance in the Account Trie
 pub(crate) fn ws_set_contract_code<S, V>(
+    schedule: &GasSchedule,
     ws_cache: &mut WorldStateCache<S, V>,
     address: PublicAddress,
     code: Vec<u8>,
@@ -148,16 +159,16 @@ where
 {
     let key_len = ACCOUNT_TRIE_KEY_LENGTH;
     let new_val_len = CacheValue::len(&code);
-    let (old_val_len, get_cost) = ws_cached_contract_code(ws_cache, &address);
+    let (old_val_len, get_cost) = ws_cached_contract_code(schedule, ws_cache, &address);
     let old_val_len = old_val_len.as_ref().map_or(0, CacheValue::len);
 
     // old_val_len is obtained from Get so the cost of reading the key is already charged
-    let set_cost = CostChange::reward(set_cost_delete_old_value(
+    let set_cost = CostChange::reward(schedule.set_cost_delete_old_value(
         key_len,
         old_val_len,
         new_val_len,
-    )) + CostChange::deduct(set_cost_write_new_value(new_val_len))
-        + CostChange::deduct(set_cost_rehash(key_len));
+    )) + CostChange::deduct(schedule.set_cost_write_new_value(new_val_len))
+        + CostChange::deduct(schedule.set_cost_rehash(key_len));
 
     ws_cache.set_contract_code(address, code);
 
@@ -169,6 +180,7 @@ where
 /// and fetches a value associated with a provided key 
 /// from the Storage Trie for a particular account address
 pub(crate) fn ws_storage_data<S, V>(
+    schedule: &GasSchedule,
     txn_version: TxnVersion,
     ws_cache: &mut WorldStateCache<S, V>,
     address: PublicAddress,
@@ -182,17 +194,66 @@ where
     let traversed_key_len = storage_trie_traversed_key_len(txn_version, &address, key);
     let get_cost = CostChange::deduct(
         // step 1
-        get_cost_traverse(traversed_key_len)
+        schedule.get_cost_traverse(traversed_key_len)
             // step 2
-            .saturating_add(get_cost_read(value.as_ref().map_or(0, CacheValue::len))),
+            .saturating_add(schedule.get_cost_read(value.as_ref().map_or(0, CacheValue::len))),
     );
 
     (value, get_cost)
 }
 
+/// Same as [ws_storage_data], but reads through a read-only [StorageReader].
+pub(crate) fn ws_read_only_storage_data<S, V>(
+    schedule: &GasSchedule,
+    txn_version: TxnVersion,
+    reader: &mut StorageReader<S, V>,
+    key: &[u8],
+) -> OperationReceipt<Option<Vec<u8>>>
+where
+    S: DB + Send + Sync + Clone + 'static,
+    V: VersionProvider + Send + Sync + Clone,
+{
+    let value = reader.get(key);
+    let traversed_key_len = storage_trie_traversed_key_len(txn_version, &reader.address(), key);
+    let get_cost = CostChange::deduct(
+        // step 1
+        schedule.get_cost_traverse(traversed_key_len)
+            // step 2
+            .saturating_add(schedule.get_cost_read(value.as_ref().map_or(0, CacheValue::len))),
+    );
+
+    (value, get_cost)
+}
+
+/// Similar to [ws_storage_data], but only returns the length of the value associated with a provided key.
+/// The value is still read from the Storage Trie to get its length, so its full read cost is charged.
+pub(crate) fn ws_storage_data_len<S, V>(
+    schedule: &GasSchedule,
+    txn_version: TxnVersion,
+    ws_cache: &mut WorldStateCache<S, V>,
+    address: PublicAddress,
+    key: &[u8],
+) -> OperationReceipt<Option<usize>>
+where
+    S: DB + Send + Sync + Clone + 'static,
+    V: VersionProvider + Send + Sync + Clone,
+{
+    let value_len = ws_cache.storage_data(address, key).map(|value| value.len());
+    let traversed_key_len = storage_trie_traversed_key_len(txn_version, &address, key);
+    let get_cost = CostChange::deduct(
+        // step 1
+        schedule.get_cost_traverse(traversed_key_len)
+            // step 2
+            .saturating_add(schedule.get_cost_read(value_len.unwrap_or(0))),
+    );
+
+    (value_len, get_cost)
+}
+
 /// Implements the `G_at_get` gas cost formula in the Mainnet Protocol,
 /// and fetches the balance of a particular address from the Account Trie
 pub(crate) fn ws_balance<S, V>(
+    schedule: &GasSchedule,
     ws_cache: &WorldStateCache<S, V>,
     address: &PublicAddress,
 ) -> OperationReceipt<u64>
@@ -203,9 +264,9 @@ where
     let value = ws_cache.balance(address);
     let get_cost = CostChange::deduct(
         // step 1
-        get_cost_traverse(ACCOUNT_TRIE_KEY_LENGTH).saturating_add
+        schedule.get_cost_traverse(ACCOUNT_TRIE_KEY_LENGTH).saturating_add
             // step 2
-            (get_cost_read(value.len())),
+            (schedule.get_cost_read(value.len())),
     );
     (value, get_cost)
 }
@@ -213,6 +274,7 @@ where
 /// Implements the `G_at_get` gas cost formula in the Mainnet Protocol,
 /// and fetches the CBI version of a particular contract address from the Account Trie
 pub(crate) fn ws_cbi_version<S, V>(
+    schedule: &GasSchedule,
     ws_cache: &WorldStateCache<S, V>,
     address: &PublicAddress,
 ) -> OperationReceipt<Option<u32>>
@@ -223,9 +285,9 @@ where
     let value = ws_cache.cbi_version(address);
     let get_cost = CostChange::deduct(
         // step 1
-        get_cost_traverse(ACCOUNT_TRIE_KEY_LENGTH)
+        schedule.get_cost_traverse(ACCOUNT_TRIE_KEY_LENGTH)
             // step 2
-            .saturating_add(get_cost_read(value.as_ref().map_or(0, |v| v.len()))),
+            .saturating_add(schedule.get_cost_read(value.as_ref().map_or(0, |v| v.len()))),
     );
     (value, get_cost)
 }
@@ -233,6 +295,7 @@ where
 /// Implements the `G_at_get` gas cost formula in the Mainnet Protocol,
 /// and fetches the code bytes of a particular contract address from the Account Trie
 pub(crate) fn ws_cached_contract_code<S, V>(
+    schedule: &GasSchedule,
     ws_cache: &WorldStateCache<S, V>,
     address: &PublicAddress,
 ) -> OperationReceipt<Option<Vec<u8>>>
@@ -241,11 +304,11 @@ where
     V: VersionProvider + Send + Sync + Clone,
 {
     let value = ws_cache.contract_code(address);
-    let get_cost = CostChange::deduct(discount_code_read(
+    let get_cost = CostChange::deduct(schedule.discount_code_read(
         // step 1
-        get_cost_traverse(ACCOUNT_TRIE_KEY_LENGTH)
+        schedule.get_cost_traverse(ACCOUNT_TRIE_KEY_LENGTH)
             // step 2
-            .saturating_add(get_cost_read(value.as_ref().map_or(0, CacheValue::len))),
+            .saturating_add(schedule.get_cost_read(value.as_ref().map_or(0, CacheValue::len))),
     ));
 
     (value, get_cost)
@@ -255,6 +318,7 @@ where
 /// and tries to fetch the code bytes of a particular contract address from the contract cache,
 /// failing which fetches the code bytes from the Account Trie
 pub(crate) fn ws_cached_contract<S, V>(
+    schedule: &GasSchedule,
     ws_cache: &WorldStateCache<S, V>,
     sc_context: &SmartContractContext,
     address: PublicAddress,
@@ -265,18 +329,19 @@ where
 {
     // check smart contract cache
     if let Some(contract_module) = ContractModule::from_cache(address, sc_context) {
-        let contract_get_cost = CostChange::deduct(discount_code_read(
+        ws_cache.touch_key(|| WorldStateKey::ContractCode(address));
+        let contract_get_cost = CostChange::deduct(schedule.discount_code_read(
             // step 1
-            get_cost_traverse(ACCOUNT_TRIE_KEY_LENGTH)
+            schedule.get_cost_traverse(ACCOUNT_TRIE_KEY_LENGTH)
                 // step 2
-                .saturating_add(get_cost_read(contract_module.bytecode_length())),
+                .saturating_add(schedule.get_cost_read(contract_module.bytecode_length())),
         ));
 
         return (Some(contract_module), contract_get_cost);
     }
 
     // else check ws and charge
-    let (value, contract_get_cost) = ws_cached_contract_code(ws_cache, &address);
+    let (value, contract_get_cost) = ws_cached_contract_code(schedule, ws_cache, &address);
     let contract_code = match value {
         Some(value) => value,
         None => return (None, contract_get_cost),
@@ -292,6 +357,7 @@ where
 /// and checks the existence of a value associated with a provided key
 /// from the Storage Trie for a particular account address
 pub(crate) fn ws_contains_storage_data<S, V>(
+    schedule: &GasSchedule,
     txn_version: TxnVersion,
     ws_cache: &mut WorldStateCache<S, V>,
     address: PublicAddress,
@@ -304,7 +370,7 @@ where
     let ret = ws_cache.contains_storage_data(address, key);
     let traversed_key_len = storage_trie_traversed_key_len(txn_version, &address, key);
     let cost_change = CostChange::deduct(
-        get_cost_traverse(traversed_key_len),
+        schedule.get_cost_traverse(traversed_key_len),
     );
     (ret, cost_change)
 }
@@ -313,22 +379,24 @@ where
 
 /// Calculates the cost of writing data to memory and writes it to the provided pointer location
 pub(crate) fn write_bytes<M: MemoryContext>(
+    schedule: &GasSchedule,
     memory_ctx: &M,
     value: Vec<u8>,
     val_ptr_ptr: u32,
-) -> OperationReceipt<Result<u32, anyhow::Error>> {
-    let write_cost: u64 = wasm_memory_write_cost(value.len());
+) -> OperationReceipt<Result<u32, MemoryAccessError>> {
+    let write_cost: u64 = schedule.wasm_memory_write_cost(value.len());
     let ret = MemoryContext::write_bytes_to_memory(memory_ctx, value, val_ptr_ptr);
     (ret, CostChange::deduct(write_cost))
 }
 
 /// Calculates the cost of reading data to memory and reads it
 pub(crate) fn read_bytes<M: MemoryContext>(
+    schedule: &GasSchedule,
     memory_ctx: &M,
     offset: u32,
     len: u32,
-) -> OperationReceipt<Result<Vec<u8>, anyhow::Error>> {
-    let read_cost = wasm_memory_read_cost(len as usize);
+) -> OperationReceipt<Result<Vec<u8>, MemoryAccessError>> {
+    let read_cost = schedule.wasm_memory_read_cost(len as usize);
     let ret = MemoryContext::read_bytes_from_memory(memory_ctx, offset, len);
     (ret, CostChange::deduct(read_cost))
 }
@@ -338,8 +406,12 @@ pub(crate) fn read_bytes<M: MemoryContext>(
 
 /// Calculates the cost of storing a log on the blockchain 
 /// and pushes the relevant log onto the provided holder vector
-pub(crate) fn command_output_append_log(logs: &mut Vec<Log>, log: Log) -> OperationReceipt<()> {
-    let cost = CostChange::deduct(blockchain_log_cost(log.topic.len(), log.value.len()));
+pub(crate) fn command_output_append_log(
+    schedule: &GasSchedule,
+    logs: &mut Vec<Log>,
+    log: Log,
+) -> OperationReceipt<()> {
+    let cost = CostChange::deduct(schedule.blockchain_log_cost(log.topic.len(), log.value.len()));
     logs.push(log);
     ((), cost)
 }
@@ -347,10 +419,11 @@ pub(crate) fn command_output_append_log(logs: &mut Vec<Log>, log: Log) -> Operat
 /// Calculates the cost of storing a generic return value in CommandReceiptV1, or CommandReceiptV2::Call  on the blockchain
 /// and sets the value in the provided reference
 pub(crate) fn command_output_set_return_value(
+    schedule: &GasSchedule,
     command_output_return_value: &mut Vec<u8>,
     return_value: Vec<u8>,
 ) -> OperationReceipt<()> {
-    let cost = CostChange::deduct(blockchain_storage_cost(return_value.len()));
+    let cost = CostChange::deduct(schedule.blockchain_storage_cost(return_value.len()));
     *command_output_return_value = return_value;
     ((), cost)
 }
@@ -358,10 +431,11 @@ pub(crate) fn command_output_set_return_value(
 /// Calculates the cost of storing the amount withdrawn field in CommandReceiptV2::WithdrawDeposit on the blockchain
 /// and sets the value in the provided reference
 pub(crate) fn command_output_set_amount_withdrawn(
+    schedule: &GasSchedule,
     command_output_amount_withdrawn: &mut u64,
     amount_withdrawn: u64,
 ) -> OperationReceipt<()> {
-    let cost = CostChange::deduct(blockchain_storage_cost(std::mem::size_of::<u64>()));
+    let cost = CostChange::deduct(schedule.blockchain_storage_cost(std::mem::size_of::<u64>()));
     *command_output_amount_withdrawn = amount_withdrawn;
     ((), cost)
 }
@@ -369,10 +443,11 @@ pub(crate) fn command_output_set_amount_withdrawn(
 /// Calculates the cost of storing the amount staked field in CommandReceiptV2::StakeDeposit on the blockchain
 /// and sets the value in the provided reference
 pub(crate) fn command_output_set_amount_staked(
+    schedule: &GasSchedule,
     command_output_amount_staked: &mut u64,
     amount_staked: u64,
 ) -> OperationReceipt<()> {
-    let cost = CostChange::deduct(blockchain_storage_cost(std::mem::size_of::<u64>()));
+    let cost = CostChange::deduct(schedule.blockchain_storage_cost(std::mem::size_of::<u64>()));
     *command_output_amount_staked = amount_staked;
     ((), cost)
 }
@@ -380,10 +455,11 @@ pub(crate) fn command_output_set_amount_staked(
 /// Calculates the cost of storing the amount unstaked field in CommandReceiptV2::UnstakeDeposit on the blockchain
 /// and sets the value in the provided reference
 pub(crate) fn command_output_set_amount_unstaked(
+    schedule: &GasSchedule,
     command_output_amount_unstaked: &mut u64,
     amount_unstaked: u64,
 ) -> OperationReceipt<()> {
-    let cost = CostChange::deduct(blockchain_storage_cost(std::mem::size_of::<u64>()));
+    let cost = CostChange::deduct(schedule.blockchain_storage_cost(std::mem::size_of::<u64>()));
     *command_output_amount_unstaked = amount_unstaked;
     ((), cost)
 }
@@ -394,8 +470,8 @@ pub(crate) fn command_output_set_amount_unstaked(
 
 /// Implements the `G_wsha256` gas cost formula in the Mainnet Protocol,
 /// and hashes a provided input using the SHA256 algorithm
-pub(crate) fn sha256(input_bytes: Vec<u8>) -> OperationReceipt<Vec<u8>> {
-    let cost = CostChange::deduct(CRYPTO_SHA256_PER_BYTE * input_bytes.len() as u64);
+pub(crate) fn sha256(schedule: &GasSchedule, input_bytes: Vec<u8>) -> OperationReceipt<Vec<u8>> {
+    let cost = CostChange::deduct(schedule.crypto_sha256_per_byte * input_bytes.len() as u64);
     let mut hasher = Sha256::new();
     hasher.update(input_bytes);
     let ret = hasher.finalize().to_vec();
@@ -404,8 +480,8 @@ pub(crate) fn sha256(input_bytes: Vec<u8>) -> OperationReceipt<Vec<u8>> {
 
 /// Implements the `G_wkeccak256` gas cost formula in the Mainnet Protocol,
 /// and hashes a provided input using the Keccak256 algorithm
-pub(crate) fn keccak256(input_bytes: Vec<u8>) -> OperationReceipt<Vec<u8>> {
-    let cost = CostChange::deduct(CRYPTO_KECCAK256_PER_BYTE * input_bytes.len() as u64);
+pub(crate) fn keccak256(schedule: &GasSchedule, input_bytes: Vec<u8>) -> OperationReceipt<Vec<u8>> {
+    let cost = CostChange::deduct(schedule.crypto_keccak256_per_byte * input_bytes.len() as u64);
     let mut output_bytes = [0u8; 32];
     let mut keccak = Keccak::v256();
     keccak.update(&input_bytes);
@@ -416,8 +492,8 @@ pub(crate) fn keccak256(input_bytes: Vec<u8>) -> OperationReceipt<Vec<u8>> {
 
 /// Implements the `G_wripemd160` gas cost formula in the Mainnet Protocol,
 /// and hashes a provided input using the RIPEMD160 algorithm
-pub(crate) fn ripemd(input_bytes: Vec<u8>) -> OperationReceipt<Vec<u8>> {
-    let cost = CostChange::deduct(CRYPTO_RIPEMD160_PER_BYTE * input_bytes.len() as u64);
+pub(crate) fn ripemd(schedule: &GasSchedule, input_bytes: Vec<u8>) -> OperationReceipt<Vec<u8>> {
+    let cost = CostChange::deduct(schedule.crypto_ripemd160_per_byte * input_bytes.len() as u64);
     let mut hasher = Ripemd160::new();
     hasher.update(&input_bytes);
     let ret = hasher.finalize().to_vec();
@@ -427,12 +503,13 @@ pub(crate) fn ripemd(input_bytes: Vec<u8>) -> OperationReceipt<Vec<u8>> {
 /// Implements the `G_wvrfy25519` gas cost formula in the Mainnet Protocol,
 /// and verifies a provided message signature using the Ed25519 algorithm, given a public key
 pub(crate) fn verify_ed25519_signature(
+    schedule: &GasSchedule,
     message: Vec<u8>,
     signature: [u8; 64],
     pub_key: [u8; 32],
 ) -> OperationReceipt<Result<i32, anyhow::Error>> {
     let cost = CostChange::deduct(1_400_000_u64.saturating_add(
-        (message.len() as u64).saturating_mul(CRYPTO_ED25519_PER_BYTE)));
+        (message.len() as u64).saturating_mul(schedule.crypto_ed25519_per_byte)));
     let public_key = match ed25519_dalek::VerifyingKey::from_bytes(&pub_key) {
         Ok(public_key) => public_key,
         Err(e) => return (Err(e.into()), cost),
@@ -0,0 +1,232 @@
+/*
+    Copyright © 2023, ParallelChain Lab
+    Licensed under the Apache License, Version 2.0: http://www.apache.org/licenses/LICENSE-2.0
+*/
+
+//! Tunable prices of the operations that incur gas.
+//!
+//! A [GasSchedule] holds the per-unit costs defined as constants in [constants](crate::gas::constants), and implements
+//! the cost formulas over them. The [operations](crate::gas::operations) charge gas through the schedule of the
+//! [Runtime](crate::Runtime), which is [GasSchedule::MAINNET] unless it is changed by
+//! [set_gas_schedule](crate::Runtime::set_gas_schedule), e.g. to experiment with gas prices on a testnet.
+//!
+//! The schedule does not include:
+//! - the costs of Wasm opcodes ([wasm_opcode_gas_schedule](crate::gas::wasm_opcode_gas_schedule)), which are compiled
+//!   into the machine code of contracts and cached with it.
+//! - sizes of data structures, e.g. [ACCOUNT_TRIE_KEY_LENGTH] and the minimum sizes of receipts, which are not prices.
+
+use crate::types::CommandKind;
+
+use super::constants::*;
+
+/// Per-unit costs of the operations that incur gas. See the [module-level documentation](self).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GasSchedule {
+    /// See [WASM_MEMORY_WRITE_PER64_BITS_COST].
+    pub wasm_memory_write_per64_bits_cost: u64,
+    /// See [WASM_MEMORY_READ_PER64_BITS_COST].
+    pub wasm_memory_read_per64_bits_cost: u64,
+    /// See [BLOCKCHAIN_WRITE_PER_BYTE_COST]. Also the cost of including 1 byte of a log's value in a receipt.
+    pub blockchain_write_per_byte_cost: u64,
+    /// See [MPT_WRITE_PER_BYTE_COST].
+    pub mpt_write_per_byte_cost: u64,
+    /// See [MPT_READ_PER_BYTE_COST].
+    pub mpt_read_per_byte_cost: u64,
+    /// See [MPT_TRAVERSE_PER_BYTE_COST].
+    pub mpt_traverse_per_byte_cost: u64,
+    /// See [MPT_REHASH_PER_BYTE_COST].
+    pub mpt_rehash_per_byte_cost: u64,
+    /// See [MPT_WRITE_REFUND_PROPORTION]. In percent.
+    pub mpt_write_refund_proportion: u64,
+    /// See [MPT_GET_CODE_DISCOUNT_PROPORTION]. In percent.
+    pub mpt_get_code_discount_proportion: u64,
+    /// See [STORAGE_NEW_KEY_SURCHARGE].
+    pub new_key_surcharge: u64,
+    /// See [BLOCK_PROPOSER_READ_COST].
+    pub block_proposer_read_cost: u64,
+    /// See [BLOCK_RANDOM_BYTES_READ_COST].
+    pub block_random_bytes_read_cost: u64,
+    /// See [GAS_REMAINING_READ_COST].
+    pub gas_remaining_read_cost: u64,
+    /// See [CRYPTO_SHA256_PER_BYTE]. Also charged for each byte of a log's topic.
+    pub crypto_sha256_per_byte: u64,
+    /// See [CRYPTO_KECCAK256_PER_BYTE].
+    pub crypto_keccak256_per_byte: u64,
+    /// See [CRYPTO_RIPEMD160_PER_BYTE].
+    pub crypto_ripemd160_per_byte: u64,
+    /// See [CRYPTO_ED25519_PER_BYTE].
+    pub crypto_ed25519_per_byte: u64,
+}
+
+impl Default for GasSchedule {
+    fn default() -> Self {
+        Self::MAINNET
+    }
+}
+
+impl GasSchedule {
+    /// The gas schedule of the Mainnet Protocol, made up of the constants in [constants](crate::gas::constants).
+    pub const MAINNET: GasSchedule = GasSchedule {
+        wasm_memory_write_per64_bits_cost: WASM_MEMORY_WRITE_PER64_BITS_COST,
+        wasm_memory_read_per64_bits_cost: WASM_MEMORY_READ_PER64_BITS_COST,
+        blockchain_write_per_byte_cost: BLOCKCHAIN_WRITE_PER_BYTE_COST,
+        mpt_write_per_byte_cost: MPT_WRITE_PER_BYTE_COST,
+        mpt_read_per_byte_cost: MPT_READ_PER_BYTE_COST,
+        mpt_traverse_per_byte_cost: MPT_TRAVERSE_PER_BYTE_COST,
+        mpt_rehash_per_byte_cost: MPT_REHASH_PER_BYTE_COST,
+        mpt_write_refund_proportion: MPT_WRITE_REFUND_PROPORTION,
+        mpt_get_code_discount_proportion: MPT_GET_CODE_DISCOUNT_PROPORTION,
+        new_key_surcharge: STORAGE_NEW_KEY_SURCHARGE,
+        block_proposer_read_cost: BLOCK_PROPOSER_READ_COST,
+        block_random_bytes_read_cost: BLOCK_RANDOM_BYTES_READ_COST,
+        gas_remaining_read_cost: GAS_REMAINING_READ_COST,
+        crypto_sha256_per_byte: CRYPTO_SHA256_PER_BYTE,
+        crypto_keccak256_per_byte: CRYPTO_KECCAK256_PER_BYTE,
+        crypto_ripemd160_per_byte: CRYPTO_RIPEMD160_PER_BYTE,
+        crypto_ed25519_per_byte: CRYPTO_ED25519_PER_BYTE,
+    };
+
+    /* ↓↓↓ Accessing Wasm memory from host functions ↓↓↓ */
+
+    /// See [wasm_memory_read_cost](crate::gas::wasm_memory_read_cost).
+    pub const fn wasm_memory_read_cost(&self, len: usize) -> u64 {
+        let cost = ceil_div_8(len as u64).saturating_mul(self.wasm_memory_read_per64_bits_cost);
+        if cost == 0 {
+            return 1;
+        } // = max(cost, 1) to make sure charging for a non-zero cost
+        cost
+    }
+
+    /// See [wasm_memory_write_cost](crate::gas::wasm_memory_write_cost).
+    pub const fn wasm_memory_write_cost(&self, len: usize) -> u64 {
+        let cost = ceil_div_8(len as u64).saturating_mul(self.wasm_memory_write_per64_bits_cost);
+        if cost == 0 {
+            return 1;
+        } // = max(cost, 1) to make sure charging for a non-zero cost
+        cost
+    }
+
+    /* ↓↓↓ Transaction-related data storage ↓↓↓ */
+
+    /// See [tx_inclusion_cost_v1](crate::gas::tx_inclusion_cost_v1).
+    pub fn tx_inclusion_cost_v1(&self, tx_size: usize, commands: &Vec<CommandKind>) -> u64 {
+        (tx_size as u64)
+            .saturating_add(minimum_receipt_size_v1(commands))
+            .saturating_mul(self.blockchain_write_per_byte_cost)
+            .saturating_add(self.inclusion_rw_key_cost())
+    }
+
+    /// See [tx_inclusion_cost_v2](crate::gas::tx_inclusion_cost_v2).
+    pub fn tx_inclusion_cost_v2(&self, tx_size: usize, commands: &Vec<CommandKind>) -> u64 {
+        (tx_size as u64)
+            .saturating_add(minimum_receipt_size_v2(commands))
+            .saturating_mul(self.blockchain_write_per_byte_cost)
+            .saturating_add(self.inclusion_rw_key_cost())
+    }
+
+    /// Cost of the 5 read-write operations on the Account Trie which are part of the inclusion cost.
+    const fn inclusion_rw_key_cost(&self) -> u64 {
+        (
+            // Read cost
+            self.get_cost_traverse(ACCOUNT_TRIE_KEY_LENGTH)
+                .saturating_add(self.get_cost_read(8))
+                // Write cost
+                .saturating_add(self.set_cost_write_new_value(8))
+                .saturating_add(self.set_cost_rehash(ACCOUNT_TRIE_KEY_LENGTH))
+        )
+        .saturating_mul(5)
+    }
+
+    /// See [blockchain_storage_cost](crate::gas::blockchain_storage_cost).
+    pub const fn blockchain_storage_cost(&self, data_len: usize) -> u64 {
+        // data_len * C_txdata
+        (data_len as u64).saturating_mul(self.blockchain_write_per_byte_cost)
+    }
+
+    /// See [blockchain_log_cost](crate::gas::blockchain_log_cost).
+    pub const fn blockchain_log_cost(&self, topic_len: usize, val_len: usize) -> u64 {
+        let topic_len = topic_len as u64;
+        let val_len = val_len as u64;
+        let log_len = topic_len.saturating_add(val_len);
+        let log_topic_per_byte_cost = self
+            .crypto_sha256_per_byte
+            .saturating_add(self.blockchain_write_per_byte_cost);
+
+        // Ceil(l/8) * C_wasmread
+        (ceil_div_8(log_len).saturating_mul(self.wasm_memory_read_per64_bits_cost))
+            // t * (C_sha256 + Z)
+            .saturating_add(topic_len.saturating_mul(log_topic_per_byte_cost))
+            // v * Z
+            .saturating_add(val_len.saturating_mul(self.blockchain_write_per_byte_cost))
+    }
+
+    /* ↓↓↓ World state storage and access ↓↓↓ */
+
+    /// See [get_cost_traverse](crate::gas::get_cost_traverse).
+    pub const fn get_cost_traverse(&self, key_len: usize) -> u64 {
+        (key_len as u64).saturating_mul(self.mpt_traverse_per_byte_cost)
+    }
+
+    /// See [get_cost_read](crate::gas::get_cost_read).
+    pub const fn get_cost_read(&self, value_len: usize) -> u64 {
+        (value_len as u64).saturating_mul(self.mpt_read_per_byte_cost)
+    }
+
+    /// See [discount_code_read](crate::gas::discount_code_read).
+    pub const fn discount_code_read(&self, code_read_cost: u64) -> u64 {
+        code_read_cost
+            .saturating_mul(self.mpt_get_code_discount_proportion)
+            .saturating_div(100)
+    }
+
+    /// See [set_cost_delete_old_value](crate::gas::set_cost_delete_old_value).
+    #[allow(clippy::double_comparisons)]
+    pub const fn set_cost_delete_old_value(
+        &self,
+        key_len: usize,
+        old_val_len: usize,
+        new_val_len: usize,
+    ) -> u64 {
+        let old_val_len = old_val_len as u64; // (a)
+        let new_val_len = new_val_len as u64; // (b)
+        let refund_per_byte = self
+            .mpt_write_per_byte_cost
+            .saturating_mul(self.mpt_write_refund_proportion);
+        if (old_val_len > 0 || old_val_len == 0) && new_val_len > 0 {
+            // a * C_write * C_refund
+            old_val_len
+                .saturating_mul(refund_per_byte)
+                .saturating_div(100)
+        } else if old_val_len > 0 && new_val_len == 0 {
+            // (k + a) * C_write * C_refund
+            ((key_len as u64).saturating_add(old_val_len))
+                .saturating_mul(refund_per_byte)
+                .saturating_div(100)
+        } else {
+            // old_val_len == 0 && new_val_len == 0
+            0
+        }
+    }
+
+    /// See [set_cost_write_new_value](crate::gas::set_cost_write_new_value).
+    pub const fn set_cost_write_new_value(&self, new_val_len: usize) -> u64 {
+        // b * C_write
+        (new_val_len as u64).saturating_mul(self.mpt_write_per_byte_cost)
+    }
+
+    /// See [set_cost_rehash](crate::gas::set_cost_rehash).
+    pub const fn set_cost_rehash(&self, key_len: usize) -> u64 {
+        // k * C_rehash
+        (key_len as u64).saturating_mul(self.mpt_rehash_per_byte_cost)
+    }
+
+    /// See [set_cost_new_key](crate::gas::set_cost_new_key).
+    pub const fn set_cost_new_key(&self, old_val_len: usize, new_val_len: usize) -> u64 {
+        // a deletion, or an overwrite of an existing value, does not grow the World State
+        if old_val_len == 0 && new_val_len > 0 {
+            self.new_key_surcharge
+        } else {
+            0
+        }
+    }
+}
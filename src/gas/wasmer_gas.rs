@@ -20,18 +20,21 @@ use core::panic;
 use std::mem::MaybeUninit;
 
 use pchain_types::{blockchain::Log, cryptography::PublicAddress};
-use pchain_world_state::{VersionProvider, DB};
+use pchain_world_state::{NetworkAccountStorage, VersionProvider, DB, NETWORK_ADDRESS};
 use wasmer::Global;
 
 use crate::{
-    contract::{wasmer::memory::MemoryContext, ContractModule, SmartContractContext},
-    execution::cache::{CommandOutputCache, WorldStateCache},
-    types::TxnVersion,
+    contract::{
+        wasmer::memory::{MemoryAccessError, MemoryContext},
+        ContractModule, SmartContractContext,
+    },
+    execution::cache::{CommandOutputCache, StorageReads, WorldStateCache},
+    types::{RuntimeEvent, TxnVersion},
 };
 
 use super::{
     operations::{self, OperationReceipt},
-    GasMeter,
+    GasMeter, GasSchedule, GasUsed,
 };
 
 /// Source of truth for total gas used during a contract call execution.
@@ -90,6 +93,16 @@ impl WasmerGasGlobal {
         }
     }
 
+    /// sets the remaining gas to zero, which traps the Wasm execution at the start of its next block of opcodes. Does
+    /// nothing if the variable is not initialized, i.e. the contract call has finished.
+    pub fn exhaust(&self) {
+        if self.is_initialized {
+            unsafe {
+                self.wasmer_gas.assume_init_ref().set(0u64.into()).unwrap();
+            }
+        }
+    }
+
     /// check for initialization before accessing the Wasmer variable
     /// ### panics
     /// panics if the variable is not initialized
@@ -120,6 +133,10 @@ where
     command_output_cache: &'b mut CommandOutputCache,
     /// mutable reference to WorldStateCache from the global gas meter
     ws_cache: &'b mut WorldStateCache<'a, S, V>,
+    /// reference to the gas used by the current command in the global gas meter, for tallying gas by kind
+    gas_used: &'b GasUsed,
+    /// reference to the costs of the chargeable operations in the global gas meter
+    schedule: &'b GasSchedule,
 }
 
 impl<'a, 'b, S, M, V> HostFuncGasMeter<'a, 'b, S, M, V>
@@ -139,6 +156,8 @@ where
             wasmer_gas_global: wasmer_remaining_gas,
             ws_cache: &mut gas_meter.ws_cache,
             command_output_cache: &mut gas_meter.output_cache_of_current_command,
+            gas_used: &gas_meter.gas_used_for_current_command,
+            schedule: &gas_meter.schedule,
         }
     }
 
@@ -147,6 +166,11 @@ where
         self.wasmer_gas_global.gas()
     }
 
+    /// returns the costs of the chargeable operations
+    pub fn schedule(&self) -> &GasSchedule {
+        self.schedule
+    }
+
     /// method for manual gas deduction from WasmerRemainingGas
     pub fn deduct_gas(&mut self, amount: u64) -> u64 {
         self.wasmer_gas_global.subtract_gas(amount)
@@ -157,26 +181,76 @@ where
     }
 
     pub fn ws_get_storage_data(&mut self, address: PublicAddress, key: &[u8]) -> Option<Vec<u8>> {
-        let result = operations::ws_storage_data(self.version, self.ws_cache, address, key);
-        self.charge(result).filter(|v| !v.is_empty())
+        let result =
+            operations::ws_storage_data(self.schedule, self.version, self.ws_cache, address, key);
+        self.charge_read(result).filter(|v| !v.is_empty())
+    }
+
+    /// Same as [ws_get_storage_data](Self::ws_get_storage_data), but reads through a read-only
+    /// [StorageReader](crate::execution::cache::StorageReader), leaving the caches of the World State untouched.
+    /// Used by view calls, which cannot write. Values read are cached in `reads`, which is kept by the call.
+    pub fn ws_get_storage_data_read_only(
+        &self,
+        reads: &mut StorageReads,
+        address: PublicAddress,
+        key: &[u8],
+    ) -> Option<Vec<u8>> {
+        let mut reader = self.ws_cache.storage_reader(address, reads);
+        let result =
+            operations::ws_read_only_storage_data(self.schedule, self.version, &mut reader, key);
+        self.charge_read(result).filter(|v| !v.is_empty())
+    }
+
+    /// Get the length of a value in storage, charging the same read cost as reading the value.
+    pub fn ws_get_storage_data_len(&mut self, address: PublicAddress, key: &[u8]) -> Option<usize> {
+        let result = operations::ws_storage_data_len(
+            self.schedule,
+            self.version,
+            self.ws_cache,
+            address,
+            key,
+        );
+        self.charge_read(result).filter(|len| *len != 0)
     }
 
     /// Get the balance from read-write set. It balance is not found, gets from WS and caches it.
     pub fn ws_get_balance(&self, address: PublicAddress) -> u64 {
-        let result = operations::ws_balance(self.ws_cache, &address);
-        self.charge(result)
+        let result = operations::ws_balance(self.schedule, self.ws_cache, &address);
+        self.charge_read(result)
+    }
+
+    pub fn ws_contains_storage_data(&mut self, address: PublicAddress, key: &[u8]) -> bool {
+        let result = operations::ws_contains_storage_data(
+            self.schedule,
+            self.version,
+            self.ws_cache,
+            address,
+            key,
+        );
+        self.charge_read(result)
     }
 
     pub fn ws_set_storage_data(&mut self, address: PublicAddress, key: &[u8], value: Vec<u8>) {
-        let result =
-            operations::ws_set_storage_data(self.version, self.ws_cache, address, key, value);
-        self.charge(result);
+        let result = operations::ws_set_storage_data(
+            self.schedule,
+            self.version,
+            self.ws_cache,
+            address,
+            key,
+            value,
+        );
+        self.charge_write(result);
     }
 
     /// Sets balance in the WSCache. It does not write to WS immediately.
     pub fn ws_set_balance(&mut self, address: PublicAddress, value: u64) {
-        let result = operations::ws_set_balance(self.ws_cache, address, value);
-        self.charge(result);
+        let result = operations::ws_set_balance(self.schedule, self.ws_cache, address, value);
+        self.charge_write(result);
+        self.command_output_cache
+            .push_event(RuntimeEvent::BalanceChanged {
+                address,
+                balance: value,
+            });
     }
 
     pub fn ws_cached_contract(
@@ -184,34 +258,43 @@ where
         address: PublicAddress,
         sc_context: &SmartContractContext,
     ) -> Option<ContractModule> {
-        let result = operations::ws_cached_contract(self.ws_cache, sc_context, address);
-        self.charge(result)
+        let result =
+            operations::ws_cached_contract(self.schedule, self.ws_cache, sc_context, address);
+        self.charge_read(result)
     }
 
     /// write data to linear memory, charge the write cost and return the length
-    pub fn write_bytes(&self, value: Vec<u8>, val_ptr_ptr: u32) -> Result<u32, anyhow::Error> {
-        let result = operations::write_bytes(self.memory_ctx, value, val_ptr_ptr);
+    pub fn write_bytes(&self, value: Vec<u8>, val_ptr_ptr: u32) -> Result<u32, MemoryAccessError> {
+        let result = operations::write_bytes(self.schedule, self.memory_ctx, value, val_ptr_ptr);
         self.charge(result)
     }
 
     /// read data from linear memory and charge the read cost
-    pub fn read_bytes(&self, offset: u32, len: u32) -> Result<Vec<u8>, anyhow::Error> {
-        let result = operations::read_bytes(self.memory_ctx, offset, len);
+    pub fn read_bytes(&self, offset: u32, len: u32) -> Result<Vec<u8>, MemoryAccessError> {
+        let result = operations::read_bytes(self.schedule, self.memory_ctx, offset, len);
         self.charge(result)
     }
 
     pub fn command_output_append_log(&mut self, log: Log) {
-        let result =
-            operations::command_output_append_log(self.command_output_cache.logs.as_mut(), log);
-        self.charge(result)
+        if self.command_output_cache.record_events {
+            self.command_output_cache
+                .push_event(RuntimeEvent::ContractLog(log.clone()));
+        }
+        let result = operations::command_output_append_log(
+            self.schedule,
+            self.command_output_cache.logs.as_mut(),
+            log,
+        );
+        self.charge_write(result)
     }
 
     pub fn command_output_set_return_value(&mut self, return_value: Vec<u8>) {
         let result = operations::command_output_set_return_value(
+            self.schedule,
             self.command_output_cache.return_value.as_mut(),
             return_value,
         );
-        self.charge(result)
+        self.charge_write(result)
     }
 
     //
@@ -221,17 +304,17 @@ where
     //
 
     pub fn sha256(&self, input_bytes: Vec<u8>) -> Vec<u8> {
-        let result = operations::sha256(input_bytes);
+        let result = operations::sha256(self.schedule, input_bytes);
         self.charge(result)
     }
 
     pub fn keccak256(&self, input_bytes: Vec<u8>) -> Vec<u8> {
-        let result = operations::keccak256(input_bytes);
+        let result = operations::keccak256(self.schedule, input_bytes);
         self.charge(result)
     }
 
     pub fn ripemd(&self, input_bytes: Vec<u8>) -> Vec<u8> {
-        let result = operations::ripemd(input_bytes);
+        let result = operations::ripemd(self.schedule, input_bytes);
         self.charge(result)
     }
 
@@ -241,13 +324,57 @@ where
         signature: [u8; 64],
         pub_key: [u8; 32],
     ) -> Result<i32, anyhow::Error> {
-        let result = operations::verify_ed25519_signature(message, signature, pub_key);
+        let result =
+            operations::verify_ed25519_signature(self.schedule, message, signature, pub_key);
         self.charge(result)
     }
 
+    /// charges an operation within the Wasm environment, i.e. on linear memory or a cryptographic function
     fn charge<T>(&self, op_receipt: OperationReceipt<T>) -> T {
+        self.gas_used.tally_wasm(op_receipt.1);
+        self.deduct(op_receipt)
+    }
+
+    /// charges a read from the World State
+    fn charge_read<T>(&self, op_receipt: OperationReceipt<T>) -> T {
+        self.gas_used.tally_read(op_receipt.1);
+        self.deduct(op_receipt)
+    }
+
+    /// charges a write to the World State or the command output
+    fn charge_write<T>(&self, op_receipt: OperationReceipt<T>) -> T {
+        self.gas_used.tally_write(op_receipt.1);
+        self.deduct(op_receipt)
+    }
+
+    fn deduct<T>(&self, op_receipt: OperationReceipt<T>) -> T {
         self.wasmer_gas_global
             .subtract_gas(op_receipt.1.net_cost().0);
         op_receipt.0
     }
 }
+
+/// HostFuncGasMeter implements NetworkAccountStorage to expose *chargeable* read-write operations to the
+/// network account's storage to host functions.
+impl<'a, 'b, S, M, V> NetworkAccountStorage for HostFuncGasMeter<'a, 'b, S, M, V>
+where
+    S: DB + Send + Sync + Clone + 'static,
+    M: MemoryContext,
+    V: VersionProvider + Send + Sync + Clone,
+{
+    fn get(&mut self, key: &[u8]) -> Option<Vec<u8>> {
+        self.ws_get_storage_data(NETWORK_ADDRESS, key)
+    }
+
+    fn contains(&mut self, key: &[u8]) -> bool {
+        self.ws_contains_storage_data(NETWORK_ADDRESS, key)
+    }
+
+    fn set(&mut self, key: &[u8], value: Vec<u8>) {
+        self.ws_set_storage_data(NETWORK_ADDRESS, key, value)
+    }
+
+    fn delete(&mut self, key: &[u8]) {
+        self.ws_set_storage_data(NETWORK_ADDRESS, key, Vec::new())
+    }
+}
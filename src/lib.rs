@@ -50,6 +50,9 @@
 //! let result = pchain_runtime::Runtime::new().transition_v2(ws, tx, bp);
 //! ```
 
+pub mod block_applier;
+pub use block_applier::{AppliedBlock, BlockApplier, BlockApplierError, ReceiptRoot};
+
 pub mod commands;
 
 pub mod context;
@@ -57,19 +60,42 @@ pub mod context;
 pub mod contract;
 pub use contract::cbi_version::cbi_version;
 pub use contract::wasmer::cache::Cache;
+pub use contract::wasmer::store::CompilerBackend;
+#[cfg(feature = "host-function-overrides")]
+pub use contract::overrides::HostFunctionOverrides;
 
 pub mod error;
 pub use error::TransitionError;
 
 pub mod execution;
 
+pub mod fee_distribution;
+pub use fee_distribution::FeeDistributor;
+
 pub mod gas;
+
+pub mod observer;
+pub use observer::WorldStateObserver;
+
 pub mod rewards_formulas;
 
+pub mod state_rent;
+pub use state_rent::StateRentPolicy;
+
+#[cfg(any(test, feature = "test-helpers"))]
+pub mod test_support;
+#[cfg(any(test, feature = "test-helpers"))]
+pub use test_support::WorldStateBuilder;
+
 pub mod transition;
 pub use transition::{
-    Runtime, TransitionV1Result, TransitionV1ToV2Result, TransitionV2Result, ValidatorChanges,
+    receipt_v2_to_bytes, validator_changes_from_bytes, validator_changes_to_bytes, AccountView,
+    CommitMode, DepositView, PoolView, ReplayReport, Runtime, TransitionV1Result,
+    TransitionV1ToV2Result, TransitionV2BatchResult, TransitionV2Result, ValidatorChanges,
 };
 
 pub mod types;
-pub use types::{BlockProposalStats, BlockchainParams, CommandKind, ValidatorPerformance};
+pub use types::{
+    command_receipt_logs, BlockProposalStats, BlockchainParams, CommandKind, RuntimeEvent,
+    StateOverride, TxnVersion, ValidatorPerformance, WorldStateKey,
+};
@@ -0,0 +1,28 @@
+/*
+    Copyright © 2023, ParallelChain Lab
+    Licensed under the Apache License, Version 2.0: http://www.apache.org/licenses/LICENSE-2.0
+*/
+
+//! Hook for observing the reads and writes of the World State made during execution, e.g. for forensic analysis.
+//!
+//! A [WorldStateObserver] is set on the [Runtime](crate::Runtime) with
+//! [set_world_state_observer](crate::Runtime::set_world_state_observer). It is called on every read and write of a
+//! balance, CBI version, contract code or storage value, in the order in which they are made, including reads served
+//! from the caches of the Runtime and the balances written directly to the World State in the pre-Charge and Charge
+//! phases. Nonces, and checks for the existence of storage values, are not observed.
+//!
+//! The observer does not change gas or results. If no observer is set, no key or value is built for it.
+
+use crate::types::WorldStateKey;
+
+/// Receives the reads and writes of the World State made during execution.
+///
+/// Values are passed as bytes: balances as a little-endian u64, CBI versions as a little-endian u32, and contract code
+/// and storage values as they are stored. A value which does not exist, or a storage value which is deleted, is None.
+pub trait WorldStateObserver: Send + Sync {
+    /// Called after `value` is read for `key`.
+    fn on_read(&self, key: &WorldStateKey, value: &Option<Vec<u8>>);
+
+    /// Called when `value` is written for `key`.
+    fn on_write(&self, key: &WorldStateKey, value: &Option<Vec<u8>>);
+}
@@ -9,7 +9,12 @@
 //! - The proportion of a transaction's base fee that will be transferred to the Treasury account ([`TREASURY_CUT_OF_BASE_FEE_NUM`]).
 //! - The calculation of total issuance to be rewarded to a single pool at the end of an epoch ([issuance]).
 //! - The Issuance Rate Reduction Factor ([ISSUANCE_RATE_REDUCTION_FACTOR]).
-//! - Calculation of pool reward and stake reward.
+//! - Calculation of pool reward and stake reward, also per transaction version through [RewardsFormula] ([for_version]).
+//! - The priority fee credited to the block proposer ([priority_fee_reward], [proposer_reward_v2]).
+
+use pchain_types::blockchain::TransactionV2;
+
+use crate::types::TxnVersion;
 
 /// Numerator of the Treasury's cut of a transaction's base fee.
 pub const TREASURY_CUT_OF_BASE_FEE_NUM: u64 = 20;
@@ -17,12 +22,31 @@ pub const TREASURY_CUT_OF_BASE_FEE_NUM: u64 = 20;
 /// Denominator of the Treasury's cut of a transaction's base fee.
 pub const TREASURY_CUT_OF_BASE_FEE_DENOM: u64 = 100;
 
+/// Calculate the number of grays credited to the block proposer in the Charge phase of a transaction
+/// which used `gas_used` gas and specified `priority_fee_per_gas`.
+pub const fn priority_fee_reward(gas_used: u64, priority_fee_per_gas: u64) -> u64 {
+    gas_used.saturating_mul(priority_fee_per_gas)
+}
+
+/// Calculate the priority fee that the proposer would receive for including `tx` in a block, assuming that the
+/// transaction uses its full gas limit. It does not execute the transaction, so block producers can use it to rank
+/// transactions before inclusion.
+///
+/// The returned amount is an upper bound of what the Charge phase credits to the proposer, which is
+/// [priority_fee_reward] over the actual gas used. The credit does not depend on the base fee of the block.
+pub fn proposer_reward_v2(tx: &TransactionV2) -> u64 {
+    priority_fee_reward(tx.gas_limit, tx.priority_fee_per_gas)
+}
+
+/// Number of epochs in a year, which [issuance] assumes in converting the annual issuance rate into a per-epoch rate.
+pub const EPOCHS_PER_YEAR: u64 = 365;
+
 /// Calculate the total issuance granted at the end of a particular epoch for a single pool, with the specified total
 /// power `pool_power`.
 ///
 /// ## Formulae
-/// - Issuance_n = (0.0835 * 0.85^(n/365)) / 365 if epoch number < [CONSTANT_ISSUANCE_EPOCH].
-/// - Issuance_n = 0.0150 / 365 otherwise.
+/// - Issuance_n = (0.0835 * 0.85^(n/[EPOCHS_PER_YEAR])) / [EPOCHS_PER_YEAR] if epoch number < [CONSTANT_ISSUANCE_EPOCH].
+/// - Issuance_n = 0.0150 / [EPOCHS_PER_YEAR] otherwise.
 ///
 /// ## Return value
 ///
@@ -37,19 +61,22 @@ pub const TREASURY_CUT_OF_BASE_FEE_DENOM: u64 = 100;
 pub const fn issuance(epoch_number: u64, pool_power: u64) -> (u128, u128) {
     if epoch_number as usize >= CONSTANT_ISSUANCE_EPOCH {
         // 15 = 0.015 mutliplied by 1_000
-        return (pool_power as u128 * 15, 365 * 1_000);
+        return (pool_power as u128 * 15, EPOCHS_PER_YEAR as u128 * 1_000);
     }
     // 835 = 0.0835 mutliplied by 10_000 x value in ISSUANCE_REDUCTION_FACTOR multiplied by 10_000
     let rate = 835 * ISSUANCE_RATE_REDUCTION_FACTOR[epoch_number as usize];
-    (pool_power as u128 * rate as u128, 365 * 100_000_000)
+    (
+        pool_power as u128 * rate as u128,
+        EPOCHS_PER_YEAR as u128 * 100_000_000,
+    )
 }
 
 /// After this epoch (and including this epoch), the issuance rate will stop decreasing and become a constant (as computed by the
 /// special case in [`issuance`].)
 pub const CONSTANT_ISSUANCE_EPOCH: usize = 3650;
 
-/// The result of computing `0.85^(n/365) * 10000` for `n = 0, 1, ..., 3649`. That is, `ISSUANCE_RATE_FACTORS[k]
-/// == 0.85^(k/365) * 10000`.
+/// The result of computing `0.85^(n/365) * 10000` for `n = 0, 1, ..., 3649`, where 365 is [EPOCHS_PER_YEAR]. That is,
+/// `ISSUANCE_RATE_FACTORS[k] == 0.85^(k/365) * 10000`.
 ///
 /// This corresponds to the symbol "E_ireduct" in the ParallelChain Protocol specification.
 pub const ISSUANCE_RATE_REDUCTION_FACTOR: [u64; CONSTANT_ISSUANCE_EPOCH] = [
@@ -326,6 +353,20 @@ pub const fn pool_reward(
 /// 1. The number of grays rewarded to the stake.
 /// 2. The commission fee to be paid to the pool operator.
 ///
+/// ## Rounding
+///
+/// Both amounts are computed from the exact product of the inputs and then rounded down:
+///
+/// ```text
+/// Reward = floor(PoolReward * StakePower / TotalStakes) - CommissionFee
+/// CommissionFee = floor(CommissionRate * PoolReward * StakePower / (100 * TotalStakes))
+/// ```
+///
+/// Rounding down never credits more grays than the pool reward. The fractional part of the commission fee stays with
+/// the stake, so the commission fee is zero whenever `CommissionRate * PoolReward * StakePower` is less than
+/// `100 * TotalStakes`, and the pool operator then receives no commission from the stake. For example, a stake entitled
+/// to 19 grays of the pool reward at a 1% commission rate keeps all 19 grays.
+///
 /// ## Safety
 ///
 /// - `commission_rate` must be a percentage (i.e. <= 100).
@@ -349,6 +390,98 @@ pub const fn stake_reward(
     )
 }
 
+/// The epoch reward formulas of a protocol version, i.e., [pool_reward] and [stake_reward] as they apply to
+/// transactions of that version. Use [for_version] to get the formulas of a version, for example to compute the
+/// expected rewards of a historical epoch offline.
+pub trait RewardsFormula: Send + Sync {
+    /// Calculate the total number of grays rewarded to the pool at the end of an epoch. See [pool_reward].
+    fn pool_reward(
+        &self,
+        current_epoch: u64,
+        pool_power: u64,
+        actual_num_of_blocks_proposed: u32,
+        expected_num_of_blocks_proposed: u32,
+    ) -> u64;
+
+    /// Calculate the number of grays rewarded to a single stake in a pool at the end of an epoch, as well as the
+    /// commission fee to-be-paid by the stake to the pool operator. See [stake_reward].
+    fn stake_reward(
+        &self,
+        pool_reward: u64,
+        commission_rate: u8,
+        stake_power: u64,
+        total_stakes: u64,
+    ) -> (u64, u64);
+}
+
+/// Epoch reward formulas applied by NextEpoch in [TransactionV1](pchain_types::blockchain::TransactionV1).
+pub struct RewardsFormulaV1;
+
+impl RewardsFormula for RewardsFormulaV1 {
+    fn pool_reward(
+        &self,
+        current_epoch: u64,
+        pool_power: u64,
+        actual_num_of_blocks_proposed: u32,
+        expected_num_of_blocks_proposed: u32,
+    ) -> u64 {
+        pool_reward(
+            current_epoch,
+            pool_power,
+            actual_num_of_blocks_proposed,
+            expected_num_of_blocks_proposed,
+        )
+    }
+
+    fn stake_reward(
+        &self,
+        pool_reward: u64,
+        commission_rate: u8,
+        stake_power: u64,
+        total_stakes: u64,
+    ) -> (u64, u64) {
+        stake_reward(pool_reward, commission_rate, stake_power, total_stakes)
+    }
+}
+
+/// Epoch reward formulas applied by NextEpoch in [TransactionV2]. They are currently the same as [RewardsFormulaV1].
+pub struct RewardsFormulaV2;
+
+impl RewardsFormula for RewardsFormulaV2 {
+    fn pool_reward(
+        &self,
+        current_epoch: u64,
+        pool_power: u64,
+        actual_num_of_blocks_proposed: u32,
+        expected_num_of_blocks_proposed: u32,
+    ) -> u64 {
+        pool_reward(
+            current_epoch,
+            pool_power,
+            actual_num_of_blocks_proposed,
+            expected_num_of_blocks_proposed,
+        )
+    }
+
+    fn stake_reward(
+        &self,
+        pool_reward: u64,
+        commission_rate: u8,
+        stake_power: u64,
+        total_stakes: u64,
+    ) -> (u64, u64) {
+        stake_reward(pool_reward, commission_rate, stake_power, total_stakes)
+    }
+}
+
+/// Get the epoch reward formulas of the transaction version `version`.
+pub fn for_version(version: TxnVersion) -> &'static dyn RewardsFormula {
+    match version {
+        TxnVersion::V1 => &RewardsFormulaV1,
+        TxnVersion::V2 => &RewardsFormulaV2,
+    }
+}
+
 /// Test whether the methods `pool_reward` and `stake_reward` computes the correct result when given some boundary inputs. The
 /// boundary inputs specifically tested are:
 /// 1. `actual_num_of_blocks_proposed == 0`: pool reward should be zero.
@@ -380,3 +513,41 @@ fn test_boundary_inputs() {
     assert_eq!(0, reward_to_stake);
     assert_eq!(max_pool_reward, commission_fee);
 }
+
+/// Test that a commission fee which truncates to zero is not paid to the pool operator, and that the stake keeps its
+/// whole share of the pool reward.
+#[test]
+fn test_commission_fee_truncated_to_zero() {
+    // pool reward of 22 grays, shared by a stake of power 90_000 and the operator's own stake of power 10_000
+    let (reward_to_stake, commission_fee) = stake_reward(22, 1, 90_000, 100_000);
+    assert_eq!(19, reward_to_stake);
+    assert_eq!(0, commission_fee);
+
+    // the operator receives only the reward of its own stake
+    let (reward_to_operator, _) = stake_reward(22, 0, 10_000, 100_000);
+    assert_eq!(2, reward_to_operator + commission_fee);
+
+    // the commission fee becomes non-zero once the product reaches 100 * total_stakes
+    assert_eq!((99, 0), stake_reward(99, 1, 1, 1));
+    assert_eq!((99, 1), stake_reward(100, 1, 1, 1));
+}
+
+/// Test that the formulas returned by [for_version] compute the same rewards for a single pool as [pool_reward] and
+/// [stake_reward].
+#[test]
+fn test_for_version_matches_formulas() {
+    for version in [TxnVersion::V1, TxnVersion::V2] {
+        let formula = for_version(version);
+        for (epoch, blocks_proposed) in
+            [(0, 8640), (100, 4320), (CONSTANT_ISSUANCE_EPOCH as u64, 0)]
+        {
+            let expected_pool_reward = pool_reward(epoch, 1_000_000_000, blocks_proposed, 8640);
+            let pool_reward = formula.pool_reward(epoch, 1_000_000_000, blocks_proposed, 8640);
+            assert_eq!(expected_pool_reward, pool_reward);
+            assert_eq!(
+                stake_reward(pool_reward, 5, 400_000_000, 1_000_000_000),
+                formula.stake_reward(pool_reward, 5, 400_000_000, 1_000_000_000)
+            );
+        }
+    }
+}
@@ -0,0 +1,25 @@
+/*
+    Copyright © 2023, ParallelChain Lab
+    Licensed under the Apache License, Version 2.0: http://www.apache.org/licenses/LICENSE-2.0
+*/
+
+//! Pluggable policy for charging a fee on the growth of state, also known as state rent.
+//!
+//! A [StateRentPolicy] is set on the [Runtime](crate::Runtime) with
+//! [set_state_rent_policy](crate::Runtime::set_state_rent_policy). In the Charge phase of every transaction, the policy
+//! is given the net change in the size of the World State caused by the transaction, and the fee it returns is
+//! transferred from the signer to the Treasury account. No fee is charged if no policy is set.
+//!
+//! The size of the World State counts contract code, as well as the keys and values of contract storage. Balances,
+//! nonces and CBI versions have a fixed size, and are not counted.
+
+/// Computes the state rent of a transaction.
+///
+/// Implementations must be deterministic: the same size delta must always result in the same fee.
+pub trait StateRentPolicy: Send + Sync {
+    /// Returns the fee, in grays, for a transaction which changed the size of the World State by `size_delta` bytes.
+    /// `size_delta` is negative if the transaction shrank the World State.
+    ///
+    /// The fee is capped at the balance of the signer remaining after the Charge phase.
+    fn rent(&self, size_delta: i64) -> u64;
+}
@@ -0,0 +1,126 @@
+/*
+    Copyright © 2023, ParallelChain Lab
+    Licensed under the Apache License, Version 2.0: http://www.apache.org/licenses/LICENSE-2.0
+*/
+
+//! Helpers for setting up a World State in tests of applications built on the Runtime.
+//!
+//! [WorldStateBuilder] writes account balances, pools, deposits and stakes directly into a World State, without
+//! executing transactions or charging gas. Pools and stakes are updated the same way as the Staking Commands update
+//! them, so pools are kept in the Next Validator Pools (NVP) ordered by power. The Previous and current Validator
+//! Pools are only populated by a [Next Epoch](pchain_types::blockchain::Command::NextEpoch) transition.
+
+use pchain_types::cryptography::PublicAddress;
+use pchain_world_state::{NetworkAccount, PoolKey, VersionProvider, WorldState, DB};
+
+use crate::{
+    commands::{
+        protocol::NetworkAccountWorldState,
+        staking::{increase_stake_power, stake_of_pool},
+    },
+    execution::cache::WorldStateCache,
+};
+
+/// Builds a World State with balances, pools, deposits and stakes for testing. See the
+/// [module-level documentation](self).
+///
+/// ```ignore
+/// let ws = WorldStateBuilder::new(ws)
+///     .with_balance(owner, 500_000_000)
+///     .with_pool(operator, 1)
+///     .with_deposit(operator, owner, 90_000, false)
+///     .with_stake(operator, owner, 90_000)
+///     .build();
+/// ```
+///
+/// The builder does not check that stakes are backed by deposits, or that deposits are funded by balances. Use
+/// [validate_world_state](crate::commands::protocol::validate_world_state) to check the staking invariants of the
+/// built World State.
+pub struct WorldStateBuilder<'a, S, V>
+where
+    S: DB + Send + Sync + Clone + 'static,
+    V: VersionProvider + Send + Sync + Clone + 'static,
+{
+    ws_cache: WorldStateCache<'a, S, V>,
+}
+
+impl<'a, S, V> WorldStateBuilder<'a, S, V>
+where
+    S: DB + Send + Sync + Clone + 'static,
+    V: VersionProvider + Send + Sync + Clone + 'static,
+{
+    /// Creates a WorldStateBuilder which adds to the contents of `ws`.
+    pub fn new(ws: WorldState<'a, S, V>) -> Self {
+        Self {
+            ws_cache: WorldStateCache::new(ws),
+        }
+    }
+
+    /// Sets the balance of the account at `address`.
+    pub fn with_balance(mut self, address: PublicAddress, balance: u64) -> Self {
+        self.ws_cache.set_balance(address, balance);
+        self
+    }
+
+    /// Creates a pool of `operator` with `commission_rate` (in percent) and no stakes, and inserts it into the NVP if
+    /// there is room.
+    ///
+    /// # Panics
+    /// If the pool already exists.
+    pub fn with_pool(mut self, operator: PublicAddress, commission_rate: u8) -> Self {
+        let mut state = NetworkAccountWorldState::from_ws_cache(&mut self.ws_cache);
+        let mut pool = NetworkAccount::pools(&mut state, operator);
+        assert!(!pool.exists(), "pool already exists");
+        pool.set_operator(operator);
+        pool.set_power(0);
+        pool.set_commission_rate(commission_rate);
+        pool.set_operator_stake(None);
+        let _ = NetworkAccount::nvp(&mut state).insert_extract(PoolKey { operator, power: 0 });
+        self
+    }
+
+    /// Sets the deposit of `owner` in the pool of `operator` to `balance`, with the `auto_stake_rewards` setting. The
+    /// balance of `owner` is not changed.
+    pub fn with_deposit(
+        mut self,
+        operator: PublicAddress,
+        owner: PublicAddress,
+        balance: u64,
+        auto_stake_rewards: bool,
+    ) -> Self {
+        let mut state = NetworkAccountWorldState::from_ws_cache(&mut self.ws_cache);
+        let mut deposit = NetworkAccount::deposits(&mut state, operator, owner);
+        deposit.set_balance(balance);
+        deposit.set_auto_stake_rewards(auto_stake_rewards);
+        self
+    }
+
+    /// Increases the stake of `owner` in the pool of `operator` by `power`, and the power of the pool with it. The
+    /// stake is the operator's own stake if `owner` is `operator`, and a delegated stake otherwise.
+    ///
+    /// # Panics
+    /// If the pool does not exist, or it already has the maximum number of delegated stakes, all with more power.
+    pub fn with_stake(mut self, operator: PublicAddress, owner: PublicAddress, power: u64) -> Self {
+        let mut state = NetworkAccountWorldState::from_ws_cache(&mut self.ws_cache);
+        let pool_power = NetworkAccount::pools(&mut state, operator)
+            .power()
+            .expect("pool does not exist");
+        let stake_power = stake_of_pool(&mut state, operator, owner);
+        increase_stake_power(
+            &mut state,
+            operator,
+            pool_power,
+            owner,
+            stake_power,
+            power,
+            true,
+        )
+        .expect("pool has no room for the stake");
+        self
+    }
+
+    /// Commits the changes to the World State and returns it.
+    pub fn build(self) -> WorldState<'a, S, V> {
+        self.ws_cache.commit_to_world_state()
+    }
+}
@@ -20,29 +20,51 @@
 //! 
 //! Besides the different versions of the transition function, Runtime also offers the methods [view_v1](Runtime::view_v1)
 //! [view_v2](Runtime::view_v2). These execute [view calls](https://github.com/parallelchain-io/parallelchain-protocol/blob/master/Contracts.md#view-calls).
+//! [accounts_snapshot](Runtime::accounts_snapshot) reads the balances and nonces of many accounts at once.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{atomic::AtomicBool, Arc},
+    time::Duration,
+};
 
 use pchain_types::{
     blockchain::{
-        Command, CommandReceiptV1, CommandReceiptV2, ReceiptV1, ReceiptV2, TransactionV1,
-        TransactionV2,
+        Command, CommandReceiptV1, CommandReceiptV2, ExitCodeV2, ReceiptV1, ReceiptV2,
+        TransactionV1, TransactionV2,
     },
-    cryptography::PublicAddress,
+    cryptography::{PublicAddress, Sha256Hash},
+    runtime::CallInput,
+};
+use pchain_world_state::{
+    NetworkAccount, VersionProvider, WorldState, DB, NETWORK_ADDRESS, V1, V2,
 };
-use pchain_world_state::{VersionProvider, WorldState, DB, V1, V2};
 
 use crate::{
+    cbi_version,
+    commands::protocol,
     context::TransitionContext,
-    contract::SmartContractContext,
+    contract::{ContractModule, SmartContractContext},
     execution::{
-        execute_commands::{execute_commands_v1, execute_commands_v2},
+        cache::WorldStateCache,
+        execute_commands::{
+            execute_commands_v1, execute_commands_v2, execute_commands_v2_uncommitted,
+        },
         // execute_commands::{execute_commands_v1, execute_commands_v2},
         execute_next_epoch::{execute_next_epoch_v1, execute_next_epoch_v2},
         execute_view::{execute_view_v1, execute_view_v2},
         state::ExecutionState,
     },
-    types::{TxnMetadata, TxnVersion},
-    BlockchainParams, Cache, TransitionError,
+    gas::{GasBreakdown, GasSchedule, OpcodeGasProfile},
+    types::{
+        write_set, write_set_commitment, RuntimeEvent, StateOverride, TxnMetadata, TxnVersion,
+        WorldStateKey,
+    },
+    BlockchainParams, Cache, CompilerBackend, FeeDistributor, StateRentPolicy, TransitionError,
+    WorldStateObserver,
 };
+#[cfg(feature = "host-function-overrides")]
+use crate::HostFunctionOverrides;
 
 /// A Runtime for state transition.
 /// Instances share the same execution logic,
@@ -51,6 +73,43 @@ use crate::{
 #[derive(Default)]
 pub struct Runtime {
     sc_context: SmartContractContext,
+    compute_write_set_commitment: bool,
+    gas_accounting: bool,
+    balance_changes: bool,
+    commit_mode: CommitMode,
+    block_gas_limit: Option<u64>,
+    state_rent_policy: Option<Arc<dyn StateRentPolicy>>,
+    fee_distributor: Option<Arc<dyn FeeDistributor>>,
+    gas_schedule: GasSchedule,
+    world_state_observer: Option<Arc<dyn WorldStateObserver>>,
+    skip_nonce_check: bool,
+    command_time_budget: Option<Duration>,
+}
+
+/// Gas limit of the call executed by [run_contract_method](Runtime::run_contract_method).
+pub const RUN_CONTRACT_METHOD_GAS_LIMIT: u64 = 10_000_000_000;
+
+/// Storage without any data, backing the World State of [run_contract_method](Runtime::run_contract_method).
+#[derive(Clone)]
+struct EmptyStorage;
+
+impl DB for EmptyStorage {
+    fn get(&self, _key: &[u8]) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+/// Defines when [transition_v2_batch](Runtime::transition_v2_batch) commits the changes of each transaction to the
+/// World State.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CommitMode {
+    /// Commit to the World State after every transaction.
+    #[default]
+    Eager,
+    /// Keep the changes of the transactions in a cache, and commit to the World State once at the end of the batch.
+    /// Transactions containing a [NextEpoch](pchain_types::blockchain::Command::NextEpoch) command are still
+    /// committed individually.
+    Lazy,
 }
 
 impl Runtime {
@@ -74,6 +133,222 @@ impl Runtime {
         self
     }
 
+    /// Specify the compiler which translates contract bytecode into machine code. Defaults to
+    /// [Singlepass](CompilerBackend::Singlepass). See [CompilerBackend] for the requirements on the choice.
+    ///
+    /// The smart contract cache records the backend which compiled each contract, and contracts compiled with another
+    /// backend are compiled again instead of being loaded.
+    pub fn set_compiler_backend(mut self, compiler_backend: CompilerBackend) -> Self {
+        self.sc_context.compiler_backend = compiler_backend;
+        self
+    }
+
+    /// Specify the maximum length of a key that a contract may pass to the `set` and `get` host functions. Oversized keys
+    /// are never truncated or hashed: the host function fails with [KeyTooLong](crate::TransitionError::KeyTooLong) instead.
+    /// Defaults to [DEFAULT_MAX_STORAGE_KEY_LENGTH](crate::contract::context::DEFAULT_MAX_STORAGE_KEY_LENGTH).
+    pub fn set_max_storage_key_length(mut self, max_key_length: usize) -> Self {
+        self.sc_context.max_storage_key_length = Some(max_key_length);
+        self
+    }
+
+    /// Specify the maximum depth of contract-to-contract calls within a Call command. The contract called by the Call
+    /// command is at depth 0, and each contract it calls, directly or indirectly, is one deeper. A call beyond the
+    /// maximum depth fails with [CallDepthExceeded](crate::TransitionError::CallDepthExceeded), which fails the Call
+    /// command. Defaults to [DEFAULT_MAX_CALL_DEPTH](crate::contract::context::DEFAULT_MAX_CALL_DEPTH).
+    pub fn set_max_call_depth(mut self, max_call_depth: u32) -> Self {
+        self.sc_context.max_call_depth = Some(max_call_depth);
+        self
+    }
+
+    /// Specify the maximum total value that contracts may transfer within a single transaction, counting both the
+    /// `transfer` host function and the amount of contract-to-contract calls. Value sent by the signer in a Call command
+    /// is not counted. A transfer beyond the limit fails with
+    /// [TransferLimitExceeded](crate::TransitionError::TransferLimitExceeded). Unlimited by default.
+    pub fn set_max_contract_transfer_value(mut self, max_value: u64) -> Self {
+        self.sc_context.max_contract_transfer_value = Some(max_value);
+        self
+    }
+
+    /// Specify the maximum length, in bytes, of the contract bytecode in a Deploy command, e.g. to bound the memory used
+    /// to compile contracts. Longer bytecode is rejected before it is compiled, and the Deploy command fails with
+    /// [ContractTooLarge](crate::TransitionError::ContractTooLarge). The protocol has no limit of its own, so it is
+    /// unlimited by default.
+    pub fn set_max_contract_size(mut self, max_contract_size: usize) -> Self {
+        self.sc_context.max_contract_size = Some(max_contract_size);
+        self
+    }
+
+    /// Specify the maximum number of commands that contracts may defer, e.g. with `defer_stake_deposit`, within a single
+    /// transaction, counting the commands deferred by all Call commands of the transaction. This bounds the breadth of
+    /// the commands spawned by contracts, as [set_max_call_depth](Self::set_max_call_depth) bounds their depth. A
+    /// contract which defers a command beyond the limit fails with
+    /// [SpawnedCommandLimitExceeded](crate::TransitionError::SpawnedCommandLimitExceeded), which fails its Call
+    /// command, and the transaction is charged for the commands completed before it. Unlimited by default.
+    pub fn set_max_spawned_commands(mut self, max_spawned_commands: u32) -> Self {
+        self.sc_context.max_spawned_commands = Some(max_spawned_commands);
+        self
+    }
+
+    /// Specify multipliers, in percent, of the gas used by Wasm execution of specific contracts, e.g. 150 to charge
+    /// calls to a contract 1.5 times the gas of the schedule. The multiplier applies to the gas used by the contract's
+    /// Wasm opcodes in Call commands and contract-to-contract calls, including the gas of the contracts it calls.
+    /// Host function and storage costs are unchanged. Contracts without a multiplier, and multipliers below 100, use
+    /// the gas schedule as is.
+    ///
+    /// Multipliers change the gas used by transactions, so all nodes must use the same multipliers.
+    pub fn set_contract_gas_multipliers(
+        mut self,
+        multipliers: HashMap<PublicAddress, u64>,
+    ) -> Self {
+        self.sc_context.gas_multipliers = Some(Arc::new(multipliers));
+        self
+    }
+
+    /// Specify the cumulative time that [transition_v2_batch](Runtime::transition_v2_batch) may spend compiling
+    /// contracts within a block. Once the time spent exceeds the budget, the remaining transactions of the block are
+    /// not executed, and are returned as [excluded](TransitionV2BatchResult::excluded). Contracts are always compiled
+    /// with the configured [CompilerBackend]. Unlimited by default.
+    ///
+    /// Compilation time depends on the wall clock, so like the [block gas limit](Runtime::set_block_gas_limit), the
+    /// budget is meant for proposing blocks. A [BlockApplier](crate::BlockApplier) rejects a block whose transactions
+    /// are not all executed.
+    pub fn set_block_compilation_time_budget(mut self, budget: Duration) -> Self {
+        self.sc_context.compilation_time_budget = Some(budget);
+        self
+    }
+
+    /// Specify whether [transition_v2](Runtime::transition_v2) should record the peak Wasm linear memory (in pages) used
+    /// by contract calls in each command, returned as [memory_profile](TransitionV2Result::memory_profile). Intended for
+    /// profiling contracts off-chain. Disabled by default.
+    pub fn set_memory_profiling(mut self, enabled: bool) -> Self {
+        self.sc_context.memory_profiling = enabled;
+        self
+    }
+
+    /// Specify [HostFunctionOverrides] which replace host functions imported by contracts, e.g. to stub block context
+    /// or capture logs in contract tests. Only available with the `host-function-overrides` feature.
+    #[cfg(feature = "host-function-overrides")]
+    pub fn set_host_function_overrides(mut self, overrides: HostFunctionOverrides) -> Self {
+        self.sc_context.host_function_overrides = Some(overrides);
+        self
+    }
+
+    /// Specify a flag which stops the execution of contracts when it is set, e.g. to enforce a wall-clock timeout on
+    /// view calls made on behalf of untrusted users. While a contract executes, the flag is checked about every
+    /// millisecond, and once it is set, the contract call traps at the next block of opcodes and the transition or view
+    /// call fails with [Interrupted](crate::TransitionError::Interrupted). An interrupted call uses all of its gas.
+    /// Execution outside contracts, e.g. of staking commands, is not interrupted.
+    ///
+    /// When the flag is set depends on the wall clock, so interrupted transitions are not deterministic and their
+    /// results must not be included in blocks. The flag is never cleared by the Runtime.
+    pub fn set_interrupt(mut self, interrupt: Arc<AtomicBool>) -> Self {
+        self.sc_context.interrupt = Some(interrupt);
+        self
+    }
+
+    /// Specify the wall-clock time allowed for each Call command, e.g. for fairness between the users of a shared RPC
+    /// server, whose calls may use the same gas but very different CPU time. The time starts when the contract is
+    /// instantiated, and is checked about every millisecond while the contract executes. Once it is used up, the
+    /// contract call traps at the next block of opcodes and fails with
+    /// [CommandTimeout](crate::TransitionError::CommandTimeout), using all of its gas.
+    ///
+    /// Wall-clock time is not deterministic, so the budget only applies to [view calls](Runtime::view_v2) and
+    /// [estimates](Runtime::estimate_v2). Transitions, whose results are included in blocks, ignore it.
+    pub fn set_command_time_budget(mut self, budget: Duration) -> Self {
+        self.command_time_budget = Some(budget);
+        self
+    }
+
+    /// Specify whether [transition_v2](Runtime::transition_v2) should compute a commitment hash over the write set it
+    /// produced, returned as [write_set_commitment](TransitionV2Result::write_set_commitment). Disabled by default to avoid
+    /// the hashing overhead.
+    pub fn set_write_set_commitment(mut self, enabled: bool) -> Self {
+        self.compute_write_set_commitment = enabled;
+        self
+    }
+
+    /// Specify whether [transition_v2](Runtime::transition_v2) and
+    /// [transition_v2_with_events](Runtime::transition_v2_with_events) should record how the gas used by each command
+    /// splits into World State reads, writes, host functions in the Wasm environment and Wasm opcodes, returned as
+    /// [gas_breakdown](TransitionV2Result::gas_breakdown). Intended for auditing gas usage. Disabled by default.
+    pub fn set_gas_accounting(mut self, enabled: bool) -> Self {
+        self.gas_accounting = enabled;
+        self
+    }
+
+    /// Specify whether transitions and dry runs should report the final balances of the accounts whose balances they
+    /// changed, returned by [balance_changes](TransitionV2Result::balance_changes). Disabled by default to avoid reading
+    /// the balances before the transaction in the Charge phase of every transaction.
+    pub fn set_balance_changes(mut self, enabled: bool) -> Self {
+        self.balance_changes = enabled;
+        self
+    }
+
+    /// Specify the [GasSchedule] which prices the operations that incur gas, e.g. to tune gas costs on a testnet.
+    /// Defaults to [GasSchedule::MAINNET]. The costs of Wasm opcodes are not part of the schedule and are unchanged.
+    ///
+    /// The schedule changes the gas used by transactions, so all nodes must use the same schedule.
+    pub fn set_gas_schedule(mut self, gas_schedule: GasSchedule) -> Self {
+        self.gas_schedule = gas_schedule;
+        self
+    }
+
+    /// Specify whether the nonce of a transaction must equal the nonce of its signer in the World State, in both
+    /// the pre-Charge phase and the NextEpoch command. Enabled by default. If disabled, a transaction with any nonce
+    /// is executed as if its nonce were correct, and the nonce of the signer is still incremented by one.
+    ///
+    /// **This is for simulation only, e.g. to execute the next transaction of an account whose nonce is not yet
+    /// known. It must never be disabled in a Runtime which produces or validates blocks**: doing so allows
+    /// transactions to be replayed.
+    pub fn set_nonce_check(mut self, enabled: bool) -> Self {
+        self.skip_nonce_check = !enabled;
+        self
+    }
+
+    /// Specify a [StateRentPolicy] which charges the signer of every transaction a fee for the change in state size
+    /// that the transaction causes. The fee is transferred to the Treasury account in the Charge phase. By default,
+    /// no state rent is charged.
+    pub fn set_state_rent_policy(mut self, state_rent_policy: Arc<dyn StateRentPolicy>) -> Self {
+        self.state_rent_policy = Some(state_rent_policy);
+        self
+    }
+
+    /// Specify a [FeeDistributor] which decides how the fees of every transaction are credited in the Charge phase.
+    /// Defaults to [DefaultFeeDistributor](crate::fee_distribution::DefaultFeeDistributor), which credits the priority
+    /// fee to the proposer and a cut of the base fee to the Treasury, and burns the rest.
+    pub fn set_fee_distributor(mut self, fee_distributor: Arc<dyn FeeDistributor>) -> Self {
+        self.fee_distributor = Some(fee_distributor);
+        self
+    }
+
+    /// Specify a [WorldStateObserver] which is called on every read and write of the World State made while executing
+    /// transactions and views, in the order in which they are made. The observer does not change gas or results. By
+    /// default, there is no observer.
+    pub fn set_world_state_observer(mut self, observer: Arc<dyn WorldStateObserver>) -> Self {
+        self.world_state_observer = Some(observer);
+        self
+    }
+
+    /// Specify when [transition_v2_batch](Runtime::transition_v2_batch) commits changes to the World State. Both modes
+    /// produce identical receipts and an identical final World State. Defaults to [CommitMode::Eager].
+    pub fn set_commit_mode(mut self, commit_mode: CommitMode) -> Self {
+        self.commit_mode = commit_mode;
+        self
+    }
+
+    /// Specify the maximum total gas used by the transactions that [transition_v2_batch](Runtime::transition_v2_batch)
+    /// executes, e.g. to pack a block. A transaction is executed only if the gas used by the transactions before it
+    /// plus its gas limit is within the block gas limit, so the limit is never exceeded. The first transaction which
+    /// does not fit, and all transactions after it, are not executed and are returned as
+    /// [excluded](TransitionV2BatchResult::excluded). Unlimited by default.
+    ///
+    /// The limit is meant for proposing blocks. A Runtime that applies blocks proposed by others, e.g. through a
+    /// [BlockApplier](crate::BlockApplier), should not set it.
+    pub fn set_block_gas_limit(mut self, block_gas_limit: u64) -> Self {
+        self.block_gas_limit = Some(block_gas_limit);
+        self
+    }
+
     /// state transition of world state (WS) from transaction (tx) and blockchain data (bd) as inputs.
     pub fn transition_v1<'a, S, V>(
         &self,
@@ -90,8 +365,7 @@ impl Runtime {
         let commands = tx.commands;
 
         // create transition context from world state
-        let mut ctx = TransitionContext::new(txn_meta.version, ws, tx.gas_limit);
-        ctx.sc_context = self.sc_context.clone();
+        let ctx = self.new_ctx(txn_meta.version, WorldStateCache::new(ws), tx.gas_limit);
 
         // initial state for transition
         let state = ExecutionState::new(txn_meta, bd, ctx);
@@ -104,6 +378,55 @@ impl Runtime {
         }
     }
 
+    /// validate_v2 performs stateless checks on the commands of `tx`, without reading World State or charging gas, so
+    /// that obviously-invalid transactions can be rejected cheaply, e.g. on entry to a mempool. The checks are:
+    /// - a [Next Epoch](Command::NextEpoch) Command must be the only Command in the transaction, otherwise
+    /// [InvalidNextEpochCommand](TransitionError::InvalidNextEpochCommand).
+    /// - the commission rate of a [Create Pool](Command::CreatePool) or [Set Pool Settings](Command::SetPoolSettings)
+    /// Command must be at most 100, otherwise [InvalidPoolPolicy](TransitionError::InvalidPoolPolicy).
+    ///
+    /// The checks are a subset of those in [transition_v2](Runtime::transition_v2), so a transaction which fails them
+    /// also fails in execution, though possibly with another error, e.g. from the pre-Charge phase. A transaction which
+    /// passes them can still fail in execution.
+    pub fn validate_v2(&self, tx: &TransactionV2) -> Result<(), TransitionError> {
+        if tx.commands.len() > 1 && tx.commands.iter().any(|c| matches!(c, Command::NextEpoch)) {
+            return Err(TransitionError::InvalidNextEpochCommand);
+        }
+
+        for command in &tx.commands {
+            let commission_rate = match command {
+                Command::CreatePool(input) => input.commission_rate,
+                Command::SetPoolSettings(input) => input.commission_rate,
+                _ => continue,
+            };
+            if commission_rate > 100 {
+                return Err(TransitionError::InvalidPoolPolicy);
+            }
+        }
+        Ok(())
+    }
+
+    /// inclusion_cost_v1 is the gas that the Pre-Charge phase of [transition_v1](Runtime::transition_v1) charges for
+    /// including `tx` in a block, with the [gas schedule](Runtime::set_gas_schedule) of the Runtime. See
+    /// [inclusion_cost_v2](Runtime::inclusion_cost_v2).
+    pub fn inclusion_cost_v1(&self, tx: &TransactionV1) -> u64 {
+        let txn_meta = TxnMetadata::from(tx);
+        self.gas_schedule
+            .tx_inclusion_cost_v1(txn_meta.size, &txn_meta.command_kinds)
+    }
+
+    /// inclusion_cost_v2 is the gas that the Pre-Charge phase of [transition_v2](Runtime::transition_v2) charges for
+    /// including `tx` in a block, with the [gas schedule](Runtime::set_gas_schedule) of the Runtime, e.g. for a wallet
+    /// to show the minimum cost of a transaction before it is signed. A gas limit below it fails with
+    /// [GasLimitTooLow](TransitionError::GasLimitTooLow). The cost depends on the serialized size of `tx`, including
+    /// its signature and hash, and on the kind of each Command, as the minimum size of a receipt differs between
+    /// kinds. The signature and hash have a fixed length, so they can be left as placeholders in an unsigned `tx`.
+    pub fn inclusion_cost_v2(&self, tx: &TransactionV2) -> u64 {
+        let txn_meta = TxnMetadata::from(tx);
+        self.gas_schedule
+            .tx_inclusion_cost_v2(txn_meta.size, &txn_meta.command_kinds)
+    }
+
     /// state transition of world state (WS) from transaction (tx) and blockchain data (bd) as inputs.
     pub fn transition_v2<'a, S, V>(
         &self,
@@ -120,21 +443,618 @@ impl Runtime {
         let commands = tx.commands;
 
         // create transition context from world state
-        let mut ctx = TransitionContext::new(txn_meta.version, ws, tx.gas_limit);
-        ctx.sc_context = self.sc_context.clone();
+        let mut ctx = self.new_ctx(txn_meta.version, WorldStateCache::new(ws), tx.gas_limit);
+        ctx.gas_meter
+            .output_cache_of_current_command
+            .record_gas_breakdown = self.gas_accounting;
 
         // initial state for transition
         let state = ExecutionState::new(txn_meta, bd, ctx);
 
         // initiate command execution
-        if commands.iter().any(|c| matches!(c, Command::NextEpoch)) {
+        let mut result = if commands.iter().any(|c| matches!(c, Command::NextEpoch)) {
             execute_next_epoch_v2(state, commands)
         } else {
             execute_commands_v2(state, commands)
+        };
+
+        if self.compute_write_set_commitment {
+            result.write_set_commitment = Some(write_set_commitment(&result.new_state));
+        }
+        result
+    }
+
+    /// Same as [transition_v2](Runtime::transition_v2), but also records the effects of the transaction as a
+    /// chronologically-ordered stream of [RuntimeEvent]s, returned as [events](TransitionV2Result::events).
+    ///
+    /// Effects of commands which are reverted because a command failed are not included. Events are not recorded for
+    /// transactions with a [Next Epoch](pchain_types::blockchain::Command::NextEpoch) Command.
+    pub fn transition_v2_with_events<'a, S, V>(
+        &self,
+        ws: WorldState<'a, S, V>,
+        tx: TransactionV2,
+        bd: BlockchainParams,
+    ) -> TransitionV2Result<'a, S, V>
+    where
+        S: DB + Send + Sync + Clone + 'static,
+        V: VersionProvider + Send + Sync + Clone + 'static,
+    {
+        // transaction inputs
+        let txn_meta = TxnMetadata::from(&tx);
+        let commands = tx.commands;
+
+        // create transition context from world state
+        let mut ctx = self.new_ctx(txn_meta.version, WorldStateCache::new(ws), tx.gas_limit);
+
+        if commands.iter().any(|c| matches!(c, Command::NextEpoch)) {
+            return execute_next_epoch_v2(ExecutionState::new(txn_meta, bd, ctx), commands);
+        }
+
+        ctx.gas_meter.output_cache_of_current_command.record_events = true;
+        ctx.gas_meter
+            .output_cache_of_current_command
+            .record_gas_breakdown = self.gas_accounting;
+        let mut result = execute_commands_v2(ExecutionState::new(txn_meta, bd, ctx), commands);
+
+        if self.compute_write_set_commitment {
+            result.write_set_commitment = Some(write_set_commitment(&result.new_state));
+        }
+        result
+    }
+
+    /// dry-runs the state transition of world state (WS) from transaction (tx) and blockchain data (bd) as inputs, e.g. to
+    /// estimate the gas used by `tx`. All phases are executed as in [transition_v2](Runtime::transition_v2), including
+    /// the nonce and balance checks of the pre-charge phase, so the receipt and error are identical to those of a real
+    /// transition, unless a Call command exceeds the [command time budget](Runtime::set_command_time_budget). The
+    /// changes are not written: `new_state` is `ws` as it was, and [dry_run](TransitionV2Result::dry_run) is set.
+    ///
+    /// A [Next Epoch](pchain_types::blockchain::Command::NextEpoch) transaction writes the World State directly, so it
+    /// is executed on a copy of `ws`.
+    pub fn estimate_v2<'a, S, V>(
+        &self,
+        ws: WorldState<'a, S, V>,
+        tx: TransactionV2,
+        bd: BlockchainParams,
+    ) -> TransitionV2Result<'a, S, V>
+    where
+        S: DB + Send + Sync + Clone + 'static,
+        V: VersionProvider + Send + Sync + Clone + 'static,
+    {
+        if tx.commands.iter().any(|c| matches!(c, Command::NextEpoch)) {
+            let result = self.transition_v2(ws.clone(), tx, bd);
+            return TransitionV2Result {
+                new_state: ws,
+                write_set_commitment: None,
+                dry_run: true,
+                ..result
+            };
+        }
+
+        // transaction inputs
+        let txn_meta = TxnMetadata::from(&tx);
+        let commands = tx.commands;
+
+        // create transition context from a copy of the world state, as the pre-charge and charge phases write to it
+        let mut ctx = self.new_ctx(
+            txn_meta.version,
+            WorldStateCache::new(ws.clone()),
+            tx.gas_limit,
+        );
+        ctx.sc_context.command_time_budget = self.command_time_budget;
+
+        // execute without committing, and discard the changes held in the cache
+        let state = ExecutionState::new(txn_meta, bd, ctx);
+        let result = execute_commands_v2_uncommitted(state, commands);
+        TransitionV2Result {
+            new_state: ws,
+            receipt: result.receipt,
+            error: result.error,
+            validator_changes: None,
+            write_set_commitment: None,
+            memory_profile: None,
+            opcode_gas_profile: None,
+            gas_breakdown: None,
+            events: None,
+            dry_run: true,
+            base_fee_paid: result.fees_paid.0,
+            priority_fee_paid: result.fees_paid.1,
+            balance_changes: result.balance_changes,
+        }
+    }
+
+    /// dry-runs the state transition of `tx` as [estimate_v2](Runtime::estimate_v2) does, on `ws` with the data in
+    /// `overrides` replacing the data in `ws`, e.g. to find out what a Call would do if an account had a different
+    /// balance or storage. All phases are executed as usual, including the balance checks of the pre-charge phase and
+    /// gas charging. Neither the overrides nor the changes of the transaction are written: `new_state` is `ws` as it
+    /// was, and [dry_run](TransitionV2Result::dry_run) is set.
+    ///
+    /// Overrides are applied in order, so a later override of the same data wins. Contracts whose code is overridden
+    /// are compiled from the overriding code, bypassing the smart contract cache.
+    ///
+    /// A [Next Epoch](pchain_types::blockchain::Command::NextEpoch) transaction writes the World State directly, so the
+    /// overrides are not applied to it, and it is dry-run as by [estimate_v2](Runtime::estimate_v2).
+    pub fn simulate_v2_with_overrides<'a, S, V>(
+        &self,
+        ws: WorldState<'a, S, V>,
+        tx: TransactionV2,
+        bd: BlockchainParams,
+        overrides: &[StateOverride],
+    ) -> TransitionV2Result<'a, S, V>
+    where
+        S: DB + Send + Sync + Clone + 'static,
+        V: VersionProvider + Send + Sync + Clone + 'static,
+    {
+        if tx.commands.iter().any(|c| matches!(c, Command::NextEpoch)) {
+            return self.estimate_v2(ws, tx, bd);
+        }
+
+        // apply the overrides to a cache over a copy of the world state, as the data before the transaction
+        let mut ws_cache = WorldStateCache::new(ws.clone());
+        for state_override in overrides {
+            match state_override {
+                StateOverride::Balance(address, balance) => {
+                    ws_cache.set_balance(*address, *balance);
+                }
+                StateOverride::CbiVersion(address, cbi_version) => {
+                    ws_cache.set_cbi_version(*address, *cbi_version);
+                }
+                StateOverride::ContractCode(address, code) => {
+                    ws_cache.set_contract_code(*address, code.clone());
+                }
+                StateOverride::StorageData(address, key, value) => {
+                    ws_cache.set_storage_data(*address, key, value.clone().unwrap_or_default());
+                }
+            }
+        }
+        ws_cache.checkpoint();
+
+        // transaction inputs
+        let txn_meta = TxnMetadata::from(&tx);
+        let commands = tx.commands;
+
+        // create transition context from the overridden world state cache
+        let mut ctx = self.new_ctx(txn_meta.version, ws_cache, tx.gas_limit);
+        if overrides
+            .iter()
+            .any(|state_override| matches!(state_override, StateOverride::ContractCode(..)))
+        {
+            // the smart contract cache is keyed by address, so it may hold the code which is overridden
+            ctx.sc_context.cache = None;
+        }
+        ctx.sc_context.command_time_budget = self.command_time_budget;
+
+        // execute without committing, and discard the changes held in the cache
+        let state = ExecutionState::new(txn_meta, bd, ctx);
+        let result = execute_commands_v2_uncommitted(state, commands);
+        TransitionV2Result {
+            new_state: ws,
+            receipt: result.receipt,
+            error: result.error,
+            validator_changes: None,
+            write_set_commitment: None,
+            memory_profile: None,
+            opcode_gas_profile: None,
+            gas_breakdown: None,
+            events: None,
+            dry_run: true,
+            base_fee_paid: result.fees_paid.0,
+            priority_fee_paid: result.fees_paid.1,
+            balance_changes: result.balance_changes,
+        }
+    }
+
+    /// state transition of world state (WS) from a batch of transactions (txs) in the same block, with blockchain data (bd)
+    /// as input. Transactions are applied in order, and the changes are committed according to the
+    /// [CommitMode](Runtime::set_commit_mode).
+    ///
+    /// An epoch ends at most once in a block. After a [NextEpoch](Command::NextEpoch) transaction in the batch is
+    /// executed successfully, later transactions containing a NextEpoch command are not executed, and fail with
+    /// [InvalidNextEpochCommand](TransitionError::InvalidNextEpochCommand) without a receipt.
+    pub fn transition_v2_batch<'a, S, V>(
+        &self,
+        ws: WorldState<'a, S, V>,
+        txs: Vec<TransactionV2>,
+        bd: BlockchainParams,
+    ) -> TransitionV2BatchResult<'a, S, V>
+    where
+        S: DB + Send + Sync + Clone + 'static,
+        V: VersionProvider + Send + Sync + Clone + 'static,
+    {
+        // compilation time is accounted per batch
+        if self.sc_context.compilation_time_budget.is_some() {
+            return self.for_block().transition_v2_batch_inner(ws, txs, bd);
+        }
+        self.transition_v2_batch_inner(ws, txs, bd)
+    }
+
+    /// returns a copy of the Runtime which accounts the compilation time of a new block
+    fn for_block(&self) -> Runtime {
+        Runtime {
+            sc_context: self.sc_context.for_block(),
+            compute_write_set_commitment: self.compute_write_set_commitment,
+            gas_accounting: self.gas_accounting,
+            balance_changes: self.balance_changes,
+            commit_mode: self.commit_mode,
+            block_gas_limit: self.block_gas_limit,
+            state_rent_policy: self.state_rent_policy.clone(),
+            fee_distributor: self.fee_distributor.clone(),
+            gas_schedule: self.gas_schedule,
+            world_state_observer: self.world_state_observer.clone(),
+            skip_nonce_check: self.skip_nonce_check,
+            command_time_budget: self.command_time_budget,
+        }
+    }
+
+    /// whether [transition_v2_batch](Runtime::transition_v2_batch) may leave transactions of a batch unexecuted
+    pub(crate) fn may_exclude_transactions(&self) -> bool {
+        self.block_gas_limit.is_some() || self.sc_context.compilation_time_budget.is_some()
+    }
+
+    /// creates the transition context of a transaction over `ws_cache`, configured as the Runtime is. The
+    /// [command time budget](Runtime::set_command_time_budget) is left unset, as only view calls and dry runs use it.
+    fn new_ctx<'a, S, V>(
+        &self,
+        version: TxnVersion,
+        ws_cache: WorldStateCache<'a, S, V>,
+        gas_limit: u64,
+    ) -> TransitionContext<'a, S, V>
+    where
+        S: DB + Send + Sync + Clone + 'static,
+        V: VersionProvider + Send + Sync + Clone + 'static,
+    {
+        let mut ctx = TransitionContext::from_ws_cache(version, ws_cache, gas_limit);
+        ctx.sc_context = self.sc_context.clone();
+        ctx.gas_meter.schedule = self.gas_schedule;
+        ctx.gas_free_ws_cache_mut()
+            .set_observer(self.world_state_observer.clone());
+        ctx.state_rent_policy = self.state_rent_policy.clone();
+        ctx.fee_distributor = self.fee_distributor.clone();
+        ctx.skip_nonce_check = self.skip_nonce_check;
+        ctx.record_balance_changes = self.balance_changes;
+        ctx
+    }
+
+    fn transition_v2_batch_inner<'a, S, V>(
+        &self,
+        ws: WorldState<'a, S, V>,
+        txs: Vec<TransactionV2>,
+        bd: BlockchainParams,
+    ) -> TransitionV2BatchResult<'a, S, V>
+    where
+        S: DB + Send + Sync + Clone + 'static,
+        V: VersionProvider + Send + Sync + Clone + 'static,
+    {
+        let mut receipts = Vec::with_capacity(txs.len());
+        let mut errors = Vec::with_capacity(txs.len());
+        let mut validator_changes = None;
+        let mut excluded = Vec::new();
+        let mut gas_used: u64 = 0;
+
+        let new_state = match self.commit_mode {
+            CommitMode::Eager => {
+                let mut ws = ws;
+                for tx in txs {
+                    if !excluded.is_empty() || !self.within_block_gas_limit(gas_used, &tx) {
+                        excluded.push(tx);
+                        continue;
+                    }
+                    // an epoch ends at most once in a block
+                    if validator_changes.is_some()
+                        && tx.commands.iter().any(|c| matches!(c, Command::NextEpoch))
+                    {
+                        receipts.push(None);
+                        errors.push(Some(TransitionError::InvalidNextEpochCommand));
+                        continue;
+                    }
+                    let result = self.transition_v2(ws, tx, bd.clone());
+                    ws = result.new_state;
+                    gas_used =
+                        gas_used.saturating_add(result.receipt.as_ref().map_or(0, |r| r.gas_used));
+                    receipts.push(result.receipt);
+                    errors.push(result.error);
+                    if result.validator_changes.is_some() {
+                        validator_changes = result.validator_changes;
+                    }
+                }
+                ws
+            }
+            CommitMode::Lazy => {
+                let mut ws_cache = WorldStateCache::new(ws);
+                for tx in txs {
+                    if !excluded.is_empty() || !self.within_block_gas_limit(gas_used, &tx) {
+                        excluded.push(tx);
+                        continue;
+                    }
+
+                    // an epoch ends at most once in a block
+                    if validator_changes.is_some()
+                        && tx.commands.iter().any(|c| matches!(c, Command::NextEpoch))
+                    {
+                        receipts.push(None);
+                        errors.push(Some(TransitionError::InvalidNextEpochCommand));
+                        continue;
+                    }
+
+                    // Next Epoch reads and writes the World State directly, so pending changes are committed first
+                    if tx.commands.iter().any(|c| matches!(c, Command::NextEpoch)) {
+                        let result =
+                            self.transition_v2(ws_cache.commit_to_world_state(), tx, bd.clone());
+                        ws_cache = WorldStateCache::new(result.new_state);
+                        gas_used = gas_used
+                            .saturating_add(result.receipt.as_ref().map_or(0, |r| r.gas_used));
+                        receipts.push(result.receipt);
+                        errors.push(result.error);
+                        if result.validator_changes.is_some() {
+                            validator_changes = result.validator_changes;
+                        }
+                        continue;
+                    }
+
+                    // transaction inputs
+                    let txn_meta = TxnMetadata::from(&tx);
+                    let commands = tx.commands;
+
+                    // create transition context from the carried over world state cache
+                    ws_cache.checkpoint();
+                    let ctx = self.new_ctx(txn_meta.version, ws_cache, tx.gas_limit);
+
+                    let state = ExecutionState::new(txn_meta, bd.clone(), ctx);
+                    let result = execute_commands_v2_uncommitted(state, commands);
+                    ws_cache = result.ws_cache;
+                    gas_used =
+                        gas_used.saturating_add(result.receipt.as_ref().map_or(0, |r| r.gas_used));
+                    receipts.push(result.receipt);
+                    errors.push(result.error);
+                }
+                ws_cache.commit_to_world_state()
+            }
+        };
+
+        TransitionV2BatchResult {
+            new_state,
+            receipts,
+            errors,
+            validator_changes,
+            excluded,
         }
     }
 
+    /// whether `tx` fits in the block gas limit after transactions which used `gas_used` gas, and the compilation
+    /// time budget of the block is not exceeded
+    fn within_block_gas_limit(&self, gas_used: u64, tx: &TransactionV2) -> bool {
+        if self.sc_context.compilation_time_exceeded() {
+            return false;
+        }
+        match self.block_gas_limit {
+            Some(block_gas_limit) => gas_used.saturating_add(tx.gas_limit) <= block_gas_limit,
+            None => true,
+        }
+    }
+
+    /// dry-runs the state transition of `tx` on `ws` with blockchain data (bd), and returns the addresses of the
+    /// accounts it touches: those whose balance, CBI version, contract code or storage is read or written, including
+    /// by contract calls and the commands they defer. The signer is always included, as are the accounts credited with
+    /// fees in the Charge phase (by default, the Proposer and the Treasury). A
+    /// [Next Epoch](pchain_types::blockchain::Command::NextEpoch) transaction touches only the signer and the Network
+    /// Account.
+    pub fn touched_accounts_v2<S, V>(
+        &self,
+        ws: WorldState<'_, S, V>,
+        tx: TransactionV2,
+        bd: BlockchainParams,
+    ) -> HashSet<PublicAddress>
+    where
+        S: DB + Send + Sync + Clone + 'static,
+        V: VersionProvider + Send + Sync + Clone + 'static,
+    {
+        let signer = tx.signer;
+        if tx.commands.iter().any(|c| matches!(c, Command::NextEpoch)) {
+            return HashSet::from([signer, NETWORK_ADDRESS]);
+        }
+
+        // transaction inputs
+        let txn_meta = TxnMetadata::from(&tx);
+        let commands = tx.commands;
+
+        // create transition context from world state
+        let mut ctx = self.new_ctx(txn_meta.version, WorldStateCache::new(ws), tx.gas_limit);
+        ctx.gas_free_ws_cache_mut().record_touched_accounts();
+
+        let result =
+            execute_commands_v2_uncommitted(ExecutionState::new(txn_meta, bd, ctx), commands);
+
+        let mut touched_accounts = result.ws_cache.touched_accounts();
+        touched_accounts.insert(signer);
+        touched_accounts
+    }
+
+    /// re-executes `tx` on `ws` with blockchain data (bd) as in [transition_v2](Runtime::transition_v2), and reports
+    /// what happened in detail, e.g. to investigate why nodes disagree on a receipt. See [ReplayReport].
+    ///
+    /// The replay is deterministic: identical inputs and Runtime configuration give identical reports. The changes are
+    /// not written, so a replay must never be used to commit a transaction to the chain.
+    ///
+    /// A [Next Epoch](pchain_types::blockchain::Command::NextEpoch) transaction writes the World State directly, so it
+    /// is executed on a copy of `ws`, and its report has neither gas breakdowns nor touched keys.
+    pub fn replay_v2<S, V>(
+        &self,
+        ws: WorldState<'_, S, V>,
+        tx: TransactionV2,
+        bd: BlockchainParams,
+    ) -> ReplayReport
+    where
+        S: DB + Send + Sync + Clone + 'static,
+        V: VersionProvider + Send + Sync + Clone + 'static,
+    {
+        if tx.commands.iter().any(|c| matches!(c, Command::NextEpoch)) {
+            let result = self.transition_v2(ws, tx, bd);
+            return ReplayReport {
+                receipt: result.receipt,
+                error: result.error,
+                gas_breakdown: Vec::new(),
+                touched_keys: Vec::new(),
+            };
+        }
+
+        // transaction inputs
+        let txn_meta = TxnMetadata::from(&tx);
+        let commands = tx.commands;
+
+        // create transition context from world state
+        let mut ctx = self.new_ctx(txn_meta.version, WorldStateCache::new(ws), tx.gas_limit);
+        ctx.gas_meter
+            .output_cache_of_current_command
+            .record_gas_breakdown = true;
+        ctx.gas_free_ws_cache_mut().record_touched_keys();
+
+        // execute without committing, and discard the changes held in the cache
+        let result =
+            execute_commands_v2_uncommitted(ExecutionState::new(txn_meta, bd, ctx), commands);
+        ReplayReport {
+            receipt: result.receipt,
+            error: result.error,
+            gas_breakdown: result.gas_breakdown.unwrap_or_default(),
+            touched_keys: result.ws_cache.touched_keys(),
+        }
+    }
+
+    /// reads the balance, nonce, and whether the account holds a contract, of each of `addresses` in `ws`. The views are
+    /// returned in the order of `addresses`, and an account which does not exist has a zero balance and nonce.
+    ///
+    /// All accounts are read through one World State Cache, without charging gas. Nothing is written, so `ws` can be
+    /// any World State, including a historical snapshot of an earlier block.
+    ///
+    /// ### Panics
+    /// Will panic on unexpected errors with the account trie, which reflects an invalid World State.
+    pub fn accounts_snapshot<S, V>(
+        &self,
+        ws: WorldState<'_, S, V>,
+        addresses: &[PublicAddress],
+    ) -> Vec<AccountView>
+    where
+        S: DB + Send + Sync + Clone + 'static,
+        V: VersionProvider + Send + Sync + Clone + 'static,
+    {
+        let ws_cache = WorldStateCache::new(ws);
+        addresses
+            .iter()
+            .map(|address| AccountView {
+                balance: ws_cache.balance(address),
+                // nonces are not cached, as they are not read during command execution
+                nonce: ws_cache
+                    .ws
+                    .account_trie()
+                    .nonce(address)
+                    .expect(&format!("Account trie should get nonce for {:?}", address)),
+                // a Deploy sets the CBI version together with the contract code, and the CBI version is cheaper to read
+                is_contract: ws_cache.cbi_version(address).is_some(),
+            })
+            .collect()
+    }
+
+    /// reads the CBI version which the contract at `address` in `ws` was deployed with, or None if the account does not
+    /// hold a contract, e.g. to warn about contracts which target an older [cbi_version](crate::cbi_version). The
+    /// version is stored in the account trie alongside the contract code, so the contract is neither loaded nor
+    /// executed, and no gas is charged.
+    ///
+    /// ### Panics
+    /// Will panic on unexpected errors with the account trie, which reflects an invalid World State.
+    pub fn contract_cbi_version<S, V>(
+        &self,
+        ws: WorldState<'_, S, V>,
+        address: PublicAddress,
+    ) -> Option<u32>
+    where
+        S: DB + Send + Sync + Clone + 'static,
+        V: VersionProvider + Send + Sync + Clone + 'static,
+    {
+        WorldStateCache::new(ws).cbi_version(&address)
+    }
+
+    /// reads the deposit of `owner` in the pool of `operator` in `ws`, or None if there is no such deposit. No gas is
+    /// charged and nothing is written, so `ws` can be any World State, including a historical snapshot.
+    pub fn deposit<S, V>(
+        &self,
+        ws: WorldState<'_, S, V>,
+        operator: PublicAddress,
+        owner: PublicAddress,
+    ) -> Option<DepositView>
+    where
+        S: DB + Send + Sync + Clone + 'static,
+        V: VersionProvider + Send + Sync + Clone + 'static,
+    {
+        let mut ws_cache = WorldStateCache::new(ws);
+        let mut state = protocol::NetworkAccountWorldState::from_ws_cache(&mut ws_cache);
+        let mut deposit = NetworkAccount::deposits(&mut state, operator, owner);
+        if !deposit.exists() {
+            return None;
+        }
+        Some(DepositView {
+            balance: deposit.balance().unwrap_or(0),
+            auto_stake_rewards: deposit.auto_stake_rewards().unwrap_or(false),
+        })
+    }
+
+    /// reads the pool of `operator` in `ws`, or None if there is no such pool. No gas is charged and nothing is
+    /// written, so `ws` can be any World State, including a historical snapshot.
+    pub fn pool<S, V>(&self, ws: WorldState<'_, S, V>, operator: PublicAddress) -> Option<PoolView>
+    where
+        S: DB + Send + Sync + Clone + 'static,
+        V: VersionProvider + Send + Sync + Clone + 'static,
+    {
+        let mut ws_cache = WorldStateCache::new(ws);
+        let mut state = protocol::NetworkAccountWorldState::from_ws_cache(&mut ws_cache);
+        let mut pool = NetworkAccount::pools(&mut state, operator);
+        if !pool.exists() {
+            return None;
+        }
+        Some(PoolView {
+            operator,
+            commission_rate: pool.commission_rate().unwrap_or(0),
+            power: pool.power().unwrap_or(0),
+            operator_stake: pool.operator_stake().flatten().map(|stake| stake.power),
+        })
+    }
+
+    /// prewarm_cache compiles the contracts at `addresses` in `ws` and puts them into the
+    /// [smart contract cache](Runtime::set_smart_contract_cache), so that the first transition or view call which
+    /// executes them loads them from the cache instead of compiling them. Contracts are compiled in the same way as
+    /// during execution, with the compiler backend and memory limit of this Runtime.
+    ///
+    /// The cache is keyed by contract address, so contracts are identified by address rather than code hash.
+    /// Addresses without a contract, and contracts which are already cached or cannot be compiled, are skipped.
+    /// Returns the number of contracts compiled. Does nothing if no smart contract cache is set.
+    pub fn prewarm_cache<S, V>(
+        &self,
+        ws: WorldState<'_, S, V>,
+        addresses: &[PublicAddress],
+    ) -> usize
+    where
+        S: DB + Send + Sync + Clone + 'static,
+        V: VersionProvider + Send + Sync + Clone + 'static,
+    {
+        if self.sc_context.cache.is_none() {
+            return 0;
+        }
+
+        let ws_cache = WorldStateCache::new(ws);
+        addresses
+            .iter()
+            .filter(|address| ContractModule::from_cache(**address, &self.sc_context).is_none())
+            .filter_map(|address| {
+                let contract_code = ws_cache.contract_code(address)?;
+                ContractModule::from_bytecode_unchecked(*address, &contract_code, &self.sc_context)
+            })
+            .count()
+    }
+
     /// view performs view call to a target contract
+    ///
+    /// The result is determined only by `ws` and the arguments, so `ws` can be any World State, including a historical
+    /// snapshot of an earlier block. See [execute_view](crate::execution::execute_view) for details.
+    ///
+    /// Like transitions, view calls load contracts from the [smart contract cache](Runtime::set_smart_contract_cache)
+    /// if one is set, and put the contracts they compile into it, so a contract is compiled once across view calls.
     pub fn view_v1<'a, S, V>(
         &self,
         ws: WorldState<'a, S, V>,
@@ -148,8 +1068,8 @@ impl Runtime {
         V: VersionProvider + Send + Sync + Clone + 'static,
     {
         // create transition context from world state
-        let mut ctx = TransitionContext::new(TxnVersion::V1, ws, gas_limit);
-        ctx.sc_context = self.sc_context.clone();
+        let mut ctx = self.new_ctx(TxnVersion::V1, WorldStateCache::new(ws), gas_limit);
+        ctx.sc_context.command_time_budget = self.command_time_budget;
 
         // create a dummy transaction
         let dummy_txn_meta = TxnMetadata {
@@ -167,6 +1087,12 @@ impl Runtime {
     }
 
     /// view performs view call to a target contract
+    ///
+    /// The result is determined only by `ws` and the arguments, so `ws` can be any World State, including a historical
+    /// snapshot of an earlier block. See [execute_view](crate::execution::execute_view) for details.
+    ///
+    /// Like transitions, view calls load contracts from the [smart contract cache](Runtime::set_smart_contract_cache)
+    /// if one is set, and put the contracts they compile into it, so a contract is compiled once across view calls.
     pub fn view_v2<'a, S, V>(
         &self,
         ws: WorldState<'a, S, V>,
@@ -180,8 +1106,8 @@ impl Runtime {
         V: VersionProvider + Send + Sync + Clone + 'static,
     {
         // create transition context from world state
-        let mut ctx = TransitionContext::new(TxnVersion::V1, ws, gas_limit);
-        ctx.sc_context = self.sc_context.clone();
+        let mut ctx = self.new_ctx(TxnVersion::V1, WorldStateCache::new(ws), gas_limit);
+        ctx.sc_context.command_time_budget = self.command_time_budget;
 
         // create a dummy transaction
         let dummy_txn_meta = TxnMetadata {
@@ -198,18 +1124,94 @@ impl Runtime {
         execute_view_v2(state, target, method, arguments)
     }
 
+    /// Calls `method` of the contract `code` with `arguments`, against a contract storage which initially holds
+    /// `initial_storage`, without preparing a World State or a transaction. Intended for unit-testing contracts.
+    ///
+    /// The call is executed as the only command of a V2 transaction with a gas limit of
+    /// [RUN_CONTRACT_METHOD_GAS_LIMIT] and no fees, on a World State which is discarded afterwards. Returns the return
+    /// value, gas used and exit code of the call.
+    pub fn run_contract_method(
+        &self,
+        code: Vec<u8>,
+        method: String,
+        arguments: Option<Vec<Vec<u8>>>,
+        initial_storage: HashMap<Vec<u8>, Vec<u8>>,
+    ) -> (Vec<u8>, u64, ExitCodeV2) {
+        let signer = [1u8; 32];
+        let target = [2u8; 32];
+
+        let storage = EmptyStorage;
+        let mut ws = WorldState::<EmptyStorage, V2>::new(&storage);
+        ws.account_trie_mut().set_code(&target, code).unwrap();
+        ws.account_trie_mut()
+            .set_cbi_version(&target, cbi_version())
+            .unwrap();
+        for (key, value) in initial_storage {
+            ws.storage_trie_mut(&target)
+                .unwrap()
+                .set(&key, value)
+                .unwrap();
+        }
+
+        let tx = TransactionV2 {
+            signer,
+            commands: vec![Command::Call(CallInput {
+                target,
+                method,
+                arguments,
+                amount: None,
+            })],
+            priority_fee_per_gas: 0,
+            gas_limit: RUN_CONTRACT_METHOD_GAS_LIMIT,
+            max_base_fee_per_gas: 0,
+            nonce: 0,
+            hash: [0u8; 32],
+            signature: [0u8; 64],
+        };
+
+        let result = self.transition_v2(ws, tx, BlockchainParams::default());
+        let call_receipt = result
+            .receipt
+            .and_then(|mut receipt| receipt.command_receipts.pop());
+        match call_receipt {
+            Some(CommandReceiptV2::Call(receipt)) => {
+                (receipt.return_value, receipt.gas_used, receipt.exit_code)
+            }
+            _ => (Vec::new(), 0, ExitCodeV2::Error),
+        }
+    }
+
     /// upgrades world state from v1 to v2, expects a valid next epoch command
+    ///
+    /// The account-by-account conversion is performed by [WorldState::upgrade] in pchain-world-state,
+    /// which rewrites every account of the V1 trie into the V2 layout. The runtime only decides whether
+    /// the upgrade happens (after a successful next epoch) and maps a failed conversion to
+    /// [TransitionError::FailedWorldStateUpgrade]. Balances, nonces and storage of every account are
+    /// expected to be carried over unchanged.
+    ///
+    /// Before anything is executed, the pool sets of the Network Account and the delegated stakes of their pools are
+    /// checked to be within their maximum sizes. A World State which violates them is rejected with
+    /// [TransitionError::MalformedWorldState], and left unchanged.
     pub fn transition_v1_to_v2<'a, S: DB + Send + Sync + Clone + 'static>(
         &self,
         ws: WorldState<'a, S, V1>,
         tx: TransactionV1,
         bd: BlockchainParams,
     ) -> TransitionV1ToV2Result<'a, S> {
+        // reject a malformed World State before its pool sets are walked
+        if !protocol::within_migration_bounds(&ws) {
+            return TransitionV1ToV2Result {
+                new_state: None,
+                receipt: None,
+                error: Some(TransitionError::MalformedWorldState),
+                validator_changes: None,
+            };
+        }
+
         let txn_meta = TxnMetadata::from(&tx);
         let commands = tx.commands;
 
-        let mut ctx = TransitionContext::new(txn_meta.version, ws, tx.gas_limit);
-        ctx.sc_context = self.sc_context.clone();
+        let ctx = self.new_ctx(txn_meta.version, WorldStateCache::new(ws), tx.gas_limit);
         let state = ExecutionState::new(txn_meta, bd, ctx);
 
         // first execute next epoch
@@ -303,14 +1305,339 @@ where
     /// Changes in validator set.
     /// Only from executing the [Next Epoch](pchain_types::blockchain::Command::NextEpoch) Command. None for other commands.
     pub validator_changes: Option<ValidatorChanges>,
+    /// Hash committing to the key-ordered write set of the transition.
+    /// Only computed if enabled by [set_write_set_commitment](Runtime::set_write_set_commitment). None otherwise.
+    pub write_set_commitment: Option<Sha256Hash>,
+    /// Peak Wasm linear memory, in pages of 64 KiB, used by contract calls in each executed command, in the order of the
+    /// command receipts. Zero for commands which did not call a contract.
+    /// Only recorded if enabled by [set_memory_profiling](Runtime::set_memory_profiling). None otherwise.
+    pub memory_profile: Option<Vec<u32>>,
+    /// Gas used by the Wasm opcodes of contract calls in each executed command by category of opcode, in the order of
+    /// the command receipts. Diagnostic only, for optimizing contracts.
+    /// Only recorded if the crate is built with the `profiling` feature. None otherwise.
+    pub opcode_gas_profile: Option<Vec<OpcodeGasProfile>>,
+    /// Gas used by each executed command by kind of operation, in the order of the command receipts. Each breakdown
+    /// adds up to the `gas_used` of its command receipt. The transaction inclusion cost is not part of any command.
+    /// Only recorded if enabled by [set_gas_accounting](Runtime::set_gas_accounting). None otherwise.
+    pub gas_breakdown: Option<Vec<GasBreakdown>>,
+    /// Effects of the transaction in the order in which they took place, including the Charge phase.
+    /// Only recorded by [transition_v2_with_events](Runtime::transition_v2_with_events), for transactions without
+    /// a [Next Epoch](pchain_types::blockchain::Command::NextEpoch) Command. None otherwise.
+    pub events: Option<Vec<RuntimeEvent>>,
+    /// True if the transition was a dry run by [estimate_v2](Runtime::estimate_v2). The changes of a dry run are not
+    /// written, so `new_state` is the input World State.
+    pub dry_run: bool,
+    pub(crate) base_fee_paid: u64,
+    pub(crate) priority_fee_paid: u64,
+    pub(crate) balance_changes: Vec<(PublicAddress, u64)>,
+}
+
+impl<'a, S, V> TransitionV2Result<'a, S, V>
+where
+    S: DB + Send + Sync + Clone + 'static,
+    V: VersionProvider + Send + Sync + Clone,
+{
+    /// Iterates over the writes to the underlying storage pending in `new_state`, in the order of their keys. A value
+    /// of `None` means that the key is deleted.
+    ///
+    /// The writes are net: changes made by commands which failed and were reverted are not included, and a key written
+    /// more than once appears once with its final value. Changes which were already pending in the World State passed
+    /// to the transition function are included.
+    ///
+    /// ### Panics
+    /// Will panic if the changes cannot be computed from `new_state`, which reflects an invalid World State.
+    pub fn writes(&self) -> impl Iterator<Item = (Vec<u8>, Option<Vec<u8>>)> {
+        write_set(&self.new_state).into_iter()
+    }
+
+    /// Base fee paid by the signer in the Charge phase, i.e. the gas used times the base fee of the block. By default,
+    /// the Treasury's cut of it is credited to the Treasury and the rest is burned. Zero if no commands were executed,
+    /// and for a [Next Epoch](pchain_types::blockchain::Command::NextEpoch) transaction.
+    pub fn base_fee_paid(&self) -> u64 {
+        self.base_fee_paid
+    }
+
+    /// Priority fee paid by the signer in the Charge phase, i.e. the gas used times the priority fee per gas of the
+    /// transaction, by default credited to the proposer. Zero in the same cases as [base_fee_paid](Self::base_fee_paid).
+    ///
+    /// Together with the base fee, it is the amount deducted from the signer's balance for the transaction, besides
+    /// values transferred by its commands and any state rent.
+    pub fn priority_fee_paid(&self) -> u64 {
+        self.priority_fee_paid
+    }
+
+    /// Final balances of the accounts whose balances were changed by the transaction, in the order of their addresses.
+    /// These include the signer, who paid for gas, the recipients of values transferred by its commands, and the
+    /// recipients of its fees. Accounts whose balances were only read, or written back with the same value, are not
+    /// included.
+    ///
+    /// Only recorded if enabled by [set_balance_changes](Runtime::set_balance_changes). Empty otherwise, if no commands
+    /// were executed, and for a [Next Epoch](pchain_types::blockchain::Command::NextEpoch) transaction. For a dry run by [estimate_v2](Runtime::estimate_v2), these are the balances that the transaction
+    /// would have resulted in.
+    pub fn balance_changes(&self) -> Vec<(PublicAddress, u64)> {
+        self.balance_changes.clone()
+    }
+}
+
+/// Return type of [replay_v2](Runtime::replay_v2).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReplayReport {
+    /// Transaction receipt, as returned by [transition_v2](Runtime::transition_v2).
+    pub receipt: Option<ReceiptV2>,
+    /// Transition error, as returned by [transition_v2](Runtime::transition_v2).
+    pub error: Option<TransitionError>,
+    /// Gas used by each executed command by kind of operation, in the order of the command receipts, as in
+    /// [gas_breakdown](TransitionV2Result::gas_breakdown).
+    pub gas_breakdown: Vec<GasBreakdown>,
+    /// Data of the World State read or written during execution, including the Charge phase, in the order in which
+    /// it was first accessed. Nonces are not included.
+    pub touched_keys: Vec<WorldStateKey>,
+}
+
+/// State of an account, as returned by [accounts_snapshot](Runtime::accounts_snapshot).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AccountView {
+    /// Balance of the account.
+    pub balance: u64,
+    /// Number of transactions signed by the account which have been included in the chain.
+    pub nonce: u64,
+    /// Whether a contract is deployed at the account.
+    pub is_contract: bool,
+}
+
+/// Deposit in a pool, as returned by [deposit](Runtime::deposit).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DepositView {
+    /// Balance of the deposit.
+    pub balance: u64,
+    /// Whether the rewards of the stake backed by the deposit are added to the deposit and the stake.
+    pub auto_stake_rewards: bool,
+}
+
+/// Pool in the Network Account, as returned by [pool](Runtime::pool).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PoolView {
+    /// Address of the operator of the pool.
+    pub operator: PublicAddress,
+    /// Commission rate of the pool, in percent.
+    pub commission_rate: u8,
+    /// Power of the pool, i.e. the total power of its stakes, including the operator's own stake.
+    pub power: u64,
+    /// Power of the operator's own stake in the pool, or None if the operator has not staked.
+    pub operator_stake: Option<u64>,
+}
+
+/// Return type of `pchain_runtime::Runtime::transition_v2_batch`.
+#[derive(Clone)]
+pub struct TransitionV2BatchResult<'a, S, V>
+where
+    S: DB + Send + Sync + Clone + 'static,
+    V: VersionProvider + Send + Sync + Clone,
+{
+    /// Next world state (ws') after state transition of all transactions in the batch
+    pub new_state: WorldState<'a, S, V>,
+    /// Transaction receipts, in the order of the transactions. None if no commands were executed,
+    /// e.g. due to failing checks in the pre-charge phase
+    pub receipts: Vec<Option<ReceiptV2>>,
+    /// Transition errors, in the order of the transactions. None if no error.
+    pub errors: Vec<Option<TransitionError>>,
+    /// Changes in validator set from the last transaction in the batch that executed a
+    /// [Next Epoch](pchain_types::blockchain::Command::NextEpoch) Command. None if there is no such transaction.
+    pub validator_changes: Option<ValidatorChanges>,
+    /// Transactions which were not executed because they did not fit in the
+    /// [block gas limit](Runtime::set_block_gas_limit), or because the
+    /// [compilation time budget](Runtime::set_block_compilation_time_budget) was exceeded, in their original order.
+    /// Receipts and errors are only returned for the transactions before them.
+    pub excluded: Vec<TransactionV2>,
 }
 
 /// Defines changes to validator set. It is the transition result from
 /// executing Command [NextEpoch](pchain_types::blockchain::Command::NextEpoch).
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ValidatorChanges {
     /// the next validator set in list of tuple of operator address and power
     pub new_validator_set: Vec<(PublicAddress, u64)>,
     /// the list of address of operator who is removed from state
     pub remove_validator_set: Vec<PublicAddress>,
+    /// every validator of the next validator set with its power, in the order of the validator pools, including
+    /// validators whose power did not change
+    pub power_updates: Vec<(PublicAddress, u64)>,
+}
+
+/// Canonical encoding of [ValidatorChanges], e.g. for transmission between nodes. Fields are encoded in order:
+/// 1. `new_validator_set`: number of entries as a little-endian u32, then for each entry the operator address
+///    followed by the power as a little-endian u64.
+/// 2. `remove_validator_set`: number of entries as a little-endian u32, then each operator address.
+/// 3. `power_updates`: encoded in the same way as `new_validator_set`.
+///
+/// Entries keep their order, so identical changes are always encoded to identical bytes. The encoding is the same as
+/// the Borsh serialization of the tuple of the fields.
+pub fn validator_changes_to_bytes(changes: &ValidatorChanges) -> Vec<u8> {
+    fn extend_with_powers(bytes: &mut Vec<u8>, powers: &[(PublicAddress, u64)]) {
+        bytes.extend((powers.len() as u32).to_le_bytes());
+        for (operator, power) in powers {
+            bytes.extend(operator);
+            bytes.extend(power.to_le_bytes());
+        }
+    }
+
+    let mut bytes = Vec::with_capacity(
+        12 + changes.new_validator_set.len() * 40
+            + changes.remove_validator_set.len() * 32
+            + changes.power_updates.len() * 40,
+    );
+    extend_with_powers(&mut bytes, &changes.new_validator_set);
+    bytes.extend((changes.remove_validator_set.len() as u32).to_le_bytes());
+    for operator in &changes.remove_validator_set {
+        bytes.extend(operator);
+    }
+    extend_with_powers(&mut bytes, &changes.power_updates);
+    bytes
+}
+
+/// Decodes [ValidatorChanges] from the encoding of [validator_changes_to_bytes]. Returns None if `bytes` is not
+/// exactly such an encoding, including if it has trailing bytes.
+pub fn validator_changes_from_bytes(bytes: &[u8]) -> Option<ValidatorChanges> {
+    fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+        if bytes.len() < len {
+            return None;
+        }
+        let (taken, rest) = bytes.split_at(len);
+        *bytes = rest;
+        Some(taken)
+    }
+    fn take_u32(bytes: &mut &[u8]) -> Option<u32> {
+        take(bytes, 4).map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+    }
+    fn take_u64(bytes: &mut &[u8]) -> Option<u64> {
+        take(bytes, 8).map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+    }
+    fn take_address(bytes: &mut &[u8]) -> Option<PublicAddress> {
+        take(bytes, 32).map(|b| b.try_into().unwrap())
+    }
+    fn take_powers(bytes: &mut &[u8]) -> Option<Vec<(PublicAddress, u64)>> {
+        let num_validators = take_u32(bytes)?;
+        let mut powers = Vec::new();
+        for _ in 0..num_validators {
+            let operator = take_address(bytes)?;
+            let power = take_u64(bytes)?;
+            powers.push((operator, power));
+        }
+        Some(powers)
+    }
+
+    let mut bytes = bytes;
+
+    // lengths are not trusted for pre-allocation, as they are read from the input
+    let new_validator_set = take_powers(&mut bytes)?;
+
+    let num_removed_validators = take_u32(&mut bytes)?;
+    let mut remove_validator_set = Vec::new();
+    for _ in 0..num_removed_validators {
+        remove_validator_set.push(take_address(&mut bytes)?);
+    }
+
+    let power_updates = take_powers(&mut bytes)?;
+
+    if !bytes.is_empty() {
+        return None;
+    }
+
+    Some(ValidatorChanges {
+        new_validator_set,
+        remove_validator_set,
+        power_updates,
+    })
+}
+
+/// Canonical encoding of a [ReceiptV2], e.g. to hash receipts and compare them between nodes. Unlike the
+/// serialization of the receipt, the encoding is defined here, and does not change with the version of
+/// `pchain-types` or of the Runtime. Fields are encoded in order:
+/// 1. `gas_used` as a little-endian u64.
+/// 2. `exit_code` as a u8: 0 for Ok, 1 for Error, 2 for GasExhausted and 3 for NotExecuted.
+/// 3. `command_receipts`: number of command receipts as a little-endian u32, then each command receipt.
+///
+/// A command receipt is encoded as the kind of its command as a u8, in the order of
+/// [Command](pchain_types::blockchain::Command) from 0 for Transfer to 12 for NextEpoch, followed by its exit code as
+/// above and its gas used as a little-endian u64, and then the fields of its kind:
+/// - Call: number of logs as a little-endian u32, then the topic and the value of each log, then the return value.
+///   Each of these byte strings is prefixed with its length as a little-endian u32.
+/// - Withdraw Deposit, Stake Deposit and Unstake Deposit: the amount withdrawn, staked or unstaked as a little-endian
+///   u64.
+pub fn receipt_v2_to_bytes(receipt: &ReceiptV2) -> Vec<u8> {
+    fn exit_code_byte(exit_code: &ExitCodeV2) -> u8 {
+        match exit_code {
+            ExitCodeV2::Ok => 0,
+            ExitCodeV2::Error => 1,
+            ExitCodeV2::GasExhausted => 2,
+            ExitCodeV2::NotExecuted => 3,
+        }
+    }
+    fn extend_with_slice(bytes: &mut Vec<u8>, slice: &[u8]) {
+        bytes.extend((slice.len() as u32).to_le_bytes());
+        bytes.extend(slice);
+    }
+    fn extend_with_header(bytes: &mut Vec<u8>, kind: u8, exit_code: &ExitCodeV2, gas_used: u64) {
+        bytes.push(kind);
+        bytes.push(exit_code_byte(exit_code));
+        bytes.extend(gas_used.to_le_bytes());
+    }
+
+    let mut bytes = Vec::new();
+    bytes.extend(receipt.gas_used.to_le_bytes());
+    bytes.push(exit_code_byte(&receipt.exit_code));
+    bytes.extend((receipt.command_receipts.len() as u32).to_le_bytes());
+    for command_receipt in &receipt.command_receipts {
+        match command_receipt {
+            CommandReceiptV2::Transfer(r) => {
+                extend_with_header(&mut bytes, 0, &r.exit_code, r.gas_used)
+            }
+            CommandReceiptV2::Deploy(r) => {
+                extend_with_header(&mut bytes, 1, &r.exit_code, r.gas_used)
+            }
+            CommandReceiptV2::Call(r) => {
+                extend_with_header(&mut bytes, 2, &r.exit_code, r.gas_used);
+                bytes.extend((r.logs.len() as u32).to_le_bytes());
+                for log in &r.logs {
+                    extend_with_slice(&mut bytes, &log.topic);
+                    extend_with_slice(&mut bytes, &log.value);
+                }
+                extend_with_slice(&mut bytes, &r.return_value);
+            }
+            CommandReceiptV2::CreatePool(r) => {
+                extend_with_header(&mut bytes, 3, &r.exit_code, r.gas_used)
+            }
+            CommandReceiptV2::SetPoolSettings(r) => {
+                extend_with_header(&mut bytes, 4, &r.exit_code, r.gas_used)
+            }
+            CommandReceiptV2::DeletePool(r) => {
+                extend_with_header(&mut bytes, 5, &r.exit_code, r.gas_used)
+            }
+            CommandReceiptV2::CreateDeposit(r) => {
+                extend_with_header(&mut bytes, 6, &r.exit_code, r.gas_used)
+            }
+            CommandReceiptV2::SetDepositSettings(r) => {
+                extend_with_header(&mut bytes, 7, &r.exit_code, r.gas_used)
+            }
+            CommandReceiptV2::TopUpDeposit(r) => {
+                extend_with_header(&mut bytes, 8, &r.exit_code, r.gas_used)
+            }
+            CommandReceiptV2::WithdrawDeposit(r) => {
+                extend_with_header(&mut bytes, 9, &r.exit_code, r.gas_used);
+                bytes.extend(r.amount_withdrawn.to_le_bytes());
+            }
+            CommandReceiptV2::StakeDeposit(r) => {
+                extend_with_header(&mut bytes, 10, &r.exit_code, r.gas_used);
+                bytes.extend(r.amount_staked.to_le_bytes());
+            }
+            CommandReceiptV2::UnstakeDeposit(r) => {
+                extend_with_header(&mut bytes, 11, &r.exit_code, r.gas_used);
+                bytes.extend(r.amount_unstaked.to_le_bytes());
+            }
+            CommandReceiptV2::NextEpoch(r) => {
+                extend_with_header(&mut bytes, 12, &r.exit_code, r.gas_used)
+            }
+        }
+    }
+    bytes
 }
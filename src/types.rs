@@ -19,6 +19,10 @@ use pchain_types::{
     cryptography::{PublicAddress, Sha256Hash},
     serialization::Serializable,
 };
+use pchain_world_state::{VersionProvider, WorldState, DB};
+use sha2::{Digest, Sha256};
+
+use crate::gas::{GasBreakdown, OpcodeGasProfile};
 
 /// Metadata relating to the current block supplied to state transition function.
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
@@ -53,6 +57,34 @@ pub struct ValidatorPerformance {
     pub stats: HashMap<PublicAddress, BlockProposalStats>,
 }
 
+impl ValidatorPerformance {
+    /// Tallies the block proposals of each validator from the proposer addresses of the blocks in an epoch.
+    /// `blocks_per_epoch` is set to the number of observed blocks.
+    pub fn from_proposers(proposers: &[PublicAddress]) -> Self {
+        let mut stats: HashMap<PublicAddress, BlockProposalStats> = HashMap::new();
+        for proposer in proposers {
+            stats
+                .entry(*proposer)
+                .or_insert(BlockProposalStats::new(0))
+                .num_of_proposed_blocks += 1;
+        }
+        Self {
+            blocks_per_epoch: proposers.len() as u32,
+            stats,
+        }
+    }
+
+    /// Checks that the total number of proposed blocks in `stats` does not exceed `blocks_per_epoch`.
+    pub fn validate(&self) -> bool {
+        let total_proposed_blocks: u64 = self
+            .stats
+            .values()
+            .map(|stat| stat.num_of_proposed_blocks as u64)
+            .sum();
+        total_proposed_blocks <= self.blocks_per_epoch as u64
+    }
+}
+
 /// Statistics on the number of proposed blocks by a validator
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct BlockProposalStats {
@@ -114,8 +146,8 @@ impl From<&TransactionV2> for TxnMetadata {
 }
 
 /// Marker for transaction version
-#[derive(Clone, Copy, Debug)]
-pub(crate) enum TxnVersion {
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TxnVersion {
     V1,
     V2,
 }
@@ -127,7 +159,7 @@ impl Default for TxnVersion {
 }
 
 /// Enumerates types of commands without their payloads, providing a lightweight representation of various Command types.
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum CommandKind {
     Transfer,
     Deploy,
@@ -194,6 +226,77 @@ pub(crate) struct DeferredCommand {
     pub command: Command,
 }
 
+/// A typed effect of a transaction, returned in the order in which it took place by
+/// [transition_v2_with_events](crate::Runtime::transition_v2_with_events).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RuntimeEvent {
+    /// Balance of an account was set to a new value.
+    BalanceChanged {
+        address: PublicAddress,
+        balance: u64,
+    },
+    /// Power of the stake of `owner` in the pool of `operator` was set to a new value. Zero if the stake was removed.
+    StakeChanged {
+        operator: PublicAddress,
+        owner: PublicAddress,
+        power: u64,
+    },
+    /// A pool was created.
+    PoolCreated {
+        operator: PublicAddress,
+        commission_rate: u8,
+    },
+    /// A contract emitted a log.
+    ContractLog(Log),
+    /// A command in the transaction completed, successfully or not.
+    CommandCompleted {
+        command_index: usize,
+        command_kind: CommandKind,
+        exit_code: ExitCodeV2,
+        gas_used: u64,
+    },
+}
+
+/// Identifies an item of data of an account in the World State, as accessed during execution. Returned in the order
+/// of first access by [replay_v2](crate::Runtime::replay_v2).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum WorldStateKey {
+    /// Balance of the account.
+    Balance(PublicAddress),
+    /// CBI version of the contract account.
+    CbiVersion(PublicAddress),
+    /// Code of the contract account.
+    ContractCode(PublicAddress),
+    /// Value of a key in the storage of the account.
+    StorageData(PublicAddress, Vec<u8>),
+}
+
+impl WorldStateKey {
+    /// address of the account which the data belongs to
+    pub fn address(&self) -> PublicAddress {
+        match self {
+            WorldStateKey::Balance(address)
+            | WorldStateKey::CbiVersion(address)
+            | WorldStateKey::ContractCode(address)
+            | WorldStateKey::StorageData(address, _) => *address,
+        }
+    }
+}
+
+/// Data of an account which replaces the data in the World State when simulating a transaction with
+/// [simulate_v2_with_overrides](crate::Runtime::simulate_v2_with_overrides).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StateOverride {
+    /// Balance of the account.
+    Balance(PublicAddress, u64),
+    /// CBI version of the contract account.
+    CbiVersion(PublicAddress, u32),
+    /// Code of the contract account.
+    ContractCode(PublicAddress, Vec<u8>),
+    /// Value of a key in the storage of the account. None deletes the value.
+    StorageData(PublicAddress, Vec<u8>, Option<Vec<u8>>),
+}
+
 /// Holds various outputs from the execution of commands
 #[derive(Clone, Default)]
 pub(crate) struct CommandOutput {
@@ -207,6 +310,14 @@ pub(crate) struct CommandOutput {
     pub amount_staked: u64,
     /// Output value in [pchain_types::blockchain::UnstakeDepositReceipt].
     pub amount_unstaked: u64,
+    /// Peak Wasm linear memory pages used by contract calls. Not part of any receipt, only used for profiling.
+    pub memory_pages: u32,
+    /// Gas used by Wasm opcodes by category. Not part of any receipt, only recorded with the `profiling` feature.
+    pub opcode_gas_profile: OpcodeGasProfile,
+    /// Runtime events emitted while executing the command. Not part of any receipt.
+    pub events: Vec<RuntimeEvent>,
+    /// Gas used by the command by kind of operation. Not part of any receipt, only recorded for gas accounting.
+    pub gas_breakdown: GasBreakdown,
 }
 
 pub(crate) fn create_executed_cmd_rcp_v2(
@@ -342,6 +453,16 @@ pub(crate) fn create_not_executed_cmd_rcp_v2(command: &CommandKind) -> CommandRe
     }
 }
 
+/// Logs emitted in a [Call](CommandReceiptV2::Call) command, in the order in which the called contract, and the contracts
+/// that it called, emitted them with the `log` host function. Logs of contract calls that were rolled back are not included.
+/// Empty for the receipts of other commands, which cannot emit logs.
+pub fn command_receipt_logs(command_receipt: &CommandReceiptV2) -> &[Log] {
+    match command_receipt {
+        CommandReceiptV2::Call(receipt) => &receipt.logs,
+        _ => &[],
+    }
+}
+
 pub(crate) fn gas_used_and_exit_code_v2(
     command_receipt_v2: &CommandReceiptV2,
 ) -> (u64, ExitCodeV2) {
@@ -405,3 +526,65 @@ pub(crate) fn set_gas_used_and_exit_code_v2(
         CommandReceiptV2::NextEpoch,
     )
 }
+
+/// Computes the write set of a state transition, i.e. the changes to the underlying storage pending in `ws`, as
+/// (key, value) pairs sorted by key. A value of `None` means that the key is deleted.
+///
+/// ### Panics
+/// Will panic if the changes cannot be computed from the World State, which reflects an invalid World State.
+pub(crate) fn write_set<S, V>(ws: &WorldState<S, V>) -> Vec<(Vec<u8>, Option<Vec<u8>>)>
+where
+    S: DB + Send + Sync + Clone + 'static,
+    V: VersionProvider + Send + Sync + Clone,
+{
+    let changes = ws
+        .clone()
+        .close()
+        .expect("World state should compute its changes");
+
+    let mut writes: Vec<(Vec<u8>, Option<Vec<u8>>)> = changes
+        .inserts
+        .into_iter()
+        .map(|(key, value)| (key, Some(value)))
+        .chain(changes.deletes.into_iter().map(|key| (key, None)))
+        .collect();
+    writes.sort();
+    writes
+}
+
+/// Computes a commitment over the write set of a state transition, i.e. the changes to the underlying storage
+/// pending in `ws`. Inserted key-value pairs and deleted keys are each sorted by key, and the commitment is the
+/// SHA256 hash over their Borsh encoding.
+///
+/// ### Panics
+/// Will panic if the changes cannot be computed from the World State, which reflects an invalid World State.
+pub(crate) fn write_set_commitment<S, V>(ws: &WorldState<S, V>) -> Sha256Hash
+where
+    S: DB + Send + Sync + Clone + 'static,
+    V: VersionProvider + Send + Sync + Clone,
+{
+    let changes = ws
+        .clone()
+        .close()
+        .expect("World state should compute its changes");
+
+    let mut inserts: Vec<(Vec<u8>, Vec<u8>)> = changes.inserts.into_iter().collect();
+    inserts.sort();
+    let mut deletes: Vec<Vec<u8>> = changes.deletes.into_iter().collect();
+    deletes.sort();
+
+    let mut hasher = Sha256::new();
+    hasher.update((inserts.len() as u32).to_le_bytes());
+    for (key, value) in inserts {
+        hasher.update((key.len() as u32).to_le_bytes());
+        hasher.update(key);
+        hasher.update((value.len() as u32).to_le_bytes());
+        hasher.update(value);
+    }
+    hasher.update((deletes.len() as u32).to_le_bytes());
+    for key in deletes {
+        hasher.update((key.len() as u32).to_le_bytes());
+        hasher.update(key);
+    }
+    hasher.finalize().into()
+}
@@ -0,0 +1,34 @@
+use pchain_runtime::{BlockchainParams, Runtime};
+use pchain_types::{blockchain::TransactionV2, serialization::Serializable};
+use pchain_world_state::{WorldState, V2};
+
+use super::SimulateWorldStateStorage;
+
+/// Runs `transition_v2` twice from the same inputs and asserts that the resulting
+/// world states, receipts, errors and gas used are byte-identical.
+pub fn assert_deterministic_v2(
+    ws: WorldState<'_, SimulateWorldStateStorage, V2>,
+    tx: TransactionV2,
+    bp: BlockchainParams,
+) {
+    let result_1 = Runtime::new().transition_v2(ws.clone(), tx.clone(), bp.clone());
+    let result_2 = Runtime::new().transition_v2(ws, tx, bp);
+
+    assert_eq!(result_1.error, result_2.error);
+    assert_eq!(
+        result_1.receipt.as_ref().map(|r| r.gas_used),
+        result_2.receipt.as_ref().map(|r| r.gas_used)
+    );
+    assert_eq!(
+        result_1.receipt.map(|r| r.serialize()),
+        result_2.receipt.map(|r| r.serialize())
+    );
+
+    let mut new_state_1 = result_1.new_state;
+    let mut new_state_2 = result_2.new_state;
+    let changes_1 = new_state_1.close().unwrap();
+    let changes_2 = new_state_2.close().unwrap();
+    assert_eq!(changes_1.inserts, changes_2.inserts);
+    assert_eq!(changes_1.deletes, changes_2.deletes);
+    assert_eq!(changes_1.new_root_hash, changes_2.new_root_hash);
+}
@@ -15,3 +15,8 @@ pub use test_data::*;
 #[allow(dead_code)]
 pub mod simulate_world_state;
 pub use simulate_world_state::*;
+
+#[cfg(test)]
+#[allow(dead_code)]
+pub mod determinism;
+pub use determinism::*;
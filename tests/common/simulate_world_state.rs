@@ -18,6 +18,21 @@ pub struct SimulateWorldStateStorage {
     inner: HashMap<SimulateKey, Vec<u8>>,
 }
 
+impl SimulateWorldStateStorage {
+    /// Applies writes to the storage. A value of `None` deletes the key.
+    pub fn apply_writes(
+        &mut self,
+        writes: impl IntoIterator<Item = (SimulateKey, Option<Vec<u8>>)>,
+    ) {
+        for (key, value) in writes {
+            match value {
+                Some(value) => self.inner.insert(key, value),
+                None => self.inner.remove(&key),
+            };
+        }
+    }
+}
+
 impl DB for SimulateWorldStateStorage {
     fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
         self.inner.get(key).cloned()
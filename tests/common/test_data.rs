@@ -1,7 +1,7 @@
 use pchain_runtime::BlockchainParams;
 use pchain_types::blockchain::{TransactionV1, TransactionV2};
 
-pub const EXPECTED_CBI_VERSION: u32 = 0;
+pub const EXPECTED_CBI_VERSION: u32 = 1;
 pub const MIN_BASE_FEE: u64 = 8;
 
 // Origin Account.
@@ -1,8 +1,13 @@
 use borsh::{BorshDeserialize, BorshSerialize};
-use pchain_runtime::{gas::tx_inclusion_cost_v1, types::CommandKind};
+use pchain_runtime::{
+    gas::{tx_inclusion_cost_v1, tx_inclusion_cost_v2},
+    types::CommandKind,
+};
 use pchain_types::{
-    blockchain::{CommandReceiptV2, ExitCodeV1, ExitCodeV2, TransactionV1, TransactionV2},
+    blockchain::{Command, CommandReceiptV2, ExitCodeV1, ExitCodeV2, TransactionV1, TransactionV2},
     cryptography::{contract_address_v1, contract_address_v2},
+    runtime::CallInput,
+    serialization::Serializable,
 };
 use pchain_world_state::{V1, V2};
 
@@ -642,3 +647,114 @@ fn test_ctoc_with_insufficient_gas_limit_v2() {
         0
     ));
 }
+
+/// Wasm data string of the serialized Call command to `method` of `target`, and its length.
+fn call_input_data(target: [u8; 32], method: &str) -> (String, usize) {
+    let bytes = Command::Call(CallInput {
+        target,
+        method: method.to_string(),
+        arguments: None,
+        amount: None,
+    })
+    .serialize();
+    let data = bytes.iter().map(|b| format!("\\{:02x}", b)).collect();
+    (data, bytes.len())
+}
+
+/// Contract A calls contract B, which calls back into A. The re-entered A reads the value that the outer A set before
+/// calling B, and the outer A reads the value that the re-entered A set.
+#[test]
+fn test_ctoc_reentrancy_v2() {
+    let contract_a = [2u8; 32];
+    let contract_b = [3u8; 32];
+
+    let (call_b, call_b_len) = call_input_data(contract_b, "reenter");
+    let wasm_a = wat::parse_str(format!(
+        r#"
+        (module
+            (import "env" "set" (func $set (param i32 i32 i32 i32)))
+            (import "env" "get" (func $get (param i32 i32 i32) (result i64)))
+            (import "env" "call" (func $call (param i32 i32 i32) (result i32)))
+            (import "env" "return_value" (func $return_value (param i32 i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "k")
+            (data (i32.const 1) "j")
+            (data (i32.const 16) "a")
+            (data (i32.const 64) "{call_b}")
+            (func (export "alloc") (param i32) (result i32)
+                i32.const 1024)
+            (func (export "entrypoint")
+                (local $len i64)
+                (call $set (i32.const 0) (i32.const 1) (i32.const 16) (i32.const 1))
+                (drop (call $call (i32.const 64) (i32.const {call_b_len}) (i32.const 8)))
+                (local.set $len (call $get (i32.const 1) (i32.const 1) (i32.const 8)))
+                (call $return_value (i32.load (i32.const 8)) (i32.wrap_i64 (local.get $len))))
+            (func (export "reentered")
+                (local $len i64)
+                (local.set $len (call $get (i32.const 0) (i32.const 1) (i32.const 8)))
+                (call $set (i32.const 1) (i32.const 1) (i32.load (i32.const 8)) (i32.wrap_i64 (local.get $len)))))
+        "#
+    ))
+    .unwrap();
+
+    let (call_a, call_a_len) = call_input_data(contract_a, "reentered");
+    let wasm_b = wat::parse_str(format!(
+        r#"
+        (module
+            (import "env" "call" (func $call (param i32 i32 i32) (result i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 64) "{call_a}")
+            (func (export "alloc") (param i32) (result i32)
+                i32.const 1024)
+            (func (export "reenter")
+                (drop (call $call (i32.const 64) (i32.const {call_a_len}) (i32.const 8)))))
+        "#
+    ))
+    .unwrap();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    let mut tx = TestData::transaction_v2();
+    tx.gas_limit = 10_000_000;
+    tx.commands = vec![ArgsBuilder::new().make_call(None, contract_a, "entrypoint")];
+    let bd = TestData::block_params();
+    let init_balance = 100_000_000;
+    sws.set_balance(tx.signer, init_balance);
+    sws.add_contract(contract_a, wasm_a, pchain_runtime::cbi_version());
+    sws.add_contract(contract_b, wasm_b, pchain_runtime::cbi_version());
+
+    let result =
+        pchain_runtime::Runtime::new().transition_v2(sws.world_state, tx.clone(), bd.clone());
+    assert!(result.error.is_none());
+    let receipt = result.receipt.unwrap();
+    match receipt.command_receipts.last() {
+        Some(CommandReceiptV2::Call(cr)) => {
+            assert_eq!(cr.exit_code, ExitCodeV2::Ok);
+            assert_eq!(cr.return_value, b"a".to_vec());
+            // the gas of the nested calls is charged to the Call command
+            assert_eq!(
+                cr.gas_used,
+                receipt.gas_used
+                    - tx_inclusion_cost_v2(
+                        tx.serialize().len(),
+                        &tx.commands.iter().map(CommandKind::from).collect(),
+                    )
+            );
+        }
+        _ => panic!("expected a call receipt"),
+    }
+
+    let mut sws: SimulateWorldState<'_, V2> = result.new_state.into();
+    assert_eq!(
+        sws.get_storage_data(contract_a, b"k".to_vec()),
+        Some(b"a".to_vec())
+    );
+    assert_eq!(
+        sws.get_storage_data(contract_a, b"j".to_vec()),
+        Some(b"a".to_vec())
+    );
+    assert_eq!(
+        sws.get_balance(tx.signer),
+        init_balance - receipt.gas_used * bd.this_base_fee
+    );
+}
@@ -1030,6 +1030,25 @@ fn test_short_circuit_insufficient_gas_for_logs_v2() {
     assert_eq!(cr.logs.len(), 0);
 }
 
+/// Log topics cost more per byte than log values.
+#[test]
+fn test_log_cost_topic_and_data() {
+    use pchain_runtime::gas::{
+        blockchain_log_cost, CRYPTO_SHA256_PER_BYTE, LOG_DATA_PER_BYTE_COST,
+        LOG_TOPIC_PER_BYTE_COST,
+    };
+
+    assert!(blockchain_log_cost(1, 0) > blockchain_log_cost(0, 1));
+    assert_eq!(
+        blockchain_log_cost(32, 0) - blockchain_log_cost(0, 32),
+        32 * CRYPTO_SHA256_PER_BYTE
+    );
+    assert_eq!(
+        blockchain_log_cost(10, 20) - blockchain_log_cost(0, 30),
+        10 * (LOG_TOPIC_PER_BYTE_COST - LOG_DATA_PER_BYTE_COST)
+    );
+}
+
 fn init_ws<'a, V: VersionProvider + Send + Sync + Clone>(
     storage: &'a SimulateWorldStateStorage,
 ) -> (PublicAddress, BlockchainParams, SimulateWorldState<'a, V>) {
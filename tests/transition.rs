@@ -1,23 +1,48 @@
 use core::panic;
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
 
 use pchain_runtime::{
-    gas::{tx_inclusion_cost_v1, tx_inclusion_cost_v2},
+    command_receipt_logs,
+    contract::wasmer::non_determinism_filter::{disallowed_opcode, DisallowedOpcode},
+    execution::execute_commands::phases::min_signer_balance_v2,
+    fee_distribution::DefaultFeeDistributor,
+    gas::{
+        explain_gas_delta, tx_inclusion_cost_v1, tx_inclusion_cost_v2, GasDeltaCause, GasSchedule,
+    },
+    receipt_v2_to_bytes,
     rewards_formulas::{TREASURY_CUT_OF_BASE_FEE_DENOM, TREASURY_CUT_OF_BASE_FEE_NUM},
     types::CommandKind,
-    BlockProposalStats, TransitionError, ValidatorPerformance,
+    validator_changes_from_bytes, validator_changes_to_bytes, AccountView, BlockApplier,
+    BlockApplierError, BlockProposalStats, BlockchainParams, CommitMode, CompilerBackend,
+    FeeDistributor, ReceiptRoot, RuntimeEvent, StateOverride, StateRentPolicy, TransitionError,
+    ValidatorChanges, ValidatorPerformance, WorldStateKey, WorldStateObserver,
 };
 use pchain_types::{
-    blockchain::{Command, CommandReceiptV2, ExitCodeV1, ExitCodeV2, TransactionV1, TransactionV2},
+    blockchain::{
+        CallReceipt, Command, CommandReceiptV2, ExitCodeV1, ExitCodeV2, Log, ReceiptV2,
+        StakeDepositReceipt, TransactionV1, TransactionV2, TransferReceipt,
+    },
     cryptography::{contract_address_v1, contract_address_v2},
-    runtime::TransferInput,
+    runtime::{
+        CallInput, CreateDepositInput, CreatePoolInput, DeployInput, SetDepositSettingsInput,
+        SetPoolSettingsInput, StakeDepositInput, TopUpDepositInput, TransferInput,
+        UnstakeDepositInput, WithdrawDepositInput,
+    },
     serialization::Serializable,
 };
-use pchain_world_state::{WorldState, V1, V2};
+use pchain_world_state::{NetworkAccount, WorldState, V1, V2};
+use sha2::Digest;
 
 use crate::common::{
-    ArgsBuilder, CallResult, SimulateWorldState, SimulateWorldStateStorage, TestData,
-    EXPECTED_CBI_VERSION,
+    assert_deterministic_v2, gas::gas_used_and_exit_code_v2, ArgsBuilder, CallResult,
+    SimulateWorldState, SimulateWorldStateStorage, TestData, EXPECTED_CBI_VERSION,
 };
 
 mod common;
@@ -62,6 +87,115 @@ fn cbi_version() {
     assert_eq!(pchain_runtime::cbi_version(), EXPECTED_CBI_VERSION);
 }
 
+/// Every Command is classified as its own CommandKind
+#[test]
+fn command_kinds() {
+    let operator = [1u8; 32];
+    let cases = vec![
+        (
+            Command::Transfer(TransferInput {
+                recipient: operator,
+                amount: 1,
+            }),
+            CommandKind::Transfer,
+        ),
+        (
+            Command::Deploy(DeployInput {
+                contract: Vec::new(),
+                cbi_version: 0,
+            }),
+            CommandKind::Deploy,
+        ),
+        (
+            Command::Call(CallInput {
+                target: operator,
+                method: "entrypoint".to_string(),
+                arguments: None,
+                amount: None,
+            }),
+            CommandKind::Call,
+        ),
+        (
+            Command::CreatePool(CreatePoolInput { commission_rate: 1 }),
+            CommandKind::CreatePool,
+        ),
+        (
+            Command::SetPoolSettings(SetPoolSettingsInput { commission_rate: 1 }),
+            CommandKind::SetPoolSettings,
+        ),
+        (Command::DeletePool, CommandKind::DeletePool),
+        (
+            Command::CreateDeposit(CreateDepositInput {
+                operator,
+                balance: 1,
+                auto_stake_rewards: false,
+            }),
+            CommandKind::CreateDeposit,
+        ),
+        (
+            Command::SetDepositSettings(SetDepositSettingsInput {
+                operator,
+                auto_stake_rewards: true,
+            }),
+            CommandKind::SetDepositSettings,
+        ),
+        (
+            Command::TopUpDeposit(TopUpDepositInput {
+                operator,
+                amount: 1,
+            }),
+            CommandKind::TopUpDeposit,
+        ),
+        (
+            Command::WithdrawDeposit(WithdrawDepositInput {
+                operator,
+                max_amount: 1,
+            }),
+            CommandKind::WithdrawDeposit,
+        ),
+        (
+            Command::StakeDeposit(StakeDepositInput {
+                operator,
+                max_amount: 1,
+            }),
+            CommandKind::StakeDeposit,
+        ),
+        (
+            Command::UnstakeDeposit(UnstakeDepositInput {
+                operator,
+                max_amount: 1,
+            }),
+            CommandKind::UnstakeDeposit,
+        ),
+        (Command::NextEpoch, CommandKind::NextEpoch),
+    ];
+
+    for (command, kind) in &cases {
+        // a new Command fails to compile here until it is added to the cases
+        match command {
+            Command::Transfer(_)
+            | Command::Deploy(_)
+            | Command::Call(_)
+            | Command::CreatePool(_)
+            | Command::SetPoolSettings(_)
+            | Command::DeletePool
+            | Command::CreateDeposit(_)
+            | Command::SetDepositSettings(_)
+            | Command::TopUpDeposit(_)
+            | Command::WithdrawDeposit(_)
+            | Command::StakeDeposit(_)
+            | Command::UnstakeDeposit(_)
+            | Command::NextEpoch => {}
+        }
+        assert_eq!(CommandKind::from(command), *kind);
+    }
+
+    // no two Commands share a CommandKind
+    for (i, (_, kind)) in cases.iter().enumerate() {
+        assert!(cases[i + 1..].iter().all(|(_, other)| other != kind));
+    }
+}
+
 //
 //
 //
@@ -1032,10 +1166,7 @@ fn test_fail_in_pre_charge() {
     };
     let result = pchain_runtime::Runtime::new().transition_v1(sws.world_state, tx1, bd.clone());
     assert!(result.receipt.is_none());
-    assert_eq!(
-        result.error,
-        Some(TransitionError::PreExecutionGasExhausted)
-    );
+    assert_eq!(result.error, Some(TransitionError::GasLimitTooLow));
     let sws: SimulateWorldState<'_, V1> = result.new_state.into();
 
     // 2. nonce is incorrect
@@ -1136,6 +1267,45 @@ fn test_upgrade_world_state() {
     assert_eq!(v2_to_balance, transfer_value);
 }
 
+/// Test that every account is carried over unchanged by the V1 to V2 world state upgrade
+#[test]
+fn test_world_state_upgrade_conserves_accounts() {
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V1> = SimulateWorldState::new(&storage);
+    let accounts: Vec<([u8; 32], u64)> = (3u8..8).map(|i| ([i; 32], 1_000 * i as u64)).collect();
+    for (address, balance) in &accounts {
+        sws.set_balance(*address, *balance);
+    }
+    let key = b"key".to_vec();
+    let value = b"value".to_vec();
+    sws.set_storage_data(accounts[0].0, key.clone(), value.clone());
+    let code = vec![1u8, 2, 3];
+    sws.add_contract(accounts[1].0, code.clone(), EXPECTED_CBI_VERSION);
+
+    let mut tx = TestData::transaction_v1();
+    tx.commands = vec![Command::NextEpoch];
+    let from_address = tx.signer;
+
+    let mut bd = TestData::block_params();
+    let mut stats = HashMap::new();
+    stats.insert(from_address, BlockProposalStats::new(1));
+    bd.validator_performance = Some(ValidatorPerformance {
+        blocks_per_epoch: 1,
+        stats,
+    });
+
+    let upgraded = pchain_runtime::Runtime::new().transition_v1_to_v2(sws.world_state, tx, bd);
+    assert_eq!(upgraded.error, None);
+
+    let mut sws: SimulateWorldState<'_, V2> = upgraded.new_state.unwrap().into();
+    for (address, balance) in &accounts {
+        assert_eq!(sws.get_balance(*address), *balance);
+        assert_eq!(sws.get_nonce(*address), 0);
+    }
+    assert_eq!(sws.get_storage_data(accounts[0].0, key), Some(value));
+    assert_eq!(sws.get_contract_code(accounts[1].0), Some(code));
+}
+
 /// Test that the runtime will reject invalid commands when transitioning from WS V1 to WS V2
 #[test]
 fn test_failed_world_state_upgrade_improper_command() {
@@ -1244,6 +1414,8 @@ fn test_etoe_v2() {
     let init_from_balance = 100_000_000;
     sws.set_balance(from_address, init_from_balance);
 
+    assert_deterministic_v2(sws.world_state.clone(), tx.clone(), bd.clone());
+
     let result = pchain_runtime::Runtime::new().transition_v2(sws.world_state, tx, bd);
     let receipt = result.receipt.unwrap();
     assert_eq!(receipt.exit_code, ExitCodeV2::Ok);
@@ -1267,171 +1439,2831 @@ fn test_etoe_v2() {
     assert_eq!(sws.get_nonce(to_address), 0);
 }
 
-/// Contract Call from external account
+/// Write set commitment is only computed when enabled, and is deterministic
 #[test]
-fn test_etoc_v2() {
-    let wasm_bytes = TestData::get_test_contract_code("basic_contract");
-    let method_args = "arg".to_string();
-    let method_name = "emit_event_with_return";
-    let target = [2u8; 32];
+fn test_write_set_commitment_v2() {
     let mut tx = TestData::transaction_v2();
-    tx.gas_limit = 10_000_000;
-    tx.commands =
-        vec![ArgsBuilder::new()
-            .add(method_args.clone())
-            .make_call(Some(0), target, method_name)];
-
+    tx.commands = vec![Command::Transfer(TransferInput {
+        recipient: [2u8; 32],
+        amount: 1,
+    })];
     let bd = TestData::block_params();
-    let base_fee_per_gas = bd.this_base_fee;
 
-    // initialize world state
     let storage = SimulateWorldStateStorage::default();
     let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
-    let from_address = tx.signer;
-    let to_address = target;
-    let init_from_balance = 100_000_000;
-    sws.set_balance(from_address, init_from_balance);
-    sws.add_contract(to_address, wasm_bytes, pchain_runtime::cbi_version());
+    sws.set_balance(tx.signer, 100_000_000);
 
-    let result = pchain_runtime::Runtime::new().transition_v2(sws.world_state, tx, bd);
-    let receipt = result.receipt.unwrap();
-    assert_eq!(receipt.exit_code, ExitCodeV2::Ok);
+    let result = pchain_runtime::Runtime::new().transition_v2(
+        sws.world_state.clone(),
+        tx.clone(),
+        bd.clone(),
+    );
+    assert!(result.write_set_commitment.is_none());
 
-    let sws: SimulateWorldState<'_, V2> = result.new_state.into();
+    let runtime = pchain_runtime::Runtime::new().set_write_set_commitment(true);
+    let result_1 = runtime.transition_v2(sws.world_state.clone(), tx.clone(), bd.clone());
+    let result_2 = runtime.transition_v2(sws.world_state, tx, bd);
+    assert!(result_1.write_set_commitment.is_some());
+    assert_eq!(result_1.write_set_commitment, result_2.write_set_commitment);
+}
 
-    // check from_address balance
-    let gas_used = receipt.gas_used;
-    let new_from_balance = sws.get_balance(from_address);
-    assert_eq!(
-        new_from_balance,
-        init_from_balance - base_fee_per_gas * gas_used
+/// Gas is charged by the gas schedule of the Runtime, which defaults to the Mainnet schedule
+#[test]
+fn test_gas_schedule_v2() {
+    let mut tx = TestData::transaction_v2();
+    tx.commands = vec![Command::Transfer(TransferInput {
+        recipient: [2u8; 32],
+        amount: 1,
+    })];
+    let bd = TestData::block_params();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(tx.signer, 100_000_000);
+
+    let result = pchain_runtime::Runtime::new().transition_v2(
+        sws.world_state.clone(),
+        tx.clone(),
+        bd.clone(),
     );
-    assert_eq!(sws.get_nonce(from_address), 1);
+    let gas_used = result.receipt.unwrap().gas_used;
 
-    // check to_address balance
-    let new_to_balance = sws.get_balance(to_address);
-    assert_eq!(new_to_balance, 0);
-    assert_eq!(sws.get_nonce(to_address), 0);
+    let runtime = pchain_runtime::Runtime::new().set_gas_schedule(GasSchedule::MAINNET);
+    let result = runtime.transition_v2(sws.world_state.clone(), tx.clone(), bd.clone());
+    assert_eq!(result.receipt.unwrap().gas_used, gas_used);
 
-    let cr = match receipt.command_receipts.last() {
-        Some(CommandReceiptV2::Call(cr)) => cr,
-        _ => panic!("Expecting a Call command receipt"),
+    let runtime = pchain_runtime::Runtime::new().set_gas_schedule(GasSchedule {
+        blockchain_write_per_byte_cost: 2 * GasSchedule::MAINNET.blockchain_write_per_byte_cost,
+        mpt_write_per_byte_cost: 2 * GasSchedule::MAINNET.mpt_write_per_byte_cost,
+        ..GasSchedule::MAINNET
+    });
+    let result = runtime.transition_v2(sws.world_state, tx, bd);
+    let receipt = result.receipt.unwrap();
+    assert_eq!(receipt.exit_code, ExitCodeV2::Ok);
+    assert!(receipt.gas_used > gas_used);
+}
+
+/// The new key surcharge of the gas schedule is charged for setting a storage key which holds no value, and not for
+/// overwriting one
+#[test]
+fn test_gas_schedule_new_key_surcharge_v2() {
+    let wasm_bytes = wat::parse_str(
+        r#"
+        (module
+            (import "env" "set" (func $set (param i32 i32 i32 i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "k")
+            (data (i32.const 16) "v")
+            (func (export "alloc") (param i32) (result i32)
+                i32.const 1024)
+            (func (export "set_k")
+                (call $set (i32.const 0) (i32.const 1) (i32.const 16) (i32.const 1))))
+        "#,
+    )
+    .unwrap();
+    let target = [2u8; 32];
+    let mut tx = TestData::transaction_v2();
+    tx.gas_limit = 10_000_000;
+    tx.commands = vec![ArgsBuilder::new().make_call(None, target, "set_k")];
+    let bd = TestData::block_params();
+    let surcharge = 10_000;
+    let gas_used = |sws: &SimulateWorldState<'_, V2>, new_key_surcharge: u64| {
+        let result = pchain_runtime::Runtime::new()
+            .set_gas_schedule(GasSchedule {
+                new_key_surcharge,
+                ..GasSchedule::MAINNET
+            })
+            .transition_v2(sws.world_state.clone(), tx.clone(), bd.clone());
+        assert!(result.error.is_none());
+        result.receipt.unwrap().gas_used
     };
 
-    // check return value from the called method
-    let result_value: u32 = CallResult::parse(cr.return_value.clone()).unwrap();
-    assert_eq!(result_value as usize, method_args.len());
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(tx.signer, 100_000_000);
+    sws.add_contract(target, wasm_bytes, pchain_runtime::cbi_version());
+    assert_eq!(GasSchedule::MAINNET.new_key_surcharge, 0);
+    assert_eq!(gas_used(&sws, surcharge) - gas_used(&sws, 0), surcharge);
 
-    // check event is emitted in logs
-    assert!(cr
-        .logs
-        .iter()
-        .find(|e| {
-            String::from_utf8(e.topic.clone()).unwrap() == "topic: Hello From".to_string()
-                && String::from_utf8(e.value.clone()).unwrap()
-                    == format!("Hello, Contract. From: {}", method_args).to_string()
-        })
-        .is_some());
+    sws.set_storage_data(target, b"k".to_vec(), b"w".to_vec());
+    assert_eq!(gas_used(&sws, surcharge), gas_used(&sws, 0));
 }
 
-/// Multiple Contract Calls in a Transaction
+/// The inclusion cost of the Runtime is the gas charged for including a transaction in a transition, and the minimum
+/// gas limit of the transaction
 #[test]
-fn test_etoc_multiple_v2() {
-    let wasm_bytes_1 = TestData::get_test_contract_code("all_features");
-    let contract_address_1 = [22u8; 32];
-    let method_args_1 = 123_i32;
-    let method_name_1 = "set_data_only";
-    let command_1 =
-        ArgsBuilder::new()
-            .add(method_args_1)
-            .make_call(Some(1), contract_address_1, method_name_1);
+fn test_inclusion_cost_v2() {
+    let mut tx = TestData::transaction_v2();
+    tx.commands = vec![
+        Command::Transfer(TransferInput {
+            recipient: [2u8; 32],
+            amount: 1,
+        }),
+        ArgsBuilder::new().make_call(None, [3u8; 32], "entrypoint"),
+    ];
+    let bd = TestData::block_params();
 
-    let wasm_bytes_2 = TestData::get_test_contract_code("basic_contract");
-    let contract_address_2 = [2u8; 32];
-    let method_args_2 = "arg".to_string();
-    let method_name_2 = "emit_event_with_return";
-    let command_2 = ArgsBuilder::new().add(method_args_2.clone()).make_call(
-        Some(2),
-        contract_address_2,
-        method_name_2,
-    );
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(tx.signer, 100_000_000);
 
-    let method_name_3 = "get_data_only";
-    let command_3 = ArgsBuilder::new().make_call(Some(3), contract_address_1, method_name_3);
+    let schedule = GasSchedule {
+        blockchain_write_per_byte_cost: 2 * GasSchedule::MAINNET.blockchain_write_per_byte_cost,
+        ..GasSchedule::MAINNET
+    };
+    for runtime in [
+        pchain_runtime::Runtime::new(),
+        pchain_runtime::Runtime::new().set_gas_schedule(schedule),
+    ] {
+        let inclusion_cost = runtime.inclusion_cost_v2(&tx);
+        let result = runtime.transition_v2(sws.world_state.clone(), tx.clone(), bd.clone());
+        let receipt = result.receipt.unwrap();
+        let commands_gas_used: u64 = receipt
+            .command_receipts
+            .iter()
+            .map(|r| match r {
+                CommandReceiptV2::Transfer(r) => r.gas_used,
+                CommandReceiptV2::Call(r) => r.gas_used,
+                _ => panic!("unexpected command receipt"),
+            })
+            .sum();
+        assert_eq!(receipt.gas_used - commands_gas_used, inclusion_cost);
+
+        let mut tx = tx.clone();
+        tx.gas_limit = inclusion_cost - 1;
+        let result = runtime.transition_v2(sws.world_state.clone(), tx, bd.clone());
+        assert_eq!(result.error, Some(TransitionError::GasLimitTooLow));
+    }
+
+    assert_eq!(
+        pchain_runtime::Runtime::new().inclusion_cost_v2(&tx),
+        tx_inclusion_cost_v2(
+            tx.serialize().len(),
+            &tx.commands.iter().map(CommandKind::from).collect()
+        )
+    );
+    assert!(
+        pchain_runtime::Runtime::new()
+            .set_gas_schedule(schedule)
+            .inclusion_cost_v2(&tx)
+            > pchain_runtime::Runtime::new().inclusion_cost_v2(&tx)
+    );
+}
 
+/// Memory profile records the peak linear memory of contract calls only when enabled
+#[test]
+fn test_memory_profile_v2() {
+    let wasm_bytes = TestData::get_test_contract_code("basic_contract");
+    let target = [2u8; 32];
     let mut tx = TestData::transaction_v2();
-    tx.gas_limit = 20_000_000;
-    let proposer_address = [4u8; 32];
-    let treasury_address = [100u8; 32];
+    tx.gas_limit = 10_000_000;
+    tx.commands = vec![
+        Command::Transfer(TransferInput {
+            recipient: target,
+            amount: 1,
+        }),
+        ArgsBuilder::new().add("arg".to_string()).make_call(
+            Some(0),
+            target,
+            "emit_event_with_return",
+        ),
+    ];
     let bd = TestData::block_params();
-    let base_fee_per_gas = bd.this_base_fee;
 
-    // initialize world state
     let storage = SimulateWorldStateStorage::default();
     let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
-    let from_address = tx.signer;
-    let init_from_balance = 500_000_000;
-    sws.set_balance(from_address, init_from_balance);
-    sws.add_contract(
-        contract_address_1,
-        wasm_bytes_1,
-        pchain_runtime::cbi_version(),
+    sws.set_balance(tx.signer, 100_000_000);
+    sws.add_contract(target, wasm_bytes, pchain_runtime::cbi_version());
+
+    let result = pchain_runtime::Runtime::new().transition_v2(
+        sws.world_state.clone(),
+        tx.clone(),
+        bd.clone(),
     );
-    sws.add_contract(
-        contract_address_2,
-        wasm_bytes_2,
-        pchain_runtime::cbi_version(),
+    assert!(result.memory_profile.is_none());
+
+    let profiled = pchain_runtime::Runtime::new()
+        .set_memory_profiling(true)
+        .transition_v2(sws.world_state, tx, bd);
+    let memory_profile = profiled.memory_profile.unwrap();
+    assert_eq!(memory_profile.len(), 2);
+    assert_eq!(memory_profile[0], 0);
+    assert!(memory_profile[1] > 0);
+    // profiling does not affect gas
+    assert_eq!(
+        profiled.receipt.unwrap().gas_used,
+        result.receipt.unwrap().gas_used
     );
+}
+
+/// The gas breakdown of each command adds up to the gas used in its command receipt
+#[test]
+fn test_gas_breakdown_v2() {
+    let wasm_bytes = TestData::get_test_contract_code("basic_contract");
+    let target = [2u8; 32];
+    let mut tx = TestData::transaction_v2();
+    tx.gas_limit = 10_000_000;
+    tx.commands = vec![
+        Command::Transfer(TransferInput {
+            recipient: target,
+            amount: 1,
+        }),
+        ArgsBuilder::new().add("arg".to_string()).make_call(
+            Some(0),
+            target,
+            "emit_event_with_return",
+        ),
+    ];
+    let bd = TestData::block_params();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(tx.signer, 100_000_000);
+    sws.add_contract(target, wasm_bytes, pchain_runtime::cbi_version());
 
-    // test for two commands (dry run)
-    tx.commands = vec![command_1.clone(), command_3.clone()];
     let result = pchain_runtime::Runtime::new().transition_v2(
         sws.world_state.clone(),
         tx.clone(),
         bd.clone(),
     );
-    let receipt = result.receipt.unwrap();
+    assert!(result.gas_breakdown.is_none());
+
+    let accounted = pchain_runtime::Runtime::new()
+        .set_gas_accounting(true)
+        .transition_v2(sws.world_state, tx, bd);
+    assert!(accounted.error.is_none());
+    let gas_breakdown = accounted.gas_breakdown.unwrap();
+    let receipt = accounted.receipt.unwrap();
+    assert_eq!(gas_breakdown.len(), receipt.command_receipts.len());
+    for (breakdown, command_receipt) in gas_breakdown.iter().zip(&receipt.command_receipts) {
+        let (gas_used, _) = gas_used_and_exit_code_v2(command_receipt);
+        assert_eq!(breakdown.total(), gas_used);
+    }
 
-    assert_eq!(receipt.command_receipts.len(), tx.commands.len());
-    let gas_consumed_1 = extract_gas_success_call_v2(&receipt.command_receipts[0]);
-    let gas_consumed_3 = extract_gas_success_call_v2(&receipt.command_receipts[1]);
+    // a Transfer reads and writes balances without executing Wasm
+    let transfer = gas_breakdown[0];
+    assert!(transfer.read_gas > 0);
+    assert!(transfer.write_gas > 0);
+    assert_eq!(transfer.wasm_gas, 0);
+    assert_eq!(transfer.opcode_gas, 0);
+
+    // a Call also executes Wasm
+    let call = gas_breakdown[1];
+    assert!(call.read_gas > 0);
+    assert!(call.write_gas > 0);
+    assert!(call.wasm_gas > 0);
+    assert!(call.opcode_gas > 0);
+
+    // gas accounting does not affect gas
+    assert_eq!(receipt.gas_used, result.receipt.unwrap().gas_used);
+}
 
-    // test for three commands (insert a command in the middle)
-    tx.commands = vec![command_1.clone(), command_2.clone(), command_3.clone()];
+/// Replays of a transaction are identical, and report the receipt of the transition with its gas breakdowns and the
+/// World State data touched in order
+#[test]
+fn test_replay_v2() {
+    let wasm_bytes = TestData::get_test_contract_code("basic_contract");
+    let target = [2u8; 32];
+    let mut tx = TestData::transaction_v2();
+    tx.gas_limit = 10_000_000;
+    tx.commands = vec![
+        Command::Transfer(TransferInput {
+            recipient: target,
+            amount: 1,
+        }),
+        ArgsBuilder::new().add("arg".to_string()).make_call(
+            Some(0),
+            target,
+            "emit_event_with_return",
+        ),
+    ];
+    let bd = TestData::block_params();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(tx.signer, 100_000_000);
+    sws.add_contract(target, wasm_bytes, pchain_runtime::cbi_version());
+
+    let runtime = pchain_runtime::Runtime::new();
+    let replay_1 = runtime.replay_v2(sws.world_state.clone(), tx.clone(), bd.clone());
+    let replay_2 = runtime.replay_v2(sws.world_state.clone(), tx.clone(), bd.clone());
+    assert_eq!(replay_1, replay_2);
+
+    let result = runtime.transition_v2(sws.world_state, tx.clone(), bd);
+    assert_eq!(replay_1.error, result.error);
+    assert_eq!(replay_1.receipt, result.receipt);
+
+    let receipt = replay_1.receipt.unwrap();
+    assert_eq!(replay_1.gas_breakdown.len(), receipt.command_receipts.len());
+    for (breakdown, command_receipt) in replay_1.gas_breakdown.iter().zip(&receipt.command_receipts)
+    {
+        assert_eq!(
+            breakdown.total(),
+            gas_used_and_exit_code_v2(command_receipt).0
+        );
+    }
+
+    // the Pre-Charge phase reads the balance of the signer first
+    assert_eq!(replay_1.touched_keys[0], WorldStateKey::Balance(tx.signer));
+    for key in [
+        WorldStateKey::Balance(target),
+        WorldStateKey::ContractCode(target),
+    ] {
+        assert_eq!(
+            replay_1.touched_keys.iter().filter(|k| **k == key).count(),
+            1
+        );
+    }
+}
+
+/// A deployed contract reports the CBI version it was deployed with, and an account without a contract reports None
+#[test]
+fn test_contract_cbi_version() {
+    let wasm_bytes = TestData::get_test_contract_code("basic_contract");
+    let origin_address = [1u8; 32];
+    let contract_address = contract_address_v2(&origin_address, 0, 0);
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(origin_address, 500_000_000);
+
+    let mut tx = TestData::transaction_v2();
+    tx.gas_limit = 400_000_000;
+    tx.commands = vec![ArgsBuilder::new().make_deploy(wasm_bytes, pchain_runtime::cbi_version())];
     let result =
-        pchain_runtime::Runtime::new().transition_v2(sws.world_state, tx.clone(), bd.clone());
-    let receipt = result.receipt.unwrap();
-    assert_eq!(receipt.command_receipts.len(), tx.commands.len());
-    let gas_consumed_2 = extract_gas_success_call_v2(&receipt.command_receipts[1]);
-    // check gas consumption and receipt is independent on the inserted command
+        pchain_runtime::Runtime::new().transition_v2(sws.world_state, tx, TestData::block_params());
+    assert_eq!(result.error, None);
+
+    let runtime = pchain_runtime::Runtime::new();
     assert_eq!(
-        gas_consumed_1,
-        extract_gas_success_call_v2(&receipt.command_receipts[0])
+        runtime.contract_cbi_version(result.new_state.clone(), contract_address),
+        Some(pchain_runtime::cbi_version())
     );
-    assert_ne!(gas_consumed_1, gas_consumed_2);
     assert_eq!(
-        gas_consumed_3,
-        extract_gas_success_call_v2(&receipt.command_receipts[2])
+        runtime.contract_cbi_version(result.new_state, origin_address),
+        None
     );
+}
 
-    if let CommandReceiptV2::Call(cr) = &receipt.command_receipts[2] {
-        assert_eq!(cr.exit_code, ExitCodeV2::Ok);
-        assert!(cr.logs.is_empty());
-        let get_data_value: i32 = CallResult::parse(cr.return_value.clone()).unwrap();
-        assert_eq!(method_args_1, get_data_value);
-    } else {
-        panic!("Expected CommandReceiptV2::Call");
-    }
-
-    let mut sws: SimulateWorldState<'_, V2> = result.new_state.into();
+/// The snapshot of funded accounts, including a contract account, reports their balances and nonces, and the
+/// snapshot of an account which does not exist reports zeros
+#[test]
+fn test_accounts_snapshot() {
+    let wasm_bytes = TestData::get_test_contract_code("basic_contract");
+    let accounts = [[1u8; 32], [2u8; 32], [3u8; 32]];
+    let empty_account = [4u8; 32];
 
-    // check state change from contract
-    let data = sws.get_storage_data(contract_address_1, vec![0u8]);
-    assert_eq!(data, Some(123_i32.to_le_bytes().to_vec()));
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(accounts[0], 100);
+    sws.set_balance(accounts[1], 200);
+    sws.set_balance(accounts[2], 300);
+    sws.world_state
+        .account_trie_mut()
+        .set_nonce(&accounts[0], 5)
+        .unwrap();
+    sws.add_contract(accounts[2], wasm_bytes, pchain_runtime::cbi_version());
 
-    // check from_address balance
-    let total_gas_used = gas_consumed_1 + gas_consumed_2 + gas_consumed_3 + tx_base_cost_v2(&tx);
-    let new_from_balance = sws.get_balance(from_address);
+    let snapshot = pchain_runtime::Runtime::new().accounts_snapshot(
+        sws.world_state,
+        &[accounts[0], accounts[1], empty_account, accounts[2]],
+    );
+    assert_eq!(
+        snapshot,
+        vec![
+            AccountView {
+                balance: 100,
+                nonce: 5,
+                is_contract: false
+            },
+            AccountView {
+                balance: 200,
+                nonce: 0,
+                is_contract: false
+            },
+            AccountView {
+                balance: 0,
+                nonce: 0,
+                is_contract: false
+            },
+            AccountView {
+                balance: 300,
+                nonce: 0,
+                is_contract: true
+            },
+        ]
+    );
+}
+
+/// The writes of a transition, applied to the storage of the input World State, reproduce the next World State
+#[test]
+fn test_writes_v2() {
+    let target = [2u8; 32];
+    let mut tx = TestData::transaction_v2();
+    tx.commands = vec![Command::Transfer(TransferInput {
+        recipient: target,
+        amount: 1,
+    })];
+
+    // commit the initial state so that the input World State has no pending changes
+    let mut storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(tx.signer, 500_000_000);
+    let initial = sws.world_state.close().unwrap();
+    storage.apply_writes(
+        initial
+            .inserts
+            .into_iter()
+            .map(|(key, value)| (key, Some(value))),
+    );
+
+    let ws = WorldState::<SimulateWorldStateStorage, V2>::open(&storage, initial.new_root_hash);
+    let result =
+        pchain_runtime::Runtime::new().transition_v2(ws, tx.clone(), TestData::block_params());
+    assert!(result.error.is_none());
+
+    let writes: Vec<(Vec<u8>, Option<Vec<u8>>)> = result.writes().collect();
+    assert!(!writes.is_empty());
+    assert!(writes.windows(2).all(|w| w[0].0 < w[1].0));
+
+    let new_state = SimulateWorldState::from(result.new_state.clone());
+    let new_root_hash = result.new_state.close().unwrap().new_root_hash;
+    let mut next_storage = storage.clone();
+    next_storage.apply_writes(writes);
+    let next_ws = WorldState::<SimulateWorldStateStorage, V2>::open(&next_storage, new_root_hash);
+    let next_sws = SimulateWorldState::from(next_ws);
+    assert_eq!(next_sws.get_balance(target), 1);
+    assert_eq!(next_sws.get_balance(target), new_state.get_balance(target));
+    assert_eq!(
+        next_sws.get_balance(tx.signer),
+        new_state.get_balance(tx.signer)
+    );
+    assert_eq!(next_sws.get_nonce(tx.signer), 1);
+}
+
+/// Runtime events describe the effects of a transaction in the order in which they took place
+#[test]
+fn test_transition_v2_with_events() {
+    let signer = [1u8; 32];
+    let recipient = [2u8; 32];
+    let mut tx = TestData::transaction_v2();
+    tx.commands = vec![Command::Transfer(TransferInput {
+        recipient,
+        amount: 10,
+    })];
+    let bd = TestData::block_params();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(signer, 100_000_000);
+
+    let result = pchain_runtime::Runtime::new().transition_v2(
+        sws.world_state.clone(),
+        tx.clone(),
+        bd.clone(),
+    );
+    assert!(result.events.is_none());
+
+    let result = pchain_runtime::Runtime::new().transition_v2_with_events(
+        sws.world_state.clone(),
+        tx.clone(),
+        bd.clone(),
+    );
+    let transfer_gas_used = match &result.receipt.unwrap().command_receipts[0] {
+        CommandReceiptV2::Transfer(receipt) => receipt.gas_used,
+        _ => panic!("Expecting a Transfer command receipt"),
+    };
+    let events = result.events.unwrap();
+    let new_sws: SimulateWorldState<'_, V2> = result.new_state.into();
+    let pre_charged_balance = 100_000_000 - tx.gas_limit * bd.this_base_fee;
+    assert_eq!(
+        events[..3],
+        [
+            RuntimeEvent::BalanceChanged {
+                address: signer,
+                balance: pre_charged_balance - 10,
+            },
+            RuntimeEvent::BalanceChanged {
+                address: recipient,
+                balance: 10,
+            },
+            RuntimeEvent::CommandCompleted {
+                command_index: 0,
+                command_kind: CommandKind::Transfer,
+                exit_code: ExitCodeV2::Ok,
+                gas_used: transfer_gas_used,
+            },
+        ]
+    );
+    // the Charge phase sets the final balances of the signer, proposer and treasury
+    assert_eq!(events.len(), 6);
+    for event in &events[3..] {
+        match event {
+            RuntimeEvent::BalanceChanged { address, balance } => {
+                assert_eq!(new_sws.get_balance(*address), *balance)
+            }
+            _ => panic!("Expecting a BalanceChanged event"),
+        }
+    }
+
+    // a failed command reverts the effects of all commands
+    tx.commands.push(Command::Transfer(TransferInput {
+        recipient,
+        amount: u64::MAX,
+    }));
+    let result = pchain_runtime::Runtime::new().transition_v2_with_events(sws.world_state, tx, bd);
+    assert_eq!(
+        result.error,
+        Some(TransitionError::NotEnoughBalanceForTransfer)
+    );
+    let events = result.events.unwrap();
+    assert_eq!(events.len(), 5);
+    assert!(matches!(
+        events[0],
+        RuntimeEvent::CommandCompleted {
+            command_index: 0,
+            exit_code: ExitCodeV2::Ok,
+            ..
+        }
+    ));
+    assert!(matches!(
+        events[1],
+        RuntimeEvent::CommandCompleted {
+            command_index: 1,
+            exit_code: ExitCodeV2::Error,
+            ..
+        }
+    ));
+}
+
+/// Records the reads and writes of the World State passed to a [WorldStateObserver], in the order of the calls
+#[derive(Default)]
+struct RecordingObserver {
+    accesses: Mutex<Vec<(&'static str, WorldStateKey, Option<Vec<u8>>)>>,
+}
+
+impl WorldStateObserver for RecordingObserver {
+    fn on_read(&self, key: &WorldStateKey, value: &Option<Vec<u8>>) {
+        self.accesses
+            .lock()
+            .unwrap()
+            .push(("read", key.clone(), value.clone()));
+    }
+
+    fn on_write(&self, key: &WorldStateKey, value: &Option<Vec<u8>>) {
+        self.accesses
+            .lock()
+            .unwrap()
+            .push(("write", key.clone(), value.clone()));
+    }
+}
+
+/// A World State observer sees the reads and writes of a Transfer in the order in which they are made, and does not
+/// change the result of the transition
+#[test]
+fn test_world_state_observer_v2() {
+    let signer = [1u8; 32];
+    let recipient = [2u8; 32];
+    let mut tx = TestData::transaction_v2();
+    tx.commands = vec![Command::Transfer(TransferInput {
+        recipient,
+        amount: 10,
+    })];
+    let bd = TestData::block_params();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(signer, 100_000_000);
+
+    let expected = pchain_runtime::Runtime::new().transition_v2(
+        sws.world_state.clone(),
+        tx.clone(),
+        bd.clone(),
+    );
+    let observer = Arc::new(RecordingObserver::default());
+    let result = pchain_runtime::Runtime::new()
+        .set_world_state_observer(observer.clone())
+        .transition_v2(sws.world_state, tx.clone(), bd.clone());
+    assert_eq!(result.error, expected.error);
+    assert_eq!(result.receipt, expected.receipt);
+
+    let new_sws: SimulateWorldState<'_, V2> = result.new_state.into();
+    let expected_sws: SimulateWorldState<'_, V2> = expected.new_state.into();
+    for address in [signer, recipient, bd.proposer_address, bd.treasury_address] {
+        assert_eq!(
+            new_sws.get_balance(address),
+            expected_sws.get_balance(address)
+        );
+    }
+
+    let read = |address, balance: u64| {
+        (
+            "read",
+            WorldStateKey::Balance(address),
+            Some(balance.to_le_bytes().to_vec()),
+        )
+    };
+    let write = |address, balance: u64| {
+        (
+            "write",
+            WorldStateKey::Balance(address),
+            Some(balance.to_le_bytes().to_vec()),
+        )
+    };
+    let pre_charged_balance = 100_000_000 - tx.gas_limit * bd.this_base_fee;
+    assert_eq!(
+        *observer.accesses.lock().unwrap(),
+        vec![
+            // pre-Charge
+            read(signer, 100_000_000),
+            read(signer, 100_000_000),
+            write(signer, pre_charged_balance),
+            // Transfer
+            read(signer, pre_charged_balance),
+            read(signer, pre_charged_balance),
+            write(signer, pre_charged_balance - 10),
+            read(recipient, 0),
+            read(recipient, 0),
+            write(recipient, 10),
+            // Charge
+            read(signer, pre_charged_balance - 10),
+            read(bd.proposer_address, 0),
+            read(bd.treasury_address, 0),
+            write(signer, new_sws.get_balance(signer)),
+            write(
+                bd.proposer_address,
+                new_sws.get_balance(bd.proposer_address)
+            ),
+            write(
+                bd.treasury_address,
+                new_sws.get_balance(bd.treasury_address)
+            ),
+        ]
+    );
+}
+
+/// The base fee and priority fee paid by the signer add up to the decrease in its balance, less the value transferred
+#[test]
+fn test_fees_paid_v2() {
+    let signer = [1u8; 32];
+    let recipient = [2u8; 32];
+    let mut tx = TestData::transaction_v2();
+    tx.priority_fee_per_gas = 3;
+    tx.commands = vec![Command::Transfer(TransferInput {
+        recipient,
+        amount: 10,
+    })];
+    let bd = TestData::block_params();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(signer, 100_000_000);
+
+    let result =
+        pchain_runtime::Runtime::new().transition_v2(sws.world_state, tx.clone(), bd.clone());
+    assert!(result.error.is_none());
+    let gas_used = result.receipt.as_ref().unwrap().gas_used;
+    let base_fee_paid = result.base_fee_paid();
+    let priority_fee_paid = result.priority_fee_paid();
+    assert_eq!(base_fee_paid, gas_used * bd.this_base_fee);
+    assert_eq!(priority_fee_paid, gas_used * tx.priority_fee_per_gas);
+    assert!(priority_fee_paid > 0);
+
+    let new_sws: SimulateWorldState<'_, V2> = result.new_state.into();
+    assert_eq!(
+        100_000_000 - new_sws.get_balance(signer) - 10,
+        base_fee_paid + priority_fee_paid
+    );
+    assert_eq!(new_sws.get_balance(bd.proposer_address), priority_fee_paid);
+}
+
+/// Stateless validation rejects transactions which fail in execution with the same error
+#[test]
+fn test_validate_v2() {
+    let runtime = pchain_runtime::Runtime::new();
+    let bd = TestData::block_params();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(TestData::transaction_v2().signer, 100_000_000);
+
+    let transfer = Command::Transfer(TransferInput {
+        recipient: [2u8; 32],
+        amount: 1,
+    });
+    for (commands, expected_error) in [
+        (vec![transfer.clone()], None),
+        (
+            vec![Command::CreatePool(CreatePoolInput {
+                commission_rate: 101,
+            })],
+            Some(TransitionError::InvalidPoolPolicy),
+        ),
+        (
+            vec![
+                Command::CreatePool(CreatePoolInput { commission_rate: 1 }),
+                Command::SetPoolSettings(SetPoolSettingsInput {
+                    commission_rate: 101,
+                }),
+            ],
+            Some(TransitionError::InvalidPoolPolicy),
+        ),
+        (
+            vec![transfer.clone(), Command::NextEpoch],
+            Some(TransitionError::InvalidNextEpochCommand),
+        ),
+    ] {
+        let mut tx = TestData::transaction_v2();
+        tx.gas_limit = 10_000_000;
+        tx.commands = commands;
+        assert_eq!(runtime.validate_v2(&tx).err(), expected_error);
+
+        // execution fails with the same error
+        let result = runtime.transition_v2(sws.world_state.clone(), tx, bd.clone());
+        assert_eq!(result.error, expected_error);
+    }
+}
+
+/// Balance changes report the final balances of the accounts whose balances were changed by the transaction
+#[test]
+fn test_balance_changes_v2() {
+    struct BurnAll;
+    impl FeeDistributor for BurnAll {
+        fn distribute(
+            &self,
+            _base_fee_amount: u64,
+            _priority_fee_amount: u64,
+            _proposer: [u8; 32],
+            _treasury: [u8; 32],
+        ) -> Vec<([u8; 32], u64)> {
+            vec![]
+        }
+    }
+
+    let recipient = [2u8; 32];
+    let mut tx = TestData::transaction_v2();
+    tx.commands = vec![Command::Transfer(TransferInput {
+        recipient,
+        amount: 10,
+    })];
+    let bd = TestData::block_params();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(tx.signer, 100_000_000);
+
+    // 1. the signer, the recipient and the treasury, which receives a cut of the base fee. The proposer's balance is
+    // written without change, as the priority fee is zero.
+    let result = pchain_runtime::Runtime::new()
+        .set_balance_changes(true)
+        .transition_v2(sws.world_state.clone(), tx.clone(), bd.clone());
+    assert!(result.error.is_none());
+    let balance_changes = result.balance_changes();
+    let new_sws: SimulateWorldState<'_, V2> = result.new_state.into();
+    let mut expected: Vec<([u8; 32], u64)> = [tx.signer, recipient, bd.treasury_address]
+        .into_iter()
+        .map(|address| (address, new_sws.get_balance(address)))
+        .collect();
+    expected.sort();
+    assert_eq!(balance_changes, expected);
+    assert_eq!(new_sws.get_balance(recipient), 10);
+
+    // 2. with the fees burned, exactly the signer and the recipient
+    let result = pchain_runtime::Runtime::new()
+        .set_balance_changes(true)
+        .set_fee_distributor(Arc::new(BurnAll))
+        .transition_v2(sws.world_state.clone(), tx.clone(), bd.clone());
+    assert!(result.error.is_none());
+    let gas_used = result.receipt.as_ref().unwrap().gas_used;
+    assert_eq!(
+        result.balance_changes(),
+        vec![
+            (tx.signer, 100_000_000 - 10 - gas_used * bd.this_base_fee),
+            (recipient, 10)
+        ]
+    );
+
+    // 3. not recorded by default
+    let result = pchain_runtime::Runtime::new().transition_v2(sws.world_state, tx, bd);
+    assert!(result.error.is_none());
+    assert!(result.balance_changes().is_empty());
+}
+
+/// State rent for the growth in state size is transferred from the signer to the treasury in the Charge phase
+#[test]
+fn test_state_rent_policy_v2() {
+    struct PerByteRent(u64);
+    impl StateRentPolicy for PerByteRent {
+        fn rent(&self, size_delta: i64) -> u64 {
+            (size_delta.max(0) as u64).saturating_mul(self.0)
+        }
+    }
+
+    let contract_code = TestData::get_test_contract_code("basic_contract");
+    let code_len = contract_code.len() as u64;
+    let mut tx = TestData::transaction_v2();
+    tx.gas_limit = 400_000_000;
+    tx.commands = vec![ArgsBuilder::new().make_deploy(contract_code, 0)];
+    let bd = TestData::block_params();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(tx.signer, 500_000_000_000);
+
+    let result = pchain_runtime::Runtime::new().transition_v2(
+        sws.world_state.clone(),
+        tx.clone(),
+        bd.clone(),
+    );
+    assert!(result.error.is_none());
+    let gas_used = result.receipt.unwrap().gas_used;
+    let no_rent_sws: SimulateWorldState<'_, V2> = result.new_state.into();
+
+    let result = pchain_runtime::Runtime::new()
+        .set_state_rent_policy(Arc::new(PerByteRent(2)))
+        .transition_v2(sws.world_state, tx.clone(), bd.clone());
+    assert!(result.error.is_none());
+    assert_eq!(result.receipt.unwrap().gas_used, gas_used);
+    let rent_sws: SimulateWorldState<'_, V2> = result.new_state.into();
+
+    assert_eq!(
+        rent_sws.get_balance(tx.signer),
+        no_rent_sws.get_balance(tx.signer) - 2 * code_len
+    );
+    assert_eq!(
+        rent_sws.get_balance(bd.treasury_address),
+        no_rent_sws.get_balance(bd.treasury_address) + 2 * code_len
+    );
+    assert_eq!(
+        rent_sws.get_balance(bd.proposer_address),
+        no_rent_sws.get_balance(bd.proposer_address)
+    );
+}
+
+/// A custom fee distributor decides where the fees of a transaction are credited in the Charge phase
+#[test]
+fn test_fee_distributor_v2() {
+    struct AllToTreasury;
+    impl FeeDistributor for AllToTreasury {
+        fn distribute(
+            &self,
+            base_fee_amount: u64,
+            priority_fee_amount: u64,
+            _proposer: [u8; 32],
+            treasury: [u8; 32],
+        ) -> Vec<([u8; 32], u64)> {
+            vec![(treasury, base_fee_amount + priority_fee_amount)]
+        }
+    }
+
+    let mut tx = TestData::transaction_v2();
+    tx.commands = vec![Command::Transfer(TransferInput {
+        recipient: [2u8; 32],
+        amount: 1,
+    })];
+    let bd = TestData::block_params();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(tx.signer, 500_000_000);
+
+    let result = pchain_runtime::Runtime::new().transition_v2(
+        sws.world_state.clone(),
+        tx.clone(),
+        bd.clone(),
+    );
+    assert!(result.error.is_none());
+    let gas_used = result.receipt.unwrap().gas_used;
+    let default_sws: SimulateWorldState<'_, V2> = result.new_state.into();
+
+    // the default distributor reproduces the default behaviour
+    let result = pchain_runtime::Runtime::new()
+        .set_fee_distributor(Arc::new(DefaultFeeDistributor))
+        .transition_v2(sws.world_state.clone(), tx.clone(), bd.clone());
+    assert!(result.error.is_none());
+    let explicit_default_sws: SimulateWorldState<'_, V2> = result.new_state.into();
+    for address in [tx.signer, bd.proposer_address, bd.treasury_address] {
+        assert_eq!(
+            explicit_default_sws.get_balance(address),
+            default_sws.get_balance(address)
+        );
+    }
+
+    let result = pchain_runtime::Runtime::new()
+        .set_fee_distributor(Arc::new(AllToTreasury))
+        .transition_v2(sws.world_state, tx.clone(), bd.clone());
+    assert!(result.error.is_none());
+    assert_eq!(result.receipt.unwrap().gas_used, gas_used);
+    let custom_sws: SimulateWorldState<'_, V2> = result.new_state.into();
+
+    assert_eq!(
+        custom_sws.get_balance(tx.signer),
+        default_sws.get_balance(tx.signer)
+    );
+    assert_eq!(custom_sws.get_balance(bd.proposer_address), 0);
+    assert_eq!(
+        custom_sws.get_balance(bd.treasury_address),
+        gas_used * (bd.this_base_fee + tx.priority_fee_per_gas)
+    );
+}
+
+/// Credits of a fee distributor which exceed the fees of the transaction are capped at the fees, in the order returned
+#[test]
+fn test_fee_distributor_over_credit_v2() {
+    struct OverCredit;
+    impl FeeDistributor for OverCredit {
+        fn distribute(
+            &self,
+            base_fee_amount: u64,
+            priority_fee_amount: u64,
+            proposer: [u8; 32],
+            treasury: [u8; 32],
+        ) -> Vec<([u8; 32], u64)> {
+            let fees = base_fee_amount + priority_fee_amount;
+            vec![(proposer, fees - 1), (treasury, fees), (proposer, fees)]
+        }
+    }
+
+    let recipient = [2u8; 32];
+    let mut tx = TestData::transaction_v2();
+    tx.priority_fee_per_gas = 1;
+    tx.commands = vec![Command::Transfer(TransferInput {
+        recipient,
+        amount: 1,
+    })];
+    let bd = TestData::block_params();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(tx.signer, 500_000_000);
+
+    let result = pchain_runtime::Runtime::new()
+        .set_fee_distributor(Arc::new(OverCredit))
+        .transition_v2(sws.world_state, tx.clone(), bd.clone());
+    assert!(result.error.is_none());
+    let fees = result.receipt.unwrap().gas_used * (bd.this_base_fee + tx.priority_fee_per_gas);
+    let new_sws: SimulateWorldState<'_, V2> = result.new_state.into();
+
+    // the signer pays the fees once, and no balance is created
+    assert_eq!(new_sws.get_balance(tx.signer), 500_000_000 - 1 - fees);
+    assert_eq!(new_sws.get_balance(recipient), 1);
+    assert_eq!(new_sws.get_balance(bd.proposer_address), fees - 1);
+    assert_eq!(new_sws.get_balance(bd.treasury_address), 1);
+}
+
+/// The signer pays exactly the gas used, the amounts moved by its commands and the state rent, and is credited its
+/// share of the fees when it is also the proposer
+#[test]
+fn test_charge_signer_is_proposer_v2() {
+    struct PerByteRent(u64);
+    impl StateRentPolicy for PerByteRent {
+        fn rent(&self, size_delta: i64) -> u64 {
+            (size_delta.max(0) as u64).saturating_mul(self.0)
+        }
+    }
+
+    let contract_code = TestData::get_test_contract_code("basic_contract");
+    let code_len = contract_code.len() as u64;
+    let recipient = [2u8; 32];
+    let mut tx = TestData::transaction_v2();
+    tx.gas_limit = 400_000_000;
+    tx.priority_fee_per_gas = 3;
+    tx.commands = vec![
+        Command::Transfer(TransferInput {
+            recipient,
+            amount: 1_000,
+        }),
+        ArgsBuilder::new().make_deploy(contract_code, 0),
+    ];
+    let mut bd = TestData::block_params();
+    bd.proposer_address = tx.signer;
+
+    let origin_balance = 500_000_000_000;
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(tx.signer, origin_balance);
+
+    let result = pchain_runtime::Runtime::new()
+        .set_state_rent_policy(Arc::new(PerByteRent(2)))
+        .transition_v2(sws.world_state, tx.clone(), bd.clone());
+    assert!(result.error.is_none());
+    let gas_used = result.receipt.unwrap().gas_used;
+    let new_sws: SimulateWorldState<'_, V2> = result.new_state.into();
+
+    // the priority fee is paid by the signer and credited back to it as the proposer
+    assert_eq!(
+        new_sws.get_balance(tx.signer),
+        origin_balance - 1_000 - gas_used * bd.this_base_fee - 2 * code_len
+    );
+    assert_eq!(new_sws.get_balance(recipient), 1_000);
+}
+
+/// Lazy commit of a batch of transactions produces the same receipts and World State as eager commit
+#[test]
+fn test_transition_v2_batch_commit_modes() {
+    let signer = [1u8; 32];
+    let target = [2u8; 32];
+    let make_tx = |nonce: u64, amount: u64, signer: [u8; 32], recipient: [u8; 32]| {
+        let mut tx = TestData::transaction_v2();
+        tx.signer = signer;
+        tx.nonce = nonce;
+        tx.commands = vec![Command::Transfer(TransferInput { recipient, amount })];
+        tx
+    };
+    let txs = vec![
+        make_tx(0, 10_000_000, signer, target),
+        // transfer back the received amount, which is pending in the cache in lazy mode
+        make_tx(0, 5_000_000, target, signer),
+        // wrong nonce
+        make_tx(0, 1, signer, target),
+        // not enough balance for transfer
+        make_tx(1, 1_000_000_000, signer, target),
+        make_tx(2, 1, signer, signer),
+    ];
+    let bd = TestData::block_params();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(signer, 100_000_000);
+    sws.set_balance(target, 10_000_000);
+
+    let eager = pchain_runtime::Runtime::new()
+        .set_commit_mode(CommitMode::Eager)
+        .transition_v2_batch(sws.world_state.clone(), txs.clone(), bd.clone());
+    let lazy = pchain_runtime::Runtime::new()
+        .set_commit_mode(CommitMode::Lazy)
+        .transition_v2_batch(sws.world_state, txs, bd);
+
+    assert_eq!(eager.errors, lazy.errors);
+    assert_eq!(eager.errors[2], Some(TransitionError::WrongNonce));
+    assert_eq!(
+        eager.errors[3],
+        Some(TransitionError::NotEnoughBalanceForTransfer)
+    );
+    assert_eq!(
+        eager
+            .receipts
+            .iter()
+            .map(|r| r.as_ref().map(|r| r.serialize()))
+            .collect::<Vec<_>>(),
+        lazy.receipts
+            .iter()
+            .map(|r| r.as_ref().map(|r| r.serialize()))
+            .collect::<Vec<_>>()
+    );
+
+    let eager_sws: SimulateWorldState<'_, V2> = eager.new_state.into();
+    let lazy_sws: SimulateWorldState<'_, V2> = lazy.new_state.into();
+    for address in [signer, target, bd.proposer_address, bd.treasury_address] {
+        assert_eq!(
+            eager_sws.get_balance(address),
+            lazy_sws.get_balance(address)
+        );
+        assert_eq!(eager_sws.get_nonce(address), lazy_sws.get_nonce(address));
+    }
+}
+
+/// A batch executes at most one Next Epoch transaction, in both commit modes. Later Next Epoch transactions in the
+/// same block fail without advancing the epoch again.
+#[test]
+fn test_transition_v2_batch_next_epoch_once() {
+    let signer = [1u8; 32];
+    let make_tx = |nonce: u64| {
+        let mut tx = TestData::transaction_v2();
+        tx.signer = signer;
+        tx.nonce = nonce;
+        tx.commands = vec![Command::NextEpoch];
+        tx
+    };
+    let mut bd = TestData::block_params();
+    bd.validator_performance = Some(ValidatorPerformance::default());
+
+    for commit_mode in [CommitMode::Eager, CommitMode::Lazy] {
+        let storage = SimulateWorldStateStorage::default();
+        let sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+
+        let runtime = pchain_runtime::Runtime::new().set_commit_mode(commit_mode);
+        let result =
+            runtime.transition_v2_batch(sws.world_state, vec![make_tx(0), make_tx(1)], bd.clone());
+        assert_eq!(
+            result.errors,
+            vec![None, Some(TransitionError::InvalidNextEpochCommand)]
+        );
+        assert!(result.receipts[0].is_some());
+        assert!(result.receipts[1].is_none());
+        assert!(result.validator_changes.is_some());
+
+        let mut sws: SimulateWorldState<'_, V2> = result.new_state.into();
+        assert_eq!(NetworkAccount::new(&mut sws).current_epoch(), 1);
+        assert_eq!(sws.get_nonce(signer), 1);
+
+        // the rejected transaction can end the epoch in the next block
+        let next_bd = BlockchainParams {
+            this_block_number: bd.this_block_number + 1,
+            ..bd.clone()
+        };
+        let result = runtime.transition_v2_batch(sws.world_state, vec![make_tx(1)], next_bd);
+        assert_eq!(result.errors, vec![None]);
+        let mut sws: SimulateWorldState<'_, V2> = result.new_state.into();
+        assert_eq!(NetworkAccount::new(&mut sws).current_epoch(), 2);
+    }
+}
+
+/// Receipt root for tests: SHA256 hash of the serialized receipts.
+struct Sha256ReceiptRoot;
+
+impl ReceiptRoot for Sha256ReceiptRoot {
+    fn receipt_root(&self, receipts: &[Option<ReceiptV2>]) -> [u8; 32] {
+        let mut hasher = sha2::Sha256::new();
+        for receipt in receipts.iter().flatten() {
+            hasher.update(receipt.serialize());
+        }
+        hasher.finalize().into()
+    }
+}
+
+/// BlockApplier produces the same World State as applying blocks one at a time, and does not apply a block with
+/// a mismatching receipt root
+#[test]
+fn test_block_applier_v2() {
+    let signer = [1u8; 32];
+    let target = [2u8; 32];
+    let make_tx = |nonce: u64, amount: u64| {
+        let mut tx = TestData::transaction_v2();
+        tx.signer = signer;
+        tx.nonce = nonce;
+        tx.commands = vec![Command::Transfer(TransferInput {
+            recipient: target,
+            amount,
+        })];
+        tx
+    };
+    let block_1 = vec![make_tx(0, 100), make_tx(1, 200)];
+    let block_2 = vec![make_tx(2, 300)];
+    let bd_1 = TestData::block_params();
+    let bd_2 = BlockchainParams {
+        this_block_number: bd_1.this_block_number + 1,
+        ..bd_1.clone()
+    };
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(signer, 100_000_000);
+
+    // apply blocks one at a time through the base API
+    let runtime = pchain_runtime::Runtime::new();
+    let result_1 =
+        runtime.transition_v2_batch(sws.world_state.clone(), block_1.clone(), bd_1.clone());
+    let root_1 = Sha256ReceiptRoot.receipt_root(&result_1.receipts);
+    let result_2 = runtime.transition_v2_batch(result_1.new_state, block_2.clone(), bd_2.clone());
+    let root_2 = Sha256ReceiptRoot.receipt_root(&result_2.receipts);
+    let expected_sws: SimulateWorldState<'_, V2> = result_2.new_state.into();
+
+    let mut applier =
+        BlockApplier::new(&runtime, sws.world_state).set_receipt_root(Sha256ReceiptRoot);
+    let applied = applier
+        .apply_block(block_1, bd_1.clone(), Some(root_1))
+        .unwrap();
+    assert_eq!(applied.errors, vec![None, None]);
+    let balance_after_block_1 = applier
+        .world_state()
+        .account_trie()
+        .balance(&target)
+        .unwrap();
+    assert_eq!(balance_after_block_1, 300);
+
+    // mismatching root leaves the World State unchanged
+    assert_eq!(
+        applier
+            .apply_block(block_2.clone(), bd_2.clone(), Some(root_1))
+            .unwrap_err(),
+        BlockApplierError::ReceiptRootMismatch {
+            expected: root_1,
+            actual: root_2
+        }
+    );
+    assert_eq!(
+        applier
+            .world_state()
+            .account_trie()
+            .balance(&target)
+            .unwrap(),
+        balance_after_block_1
+    );
+
+    applier.apply_block(block_2, bd_2, Some(root_2)).unwrap();
+    let applied_sws: SimulateWorldState<'_, V2> = applier.into_world_state().into();
+    for address in [signer, target, bd_1.proposer_address, bd_1.treasury_address] {
+        assert_eq!(
+            applied_sws.get_balance(address),
+            expected_sws.get_balance(address)
+        );
+        assert_eq!(
+            applied_sws.get_nonce(address),
+            expected_sws.get_nonce(address)
+        );
+    }
+}
+
+/// Contract reads the value of a key that it set earlier in the same call, instead of the value in the World State
+#[test]
+fn test_etoc_read_own_write_v2() {
+    let wasm_bytes = wat::parse_str(
+        r#"
+        (module
+            (import "env" "set" (func $set (param i32 i32 i32 i32)))
+            (import "env" "get" (func $get (param i32 i32 i32) (result i64)))
+            (import "env" "return_value" (func $return_value (param i32 i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "k")
+            (data (i32.const 16) "new")
+            (func (export "alloc") (param i32) (result i32)
+                i32.const 1024)
+            (func (export "set_and_get_k")
+                (local $len i64)
+                (call $set (i32.const 0) (i32.const 1) (i32.const 16) (i32.const 3))
+                (local.set $len (call $get (i32.const 0) (i32.const 1) (i32.const 8)))
+                (call $return_value (i32.load (i32.const 8)) (i32.wrap_i64 (local.get $len)))))
+        "#,
+    )
+    .unwrap();
+    let target = [2u8; 32];
+    let mut tx = TestData::transaction_v2();
+    tx.gas_limit = 10_000_000;
+    tx.commands = vec![ArgsBuilder::new().make_call(None, target, "set_and_get_k")];
+    let bd = TestData::block_params();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(tx.signer, 100_000_000);
+    sws.add_contract(target, wasm_bytes, pchain_runtime::cbi_version());
+    sws.set_storage_data(target, b"k".to_vec(), b"old".to_vec());
+
+    let result = pchain_runtime::Runtime::new().transition_v2(sws.world_state, tx, bd);
+    assert!(result.error.is_none());
+    match result.receipt.unwrap().command_receipts.pop() {
+        Some(CommandReceiptV2::Call(cr)) => {
+            assert_eq!(cr.exit_code, ExitCodeV2::Ok);
+            assert_eq!(cr.return_value, b"new".to_vec());
+        }
+        _ => panic!("expected a call receipt"),
+    }
+
+    let mut sws: SimulateWorldState<'_, V2> = result.new_state.into();
+    assert_eq!(
+        sws.get_storage_data(target, b"k".to_vec()),
+        Some(b"new".to_vec())
+    );
+}
+
+/// Logs of a Call command are in the order in which the contract emitted them
+#[test]
+fn test_etoc_call_logs_v2() {
+    let logs = [
+        Log {
+            topic: b"first".to_vec(),
+            value: b"1".to_vec(),
+        },
+        Log {
+            topic: b"second".to_vec(),
+            value: b"22".to_vec(),
+        },
+    ];
+    let data: Vec<(String, usize)> = logs
+        .iter()
+        .map(|log| {
+            let bytes = log.serialize();
+            let data = bytes.iter().map(|b| format!("\\{:02x}", b)).collect();
+            (data, bytes.len())
+        })
+        .collect();
+    let wasm_bytes = wat::parse_str(format!(
+        r#"
+        (module
+            (import "env" "log" (func $log (param i32 i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 64) "{}")
+            (data (i32.const 128) "{}")
+            (func (export "alloc") (param i32) (result i32)
+                i32.const 1024)
+            (func (export "entrypoint")
+                (call $log (i32.const 64) (i32.const {}))
+                (call $log (i32.const 128) (i32.const {}))))
+        "#,
+        data[0].0, data[1].0, data[0].1, data[1].1
+    ))
+    .unwrap();
+    let target = [2u8; 32];
+    let mut tx = TestData::transaction_v2();
+    tx.gas_limit = 10_000_000;
+    tx.commands = vec![
+        Command::Transfer(TransferInput {
+            recipient: target,
+            amount: 1,
+        }),
+        ArgsBuilder::new().make_call(None, target, "entrypoint"),
+    ];
+    let bd = TestData::block_params();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(tx.signer, 100_000_000);
+    sws.add_contract(target, wasm_bytes, pchain_runtime::cbi_version());
+
+    let result = pchain_runtime::Runtime::new().transition_v2(sws.world_state, tx, bd);
+    assert!(result.error.is_none());
+    let receipt = result.receipt.unwrap();
+    assert!(command_receipt_logs(&receipt.command_receipts[0]).is_empty());
+    assert_eq!(command_receipt_logs(&receipt.command_receipts[1]), &logs);
+}
+
+/// Arguments encoded by the contract argument helpers are read back by a contract as they were encoded
+#[test]
+fn test_etoc_encode_call_args_v2() {
+    // echo_all returns the arguments as written by the host function, and echo_first the bytes of the first argument,
+    // which follow the number of arguments and the length of the first argument
+    let wasm_bytes = wat::parse_str(
+        r#"
+        (module
+            (import "env" "arguments" (func $arguments (param i32) (result i32)))
+            (import "env" "return_value" (func $return_value (param i32 i32)))
+            (memory (export "memory") 1)
+            (func (export "alloc") (param i32) (result i32)
+                i32.const 1024)
+            (func (export "echo_all")
+                (local $len i32)
+                (local.set $len (call $arguments (i32.const 0)))
+                (call $return_value (i32.load (i32.const 0)) (local.get $len)))
+            (func (export "echo_first")
+                (local $ptr i32)
+                (drop (call $arguments (i32.const 0)))
+                (local.set $ptr (i32.load (i32.const 0)))
+                (call $return_value
+                    (i32.add (local.get $ptr) (i32.const 8))
+                    (i32.load (i32.add (local.get $ptr) (i32.const 4))))))
+        "#,
+    )
+    .unwrap();
+    let target = [2u8; 32];
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.add_contract(target, wasm_bytes, pchain_runtime::cbi_version());
+
+    let args = vec![
+        pchain_runtime::contract::encode_call_arg(&"hello".to_string()),
+        pchain_runtime::contract::encode_call_arg(&42u64),
+    ];
+    let encoded = pchain_runtime::contract::encode_call_args(&args);
+    assert_eq!(
+        encoded,
+        [
+            vec![2, 0, 0, 0],
+            vec![9, 0, 0, 0, 5, 0, 0, 0],
+            b"hello".to_vec(),
+            vec![8, 0, 0, 0, 42, 0, 0, 0, 0, 0, 0, 0],
+        ]
+        .concat()
+    );
+
+    for (method, expected) in [("echo_all", encoded), ("echo_first", args[0].clone())] {
+        let (receipt, error) = pchain_runtime::Runtime::new().view_v2(
+            sws.world_state.clone(),
+            10_000_000,
+            target,
+            method.to_string(),
+            Some(args.clone()),
+        );
+        assert!(error.is_none());
+        match receipt {
+            CommandReceiptV2::Call(r) => {
+                assert_eq!(r.exit_code, ExitCodeV2::Ok);
+                assert_eq!(r.return_value, expected);
+            }
+            _ => panic!("expected a call receipt"),
+        }
+    }
+}
+
+/// Accounts touched by a transaction include those touched by the contracts that it calls
+#[test]
+fn test_touched_accounts_v2() {
+    let wasm_bytes = wat::parse_str(
+        r#"
+        (module
+            (import "env" "transfer" (func $transfer (param i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "\07\07\07\07\07\07\07\07\07\07\07\07\07\07\07\07\07\07\07\07\07\07\07\07\07\07\07\07\07\07\07\07")
+            (data (i32.const 32) "\01\00\00\00\00\00\00\00")
+            (func (export "alloc") (param i32) (result i32)
+                i32.const 1024)
+            (func (export "transfer_to_recipient")
+                (call $transfer (i32.const 0))))
+        "#,
+    )
+    .unwrap();
+    let target = [2u8; 32];
+    let recipient = [7u8; 32];
+    let mut tx = TestData::transaction_v2();
+    tx.gas_limit = 10_000_000;
+    tx.commands = vec![ArgsBuilder::new().make_call(None, target, "transfer_to_recipient")];
+    let bd = TestData::block_params();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(tx.signer, 100_000_000);
+    sws.set_balance(target, 1);
+    sws.add_contract(target, wasm_bytes, pchain_runtime::cbi_version());
+
+    let runtime = pchain_runtime::Runtime::new();
+    let result = runtime.transition_v2(sws.world_state.clone(), tx.clone(), bd.clone());
+    assert!(result.error.is_none());
+
+    let touched_accounts = runtime.touched_accounts_v2(sws.world_state, tx.clone(), bd.clone());
+    for address in [
+        tx.signer,
+        target,
+        recipient,
+        bd.proposer_address,
+        bd.treasury_address,
+    ] {
+        assert!(touched_accounts.contains(&address));
+    }
+    assert!(!touched_accounts.contains(&[9u8; 32]));
+}
+
+/// Gas multipliers scale the gas used by Wasm execution of a contract, and leave other contracts unchanged
+#[test]
+fn test_etoc_contract_gas_multipliers_v2() {
+    let wasm_bytes = wat::parse_str(
+        r#"
+        (module
+            (memory (export "memory") 1)
+            (func (export "alloc") (param i32) (result i32)
+                i32.const 1024)
+            (func (export "entrypoint")
+                (local $i i32)
+                (loop $loop
+                    (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                    (br_if $loop (i32.lt_u (local.get $i) (i32.const 100))))))
+        "#,
+    )
+    .unwrap();
+    let target = [2u8; 32];
+    let other = [3u8; 32];
+    let mut tx = TestData::transaction_v2();
+    tx.gas_limit = 10_000_000;
+    tx.commands = vec![ArgsBuilder::new().make_call(None, target, "entrypoint")];
+    let bd = TestData::block_params();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(tx.signer, 100_000_000);
+    sws.add_contract(target, wasm_bytes, pchain_runtime::cbi_version());
+
+    let call_gas_used = |multipliers: Option<HashMap<[u8; 32], u64>>| {
+        let runtime = match multipliers {
+            Some(multipliers) => {
+                pchain_runtime::Runtime::new().set_contract_gas_multipliers(multipliers)
+            }
+            None => pchain_runtime::Runtime::new(),
+        };
+        let result = runtime.transition_v2(sws.world_state.clone(), tx.clone(), bd.clone());
+        assert!(result.error.is_none());
+        extract_gas_success_call_v2(&result.receipt.unwrap().command_receipts[0])
+    };
+
+    let gas_used = call_gas_used(None);
+    let gas_used_100 = call_gas_used(Some(HashMap::from([(target, 100)])));
+    let gas_used_200 = call_gas_used(Some(HashMap::from([(target, 200)])));
+    let gas_used_300 = call_gas_used(Some(HashMap::from([(target, 300)])));
+    assert_eq!(gas_used_100, gas_used);
+    assert!(gas_used_200 > gas_used);
+    // only the Wasm execution gas is scaled
+    assert_eq!(gas_used_300 - gas_used_200, gas_used_200 - gas_used);
+
+    // multipliers below 100 percent, and multipliers of other contracts, have no effect
+    assert_eq!(call_gas_used(Some(HashMap::from([(target, 50)]))), gas_used);
+    assert_eq!(call_gas_used(Some(HashMap::from([(other, 200)]))), gas_used);
+}
+
+/// Contract Call from external account
+#[test]
+fn test_etoc_v2() {
+    let wasm_bytes = TestData::get_test_contract_code("basic_contract");
+    let method_args = "arg".to_string();
+    let method_name = "emit_event_with_return";
+    let target = [2u8; 32];
+    let mut tx = TestData::transaction_v2();
+    tx.gas_limit = 10_000_000;
+    tx.commands =
+        vec![ArgsBuilder::new()
+            .add(method_args.clone())
+            .make_call(Some(0), target, method_name)];
+
+    let bd = TestData::block_params();
+    let base_fee_per_gas = bd.this_base_fee;
+
+    // initialize world state
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    let from_address = tx.signer;
+    let to_address = target;
+    let init_from_balance = 100_000_000;
+    sws.set_balance(from_address, init_from_balance);
+    sws.add_contract(to_address, wasm_bytes, pchain_runtime::cbi_version());
+
+    assert_deterministic_v2(sws.world_state.clone(), tx.clone(), bd.clone());
+
+    let result = pchain_runtime::Runtime::new().transition_v2(sws.world_state, tx, bd);
+    let receipt = result.receipt.unwrap();
+    assert_eq!(receipt.exit_code, ExitCodeV2::Ok);
+
+    let sws: SimulateWorldState<'_, V2> = result.new_state.into();
+
+    // check from_address balance
+    let gas_used = receipt.gas_used;
+    let new_from_balance = sws.get_balance(from_address);
+    assert_eq!(
+        new_from_balance,
+        init_from_balance - base_fee_per_gas * gas_used
+    );
+    assert_eq!(sws.get_nonce(from_address), 1);
+
+    // check to_address balance
+    let new_to_balance = sws.get_balance(to_address);
+    assert_eq!(new_to_balance, 0);
+    assert_eq!(sws.get_nonce(to_address), 0);
+
+    let cr = match receipt.command_receipts.last() {
+        Some(CommandReceiptV2::Call(cr)) => cr,
+        _ => panic!("Expecting a Call command receipt"),
+    };
+
+    // check return value from the called method
+    let result_value: u32 = CallResult::parse(cr.return_value.clone()).unwrap();
+    assert_eq!(result_value as usize, method_args.len());
+
+    // check event is emitted in logs
+    assert!(cr
+        .logs
+        .iter()
+        .find(|e| {
+            String::from_utf8(e.topic.clone()).unwrap() == "topic: Hello From".to_string()
+                && String::from_utf8(e.value.clone()).unwrap()
+                    == format!("Hello, Contract. From: {}", method_args).to_string()
+        })
+        .is_some());
+}
+
+/// Contract Call which recurses until the call stack is exhausted fails with CallStackExhausted, not gas exhaustion
+#[test]
+fn test_etoc_call_stack_exhausted_v2() {
+    let wasm_bytes = wat::parse_str(
+        r#"
+        (module
+            (memory (export "memory") 1)
+            (func (export "alloc") (param i32) (result i32)
+                i32.const 0)
+            (func $recurse
+                call $recurse)
+            (func (export "entrypoint")
+                call $recurse))
+        "#,
+    )
+    .unwrap();
+    let target = [2u8; 32];
+    let mut tx = TestData::transaction_v2();
+    tx.gas_limit = 10_000_000_000;
+    tx.commands = vec![ArgsBuilder::new().make_call(None, target, "entrypoint")];
+    let bd = TestData::block_params();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(tx.signer, 100_000_000_000);
+    sws.add_contract(target, wasm_bytes, pchain_runtime::cbi_version());
+
+    let result = pchain_runtime::Runtime::new().transition_v2(sws.world_state, tx, bd);
+    assert_eq!(result.error, Some(TransitionError::CallStackExhausted));
+    let receipt = result.receipt.unwrap();
+    assert_eq!(receipt.exit_code, ExitCodeV2::Error);
+    assert!(receipt.gas_used < 10_000_000_000);
+}
+
+/// Contract Call gets the length of a storage value with get_len, which is -1 if the key has no value. Contracts of CBI
+/// version 0 cannot import get_len.
+#[test]
+fn test_etoc_get_len_v2() {
+    let wasm_bytes = wat::parse_str(
+        r#"
+        (module
+            (import "env" "set" (func $set (param i32 i32 i32 i32)))
+            (import "env" "get_len" (func $get_len (param i32 i32) (result i64)))
+            (import "env" "return_value" (func $return_value (param i32 i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "kabc")
+            (func (export "alloc") (param i32) (result i32)
+                i32.const 1024)
+            (func (export "entrypoint")
+                (i64.store (i32.const 16) (call $get_len (i32.const 0) (i32.const 1)))
+                (call $set (i32.const 0) (i32.const 1) (i32.const 1) (i32.const 3))
+                (i64.store (i32.const 24) (call $get_len (i32.const 0) (i32.const 1)))
+                (call $return_value (i32.const 16) (i32.const 16))))
+        "#,
+    )
+    .unwrap();
+    let target = [2u8; 32];
+    let mut tx = TestData::transaction_v2();
+    tx.gas_limit = 10_000_000;
+    tx.commands = vec![ArgsBuilder::new().make_call(None, target, "entrypoint")];
+    let bd = TestData::block_params();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(tx.signer, 5_000_000_000);
+    sws.add_contract(target, wasm_bytes.clone(), pchain_runtime::cbi_version());
+
+    let result =
+        pchain_runtime::Runtime::new().transition_v2(sws.world_state, tx.clone(), bd.clone());
+    assert!(result.error.is_none());
+    let cr = match result.receipt.unwrap().command_receipts.pop() {
+        Some(CommandReceiptV2::Call(cr)) => cr,
+        _ => panic!("Expecting a Call command receipt"),
+    };
+    assert_eq!(
+        cr.return_value,
+        [(-1i64).to_le_bytes(), 3i64.to_le_bytes()].concat()
+    );
+
+    // the import does not resolve against the host functions of CBI version 0
+    let tx = TransactionV2 {
+        nonce: 1,
+        gas_limit: 400_000_000,
+        commands: vec![ArgsBuilder::new().make_deploy(wasm_bytes, 0)],
+        ..tx
+    };
+    let result = pchain_runtime::Runtime::new().transition_v2(result.new_state, tx, bd);
+    assert_eq!(result.error, Some(TransitionError::CannotCompile));
+}
+
+/// A contract method can be run against an in-memory storage without preparing a World State or a transaction
+#[test]
+fn test_run_contract_method() {
+    let wasm_bytes = wat::parse_str(
+        r#"
+        (module
+            (import "env" "get" (func $get (param i32 i32 i32) (result i64)))
+            (import "env" "return_value" (func $return_value (param i32 i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "k")
+            (func (export "alloc") (param i32) (result i32)
+                i32.const 1024)
+            (func (export "entrypoint")
+                (local $len i64)
+                (local.set $len (call $get (i32.const 0) (i32.const 1) (i32.const 8)))
+                (call $return_value (i32.load (i32.const 8)) (i32.wrap_i64 (local.get $len)))))
+        "#,
+    )
+    .unwrap();
+    let initial_storage = HashMap::from([(b"k".to_vec(), b"value".to_vec())]);
+
+    let (return_value, gas_used, exit_code) = pchain_runtime::Runtime::new().run_contract_method(
+        wasm_bytes.clone(),
+        "entrypoint".to_string(),
+        None,
+        initial_storage.clone(),
+    );
+    assert_eq!(exit_code, ExitCodeV2::Ok);
+    assert_eq!(return_value, b"value".to_vec());
+    assert!(gas_used > 0);
+
+    let (return_value, _, exit_code) = pchain_runtime::Runtime::new().run_contract_method(
+        wasm_bytes,
+        "not_exist".to_string(),
+        None,
+        initial_storage,
+    );
+    assert_eq!(exit_code, ExitCodeV2::Error);
+    assert!(return_value.is_empty());
+}
+
+/// Contract transfers fail once they exceed the configured maximum value per transaction
+#[test]
+fn test_etoc_max_contract_transfer_value_v2() {
+    let wasm_bytes = wat::parse_str(
+        r#"
+        (module
+            (import "env" "transfer" (func $transfer (param i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "\03\03\03\03\03\03\03\03\03\03\03\03\03\03\03\03\03\03\03\03\03\03\03\03\03\03\03\03\03\03\03\03\3c\00\00\00\00\00\00\00")
+            (func (export "alloc") (param i32) (result i32)
+                i32.const 1024)
+            (func (export "entrypoint")
+                (call $transfer (i32.const 0))
+                (call $transfer (i32.const 0))))
+        "#,
+    )
+    .unwrap();
+    let target = [2u8; 32];
+    let recipient = [3u8; 32];
+    let mut tx = TestData::transaction_v2();
+    tx.gas_limit = 10_000_000;
+    tx.commands = vec![ArgsBuilder::new().make_call(None, target, "entrypoint")];
+    let bd = TestData::block_params();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(tx.signer, 100_000_000);
+    sws.set_balance(target, 1_000);
+    sws.add_contract(target, wasm_bytes, pchain_runtime::cbi_version());
+
+    // unlimited by default
+    let result = pchain_runtime::Runtime::new().transition_v2(
+        sws.world_state.clone(),
+        tx.clone(),
+        bd.clone(),
+    );
+    assert!(result.error.is_none());
+    let sws_after: SimulateWorldState<'_, V2> = result.new_state.into();
+    assert_eq!(sws_after.get_balance(recipient), 120);
+
+    // each transfer is within the limit, but the second one exceeds it in total
+    let result = pchain_runtime::Runtime::new()
+        .set_max_contract_transfer_value(100)
+        .transition_v2(sws.world_state, tx, bd);
+    assert_eq!(result.error, Some(TransitionError::TransferLimitExceeded));
+    assert_eq!(result.receipt.unwrap().exit_code, ExitCodeV2::Error);
+    let sws_after: SimulateWorldState<'_, V2> = result.new_state.into();
+    assert_eq!(sws_after.get_balance(recipient), 0);
+    assert_eq!(sws_after.get_balance(target), 1_000);
+}
+
+/// Contracts fail to defer commands once they exceed the configured maximum number of spawned commands per
+/// transaction, and the commands completed before are still charged
+#[test]
+fn test_etoc_max_spawned_commands_v2() {
+    let bytes = Command::CreateDeposit(CreateDepositInput {
+        operator: [5u8; 32],
+        balance: 1,
+        auto_stake_rewards: false,
+    })
+    .serialize();
+    let create_deposit: String = bytes.iter().map(|b| format!("\\{:02x}", b)).collect();
+    let create_deposit_len = bytes.len();
+    let wasm_bytes = wat::parse_str(format!(
+        r#"
+        (module
+            (import "env" "defer_create_deposit" (func $defer_create_deposit (param i32 i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 64) "{create_deposit}")
+            (func (export "alloc") (param i32) (result i32)
+                i32.const 1024)
+            (func (export "entrypoint")
+                (local $i i32)
+                (loop $loop
+                    (call $defer_create_deposit (i32.const 64) (i32.const {create_deposit_len}))
+                    (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                    (br_if $loop (i32.lt_u (local.get $i) (i32.const 3))))))
+        "#
+    ))
+    .unwrap();
+    let target = [2u8; 32];
+    let recipient = [3u8; 32];
+    let mut tx = TestData::transaction_v2();
+    tx.gas_limit = 10_000_000;
+    tx.commands = vec![
+        Command::Transfer(TransferInput {
+            recipient,
+            amount: 1_000,
+        }),
+        ArgsBuilder::new().make_call(None, target, "entrypoint"),
+    ];
+    let bd = TestData::block_params();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(tx.signer, 100_000_000);
+    sws.add_contract(target, wasm_bytes, pchain_runtime::cbi_version());
+
+    // all three commands are deferred, whether or not they succeed, if they are within the limit
+    for runtime in [
+        pchain_runtime::Runtime::new(),
+        pchain_runtime::Runtime::new().set_max_spawned_commands(3),
+    ] {
+        let result = runtime.transition_v2(sws.world_state.clone(), tx.clone(), bd.clone());
+        assert_ne!(
+            result.error,
+            Some(TransitionError::SpawnedCommandLimitExceeded)
+        );
+    }
+
+    // the third deferred command exceeds the limit
+    let result = pchain_runtime::Runtime::new()
+        .set_max_spawned_commands(2)
+        .transition_v2(sws.world_state, tx.clone(), bd.clone());
+    assert_eq!(
+        result.error,
+        Some(TransitionError::SpawnedCommandLimitExceeded)
+    );
+    let receipt = result.receipt.unwrap();
+    assert_eq!(receipt.exit_code, ExitCodeV2::Error);
+    assert_eq!(receipt.command_receipts.len(), 2);
+    match &receipt.command_receipts[0] {
+        CommandReceiptV2::Transfer(r) => assert_eq!(r.exit_code, ExitCodeV2::Ok),
+        _ => panic!("expected a transfer receipt"),
+    }
+    match &receipt.command_receipts[1] {
+        CommandReceiptV2::Call(r) => assert_eq!(r.exit_code, ExitCodeV2::Error),
+        _ => panic!("expected a call receipt"),
+    }
+    let sws_after: SimulateWorldState<'_, V2> = result.new_state.into();
+    assert_eq!(sws_after.get_balance(recipient), 0);
+    assert_eq!(
+        sws_after.get_balance(tx.signer),
+        100_000_000 - receipt.gas_used * bd.this_base_fee
+    );
+}
+
+/// Contract Call produces the same gas usage and results with every available compiler backend
+#[test]
+fn test_etoc_compiler_backends_v2() {
+    let wasm_bytes = TestData::get_test_contract_code("basic_contract");
+    let target = [2u8; 32];
+    let mut tx = TestData::transaction_v2();
+    tx.gas_limit = 10_000_000;
+    tx.commands = vec![ArgsBuilder::new().add("arg".to_string()).make_call(
+        Some(0),
+        target,
+        "emit_event_with_return",
+    )];
+    let bd = TestData::block_params();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(tx.signer, 100_000_000);
+    sws.add_contract(target, wasm_bytes, pchain_runtime::cbi_version());
+
+    let expected = pchain_runtime::Runtime::new()
+        .transition_v2(sws.world_state.clone(), tx.clone(), bd.clone())
+        .receipt
+        .unwrap();
+    assert_eq!(expected.exit_code, ExitCodeV2::Ok);
+
+    let backends = [
+        CompilerBackend::Singlepass,
+        #[cfg(feature = "cranelift")]
+        CompilerBackend::Cranelift,
+        #[cfg(feature = "llvm")]
+        CompilerBackend::LLVM,
+    ];
+    for backend in backends {
+        let receipt = pchain_runtime::Runtime::new()
+            .set_compiler_backend(backend)
+            .transition_v2(sws.world_state.clone(), tx.clone(), bd.clone())
+            .receipt
+            .unwrap();
+        assert_eq!(receipt.serialize(), expected.serialize());
+    }
+}
+
+/// Transactions of a batch after its compilation time budget is exceeded are excluded, and the transactions before
+/// them are executed as without a budget
+#[test]
+fn test_transition_v2_batch_compilation_time_budget() {
+    let wasm_bytes = TestData::get_test_contract_code("basic_contract");
+    let target = [2u8; 32];
+    let make_tx = |nonce: u64| {
+        let mut tx = TestData::transaction_v2();
+        tx.nonce = nonce;
+        tx.gas_limit = 10_000_000;
+        tx.commands = vec![ArgsBuilder::new().add("arg".to_string()).make_call(
+            Some(0),
+            target,
+            "emit_event_with_return",
+        )];
+        tx
+    };
+    let txs = vec![make_tx(0), make_tx(1)];
+    let bd = TestData::block_params();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(txs[0].signer, 100_000_000);
+    sws.add_contract(target, wasm_bytes, pchain_runtime::cbi_version());
+
+    let expected = pchain_runtime::Runtime::new().transition_v2_batch(
+        sws.world_state.clone(),
+        vec![txs[0].clone()],
+        bd.clone(),
+    );
+    let expected_sws: SimulateWorldState<'_, V2> = expected.new_state.clone().into();
+    let expected_balance = expected_sws.get_balance(txs[0].signer);
+
+    // a zero budget is exceeded by the first compilation
+    let runtime = pchain_runtime::Runtime::new().set_block_compilation_time_budget(Duration::ZERO);
+    let result = runtime.transition_v2_batch(sws.world_state.clone(), txs.clone(), bd.clone());
+    assert_eq!(result.errors, expected.errors);
+    assert_eq!(
+        result
+            .receipts
+            .iter()
+            .map(|r| r.as_ref().map(|r| r.serialize()))
+            .collect::<Vec<_>>(),
+        expected
+            .receipts
+            .iter()
+            .map(|r| r.as_ref().map(|r| r.serialize()))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(result.excluded.len(), 1);
+    assert_eq!(result.excluded[0].nonce, 1);
+    let sws_after: SimulateWorldState<'_, V2> = result.new_state.into();
+    assert_eq!(sws_after.get_nonce(txs[0].signer), 1);
+    assert_eq!(sws_after.get_balance(txs[0].signer), expected_balance);
+
+    // the budget is accounted per block, so the next block executes its first transaction again
+    let mut applier = BlockApplier::new(&runtime, sws.world_state.clone());
+    assert_eq!(
+        applier.apply_block(txs.clone(), bd.clone(), None).unwrap_err(),
+        BlockApplierError::TransactionsExcluded
+    );
+    let sws_after: SimulateWorldState<'_, V2> = applier.into_world_state().into();
+    assert_eq!(sws_after.get_nonce(txs[0].signer), 0);
+}
+
+/// Differences in gas used between an estimate and the actual execution are attributed to commands
+#[test]
+fn test_explain_gas_delta_v2() {
+    let wasm_bytes = wat::parse_str(
+        r#"
+        (module
+            (import "env" "get" (func $get (param i32 i32 i32) (result i64)))
+            (import "env" "return_value" (func $return_value (param i32 i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "k")
+            (func (export "alloc") (param i32) (result i32)
+                i32.const 1024)
+            (func (export "get_k")
+                (local $len i64)
+                (local.set $len (call $get (i32.const 0) (i32.const 1) (i32.const 8)))
+                (call $return_value (i32.load (i32.const 8)) (i32.wrap_i64 (local.get $len)))))
+        "#,
+    )
+    .unwrap();
+    let target = [2u8; 32];
+    let other_target = [3u8; 32];
+    let mut tx = TestData::transaction_v2();
+    tx.gas_limit = 10_000_000;
+    tx.commands = vec![
+        ArgsBuilder::new().make_call(None, target, "get_k"),
+        ArgsBuilder::new().make_call(None, other_target, "get_k"),
+    ];
+    let bd = TestData::block_params();
+
+    // state against which the transaction is estimated
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(tx.signer, 100_000_000);
+    sws.add_contract(target, wasm_bytes.clone(), pchain_runtime::cbi_version());
+    sws.set_storage_data(target, b"k".to_vec(), b"short".to_vec());
+    let ws_before_other_target = sws.world_state.clone();
+    sws.add_contract(other_target, wasm_bytes, pchain_runtime::cbi_version());
+    let estimate = pchain_runtime::Runtime::new()
+        .transition_v2(sws.world_state, tx.clone(), bd.clone())
+        .receipt
+        .unwrap();
+    assert_eq!(estimate.exit_code, ExitCodeV2::Ok);
+
+    // the same receipt has no delta
+    let report = explain_gas_delta(&estimate, &estimate);
+    assert_eq!(report.gas_delta(), 0);
+    assert!(report.command_deltas.is_empty());
+
+    // state against which the transaction is executed: a longer storage value, and no second contract
+    let mut sws: SimulateWorldState<'_, V2> = ws_before_other_target.into();
+    sws.set_storage_data(target, b"k".to_vec(), b"a much longer value".to_vec());
+    let actual = pchain_runtime::Runtime::new()
+        .transition_v2(sws.world_state, tx, bd)
+        .receipt
+        .unwrap();
+    assert_eq!(actual.exit_code, ExitCodeV2::Error);
+
+    let report = explain_gas_delta(&estimate, &actual);
+    assert_eq!(report.estimated_gas_used, estimate.gas_used);
+    assert_eq!(report.actual_gas_used, actual.gas_used);
+    assert_eq!(report.command_deltas.len(), 2);
+    assert_eq!(report.command_deltas[0].index, 0);
+    assert!(report.command_deltas[0].gas_delta() > 0);
+    assert_eq!(report.command_deltas[0].cause, GasDeltaCause::StateChanged);
+    assert_eq!(report.command_deltas[1].index, 1);
+    assert_eq!(
+        report.command_deltas[1].cause,
+        GasDeltaCause::ExitCodeChanged {
+            estimated: ExitCodeV2::Ok,
+            actual: ExitCodeV2::Error,
+        }
+    );
+    assert_eq!(report.unattributed_gas_delta(), 0);
+}
+
+/// Host functions replaced by HostFunctionOverrides are called instead of the Runtime's implementation
+#[cfg(feature = "host-function-overrides")]
+#[test]
+fn test_etoc_host_function_overrides_v2() {
+    use pchain_runtime::{contract::wasmer::memory::MemoryContext, HostFunctionOverrides};
+    use std::sync::Mutex;
+    use wasmer::{FunctionType, Type, Val};
+
+    let wasm_bytes = wat::parse_str(
+        r#"
+        (module
+            (import "env" "block_height" (func $block_height (result i64)))
+            (import "env" "_log" (func $log (param i32 i32)))
+            (import "env" "return_value" (func $return_value (param i32 i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "abcd")
+            (func (export "alloc") (param i32) (result i32)
+                i32.const 1024)
+            (func (export "entrypoint")
+                (i64.store (i32.const 16) (call $block_height))
+                (call $log (i32.const 0) (i32.const 4))
+                (call $return_value (i32.const 16) (i32.const 8))))
+        "#,
+    )
+    .unwrap();
+    let target = [2u8; 32];
+    let mut tx = TestData::transaction_v2();
+    tx.gas_limit = 10_000_000;
+    tx.commands = vec![ArgsBuilder::new().make_call(None, target, "entrypoint")];
+    let bd = TestData::block_params();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(tx.signer, 100_000_000);
+    sws.add_contract(target, wasm_bytes, pchain_runtime::cbi_version());
+
+    let captured_logs = Arc::new(Mutex::new(Vec::new()));
+    let overrides = HostFunctionOverrides::new()
+        .set(
+            "block_height",
+            FunctionType::new(vec![], vec![Type::I64]),
+            |_, _| Ok(vec![Val::I64(42)]),
+        )
+        .set(
+            "_log",
+            FunctionType::new(vec![Type::I32, Type::I32], vec![]),
+            {
+                let captured_logs = captured_logs.clone();
+                move |env, args| {
+                    let log = env
+                        .read_bytes_from_memory(
+                            args[0].unwrap_i32() as u32,
+                            args[1].unwrap_i32() as u32,
+                        )
+                        .unwrap();
+                    captured_logs.lock().unwrap().push(log);
+                    Ok(vec![])
+                }
+            },
+        );
+
+    let result = pchain_runtime::Runtime::new()
+        .set_host_function_overrides(overrides)
+        .transition_v2(sws.world_state, tx, bd);
+    assert!(result.error.is_none());
+    let cr = match result.receipt.unwrap().command_receipts.pop() {
+        Some(CommandReceiptV2::Call(cr)) => cr,
+        _ => panic!("expected a call receipt"),
+    };
+    assert_eq!(cr.exit_code, ExitCodeV2::Ok);
+    assert_eq!(cr.return_value, 42u64.to_le_bytes().to_vec());
+    assert!(cr.logs.is_empty());
+    assert_eq!(*captured_logs.lock().unwrap(), vec![b"abcd".to_vec()]);
+}
+
+/// Contracts read the current epoch, which increases with every NextEpoch Command. Contracts of CBI version 0 cannot
+/// import current_epoch.
+#[test]
+fn test_etoc_current_epoch_v2() {
+    let wasm_bytes = wat::parse_str(
+        r#"
+        (module
+            (import "env" "current_epoch" (func $current_epoch (result i64)))
+            (import "env" "return_value" (func $return_value (param i32 i32)))
+            (memory (export "memory") 1)
+            (func (export "alloc") (param i32) (result i32)
+                i32.const 1024)
+            (func (export "entrypoint")
+                (i64.store (i32.const 0) (call $current_epoch))
+                (call $return_value (i32.const 0) (i32.const 8))))
+        "#,
+    )
+    .unwrap();
+    let target = [2u8; 32];
+    let mut call_tx = TestData::transaction_v2();
+    call_tx.gas_limit = 10_000_000;
+    call_tx.commands = vec![ArgsBuilder::new().make_call(None, target, "entrypoint")];
+    let bd = TestData::block_params();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(call_tx.signer, 5_000_000_000);
+    sws.add_contract(target, wasm_bytes.clone(), pchain_runtime::cbi_version());
+
+    let current_epoch = |command_receipt: Option<CommandReceiptV2>| match command_receipt {
+        Some(CommandReceiptV2::Call(cr)) => {
+            assert_eq!(cr.exit_code, ExitCodeV2::Ok);
+            u64::from_le_bytes(cr.return_value.try_into().unwrap())
+        }
+        _ => panic!("expected a call receipt"),
+    };
+
+    let result =
+        pchain_runtime::Runtime::new().transition_v2(sws.world_state, call_tx.clone(), bd.clone());
+    assert_eq!(
+        current_epoch(result.receipt.unwrap().command_receipts.pop()),
+        0
+    );
+
+    let next_epoch_tx = TransactionV2 {
+        nonce: 1,
+        commands: vec![Command::NextEpoch],
+        ..call_tx.clone()
+    };
+    let mut next_epoch_bd = bd.clone();
+    next_epoch_bd.validator_performance = Some(ValidatorPerformance::default());
+    let result = pchain_runtime::Runtime::new().transition_v2(
+        result.new_state,
+        next_epoch_tx,
+        next_epoch_bd,
+    );
+    assert_eq!(result.error, None);
+
+    let call_tx = TransactionV2 {
+        nonce: 2,
+        ..call_tx
+    };
+    let result =
+        pchain_runtime::Runtime::new().transition_v2(result.new_state, call_tx.clone(), bd.clone());
+    assert_eq!(
+        current_epoch(result.receipt.unwrap().command_receipts.pop()),
+        1
+    );
+
+    // also readable in view calls, as it is part of the World State
+    let (receipt, error) = pchain_runtime::Runtime::new().view_v2(
+        result.new_state.clone(),
+        10_000_000,
+        target,
+        "entrypoint".to_string(),
+        None,
+    );
+    assert!(error.is_none());
+    assert_eq!(current_epoch(Some(receipt)), 1);
+
+    // the import does not resolve against the host functions of CBI version 0
+    let deploy_tx = TransactionV2 {
+        nonce: 3,
+        gas_limit: 400_000_000,
+        commands: vec![ArgsBuilder::new().make_deploy(wasm_bytes, 0)],
+        ..call_tx
+    };
+    let result = pchain_runtime::Runtime::new().transition_v2(result.new_state, deploy_tx, bd);
+    assert_eq!(result.error, Some(TransitionError::CannotCompile));
+}
+
+/// Contracts of CBI version 1 read the random bytes of the block. Contracts of CBI version 0 cannot import them.
+#[test]
+fn test_etoc_block_random_bytes_v2() {
+    let wasm_bytes = wat::parse_str(
+        r#"
+        (module
+            (import "env" "block_random_bytes" (func $block_random_bytes (param i32)))
+            (import "env" "return_value" (func $return_value (param i32 i32)))
+            (memory (export "memory") 1)
+            (func (export "alloc") (param i32) (result i32)
+                i32.const 1024)
+            (func (export "entrypoint")
+                (call $block_random_bytes (i32.const 0))
+                (call $return_value (i32.const 1024) (i32.const 32))))
+        "#,
+    )
+    .unwrap();
+    let origin_address = [1u8; 32];
+    let bd = TestData::block_params();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(origin_address, 5_000_000_000);
+
+    // the import does not resolve against the host functions of CBI version 0
+    let mut tx = TestData::transaction_v2();
+    tx.gas_limit = 400_000_000;
+    tx.commands = vec![ArgsBuilder::new().make_deploy(wasm_bytes.clone(), 0)];
+    let result =
+        pchain_runtime::Runtime::new().transition_v2(sws.world_state, tx.clone(), bd.clone());
+    assert_eq!(result.error, Some(TransitionError::CannotCompile));
+
+    let tx = TransactionV2 {
+        nonce: 1,
+        commands: vec![ArgsBuilder::new().make_deploy(wasm_bytes, pchain_runtime::cbi_version())],
+        ..tx
+    };
+    let result =
+        pchain_runtime::Runtime::new().transition_v2(result.new_state, tx.clone(), bd.clone());
+    assert_eq!(result.error, None);
+
+    let contract_address = contract_address_v2(&origin_address, 1, 0);
+    let tx = TransactionV2 {
+        nonce: 2,
+        gas_limit: 10_000_000,
+        commands: vec![ArgsBuilder::new().make_call(None, contract_address, "entrypoint")],
+        ..tx
+    };
+    let result = pchain_runtime::Runtime::new().transition_v2(result.new_state, tx, bd.clone());
+    match result.receipt.unwrap().command_receipts.pop() {
+        Some(CommandReceiptV2::Call(cr)) => {
+            assert_eq!(cr.exit_code, ExitCodeV2::Ok);
+            assert_eq!(cr.return_value, bd.random_bytes.to_vec());
+        }
+        _ => panic!("expected a call receipt"),
+    }
+
+    // not readable in view calls, like other block context
+    let (receipt, error) = pchain_runtime::Runtime::new().view_v2(
+        result.new_state,
+        10_000_000,
+        contract_address,
+        "entrypoint".to_string(),
+        None,
+    );
+    assert!(error.is_some());
+    match receipt {
+        CommandReceiptV2::Call(cr) => assert_ne!(cr.exit_code, ExitCodeV2::Ok),
+        _ => panic!("expected a call receipt"),
+    }
+}
+
+/// Contract passes pointers out of the bounds of its linear memory to host functions
+#[test]
+fn test_etoc_memory_out_of_bounds_v2() {
+    let wasm_bytes = wat::parse_str(
+        r#"
+        (module
+            (import "env" "calling_account" (func $calling_account (param i32)))
+            (import "env" "return_value" (func $return_value (param i32 i32)))
+            (memory (export "memory") 1)
+            (func (export "alloc") (param i32) (result i32)
+                i32.const 1024)
+            (func (export "read_out_of_bounds")
+                (call $return_value (i32.const -1) (i32.const 32)))
+            (func (export "write_out_of_bounds")
+                (call $calling_account (i32.const -1))))
+        "#,
+    )
+    .unwrap();
+    let target = [2u8; 32];
+    let bd = TestData::block_params();
+    let base_fee_per_gas = bd.this_base_fee;
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    let init_from_balance = 100_000_000;
+    let mut tx = TestData::transaction_v2();
+    tx.gas_limit = 10_000_000;
+    sws.set_balance(tx.signer, init_from_balance);
+    sws.add_contract(target, wasm_bytes, pchain_runtime::cbi_version());
+
+    for method in ["read_out_of_bounds", "write_out_of_bounds"] {
+        tx.commands = vec![ArgsBuilder::new().make_call(None, target, method)];
+        let result = pchain_runtime::Runtime::new().transition_v2(
+            sws.world_state.clone(),
+            tx.clone(),
+            bd.clone(),
+        );
+        assert_eq!(result.error, Some(TransitionError::RuntimeError));
+        let receipt = result.receipt.unwrap();
+        assert_eq!(receipt.exit_code, ExitCodeV2::Error);
+        let (gas_used, exit_code) = gas_used_and_exit_code_v2(&receipt.command_receipts[0]);
+        assert_eq!(exit_code, ExitCodeV2::Error);
+        assert!(gas_used < tx.gas_limit);
+
+        // only the gas used is charged
+        let sws: SimulateWorldState<'_, V2> = result.new_state.into();
+        assert_eq!(
+            sws.get_balance(tx.signer),
+            init_from_balance - base_fee_per_gas * receipt.gas_used
+        );
+        assert_eq!(sws.get_nonce(tx.signer), 1);
+    }
+}
+
+/// A contract calls another contract with a gas limit which the callee runs out of. The changes of the callee are
+/// discarded, and the caller continues and succeeds.
+#[test]
+fn test_ctoc_call_with_gas_limit_v2() {
+    let origin_address = [1u8; 32];
+    let bd = TestData::block_params();
+
+    // the child writes to its storage and then loops until it runs out of gas
+    let child_wasm_bytes = wat::parse_str(
+        r#"
+        (module
+            (import "env" "set" (func $set (param i32 i32 i32 i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "cv")
+            (func (export "alloc") (param i32) (result i32)
+                i32.const 1024)
+            (func (export "entrypoint")
+                (call $set (i32.const 0) (i32.const 1) (i32.const 1) (i32.const 1))
+                (loop $forever (br $forever))))
+        "#,
+    )
+    .unwrap();
+    let child_address = contract_address_v2(&origin_address, 0, 0);
+    let parent_address = contract_address_v2(&origin_address, 0, 1);
+
+    // the parent writes to its storage, calls the child with a gas limit of 1_000_000,
+    // and returns the result of the call as an i64
+    let call_input = ArgsBuilder::new()
+        .make_call(None, child_address, "entrypoint")
+        .serialize();
+    let call_input_data: String = call_input.iter().map(|b| format!("\\{:02x}", b)).collect();
+    let parent_wasm_bytes = wat::parse_str(format!(
+        r#"
+        (module
+            (import "env" "set" (func $set (param i32 i32 i32 i32)))
+            (import "env" "call_with_gas_limit" (func $call_with_gas_limit (param i32 i32 i64 i32) (result i64)))
+            (import "env" "return_value" (func $return_value (param i32 i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "pv")
+            (data (i32.const 16) "{call_input_data}")
+            (func (export "alloc") (param i32) (result i32)
+                i32.const 2048)
+            (func (export "entrypoint")
+                (call $set (i32.const 0) (i32.const 1) (i32.const 1) (i32.const 1))
+                (i64.store (i32.const 1024)
+                    (call $call_with_gas_limit (i32.const 16) (i32.const {call_input_len}) (i64.const 1000000) (i32.const 8)))
+                (call $return_value (i32.const 1024) (i32.const 8))))
+        "#,
+        call_input_len = call_input.len(),
+    ))
+    .unwrap();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(origin_address, 5_000_000_000);
+
+    let mut tx = TestData::transaction_v2();
+    tx.gas_limit = 400_000_000;
+    tx.commands = vec![
+        ArgsBuilder::new().make_deploy(child_wasm_bytes, pchain_runtime::cbi_version()),
+        ArgsBuilder::new().make_deploy(parent_wasm_bytes, pchain_runtime::cbi_version()),
+    ];
+    let result =
+        pchain_runtime::Runtime::new().transition_v2(sws.world_state, tx.clone(), bd.clone());
+    assert_eq!(result.error, None);
+
+    let tx = TransactionV2 {
+        nonce: 1,
+        gas_limit: 10_000_000,
+        commands: vec![ArgsBuilder::new().make_call(None, parent_address, "entrypoint")],
+        ..tx
+    };
+    let result = pchain_runtime::Runtime::new().transition_v2(result.new_state, tx, bd.clone());
+    assert_eq!(result.error, None);
+    match result.receipt.unwrap().command_receipts.pop() {
+        Some(CommandReceiptV2::Call(cr)) => {
+            assert_eq!(cr.exit_code, ExitCodeV2::Ok);
+            assert_eq!(cr.return_value, (-1_i64).to_le_bytes().to_vec());
+            // the gas used by the child is still charged to the parent
+            assert!(cr.gas_used > 1_000_000);
+        }
+        _ => panic!("expected a call receipt"),
+    }
+
+    let mut sws: SimulateWorldState<'_, V2> = result.new_state.into();
+    assert_eq!(
+        sws.get_storage_data(parent_address, b"p".to_vec()),
+        Some(b"v".to_vec())
+    );
+    assert_eq!(sws.get_storage_data(child_address, b"c".to_vec()), None);
+}
+
+/// A contract reads the gas remaining before and after a loop, and the second reading is smaller
+#[test]
+fn test_gas_remaining_v2() {
+    let origin_address = [1u8; 32];
+    let contract_address = contract_address_v2(&origin_address, 0, 0);
+    let bd = TestData::block_params();
+
+    // returns the two readings of gas_remaining, as little-endian u64s
+    let wasm_bytes = wat::parse_str(
+        r#"
+        (module
+            (import "env" "gas_remaining" (func $gas_remaining (result i64)))
+            (import "env" "return_value" (func $return_value (param i32 i32)))
+            (memory (export "memory") 1)
+            (func (export "alloc") (param i32) (result i32)
+                i32.const 1024)
+            (func (export "entrypoint")
+                (local $i i32)
+                (i64.store (i32.const 0) (call $gas_remaining))
+                (loop $loop
+                    (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                    (br_if $loop (i32.lt_u (local.get $i) (i32.const 100))))
+                (i64.store (i32.const 8) (call $gas_remaining))
+                (call $return_value (i32.const 0) (i32.const 16))))
+        "#,
+    )
+    .unwrap();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(origin_address, 5_000_000_000);
+
+    let mut tx = TestData::transaction_v2();
+    tx.gas_limit = 400_000_000;
+    tx.commands = vec![ArgsBuilder::new().make_deploy(wasm_bytes, pchain_runtime::cbi_version())];
+    let result =
+        pchain_runtime::Runtime::new().transition_v2(sws.world_state, tx.clone(), bd.clone());
+    assert_eq!(result.error, None);
+
+    let tx = TransactionV2 {
+        nonce: 1,
+        gas_limit: 10_000_000,
+        commands: vec![ArgsBuilder::new().make_call(None, contract_address, "entrypoint")],
+        ..tx
+    };
+    let result = pchain_runtime::Runtime::new().transition_v2(result.new_state, tx.clone(), bd);
+    assert_eq!(result.error, None);
+    match result.receipt.unwrap().command_receipts.pop() {
+        Some(CommandReceiptV2::Call(cr)) => {
+            assert_eq!(cr.exit_code, ExitCodeV2::Ok);
+            let before = u64::from_le_bytes(cr.return_value[..8].try_into().unwrap());
+            let after = u64::from_le_bytes(cr.return_value[8..].try_into().unwrap());
+            assert!(before < tx.gas_limit);
+            assert!(after < before);
+        }
+        _ => panic!("expected a call receipt"),
+    }
+}
+
+/// The gas of a contract doing a tight arithmetic loop is dominated by arithmetic opcodes in the opcode gas profile,
+/// which is only recorded with the `profiling` feature
+#[test]
+fn test_opcode_gas_profile_v2() {
+    let origin_address = [1u8; 32];
+    let contract_address = contract_address_v2(&origin_address, 0, 0);
+    let bd = TestData::block_params();
+
+    let wasm_bytes = wat::parse_str(
+        r#"
+        (module
+            (memory (export "memory") 1)
+            (func (export "alloc") (param i32) (result i32)
+                i32.const 1024)
+            (func (export "entrypoint")
+                (local $i i32)
+                (local $x i64)
+                (local.set $x (i64.const 1))
+                (loop $loop
+                    (local.set $x
+                        (i64.rem_u (i64.mul (local.get $x) (i64.const 7)) (i64.const 1000003)))
+                    (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                    (br_if $loop (i32.lt_u (local.get $i) (i32.const 1000))))))
+        "#,
+    )
+    .unwrap();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(origin_address, 5_000_000_000);
+
+    let mut tx = TestData::transaction_v2();
+    tx.gas_limit = 400_000_000;
+    tx.commands = vec![ArgsBuilder::new().make_deploy(wasm_bytes, pchain_runtime::cbi_version())];
+    let result =
+        pchain_runtime::Runtime::new().transition_v2(sws.world_state, tx.clone(), bd.clone());
+    assert_eq!(result.error, None);
+
+    let tx = TransactionV2 {
+        nonce: 1,
+        gas_limit: 10_000_000,
+        commands: vec![ArgsBuilder::new().make_call(None, contract_address, "entrypoint")],
+        ..tx
+    };
+    let result = pchain_runtime::Runtime::new().transition_v2(result.new_state, tx, bd);
+    assert_eq!(result.error, None);
+    if !cfg!(feature = "profiling") {
+        assert_eq!(result.opcode_gas_profile, None);
+        return;
+    }
+
+    let gas_used = extract_gas_success_call_v2(&result.receipt.unwrap().command_receipts[0]);
+    let profile = result.opcode_gas_profile.unwrap();
+    assert_eq!(profile.len(), 1);
+    let profile = profile[0];
+    assert!(profile.total() > 0);
+    assert!(profile.total() <= gas_used);
+    assert!(profile.arithmetic_gas > profile.control_gas);
+    assert!(profile.arithmetic_gas > profile.memory_gas);
+    assert!(profile.arithmetic_gas > profile.call_gas);
+}
+
+/// A contract which loops until it runs out of gas is stopped promptly when the interrupt flag is set from another
+/// thread, and the transition fails with Interrupted
+#[test]
+fn test_interrupt_v2() {
+    let origin_address = [1u8; 32];
+    let contract_address = [2u8; 32];
+    let bd = TestData::block_params();
+
+    let wasm_bytes = wat::parse_str(
+        r#"
+        (module
+            (memory (export "memory") 1)
+            (func (export "alloc") (param i32) (result i32)
+                i32.const 1024)
+            (func (export "entrypoint")
+                (loop $loop
+                    (br $loop))))
+        "#,
+    )
+    .unwrap();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(origin_address, 100_000_000_000);
+    sws.add_contract(contract_address, wasm_bytes, pchain_runtime::cbi_version());
+
+    let mut tx = TestData::transaction_v2();
+    tx.gas_limit = 50_000_000_000;
+    tx.commands = vec![ArgsBuilder::new().make_call(None, contract_address, "entrypoint")];
+
+    let interrupt = Arc::new(AtomicBool::new(false));
+    let interrupter = {
+        let interrupt = interrupt.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            interrupt.store(true, Ordering::Relaxed);
+        })
+    };
+    let result = pchain_runtime::Runtime::new()
+        .set_interrupt(interrupt)
+        .transition_v2(sws.world_state, tx, bd);
+    interrupter.join().unwrap();
+
+    assert_eq!(result.error, Some(TransitionError::Interrupted));
+    let receipt = result.receipt.unwrap();
+    assert_eq!(receipt.exit_code, ExitCodeV2::Error);
+}
+
+/// A view call of a contract which loops until it runs out of gas is stopped once it exceeds the command time budget,
+/// and fails with CommandTimeout. Transitions ignore the budget.
+#[test]
+fn test_command_time_budget_v2() {
+    let origin_address = [1u8; 32];
+    let contract_address = [2u8; 32];
+    let bd = TestData::block_params();
+
+    let wasm_bytes = wat::parse_str(
+        r#"
+        (module
+            (memory (export "memory") 1)
+            (func (export "alloc") (param i32) (result i32)
+                i32.const 1024)
+            (func (export "entrypoint")
+                (loop $loop
+                    (br $loop))))
+        "#,
+    )
+    .unwrap();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(origin_address, 100_000_000_000);
+    sws.add_contract(contract_address, wasm_bytes, pchain_runtime::cbi_version());
+
+    // 1. the view call times out
+    let (receipt, error) = pchain_runtime::Runtime::new()
+        .set_command_time_budget(Duration::from_millis(10))
+        .view_v2(
+            sws.world_state.clone(),
+            50_000_000_000,
+            contract_address,
+            "entrypoint".to_string(),
+            None,
+        );
+    assert_eq!(error, Some(TransitionError::CommandTimeout));
+    match receipt {
+        CommandReceiptV2::Call(cr) => assert_eq!(cr.exit_code, ExitCodeV2::Error),
+        _ => panic!("expected a call receipt"),
+    }
+
+    // 2. the transition runs out of gas, even with a budget of zero
+    let mut tx = TestData::transaction_v2();
+    tx.gas_limit = 10_000_000;
+    tx.commands = vec![ArgsBuilder::new().make_call(None, contract_address, "entrypoint")];
+    let result = pchain_runtime::Runtime::new()
+        .set_command_time_budget(Duration::ZERO)
+        .transition_v2(sws.world_state, tx, bd);
+    assert_eq!(
+        result.error,
+        Some(TransitionError::ExecutionProperGasExhausted)
+    );
+}
+
+/// A contract which calls itself until it has been called 5 times in a Call command reaches a call depth of 4. It
+/// succeeds if the maximum call depth is 4, and fails with CallDepthExceeded if the maximum call depth is 3, without
+/// affecting the commands before it.
+#[test]
+fn test_ctoc_max_call_depth_v2() {
+    let origin_address = [1u8; 32];
+    let contract_address = contract_address_v2(&origin_address, 0, 0);
+    let bd = TestData::block_params();
+
+    // the contract counts its calls in storage under the key "d", and calls itself until the count is 5
+    let call_input = ArgsBuilder::new()
+        .make_call(None, contract_address, "entrypoint")
+        .serialize();
+    let call_input_data: String = call_input.iter().map(|b| format!("\\{:02x}", b)).collect();
+    let wasm_bytes = wat::parse_str(format!(
+        r#"
+        (module
+            (import "env" "get" (func $get (param i32 i32 i32) (result i64)))
+            (import "env" "set" (func $set (param i32 i32 i32 i32)))
+            (import "env" "call" (func $call (param i32 i32 i32) (result i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "d")
+            (data (i32.const 16) "{call_input_data}")
+            (func (export "alloc") (param i32) (result i32)
+                i32.const 1024)
+            (func (export "entrypoint")
+                (local $count i32)
+                (if (i64.ge_s (call $get (i32.const 0) (i32.const 1) (i32.const 8)) (i64.const 0))
+                    (then (local.set $count (i32.load8_u (i32.const 1024)))))
+                (local.set $count (i32.add (local.get $count) (i32.const 1)))
+                (i32.store8 (i32.const 4) (local.get $count))
+                (call $set (i32.const 0) (i32.const 1) (i32.const 4) (i32.const 1))
+                (if (i32.lt_u (local.get $count) (i32.const 5))
+                    (then (drop (call $call (i32.const 16) (i32.const {call_input_len}) (i32.const 8)))))))
+        "#,
+        call_input_len = call_input.len(),
+    ))
+    .unwrap();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(origin_address, 5_000_000_000);
+
+    let mut tx = TestData::transaction_v2();
+    tx.gas_limit = 400_000_000;
+    tx.commands = vec![ArgsBuilder::new().make_deploy(wasm_bytes, pchain_runtime::cbi_version())];
+    let result =
+        pchain_runtime::Runtime::new().transition_v2(sws.world_state, tx.clone(), bd.clone());
+    assert_eq!(result.error, None);
+    let deployed_state = result.new_state;
+
+    let tx = TransactionV2 {
+        nonce: 1,
+        gas_limit: 100_000_000,
+        commands: vec![
+            Command::Transfer(TransferInput {
+                recipient: [2u8; 32],
+                amount: 100,
+            }),
+            ArgsBuilder::new().make_call(None, contract_address, "entrypoint"),
+        ],
+        ..tx
+    };
+
+    // a call depth of 4 exceeds the maximum call depth of 3
+    let result = pchain_runtime::Runtime::new()
+        .set_max_call_depth(3)
+        .transition_v2(deployed_state.clone(), tx.clone(), bd.clone());
+    assert_eq!(result.error, Some(TransitionError::CallDepthExceeded));
+    let command_receipts = result.receipt.unwrap().command_receipts;
+    match &command_receipts[..] {
+        [CommandReceiptV2::Transfer(transfer), CommandReceiptV2::Call(call)] => {
+            assert_eq!(transfer.exit_code, ExitCodeV2::Ok);
+            assert_eq!(call.exit_code, ExitCodeV2::Error);
+        }
+        _ => panic!("expected a transfer and a call receipt"),
+    }
+    let mut sws: SimulateWorldState<'_, V2> = result.new_state.into();
+    assert_eq!(sws.get_balance([2u8; 32]), 100);
+    assert_eq!(sws.get_storage_data(contract_address, b"d".to_vec()), None);
+
+    // a call depth of 4 is within the maximum call depth of 4
+    let result = pchain_runtime::Runtime::new()
+        .set_max_call_depth(4)
+        .transition_v2(deployed_state, tx, bd);
+    assert_eq!(result.error, None);
+    let mut sws: SimulateWorldState<'_, V2> = result.new_state.into();
+    assert_eq!(
+        sws.get_storage_data(contract_address, b"d".to_vec()),
+        Some(vec![5])
+    );
+}
+
+/// Multiple Contract Calls in a Transaction
+#[test]
+fn test_etoc_multiple_v2() {
+    let wasm_bytes_1 = TestData::get_test_contract_code("all_features");
+    let contract_address_1 = [22u8; 32];
+    let method_args_1 = 123_i32;
+    let method_name_1 = "set_data_only";
+    let command_1 =
+        ArgsBuilder::new()
+            .add(method_args_1)
+            .make_call(Some(1), contract_address_1, method_name_1);
+
+    let wasm_bytes_2 = TestData::get_test_contract_code("basic_contract");
+    let contract_address_2 = [2u8; 32];
+    let method_args_2 = "arg".to_string();
+    let method_name_2 = "emit_event_with_return";
+    let command_2 = ArgsBuilder::new().add(method_args_2.clone()).make_call(
+        Some(2),
+        contract_address_2,
+        method_name_2,
+    );
+
+    let method_name_3 = "get_data_only";
+    let command_3 = ArgsBuilder::new().make_call(Some(3), contract_address_1, method_name_3);
+
+    let mut tx = TestData::transaction_v2();
+    tx.gas_limit = 20_000_000;
+    let proposer_address = [4u8; 32];
+    let treasury_address = [100u8; 32];
+    let bd = TestData::block_params();
+    let base_fee_per_gas = bd.this_base_fee;
+
+    // initialize world state
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    let from_address = tx.signer;
+    let init_from_balance = 500_000_000;
+    sws.set_balance(from_address, init_from_balance);
+    sws.add_contract(
+        contract_address_1,
+        wasm_bytes_1,
+        pchain_runtime::cbi_version(),
+    );
+    sws.add_contract(
+        contract_address_2,
+        wasm_bytes_2,
+        pchain_runtime::cbi_version(),
+    );
+
+    // test for two commands (dry run)
+    tx.commands = vec![command_1.clone(), command_3.clone()];
+    let result = pchain_runtime::Runtime::new().transition_v2(
+        sws.world_state.clone(),
+        tx.clone(),
+        bd.clone(),
+    );
+    let receipt = result.receipt.unwrap();
+
+    assert_eq!(receipt.command_receipts.len(), tx.commands.len());
+    let gas_consumed_1 = extract_gas_success_call_v2(&receipt.command_receipts[0]);
+    let gas_consumed_3 = extract_gas_success_call_v2(&receipt.command_receipts[1]);
+
+    // test for three commands (insert a command in the middle)
+    tx.commands = vec![command_1.clone(), command_2.clone(), command_3.clone()];
+    let result =
+        pchain_runtime::Runtime::new().transition_v2(sws.world_state, tx.clone(), bd.clone());
+    let receipt = result.receipt.unwrap();
+    assert_eq!(receipt.command_receipts.len(), tx.commands.len());
+    let gas_consumed_2 = extract_gas_success_call_v2(&receipt.command_receipts[1]);
+    // check gas consumption and receipt is independent on the inserted command
+    assert_eq!(
+        gas_consumed_1,
+        extract_gas_success_call_v2(&receipt.command_receipts[0])
+    );
+    assert_ne!(gas_consumed_1, gas_consumed_2);
+    assert_eq!(
+        gas_consumed_3,
+        extract_gas_success_call_v2(&receipt.command_receipts[2])
+    );
+
+    if let CommandReceiptV2::Call(cr) = &receipt.command_receipts[2] {
+        assert_eq!(cr.exit_code, ExitCodeV2::Ok);
+        assert!(cr.logs.is_empty());
+        let get_data_value: i32 = CallResult::parse(cr.return_value.clone()).unwrap();
+        assert_eq!(method_args_1, get_data_value);
+    } else {
+        panic!("Expected CommandReceiptV2::Call");
+    }
+
+    let mut sws: SimulateWorldState<'_, V2> = result.new_state.into();
+
+    // check state change from contract
+    let data = sws.get_storage_data(contract_address_1, vec![0u8]);
+    assert_eq!(data, Some(123_i32.to_le_bytes().to_vec()));
+
+    // check from_address balance
+    let total_gas_used = gas_consumed_1 + gas_consumed_2 + gas_consumed_3 + tx_base_cost_v2(&tx);
+    let new_from_balance = sws.get_balance(from_address);
     assert_eq!(
         new_from_balance,
         init_from_balance - 6 - base_fee_per_gas * total_gas_used
@@ -1840,6 +4672,65 @@ fn test_etoc_host_functions_v2() {
     assert_eq!(receipt.exit_code, ExitCodeV2::Ok);
 }
 
+/// Contracts of CBI version 1 read the address of the block proposer. Contracts of CBI version 0 cannot import it.
+#[test]
+fn test_etoc_block_proposer_v2() {
+    let wasm_bytes = wat::parse_str(
+        r#"
+        (module
+            (import "env" "block_proposer" (func $block_proposer (param i32)))
+            (import "env" "return_value" (func $return_value (param i32 i32)))
+            (memory (export "memory") 1)
+            (func (export "alloc") (param i32) (result i32)
+                i32.const 1024)
+            (func (export "entrypoint")
+                (call $block_proposer (i32.const 0))
+                (call $return_value (i32.const 0) (i32.const 32))))
+        "#,
+    )
+    .unwrap();
+    let origin_address = [1u8; 32];
+    let mut bd = TestData::block_params();
+    bd.proposer_address = [7u8; 32];
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(origin_address, 5_000_000_000);
+
+    // the import does not resolve against the host functions of CBI version 0
+    let mut tx = TestData::transaction_v2();
+    tx.gas_limit = 400_000_000;
+    tx.commands = vec![ArgsBuilder::new().make_deploy(wasm_bytes.clone(), 0)];
+    let result =
+        pchain_runtime::Runtime::new().transition_v2(sws.world_state, tx.clone(), bd.clone());
+    assert_eq!(result.error, Some(TransitionError::CannotCompile));
+
+    let tx = TransactionV2 {
+        nonce: 1,
+        commands: vec![ArgsBuilder::new().make_deploy(wasm_bytes, pchain_runtime::cbi_version())],
+        ..tx
+    };
+    let result =
+        pchain_runtime::Runtime::new().transition_v2(result.new_state, tx.clone(), bd.clone());
+    assert_eq!(result.error, None);
+
+    let contract_address = contract_address_v2(&origin_address, 1, 0);
+    let tx = TransactionV2 {
+        nonce: 2,
+        gas_limit: 10_000_000,
+        commands: vec![ArgsBuilder::new().make_call(None, contract_address, "entrypoint")],
+        ..tx
+    };
+    let result = pchain_runtime::Runtime::new().transition_v2(result.new_state, tx, bd.clone());
+    match result.receipt.unwrap().command_receipts.pop() {
+        Some(CommandReceiptV2::Call(cr)) => {
+            assert_eq!(cr.exit_code, ExitCodeV2::Ok);
+            assert_eq!(cr.return_value, bd.proposer_address.to_vec());
+        }
+        _ => panic!("expected a call receipt"),
+    }
+}
+
 #[test]
 fn test_ctoc_v2() {
     let wasm_bytes_1 = TestData::get_test_contract_code("all_features");
@@ -2035,6 +4926,40 @@ fn test_deploy_v2() {
     assert!(sws.get_contract_code(contract_address).is_some());
 }
 
+/// Contract address computed before deployment is where the Deploy command deploys the contract, so it can be
+/// pre-funded by an earlier command of the same transaction
+#[test]
+fn test_deploy_to_precomputed_address_v2() {
+    let wasm_bytes = TestData::get_test_contract_code("basic_contract");
+
+    let mut tx = TestData::transaction_v2();
+    let contract_address = pchain_runtime::contract::contract_address_v2(tx.signer, tx.nonce, 1);
+    tx.commands = vec![
+        Command::Transfer(TransferInput {
+            recipient: contract_address,
+            amount: 1_000,
+        }),
+        ArgsBuilder::new().make_deploy(wasm_bytes, 0),
+    ];
+    tx.gas_limit = 400_000_000;
+    let bd = TestData::block_params();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(tx.signer, 5_000_000_000);
+
+    let result = pchain_runtime::Runtime::new().transition_v2(sws.world_state, tx.clone(), bd);
+    assert_eq!(result.receipt.unwrap().exit_code, ExitCodeV2::Ok);
+    let sws: SimulateWorldState<'_, V2> = result.new_state.into();
+
+    assert_eq!(
+        contract_address,
+        contract_address_v2(&tx.signer, tx.nonce, 1)
+    );
+    assert_eq!(sws.get_balance(contract_address), 1_000);
+    assert!(sws.get_contract_code(contract_address).is_some());
+}
+
 /// Simulate test to deploy an invalid contract.
 /// The contract is invalid for several reasons:
 /// 1. Fails to instantiate from provided the Module and imports (InstantiationError).
@@ -2107,6 +5032,45 @@ fn test_deploy_contract_with_invalid_opcode_v2() {
     assert_eq!(receipt.exit_code, ExitCodeV2::Error);
 }
 
+/// Deploying a contract with a floating point addition fails with DisallowedOpcode, and the opcode and its offset in
+/// the function body are found from the bytecode
+#[test]
+fn test_deploy_contract_with_float_opcode_v2() {
+    let wasm_bytes = wat::parse_str(
+        r#"
+        (module
+            (func $add (param f64 f64) (result f64)
+                local.get 0
+                local.get 1
+                f64.add
+            )
+        )
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        disallowed_opcode(&wasm_bytes),
+        Some(DisallowedOpcode {
+            opcode: "F64Add".to_string(),
+            function_index: 0,
+            offset: 2,
+        })
+    );
+
+    let mut tx = TestData::transaction_v2();
+    tx.commands = vec![ArgsBuilder::new().make_deploy(wasm_bytes, 0)];
+    tx.gas_limit = 20_000_000;
+    let bd = TestData::block_params();
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(tx.signer, 500_000_000);
+
+    let result = pchain_runtime::Runtime::new().transition_v2(sws.world_state, tx, bd);
+    assert_eq!(result.error, Some(TransitionError::DisallowedOpcode));
+    assert_eq!(result.receipt.unwrap().exit_code, ExitCodeV2::Error);
+}
+
 #[test]
 fn test_deploy_insufficient_gas_v2() {
     let wasm_bytes = TestData::get_test_contract_code("basic_contract");
@@ -2198,6 +5162,41 @@ fn test_memory_limited_contract_module_v2() {
     assert_eq!(receipt.exit_code, ExitCodeV2::Error);
 }
 
+/// Bytecode longer than the maximum contract size is rejected with ContractTooLarge before it is compiled, while
+/// bytecode within the limit is compiled as usual
+#[test]
+fn test_max_contract_size_v2() {
+    let wasm_bytes = TestData::get_test_contract_code("basic_contract");
+    let contract_size = wasm_bytes.len();
+    let mut tx = TestData::transaction_v2();
+    tx.commands = vec![ArgsBuilder::new().make_deploy(wasm_bytes, 0)];
+    tx.gas_limit = 400_000_000;
+    let bd = TestData::block_params();
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(tx.signer, 5_000_000_000);
+
+    // at the limit
+    let runtime = pchain_runtime::Runtime::new().set_max_contract_size(contract_size);
+    let result = runtime.transition_v2(sws.world_state.clone(), tx, bd.clone());
+    assert_eq!(result.error, None);
+    assert_eq!(result.receipt.unwrap().exit_code, ExitCodeV2::Ok);
+
+    // an oversized blob which is not Wasm fails on its size, so it is never compiled
+    let mut tx = TestData::transaction_v2();
+    tx.commands = vec![ArgsBuilder::new().make_deploy(vec![0xff; 1024], 0)];
+    tx.gas_limit = 400_000_000;
+    let runtime = pchain_runtime::Runtime::new().set_max_contract_size(1023);
+    let result = runtime.transition_v2(sws.world_state.clone(), tx.clone(), bd.clone());
+    assert_eq!(result.error, Some(TransitionError::ContractTooLarge));
+    assert_eq!(result.receipt.unwrap().exit_code, ExitCodeV2::Error);
+
+    // within the limit, the same blob is compiled and fails to compile
+    let runtime = pchain_runtime::Runtime::new().set_max_contract_size(1024);
+    let result = runtime.transition_v2(sws.world_state, tx, bd);
+    assert_eq!(result.error, Some(TransitionError::CannotCompile));
+}
+
 /// Possible fail cases in PreCharge Phase:
 /// - transaction gas limit is smaller than minimum required gas
 /// - incorrect nonce
@@ -2220,10 +5219,7 @@ fn test_fail_in_pre_charge_v2() {
     };
     let result = pchain_runtime::Runtime::new().transition_v2(sws.world_state, tx1, bd.clone());
     assert!(result.receipt.is_none());
-    assert_eq!(
-        result.error,
-        Some(TransitionError::PreExecutionGasExhausted)
-    );
+    assert_eq!(result.error, Some(TransitionError::GasLimitTooLow));
     let sws: SimulateWorldState<'_, V2> = result.new_state.into();
 
     // 2. nonce is incorrect
@@ -2254,3 +5250,468 @@ fn test_fail_in_pre_charge_v2() {
     assert_eq!(new_from_balance, init_from_balance);
     assert_eq!(sws.get_nonce(tx.signer), 0);
 }
+
+/// The minimum signer balance is exactly the amount required to pass the PreCharge phase.
+#[test]
+fn test_min_signer_balance_v2() {
+    let tx = TransactionV2 {
+        priority_fee_per_gas: 1,
+        ..TestData::transaction_v2()
+    };
+    let bd = TestData::block_params();
+    let min_balance = min_signer_balance_v2(&tx, &bd).unwrap();
+    assert_eq!(
+        min_balance,
+        tx.gas_limit * (bd.this_base_fee + tx.priority_fee_per_gas)
+    );
+
+    // 1. balance is one less than the minimum
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(tx.signer, min_balance - 1);
+    let result =
+        pchain_runtime::Runtime::new().transition_v2(sws.world_state, tx.clone(), bd.clone());
+    assert!(result.receipt.is_none());
+    assert_eq!(
+        result.error,
+        Some(TransitionError::NotEnoughBalanceForGasLimit)
+    );
+
+    // 2. balance is exactly the minimum
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(tx.signer, min_balance);
+    let result =
+        pchain_runtime::Runtime::new().transition_v2(sws.world_state, tx.clone(), bd.clone());
+    assert!(result.error.is_none());
+    assert_eq!(result.receipt.unwrap().exit_code, ExitCodeV2::Ok);
+
+    // 3. no balance is enough if the amount overflows
+    let tx = TransactionV2 {
+        priority_fee_per_gas: u64::MAX,
+        ..tx
+    };
+    assert_eq!(min_signer_balance_v2(&tx, &bd), None);
+}
+
+/// Every TransitionError has a fixed code, and is decoded back from it.
+#[test]
+fn test_transition_error_codes() {
+    let codes = [
+        (TransitionError::FailedWorldStateUpgrade, 1),
+        (TransitionError::MalformedWorldState, 2),
+        (TransitionError::WrongNonce, 3),
+        (TransitionError::NotEnoughBalanceForGasLimit, 4),
+        (TransitionError::NotEnoughBalanceForTransfer, 5),
+        (TransitionError::GasLimitTooLow, 6),
+        (TransitionError::PreExecutionGasExhausted, 7),
+        (TransitionError::DisallowedOpcode, 8),
+        (TransitionError::CannotCompile, 9),
+        (TransitionError::NoExportedContractMethod, 10),
+        (TransitionError::OtherDeployError, 11),
+        (TransitionError::ContractAlreadyExists, 12),
+        (TransitionError::NoContractcode, 13),
+        (TransitionError::InvalidCBI, 14),
+        (TransitionError::ExecutionProperGasExhausted, 15),
+        (TransitionError::RuntimeError, 16),
+        (TransitionError::CallStackExhausted, 17),
+        (TransitionError::InternalExecutionProperGasExhaustion, 18),
+        (TransitionError::InternalRuntimeError, 19),
+        (TransitionError::PoolAlreadyExists, 20),
+        (TransitionError::PoolNotExists, 21),
+        (TransitionError::PoolHasNoStakes, 22),
+        (TransitionError::InvalidPoolPolicy, 23),
+        (TransitionError::CommissionRateUnchanged, 24),
+        (TransitionError::DepositsAlreadyExists, 25),
+        (TransitionError::DepositsNotExists, 26),
+        (TransitionError::InvalidDepositPolicy, 27),
+        (TransitionError::AutoStakeRewardsUnchanged, 28),
+        (TransitionError::InvalidStakeAmount, 29),
+        (TransitionError::InvalidCommands, 30),
+        (TransitionError::InvalidNextEpochCommand, 31),
+        (TransitionError::KeyTooLong, 32),
+        (TransitionError::TransferLimitExceeded, 33),
+        (TransitionError::CallDepthExceeded, 34),
+        (TransitionError::Interrupted, 35),
+        (TransitionError::CommandTimeout, 36),
+        (TransitionError::ContractTooLarge, 37),
+        (TransitionError::SpawnedCommandLimitExceeded, 38),
+    ];
+    for (error, code) in codes.iter() {
+        assert_eq!(error.code(), *code);
+        assert_eq!(TransitionError::from_code(*code).as_ref(), Some(error));
+    }
+
+    assert_eq!(TransitionError::from_code(0), None);
+    assert_eq!(TransitionError::from_code(codes.len() as u32 + 1), None);
+}
+
+/// ValidatorChanges are encoded canonically, and decoded back to the same changes.
+#[test]
+fn test_validator_changes_bytes() {
+    let changes = ValidatorChanges {
+        new_validator_set: vec![([1u8; 32], 100), ([2u8; 32], 50)],
+        remove_validator_set: vec![[3u8; 32]],
+        power_updates: vec![([1u8; 32], 100), ([2u8; 32], 50), ([4u8; 32], 10)],
+    };
+    let bytes = validator_changes_to_bytes(&changes);
+    assert_eq!(bytes.len(), 4 + 2 * 40 + 4 + 32 + 4 + 3 * 40);
+    assert_eq!(
+        bytes,
+        borsh::BorshSerialize::try_to_vec(&(
+            changes.new_validator_set.clone(),
+            changes.remove_validator_set.clone(),
+            changes.power_updates.clone()
+        ))
+        .unwrap()
+    );
+    assert_eq!(validator_changes_from_bytes(&bytes), Some(changes));
+
+    // truncated or trailing bytes are rejected
+    assert_eq!(
+        validator_changes_from_bytes(&bytes[..bytes.len() - 1]),
+        None
+    );
+    assert_eq!(
+        validator_changes_from_bytes(&[bytes, vec![0]].concat()),
+        None
+    );
+}
+
+/// Receipts are encoded canonically, to the same bytes as a known vector, and changing any gas value changes the bytes.
+#[test]
+fn test_receipt_v2_bytes() {
+    let mut receipt = ReceiptV2 {
+        gas_used: 1_000,
+        exit_code: ExitCodeV2::Error,
+        command_receipts: vec![
+            CommandReceiptV2::Transfer(TransferReceipt {
+                exit_code: ExitCodeV2::Ok,
+                gas_used: 10,
+            }),
+            CommandReceiptV2::Call(CallReceipt {
+                exit_code: ExitCodeV2::Error,
+                gas_used: 20,
+                logs: vec![Log {
+                    topic: vec![1],
+                    value: vec![2, 3],
+                }],
+                return_value: vec![4],
+            }),
+            CommandReceiptV2::StakeDeposit(StakeDepositReceipt {
+                exit_code: ExitCodeV2::NotExecuted,
+                gas_used: 0,
+                amount_staked: 5,
+            }),
+        ],
+    };
+    let bytes = receipt_v2_to_bytes(&receipt);
+    let expected = [
+        // gas used, exit code and number of command receipts
+        vec![0xe8, 0x03, 0, 0, 0, 0, 0, 0],
+        vec![1],
+        vec![3, 0, 0, 0],
+        // transfer
+        vec![0, 0],
+        vec![10, 0, 0, 0, 0, 0, 0, 0],
+        // call with one log and a return value
+        vec![2, 1],
+        vec![20, 0, 0, 0, 0, 0, 0, 0],
+        vec![1, 0, 0, 0],
+        vec![1, 0, 0, 0, 1],
+        vec![2, 0, 0, 0, 2, 3],
+        vec![1, 0, 0, 0, 4],
+        // stake deposit
+        vec![10, 3],
+        vec![0, 0, 0, 0, 0, 0, 0, 0],
+        vec![5, 0, 0, 0, 0, 0, 0, 0],
+    ]
+    .concat();
+    assert_eq!(bytes, expected);
+    assert_eq!(receipt_v2_to_bytes(&receipt.clone()), bytes);
+
+    match &mut receipt.command_receipts[1] {
+        CommandReceiptV2::Call(r) => r.gas_used += 1,
+        _ => unreachable!(),
+    }
+    assert_ne!(receipt_v2_to_bytes(&receipt), bytes);
+}
+
+/// The deposit and pool views report the values of a prepared pool, and None for a missing deposit or pool
+#[test]
+fn test_deposit_and_pool_views() {
+    use pchain_runtime::{DepositView, PoolView};
+    use pchain_world_state::{Stake, StakeValue};
+
+    let operator = [1u8; 32];
+    let owner = [2u8; 32];
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    let mut pool = NetworkAccount::pools(&mut sws, operator);
+    pool.set_operator(operator);
+    pool.set_power(50_000);
+    pool.set_commission_rate(5);
+    pool.set_operator_stake(Some(Stake {
+        owner: operator,
+        power: 10_000,
+    }));
+    pool.delegated_stakes()
+        .insert(StakeValue::new(Stake {
+            owner,
+            power: 40_000,
+        }))
+        .unwrap();
+    let mut deposit = NetworkAccount::deposits(&mut sws, operator, operator);
+    deposit.set_balance(10_000);
+    deposit.set_auto_stake_rewards(false);
+    let mut deposit = NetworkAccount::deposits(&mut sws, operator, owner);
+    deposit.set_balance(90_000);
+    deposit.set_auto_stake_rewards(true);
+    let ws = sws.world_state;
+
+    let runtime = pchain_runtime::Runtime::new();
+    assert_eq!(
+        runtime.deposit(ws.clone(), operator, owner),
+        Some(DepositView {
+            balance: 90_000,
+            auto_stake_rewards: true
+        })
+    );
+    assert_eq!(
+        runtime.deposit(ws.clone(), operator, operator),
+        Some(DepositView {
+            balance: 10_000,
+            auto_stake_rewards: false
+        })
+    );
+    assert_eq!(runtime.deposit(ws.clone(), owner, operator), None);
+
+    assert_eq!(
+        runtime.pool(ws.clone(), operator),
+        Some(PoolView {
+            operator,
+            commission_rate: 5,
+            power: 50_000,
+            operator_stake: Some(10_000)
+        })
+    );
+    assert_eq!(runtime.pool(ws, owner), None);
+}
+
+/// Estimation produces the same receipt and error as the transition, including for failing commands and failing
+/// pre-charge checks, and does not write the World State
+#[test]
+fn test_estimate_v2() {
+    let target = [2u8; 32];
+    let mut tx = TestData::transaction_v2();
+    tx.commands = vec![
+        Command::Transfer(TransferInput {
+            recipient: target,
+            amount: 1_000,
+        }),
+        // not enough balance for transfer
+        Command::Transfer(TransferInput {
+            recipient: target,
+            amount: 1_000_000_000,
+        }),
+        Command::Transfer(TransferInput {
+            recipient: target,
+            amount: 1_000,
+        }),
+    ];
+    let bd = TestData::block_params();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(tx.signer, 100_000_000);
+
+    let runtime = pchain_runtime::Runtime::new();
+    let estimate = runtime.estimate_v2(sws.world_state.clone(), tx.clone(), bd.clone());
+    let result = runtime.transition_v2(sws.world_state.clone(), tx.clone(), bd.clone());
+    assert!(estimate.dry_run);
+    assert!(!result.dry_run);
+    assert_eq!(
+        estimate.error,
+        Some(TransitionError::NotEnoughBalanceForTransfer)
+    );
+    assert_eq!(estimate.error, result.error);
+    let receipt = estimate.receipt.unwrap();
+    match receipt.command_receipts.last() {
+        Some(CommandReceiptV2::Transfer(r)) => assert_eq!(r.exit_code, ExitCodeV2::NotExecuted),
+        _ => panic!("expected a transfer receipt"),
+    }
+    assert_eq!(receipt.serialize(), result.receipt.unwrap().serialize());
+
+    let sws_after: SimulateWorldState<'_, V2> = estimate.new_state.into();
+    assert_eq!(sws_after.get_balance(tx.signer), 100_000_000);
+    assert_eq!(sws_after.get_balance(target), 0);
+    assert_eq!(sws_after.get_nonce(tx.signer), 0);
+
+    // pre-charge checks fail the same way
+    tx.nonce = 1;
+    let estimate = runtime.estimate_v2(sws.world_state.clone(), tx.clone(), bd.clone());
+    assert_eq!(estimate.error, Some(TransitionError::WrongNonce));
+    assert!(estimate.receipt.is_none());
+    tx.nonce = 0;
+    tx.priority_fee_per_gas = u64::MAX;
+    let estimate = runtime.estimate_v2(sws.world_state, tx, bd);
+    assert_eq!(
+        estimate.error,
+        Some(TransitionError::NotEnoughBalanceForGasLimit)
+    );
+    assert!(estimate.receipt.is_none());
+}
+
+/// A balance override lets a transfer succeed in simulation which fails in the transition, while charging gas as
+/// usual, and neither the override nor the transfer is written to the World State
+#[test]
+fn test_simulate_v2_with_overrides() {
+    let target = [2u8; 32];
+    let mut tx = TestData::transaction_v2();
+    tx.commands = vec![Command::Transfer(TransferInput {
+        recipient: target,
+        amount: 50_000_000,
+    })];
+    let bd = TestData::block_params();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(tx.signer, 10_000_000);
+
+    let runtime = pchain_runtime::Runtime::new();
+    let result = runtime.transition_v2(sws.world_state.clone(), tx.clone(), bd.clone());
+    assert_eq!(
+        result.error,
+        Some(TransitionError::NotEnoughBalanceForTransfer)
+    );
+    let simulated =
+        runtime.simulate_v2_with_overrides(sws.world_state.clone(), tx.clone(), bd.clone(), &[]);
+    assert!(simulated.dry_run);
+    assert_eq!(simulated.error, result.error);
+
+    let overrides = [StateOverride::Balance(tx.signer, 100_000_000)];
+    let simulated =
+        runtime.simulate_v2_with_overrides(sws.world_state.clone(), tx.clone(), bd, &overrides);
+    assert!(simulated.dry_run);
+    assert_eq!(simulated.error, None);
+    let receipt = simulated.receipt.unwrap();
+    assert_eq!(receipt.exit_code, ExitCodeV2::Ok);
+    match receipt.command_receipts.last() {
+        Some(CommandReceiptV2::Transfer(r)) => {
+            assert_eq!(r.exit_code, ExitCodeV2::Ok);
+            assert!(r.gas_used > 0);
+        }
+        _ => panic!("expected a transfer receipt"),
+    }
+    assert!(simulated.base_fee_paid() > 0);
+
+    // neither the override nor the transfer is written
+    let sws_after: SimulateWorldState<'_, V2> = simulated.new_state.into();
+    assert_eq!(sws_after.get_balance(tx.signer), 10_000_000);
+    assert_eq!(sws_after.get_balance(target), 0);
+    assert_eq!(sws_after.get_nonce(tx.signer), 0);
+}
+
+/// A batch stops at the first transaction which does not fit in the block gas limit, and returns it and the
+/// transactions after it without executing them
+#[test]
+fn test_transition_v2_batch_block_gas_limit() {
+    let signer = [1u8; 32];
+    let target = [2u8; 32];
+    let make_tx = |nonce: u64, gas_limit: u64| {
+        let mut tx = TestData::transaction_v2();
+        tx.signer = signer;
+        tx.nonce = nonce;
+        tx.gas_limit = gas_limit;
+        tx.commands = vec![Command::Transfer(TransferInput {
+            recipient: target,
+            amount: 1_000,
+        })];
+        tx
+    };
+    // the second transaction does not fit, and the third is excluded with it although it would fit
+    let txs = vec![
+        make_tx(0, 1_000_000),
+        make_tx(1, 2_000_000),
+        make_tx(1, 1_000_000),
+    ];
+    let bd = TestData::block_params();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(signer, 100_000_000);
+
+    for commit_mode in [CommitMode::Eager, CommitMode::Lazy] {
+        let result = pchain_runtime::Runtime::new()
+            .set_commit_mode(commit_mode)
+            .set_block_gas_limit(1_500_000)
+            .transition_v2_batch(sws.world_state.clone(), txs.clone(), bd.clone());
+        assert_eq!(result.errors, vec![None]);
+        assert_eq!(result.receipts.len(), 1);
+        assert!(result.receipts[0].as_ref().unwrap().gas_used <= 1_500_000);
+        assert_eq!(
+            result
+                .excluded
+                .iter()
+                .map(|tx| (tx.nonce, tx.gas_limit))
+                .collect::<Vec<_>>(),
+            vec![(1, 2_000_000), (1, 1_000_000)]
+        );
+
+        let sws_after: SimulateWorldState<'_, V2> = result.new_state.into();
+        assert_eq!(sws_after.get_nonce(signer), 1);
+        assert_eq!(sws_after.get_balance(target), 1_000);
+    }
+
+    // without a limit, all transactions are executed
+    let result = pchain_runtime::Runtime::new().transition_v2_batch(sws.world_state, txs, bd);
+    assert_eq!(result.receipts.len(), 3);
+    assert!(result.excluded.is_empty());
+}
+
+/// Disabling the nonce check lets a transaction with a wrong nonce execute as if its nonce were correct
+#[test]
+fn test_nonce_check_disabled_v2() {
+    let mut tx = TestData::transaction_v2();
+    let target = [2u8; 32];
+    tx.commands = vec![ArgsBuilder::new().make_transfer(1_000, target)];
+    let bd = TestData::block_params();
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.set_balance(tx.signer, 100_000_000);
+
+    let wrong_nonce_tx = TransactionV2 {
+        nonce: 5,
+        ..tx.clone()
+    };
+
+    // enabled by default
+    let result = pchain_runtime::Runtime::new().transition_v2(
+        sws.world_state.clone(),
+        wrong_nonce_tx.clone(),
+        bd.clone(),
+    );
+    assert!(result.receipt.is_none());
+    assert_eq!(result.error, Some(TransitionError::WrongNonce));
+
+    let result = pchain_runtime::Runtime::new()
+        .set_nonce_check(false)
+        .transition_v2(sws.world_state.clone(), wrong_nonce_tx, bd.clone());
+    assert_eq!(result.error, None);
+    let receipt = result.receipt.unwrap();
+    let sws_unchecked: SimulateWorldState<'_, V2> = result.new_state.into();
+
+    // charged exactly as the transaction with the correct nonce
+    let result = pchain_runtime::Runtime::new().transition_v2(sws.world_state, tx.clone(), bd);
+    assert_eq!(result.error, None);
+    assert_eq!(result.receipt.unwrap(), receipt);
+    let sws_checked: SimulateWorldState<'_, V2> = result.new_state.into();
+
+    assert_eq!(sws_unchecked.get_nonce(tx.signer), 1);
+    assert_eq!(sws_unchecked.get_balance(target), 1_000);
+    assert_eq!(
+        sws_unchecked.get_balance(tx.signer),
+        sws_checked.get_balance(tx.signer)
+    );
+}
@@ -314,3 +314,188 @@ fn test_view_failure_v2() {
         panic!("Call command receipt expected");
     }
 }
+
+/// View calls are determined only by the given World State, so they can be executed against historical snapshots
+#[test]
+fn test_view_historical_world_state_v2() {
+    let wasm_bytes = wat::parse_str(
+        r#"
+        (module
+            (import "env" "get" (func $get (param i32 i32 i32) (result i64)))
+            (import "env" "return_value" (func $return_value (param i32 i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "k")
+            (func (export "alloc") (param i32) (result i32)
+                i32.const 1024)
+            (func (export "get_k")
+                (local $len i64)
+                (local.set $len (call $get (i32.const 0) (i32.const 1) (i32.const 8)))
+                (call $return_value (i32.load (i32.const 8)) (i32.wrap_i64 (local.get $len)))))
+        "#,
+    )
+    .unwrap();
+    let contract_address = contract_address_v1(&[123u8; 32], 0);
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.add_contract(contract_address, wasm_bytes, pchain_runtime::cbi_version());
+    sws.set_storage_data(contract_address, b"k".to_vec(), b"old".to_vec());
+    let snapshot = sws.world_state.clone();
+
+    // the latest World State moves on after the snapshot is taken
+    sws.set_storage_data(contract_address, b"k".to_vec(), b"new".to_vec());
+
+    let runtime = pchain_runtime::Runtime::new();
+    let view = |ws| runtime.view_v2(ws, u64::MAX, contract_address, "get_k".to_string(), None);
+
+    let (snapshot_receipt, error) = view(snapshot.clone());
+    assert!(error.is_none());
+    let (latest_receipt, error) = view(sws.world_state.clone());
+    assert!(error.is_none());
+    let (repeated_receipt, error) = view(snapshot);
+    assert!(error.is_none());
+
+    match (&snapshot_receipt, &latest_receipt, &repeated_receipt) {
+        (
+            CommandReceiptV2::Call(snapshot_cr),
+            CommandReceiptV2::Call(latest_cr),
+            CommandReceiptV2::Call(repeated_cr),
+        ) => {
+            assert_eq!(snapshot_cr.exit_code, ExitCodeV2::Ok);
+            assert_eq!(snapshot_cr.return_value, b"old".to_vec());
+            assert_eq!(latest_cr.exit_code, ExitCodeV2::Ok);
+            assert_eq!(latest_cr.return_value, b"new".to_vec());
+            assert_eq!(repeated_cr.exit_code, snapshot_cr.exit_code);
+            assert_eq!(repeated_cr.return_value, snapshot_cr.return_value);
+            assert_eq!(repeated_cr.gas_used, snapshot_cr.gas_used);
+        }
+        _ => panic!("expected call receipts"),
+    }
+}
+
+/// A contract compiled for a view call is put into the smart contract cache, and later view calls to it load the
+/// compiled module instead of compiling its code again
+#[test]
+fn test_view_compiled_once_v2() {
+    let wasm_bytes = TestData::get_test_contract_code("basic_contract");
+    let method_args = "arg".to_string();
+    let contract_address = contract_address_v1(&[124u8; 32], 0);
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.add_contract(contract_address, wasm_bytes, pchain_runtime::cbi_version());
+
+    let test_cache_folder = format!("{}/{}", CONTRACT_CACHE_FOLDER, "test_view_compiled_once_v2");
+    if std::path::Path::new(&test_cache_folder).exists() {
+        std::fs::remove_dir_all(&test_cache_folder).unwrap();
+    }
+    let runtime = pchain_runtime::Runtime::new().set_smart_contract_cache(
+        pchain_runtime::Cache::new(std::path::Path::new(&test_cache_folder)),
+    );
+    let view = |runtime: &pchain_runtime::Runtime, sws: &SimulateWorldState<'_, V2>| {
+        runtime.view_v2(
+            sws.world_state.clone(),
+            u64::MAX,
+            contract_address,
+            "emit_event_with_return".to_string(),
+            ArgsBuilder::new().add(method_args.clone()).args,
+        )
+    };
+
+    // 1. the first view call compiles the contract
+    let (first_receipt, error) = view(&runtime, &sws);
+    assert!(error.is_none());
+
+    // 2. replace the code with bytes which cannot be compiled. The view call still succeeds, as the compiled module
+    // is loaded from the cache.
+    sws.add_contract(
+        contract_address,
+        vec![0u8; 8],
+        pchain_runtime::cbi_version(),
+    );
+    let (second_receipt, error) = view(&runtime, &sws);
+    assert!(error.is_none());
+    match (&first_receipt, &second_receipt) {
+        (CommandReceiptV2::Call(first_cr), CommandReceiptV2::Call(second_cr)) => {
+            assert_eq!(second_cr.exit_code, ExitCodeV2::Ok);
+            assert_eq!(second_cr.return_value, first_cr.return_value);
+            assert_eq!(second_cr.gas_used, first_cr.gas_used);
+        }
+        _ => panic!("expected call receipts"),
+    }
+
+    // 3. without the cache, the code is compiled and fails
+    let (_, error) = view(&pchain_runtime::Runtime::new(), &sws);
+    assert!(error.is_some());
+
+    // Clear sc cache folders.
+    if std::path::Path::new(&test_cache_folder).exists() {
+        std::fs::remove_dir_all(&test_cache_folder).unwrap();
+    }
+}
+
+/// Test that a contract put into the smart contract cache by prewarm_cache is not compiled again by a call
+#[test]
+fn test_prewarm_cache_v2() {
+    let wasm_bytes = TestData::get_test_contract_code("basic_contract");
+    let contract_address = contract_address_v1(&[125u8; 32], 0);
+    let no_contract_address = contract_address_v1(&[126u8; 32], 0);
+
+    let storage = SimulateWorldStateStorage::default();
+    let mut sws: SimulateWorldState<'_, V2> = SimulateWorldState::new(&storage);
+    sws.add_contract(contract_address, wasm_bytes, pchain_runtime::cbi_version());
+
+    let test_cache_folder = format!("{}/{}", CONTRACT_CACHE_FOLDER, "test_prewarm_cache_v2");
+    if std::path::Path::new(&test_cache_folder).exists() {
+        std::fs::remove_dir_all(&test_cache_folder).unwrap();
+    }
+
+    // 1. without a cache, nothing is compiled
+    assert_eq!(
+        pchain_runtime::Runtime::new().prewarm_cache(sws.world_state.clone(), &[contract_address]),
+        0
+    );
+
+    // 2. prewarm compiles the contract, and skips the address without a contract
+    let runtime = pchain_runtime::Runtime::new().set_smart_contract_cache(
+        pchain_runtime::Cache::new(std::path::Path::new(&test_cache_folder)),
+    );
+    assert_eq!(
+        runtime.prewarm_cache(
+            sws.world_state.clone(),
+            &[contract_address, no_contract_address]
+        ),
+        1
+    );
+
+    // 3. the contract is already cached, so it is not compiled again
+    assert_eq!(
+        runtime.prewarm_cache(sws.world_state.clone(), &[contract_address]),
+        0
+    );
+
+    // 4. replace the code with bytes which cannot be compiled. The call still succeeds, as the compiled module is
+    // loaded from the cache.
+    sws.add_contract(
+        contract_address,
+        vec![0u8; 8],
+        pchain_runtime::cbi_version(),
+    );
+    let (receipt, error) = runtime.view_v2(
+        sws.world_state.clone(),
+        u64::MAX,
+        contract_address,
+        "emit_event_with_return".to_string(),
+        ArgsBuilder::new().add("arg".to_string()).args,
+    );
+    assert!(error.is_none());
+    match receipt {
+        CommandReceiptV2::Call(cr) => assert_eq!(cr.exit_code, ExitCodeV2::Ok),
+        _ => panic!("expected a call receipt"),
+    }
+
+    // Clear sc cache folders.
+    if std::path::Path::new(&test_cache_folder).exists() {
+        std::fs::remove_dir_all(&test_cache_folder).unwrap();
+    }
+}
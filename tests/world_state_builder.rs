@@ -0,0 +1,62 @@
+use pchain_runtime::{commands::protocol, ValidatorPerformance, WorldStateBuilder};
+use pchain_types::blockchain::Command;
+use pchain_world_state::{NetworkAccount, Stake, WorldState, V2};
+
+use crate::common::{SimulateWorldState, SimulateWorldStateStorage, TestData};
+
+mod common;
+
+/// WorldStateBuilder produces a World State which satisfies the staking invariants, and whose pools are selected as
+/// validators in the next epoch
+#[test]
+fn test_world_state_builder_v2() {
+    let operator = [1u8; 32];
+    let owner = [2u8; 32];
+    let storage = SimulateWorldStateStorage::default();
+    let ws = WorldStateBuilder::new(WorldState::<SimulateWorldStateStorage, V2>::new(&storage))
+        .with_balance(operator, 500_000_000)
+        .with_balance(owner, 500_000_000)
+        .with_pool(operator, 1)
+        .with_deposit(operator, operator, 10_000, false)
+        .with_stake(operator, operator, 10_000)
+        .with_deposit(operator, owner, 90_000, true)
+        .with_stake(operator, owner, 50_000)
+        .with_stake(operator, owner, 40_000)
+        .build();
+    assert_eq!(protocol::validate_world_state(&ws), Ok(()));
+
+    let mut sws: SimulateWorldState<'_, V2> = ws.clone().into();
+    assert_eq!(sws.get_balance(owner), 500_000_000);
+    let mut pool = NetworkAccount::pools(&mut sws, operator);
+    assert_eq!(pool.power(), Some(100_000));
+    assert_eq!(pool.commission_rate(), Some(1));
+    assert_eq!(
+        pool.operator_stake(),
+        Some(Some(Stake {
+            owner: operator,
+            power: 10_000
+        }))
+    );
+    assert_eq!(
+        NetworkAccount::pools(&mut sws, operator)
+            .delegated_stakes()
+            .get_by(&owner)
+            .map(|stake| stake.power),
+        Some(90_000)
+    );
+    let mut deposit = NetworkAccount::deposits(&mut sws, operator, owner);
+    assert_eq!(deposit.balance(), Some(90_000));
+    assert_eq!(deposit.auto_stake_rewards(), Some(true));
+
+    let mut tx = TestData::transaction_v2();
+    tx.signer = operator;
+    tx.commands = vec![Command::NextEpoch];
+    let mut bd = TestData::block_params();
+    bd.validator_performance = Some(ValidatorPerformance::default());
+    let result = pchain_runtime::Runtime::new().transition_v2(ws, tx, bd);
+    assert_eq!(result.error, None);
+    assert_eq!(
+        result.validator_changes.unwrap().new_validator_set,
+        vec![(operator, 100_000)]
+    );
+}